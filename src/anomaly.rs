@@ -0,0 +1,146 @@
+//! Tracking of received subnegotiation sizes, to spot abusive or broken peers.
+//!
+//! Every parser keeps a coarse histogram of subnegotiation payload sizes for each option. With an `AnomalyPolicy`
+//! set through `ParserBuilder::subnegotiation_anomalies`, a `TelnetEvents::AnomalousSubnegotiation` is also emitted
+//! for a payload far larger than usual for its option, such as a 2 MB NAWS.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// The number of histogram buckets. Bucket 0 counts empty payloads, bucket `n` counts payloads of `2^(n-1)` up to
+/// `2^n - 1` bytes, and the last bucket also counts everything larger.
+pub const BUCKETS: usize = 24;
+
+/// When to report a subnegotiation as anomalous.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AnomalyPolicy {
+  /// Report payloads more than this many standard deviations above the mean size for their option.
+  pub std_devs: Option<u32>,
+  /// The number of payloads that must have been seen for an option before `std_devs` is applied.
+  pub min_samples: u64,
+  /// Report payloads larger than this many bytes, for every option.
+  pub max_len: Option<usize>,
+  /// Report payloads larger than this many bytes for a specific option, overriding `max_len`.
+  pub option_max_len: Vec<(u8, usize)>,
+}
+
+impl AnomalyPolicy {
+  fn limit(&self, option: u8) -> Option<usize> {
+    self
+      .option_max_len
+      .iter()
+      .find(|(opt, _)| *opt == option)
+      .map(|(_, len)| *len)
+      .or(self.max_len)
+  }
+}
+
+/// Payload sizes seen for one option.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SizeHistogram {
+  /// The number of payloads seen.
+  pub count: u64,
+  /// The total size of every payload seen.
+  pub sum: u64,
+  /// The total of the squares of every payload size, for the standard deviation.
+  pub sum_sq: u128,
+  /// Payload counts by size, in power of two buckets. See `BUCKETS`.
+  pub buckets: [u64; BUCKETS],
+}
+
+impl SizeHistogram {
+  /// The bucket a payload of `len` bytes is counted in.
+  pub fn bucket(len: usize) -> usize {
+    let bits = (usize::BITS - len.leading_zeros()) as usize;
+    bits.min(BUCKETS - 1)
+  }
+  /// The mean payload size, rounded down, or None if nothing has been seen.
+  pub fn mean(&self) -> Option<u64> {
+    self.sum.checked_div(self.count)
+  }
+  fn record(&mut self, len: usize) {
+    self.count += 1;
+    self.sum += len as u64;
+    self.sum_sq += (len as u128) * (len as u128);
+    self.buckets[SizeHistogram::bucket(len)] += 1;
+  }
+  /// Whether `len` is more than `std_devs` standard deviations above the mean, worked out in integers.
+  fn is_outlier(&self, len: usize, std_devs: u32) -> bool {
+    let count = self.count as u128;
+    let sum = self.sum as u128;
+    let scaled = len as u128 * count;
+    if scaled <= sum {
+      return false;
+    }
+    // (len - mean) > k * sd, multiplied through by count and squared.
+    let deviation = scaled - sum;
+    let variance = count * self.sum_sq - sum * sum;
+    let k = std_devs as u128;
+    deviation.saturating_mul(deviation) > k * k * variance
+  }
+}
+
+/// Payload size histograms for every option that has been subnegotiated.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubnegotiationSizes {
+  options: BTreeMap<u8, SizeHistogram>,
+}
+
+impl SubnegotiationSizes {
+  /// Get the histogram for an option, if any subnegotiation has been received for it.
+  pub fn get(&self, option: impl Into<u8>) -> Option<&SizeHistogram> {
+    self.options.get(&option.into())
+  }
+  /// Iterate over the options seen and their histograms, in option order.
+  pub fn iter(&self) -> impl Iterator<Item = (u8, &SizeHistogram)> + '_ {
+    self.options.iter().map(|(option, sizes)| (*option, sizes))
+  }
+  /// Record a received payload.
+  ///
+  /// # Returns
+  ///
+  /// `bool` - Whether the payload is anomalous under `policy`, judged against the payloads seen before it.
+  pub(crate) fn record(&mut self, option: u8, len: usize, policy: Option<&AnomalyPolicy>) -> bool {
+    let sizes = self.options.entry(option).or_default();
+    let anomalous = match policy {
+      Some(policy) => {
+        // The standard deviation means nothing until a couple of sizes have been seen.
+        let enough = sizes.count >= policy.min_samples.max(2);
+        matches!(policy.limit(option), Some(limit) if len > limit)
+          || matches!(policy.std_devs, Some(k) if enough && sizes.is_outlier(len, k))
+      }
+      None => false,
+    };
+    sizes.record(len);
+    anomalous
+  }
+}
+
+#[cfg(test)]
+mod test_anomaly {
+  use super::*;
+
+  #[test]
+  fn test_buckets() {
+    assert_eq!(SizeHistogram::bucket(0), 0);
+    assert_eq!(SizeHistogram::bucket(1), 1);
+    assert_eq!(SizeHistogram::bucket(4), 3);
+    assert_eq!(SizeHistogram::bucket(usize::MAX), BUCKETS - 1);
+  }
+
+  #[test]
+  fn test_outliers() {
+    let policy = AnomalyPolicy {
+      std_devs: Some(3),
+      min_samples: 4,
+      ..Default::default()
+    };
+    let mut sizes = SubnegotiationSizes::default();
+    for &len in [4, 5, 4, 5, 4].iter() {
+      assert!(!sizes.record(31, len, Some(&policy)));
+    }
+    assert!(!sizes.record(31, 5, Some(&policy)));
+    assert!(sizes.record(31, 4000, Some(&policy)));
+    assert_eq!(sizes.get(31).unwrap().count, 7);
+    assert_eq!(sizes.get(31).unwrap().buckets[3], 6);
+  }
+}
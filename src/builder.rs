@@ -0,0 +1,299 @@
+use crate::anomaly::AnomalyPolicy;
+use crate::charset::{InvalidUtf8, Utf8Decoder};
+use crate::compatibility::CompatibilityTable;
+use crate::fingerprint::Fingerprinter;
+use crate::newline::{LineAssembler, LineEnding, NewlineTranslator, TextFormat};
+use crate::pool::{BufferPool, PooledBuffer};
+use crate::protocol::{self, Protocol};
+use crate::sanitize::{SanitizePolicy, Sanitizer};
+use crate::telnet::environ::LegacyEnviron;
+use crate::telnet::msp::MspScanner;
+use crate::telnet::op_command::AYT;
+use crate::Parser;
+use alloc::{boxed::Box, vec::Vec};
+use bytes::Bytes;
+
+/// How forgiving a `Parser` is of malformed input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ParserMode {
+  /// Report malformed sequences (bad subnegotiation framing, undefined commands) as `TelnetEvents::Error` events
+  /// and drop them. Suited to security-sensitive gateways.
+  Strict,
+  /// Make the best of malformed input, for example by leaving commands embedded in a subnegotiation out of its data.
+  /// Suited to clients talking to servers of varying quality.
+  #[default]
+  Lenient,
+}
+
+/// Optional parser behavior, set through `ParserBuilder`.
+#[derive(Clone, Default)]
+pub(crate) struct ParserConfig {
+  pub(crate) negotiation_limit: Option<u8>,
+  pub(crate) queue_subnegotiations: bool,
+  pub(crate) mirror_outbound: bool,
+  pub(crate) max_subnegotiation_len: Option<usize>,
+  pub(crate) fingerprint: bool,
+  pub(crate) compression_stats_interval: Option<u64>,
+  pub(crate) denied_commands: Vec<u8>,
+  pub(crate) mode: ParserMode,
+  pub(crate) sanitize: Option<SanitizePolicy>,
+  pub(crate) line_ending: Option<LineEnding>,
+  pub(crate) line_mode: Option<usize>,
+  pub(crate) anomalies: Option<AnomalyPolicy>,
+  pub(crate) auto_responses: Vec<(u8, Bytes)>,
+  pub(crate) prompt_markers: Vec<u8>,
+  pub(crate) text_format: TextFormat,
+  pub(crate) legacy_environ: Option<LegacyEnviron>,
+  pub(crate) utf8_text: Option<InvalidUtf8>,
+  pub(crate) msp_triggers: bool,
+}
+
+/// A builder for a `Parser` with optional behavior enabled.
+///
+/// # Example
+///
+/// ```
+/// use libtelnet_rs::Parser;
+///
+/// let parser = Parser::builder().capacity(256).negotiation_limit(3).build();
+/// ```
+pub struct ParserBuilder {
+  capacity: usize,
+  options: CompatibilityTable,
+  pool: Option<Box<dyn BufferPool>>,
+  config: ParserConfig,
+}
+
+impl Default for ParserBuilder {
+  fn default() -> Self {
+    Self {
+      capacity: 128,
+      options: CompatibilityTable::new(),
+      pool: None,
+      config: ParserConfig::default(),
+    }
+  }
+}
+
+impl ParserBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  /// Set the initial internal buffer capacity. Defaults to 128 bytes.
+  pub fn capacity(mut self, size: usize) -> Self {
+    self.capacity = size;
+    self
+  }
+  /// Directly supply the `CompatibilityTable` used by the parser.
+  pub fn support(mut self, table: CompatibilityTable) -> Self {
+    self.options = table;
+    self
+  }
+  /// Support these protocols, on both sides of the connection, in the `CompatibilityTable` used by the parser.
+  ///
+  /// Adds to the table given with `support`, so call this after it.
+  pub fn protocols(mut self, protocols: &[Protocol]) -> Self {
+    protocol::support(&mut self.options, protocols);
+    self
+  }
+  /// Acquire the internal buffer from, and release it back to, a `BufferPool`.
+  ///
+  /// Event payloads are then copied out of the buffer rather than sliced from it, so the allocation released is the
+  /// one that was acquired.
+  pub fn pool<P>(mut self, pool: P) -> Self
+  where
+    P: BufferPool + 'static,
+  {
+    self.pool = Some(Box::new(pool));
+    self
+  }
+  /// Stop answering a repeated negotiation for an option after `limit` replies that didn't change its state.
+  ///
+  /// When the limit is exceeded, a `TelnetEvents::Error` with `TelnetErrorKind::NegotiationLoop` is emitted once and
+  /// further replies for that option are dropped until its state changes. Disabled by default.
+  pub fn negotiation_limit(mut self, limit: u8) -> Self {
+    self.config.negotiation_limit = Some(limit);
+    self
+  }
+  /// Hold subnegotiations sent while the option is still being negotiated, instead of refusing them.
+  ///
+  /// Queued subnegotiations are sent as soon as the remote end agrees to enable the option. If it refuses, they are
+  /// dropped and a `TelnetEvents::Error` with `TelnetErrorKind::SubnegotiationDropped` is emitted for each one.
+  /// Disabled by default.
+  pub fn queue_subnegotiations(mut self, enabled: bool) -> Self {
+    self.config.queue_subnegotiations = enabled;
+    self
+  }
+  /// Follow every `DataSend` the parser pushes while receiving with a `TelnetEvents::Outbound` describing it.
+  ///
+  /// This gives recorders and debuggers the same structured view of generated replies as of received traffic,
+  /// without parsing outbound bytes again. Events returned directly from methods such as `Parser::_will` are not
+  /// mirrored, as the caller already knows what was asked for. Disabled by default.
+  pub fn mirror_outbound(mut self, enabled: bool) -> Self {
+    self.config.mirror_outbound = enabled;
+    self
+  }
+  /// Limit the size of a received subnegotiation payload, in bytes as received (including any IAC escapes).
+  ///
+  /// A subnegotiation that grows past the limit is discarded up to its closing `IAC SE`, and a
+  /// `TelnetEvents::Error` with `TelnetErrorKind::Overflow` is emitted carrying the bytes received before it was
+  /// cut off. Without a limit, a peer that never terminates a subnegotiation makes the internal buffer grow without
+  /// bound. Unlimited by default.
+  pub fn max_subnegotiation_len(mut self, limit: usize) -> Self {
+    self.config.max_subnegotiation_len = Some(limit);
+    self
+  }
+  /// Guess the peer's telnet stack from its opening negotiations and terminal type.
+  ///
+  /// A `TelnetEvents::PeerStack` is emitted whenever the guess changes. See the `fingerprint` module for details.
+  /// Disabled by default.
+  pub fn fingerprint(mut self, enabled: bool) -> Self {
+    self.config.fingerprint = enabled;
+    self
+  }
+  /// Emit a `TelnetEvents::CompressionStats` from `Parser::record_compression` each time another `bytes`
+  /// uncompressed bytes have been recorded, in either direction. Only the data the caller reports is counted.
+  /// Disabled by default.
+  pub fn compression_stats_interval(mut self, bytes: u64) -> Self {
+    self.config.compression_stats_interval = Some(bytes);
+    self
+  }
+  /// Refuse these 2-byte IAC commands (such as `BREAK` or `IP`) from the remote end.
+  ///
+  /// A refused command is reported as a `TelnetEvents::Error` with `TelnetErrorKind::DeniedCommand` instead of a
+  /// `TelnetEvents::IAC`. Replaces any earlier `deny_commands` or `allow_commands`.
+  pub fn deny_commands(mut self, commands: &[u8]) -> Self {
+    self.config.denied_commands = commands.to_vec();
+    self
+  }
+  /// Refuse every 2-byte IAC command from the remote end except these, as with `deny_commands`.
+  ///
+  /// Negotiations and subnegotiations are not affected. Replaces any earlier `deny_commands` or `allow_commands`.
+  pub fn allow_commands(mut self, commands: &[u8]) -> Self {
+    self.config.denied_commands = (0..=255).filter(|c| !commands.contains(c)).collect();
+    self
+  }
+  /// Choose how forgiving the parser is of malformed input. Defaults to `ParserMode::Lenient`.
+  pub fn mode(mut self, mode: ParserMode) -> Self {
+    self.config.mode = mode;
+    self
+  }
+  /// Clean every `DataReceive` according to a `SanitizePolicy`, such as stripping terminal escape sequences or
+  /// capping line length. See the `sanitize` module for details. Disabled by default.
+  pub fn sanitize(mut self, policy: SanitizePolicy) -> Self {
+    self.config.sanitize = Some(policy);
+    self
+  }
+  /// Translate line endings between the network and the application, as described in the `newline` module.
+  ///
+  /// Received `CR LF` becomes `ending` and `CR NUL` becomes `CR`. Outside of BINARY mode, text sent with
+  /// `Parser::send_text` and friends has lone LFs sent as `CR LF` and lone CRs as `CR NUL`. Disabled by default.
+  pub fn line_ending(mut self, ending: LineEnding) -> Self {
+    self.config.line_ending = Some(ending);
+    self
+  }
+  /// Set how `Parser::send_text` finishes the text it sends: its line ending, and any `IAC GA` or `IAC EOR` after it.
+  /// Defaults to `CR LF` with nothing after it.
+  pub fn text_format(mut self, format: TextFormat) -> Self {
+    self.config.text_format = format;
+    self
+  }
+  /// Also exchange environment variables over the old ENVIRON option (RFC 1408, option 36), for peers that don't
+  /// support NEW-ENVIRON.
+  ///
+  /// ENVIRON subnegotiations are handled like NEW-ENVIRON ones, using the type codes of `legacy`, and share the same
+  /// variables. ENVIRON is only used in a direction where NEW-ENVIRON is not enabled, and still has to be supported
+  /// in the `CompatibilityTable`. Disabled by default.
+  pub fn legacy_environ(mut self, legacy: LegacyEnviron) -> Self {
+    self.config.legacy_environ = Some(legacy);
+    self
+  }
+  /// Deliver received data as a `TelnetEvents::Text` holding UTF-8 text, instead of as `DataReceive` events.
+  ///
+  /// A character split across packets is held back until the rest of it arrives, or until `Parser::flush`. Data that
+  /// is not valid UTF-8 is handled according to `invalid`. Has no effect on `TelnetEvents::Line`, with
+  /// `line_mode`. Disabled by default.
+  pub fn utf8_text(mut self, invalid: InvalidUtf8) -> Self {
+    self.config.utf8_text = Some(invalid);
+    self
+  }
+  /// Remove MSP `!!SOUND(...)` and `!!MUSIC(...)` triggers from received text while the remote end has MSP enabled,
+  /// emitting each as a `TelnetEvents::Sound`.
+  ///
+  /// A trigger split across packets is held back until the rest of it arrives, or until `Parser::flush`. MSP still
+  /// has to be supported in the `CompatibilityTable`. Disabled by default.
+  pub fn msp_triggers(mut self) -> Self {
+    self.config.msp_triggers = true;
+    self
+  }
+  /// Deliver received data as a `TelnetEvents::Line` for each complete line, instead of as `DataReceive` events.
+  ///
+  /// A line ends at `LF`, `CR LF`, `CR NUL` or a lone `CR`, which is left out of the event. A line longer than
+  /// `max_len` bytes is cut off: its first `max_len` bytes are reported in a `TelnetEvents::Error` with
+  /// `TelnetErrorKind::LineTooLong`, and the rest is discarded. `Parser::flush` emits any unfinished line. Disabled by
+  /// default.
+  pub fn line_mode(mut self, max_len: usize) -> Self {
+    self.config.line_mode = Some(max_len);
+    self
+  }
+  /// Emit a `TelnetEvents::AnomalousSubnegotiation` for a received subnegotiation that is unusually large under
+  /// `policy`. See the `anomaly` module for details. Disabled by default.
+  pub fn subnegotiation_anomalies(mut self, policy: AnomalyPolicy) -> Self {
+    self.config.anomalies = Some(policy);
+    self
+  }
+  /// Automatically send `reply` whenever the remote end sends the 2-byte IAC `command`, such as `AYT`.
+  ///
+  /// The reply is sent as data, with any IAC bytes escaped, in a `DataSend` pushed right after the
+  /// `TelnetEvents::IAC` for the command. Replaces any earlier reply for the same command.
+  pub fn auto_response<T>(mut self, command: impl Into<u8>, reply: T) -> Self
+  where
+    Bytes: From<T>,
+  {
+    let command = command.into();
+    self.config.auto_responses.retain(|(c, _)| *c != command);
+    self
+      .config
+      .auto_responses
+      .push((command, Parser::escape_iac(reply)));
+    self
+  }
+  /// Answer `IAC AYT` (Are You There) with `reply`, such as `"[Yes]\r\n"`.
+  ///
+  /// The `TelnetEvents::IAC` for the AYT is still emitted, followed by the reply's `DataSend`. A shorthand for
+  /// `auto_response(AYT, reply)`. Disabled by default.
+  pub fn auto_ayt_response<T>(self, reply: T) -> Self
+  where
+    Bytes: From<T>,
+  {
+    self.auto_response(AYT, reply)
+  }
+  /// Emit a `TelnetEvents::Prompt` when one of these 2-byte IAC commands, usually `GA` and `EOR`, is received.
+  ///
+  /// The event carries the received line the command ended, which has no line ending of its own, and follows the
+  /// `TelnetEvents::IAC` for the command. With `line_mode`, that line is taken from the unfinished line so it is not
+  /// also emitted as a `Line`. Disabled by default.
+  pub fn detect_prompts(mut self, markers: &[u8]) -> Self {
+    self.config.prompt_markers = markers.to_vec();
+    self
+  }
+  /// Create the configured `Parser`.
+  pub fn build(self) -> Parser {
+    let mut parser = Parser::with_support_and_capacity(self.capacity, self.options);
+    if let Some(pool) = self.pool {
+      parser.buffer = PooledBuffer::pooled(pool, self.capacity);
+    }
+    if self.config.fingerprint {
+      parser.fingerprinter = Some(Fingerprinter::new());
+    }
+    parser.sanitizer = self.config.sanitize.map(Sanitizer::new);
+    parser.newline = self.config.line_ending.map(NewlineTranslator::new);
+    parser.lines = self.config.line_mode.map(LineAssembler::new);
+    parser.utf8 = self.config.utf8_text.map(Utf8Decoder::new);
+    if self.config.msp_triggers {
+      parser.msp = Some(MspScanner::new());
+    }
+    parser.config = self.config;
+    parser
+  }
+}
@@ -0,0 +1,372 @@
+//! Transcoding of outbound text for peers that have not agreed to UTF-8, and decoding of inbound UTF-8.
+//!
+//! Applications keep text as UTF-8 internally. Once another character set has been agreed with the remote end, an
+//! encoder for it can be installed with `Parser::set_text_encoder`, and `send_text` and friends convert text into it.
+//!
+//! A `TextDecoder` installed with `Parser::set_text_decoder` transcodes received data into UTF-8 the same way. With
+//! the `encodings` feature, `Charset` is an encoder and decoder for any single-byte character set in `encoding_rs`,
+//! and `Parser::set_encoding` installs both for a character set by name, such as the result of a CHARSET
+//! negotiation. BBS and old MUD content is mostly CP437 or ISO-8859-1.
+//!
+//! In the other direction, `ParserBuilder::utf8_text` delivers received data as `TelnetEvents::Text`, holding back a
+//! character split across packets until the rest of it arrives.
+
+use alloc::{string::String, vec::Vec};
+use bytes::Bytes;
+
+/// What to do with a character that the target character set cannot represent.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Unmappable {
+  /// Send this byte in its place, usually `b'?'`.
+  Replace(u8),
+  /// Leave the character out.
+  Skip,
+}
+
+/// A conversion from UTF-8 text into another character set.
+pub trait TextEncoder: Send + Sync {
+  /// The name of the character set, as used in CHARSET (RFC 2066) negotiation.
+  fn name(&self) -> &str;
+  /// Append the encoding of a single character to `out`.
+  ///
+  /// # Returns
+  ///
+  /// `bool` - Whether the character could be encoded. Nothing should be appended when it could not.
+  fn encode_char(&self, c: char, out: &mut Vec<u8>) -> bool;
+}
+
+/// A conversion from a single-byte character set into UTF-8 text.
+pub trait TextDecoder: Send + Sync {
+  /// The name of the character set, as used in CHARSET (RFC 2066) negotiation.
+  fn name(&self) -> &str;
+  /// Get the character a byte stands for.
+  fn decode_byte(&self, byte: u8) -> char;
+}
+
+/// ISO-8859-1, which maps the first 256 code points directly onto single bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Latin1;
+
+impl TextEncoder for Latin1 {
+  fn name(&self) -> &str {
+    "ISO-8859-1"
+  }
+  fn encode_char(&self, c: char, out: &mut Vec<u8>) -> bool {
+    if (c as u32) < 0x100 {
+      out.push(c as u8);
+      true
+    } else {
+      false
+    }
+  }
+}
+
+impl TextDecoder for Latin1 {
+  fn name(&self) -> &str {
+    "ISO-8859-1"
+  }
+  fn decode_byte(&self, byte: u8) -> char {
+    byte as char
+  }
+}
+
+/// Code page 437, the character set of the original IBM PC and of most BBS art. The lower half is ASCII.
+///
+/// Built in, as `encoding_rs` only has the character sets used on the web.
+#[cfg(feature = "encodings")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cp437;
+
+/// The characters for bytes 0x80 to 0xFF in code page 437.
+#[cfg(feature = "encodings")]
+#[rustfmt::skip]
+const CP437_HIGH: [char; 128] = [
+  'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+  'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+  'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+  '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+  '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+  '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+  'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+  '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+#[cfg(feature = "encodings")]
+impl TextEncoder for Cp437 {
+  fn name(&self) -> &str {
+    "IBM437"
+  }
+  fn encode_char(&self, c: char, out: &mut Vec<u8>) -> bool {
+    if c.is_ascii() {
+      out.push(c as u8);
+      return true;
+    }
+    match CP437_HIGH.iter().position(|&high| high == c) {
+      Some(index) => {
+        out.push(0x80 + index as u8);
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+#[cfg(feature = "encodings")]
+impl TextDecoder for Cp437 {
+  fn name(&self) -> &str {
+    "IBM437"
+  }
+  fn decode_byte(&self, byte: u8) -> char {
+    if byte < 0x80 {
+      byte as char
+    } else {
+      CP437_HIGH[(byte - 0x80) as usize]
+    }
+  }
+}
+
+/// A single-byte character set from `encoding_rs`, such as `windows-1252`, `KOI8-R` or `ISO-8859-5`.
+///
+/// Character sets are looked up with the WHATWG labels used by `encoding_rs`, which map `ISO-8859-1` and `Latin1`
+/// onto `windows-1252`.
+#[cfg(feature = "encodings")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Charset(&'static encoding_rs::Encoding);
+
+#[cfg(feature = "encodings")]
+impl Charset {
+  /// Wrap an `encoding_rs` character set.
+  ///
+  /// # Returns
+  ///
+  /// `Option<Charset>` - The character set, or None if it is not a single-byte character set.
+  pub fn new(encoding: &'static encoding_rs::Encoding) -> Option<Self> {
+    if encoding.is_single_byte() {
+      Some(Self(encoding))
+    } else {
+      None
+    }
+  }
+  /// Look up a character set by name, ignoring case, such as one agreed in CHARSET negotiation.
+  ///
+  /// # Returns
+  ///
+  /// `Option<Charset>` - The character set, or None if the name is unknown or not a single-byte character set.
+  pub fn for_label(name: &str) -> Option<Self> {
+    encoding_rs::Encoding::for_label(name.as_bytes()).and_then(Self::new)
+  }
+  /// Get the `encoding_rs` character set.
+  pub fn encoding(&self) -> &'static encoding_rs::Encoding {
+    self.0
+  }
+}
+
+#[cfg(feature = "encodings")]
+impl TextEncoder for Charset {
+  fn name(&self) -> &str {
+    self.0.name()
+  }
+  fn encode_char(&self, c: char, out: &mut Vec<u8>) -> bool {
+    let mut src = [0; 4];
+    let mut dst = [0; 4];
+    let (result, _, written) = self.0.new_encoder().encode_from_utf8_without_replacement(
+      c.encode_utf8(&mut src),
+      &mut dst,
+      true,
+    );
+    if result == encoding_rs::EncoderResult::InputEmpty {
+      out.extend_from_slice(&dst[..written]);
+      true
+    } else {
+      false
+    }
+  }
+}
+
+#[cfg(feature = "encodings")]
+impl TextDecoder for Charset {
+  fn name(&self) -> &str {
+    self.0.name()
+  }
+  fn decode_byte(&self, byte: u8) -> char {
+    let byte = [byte];
+    let (text, _) = self.0.decode_without_bom_handling(&byte);
+    text.chars().next().unwrap_or(char::REPLACEMENT_CHARACTER)
+  }
+}
+
+/// The names of code page 437, which `encoding_rs` does not include.
+#[cfg(feature = "encodings")]
+const CP437_NAMES: [&str; 4] = ["IBM437", "CP437", "437", "CSPC8CODEPAGE437"];
+
+#[cfg(feature = "encodings")]
+impl Cp437 {
+  /// Whether a name, ignoring case, is one of the names of code page 437.
+  pub fn is_name(name: &str) -> bool {
+    CP437_NAMES.iter().any(|n| n.eq_ignore_ascii_case(name))
+  }
+}
+
+/// Decode a whole piece of data into UTF-8 text.
+pub fn decode(decoder: &dyn TextDecoder, data: &[u8]) -> String {
+  data.iter().map(|&byte| decoder.decode_byte(byte)).collect()
+}
+
+/// Encode a whole string, applying `unmappable` to any character the encoder cannot represent.
+pub fn encode(encoder: &dyn TextEncoder, text: &str, unmappable: Unmappable) -> Vec<u8> {
+  let mut out = Vec::with_capacity(text.len());
+  for c in text.chars() {
+    if !encoder.encode_char(c, &mut out) {
+      if let Unmappable::Replace(byte) = unmappable {
+        out.push(byte);
+      }
+    }
+  }
+  out
+}
+
+/// What to do with received data that is not valid UTF-8, with `ParserBuilder::utf8_text`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum InvalidUtf8 {
+  /// Replace each invalid sequence with U+FFFD and still deliver a `TelnetEvents::Text`.
+  #[default]
+  Replace,
+  /// Deliver the data as it was received, in a `TelnetEvents::DataReceive`.
+  Raw,
+}
+
+/// What a `Utf8Decoder` made of a piece of data.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Decoded {
+  Text(String),
+  Raw(Bytes),
+}
+
+/// Decodes received data as UTF-8, holding back an incomplete character at the end until the next piece arrives.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Utf8Decoder {
+  invalid: InvalidUtf8,
+  partial: Vec<u8>,
+}
+
+impl Utf8Decoder {
+  pub(crate) fn new(invalid: InvalidUtf8) -> Self {
+    Self {
+      invalid,
+      partial: Vec::new(),
+    }
+  }
+
+  /// Decode a piece of data, or None if it only holds part of a character.
+  pub(crate) fn decode(&mut self, data: &[u8]) -> Option<Decoded> {
+    let mut bytes = core::mem::take(&mut self.partial);
+    bytes.extend_from_slice(data);
+    // Keep back a character cut off at the end, as long as everything before it is valid.
+    let mut complete = bytes.len();
+    match core::str::from_utf8(&bytes) {
+      Ok(_) => (),
+      Err(err) if err.error_len().is_none() => complete = err.valid_up_to(),
+      Err(_) => {
+        let start = bytes.len().saturating_sub(3);
+        if let Some(cut) = (start..bytes.len()).find(|&i| is_incomplete(&bytes[i..])) {
+          complete = cut;
+        }
+      }
+    }
+    self.partial = bytes.split_off(complete);
+    self.finish(bytes)
+  }
+
+  /// Take an incomplete character held back at the end of the data so far, such as when the connection is closing.
+  pub(crate) fn take_partial(&mut self) -> Option<Decoded> {
+    let bytes = core::mem::take(&mut self.partial);
+    self.finish(bytes)
+  }
+
+  fn finish(&self, bytes: Vec<u8>) -> Option<Decoded> {
+    if bytes.is_empty() {
+      return None;
+    }
+    Some(match String::from_utf8(bytes) {
+      Ok(text) => Decoded::Text(text),
+      Err(err) => match self.invalid {
+        InvalidUtf8::Replace => Decoded::Text(String::from_utf8_lossy(err.as_bytes()).into_owned()),
+        InvalidUtf8::Raw => Decoded::Raw(Bytes::from(err.into_bytes())),
+      },
+    })
+  }
+}
+
+/// Whether `bytes` is the start of a valid multibyte character, cut off before its end.
+fn is_incomplete(bytes: &[u8]) -> bool {
+  matches!(core::str::from_utf8(bytes), Err(err) if err.valid_up_to() == 0 && err.error_len().is_none())
+}
+
+#[cfg(test)]
+mod test_charset {
+  use super::*;
+
+  #[test]
+  fn test_latin1() {
+    assert_eq!(
+      encode(&Latin1, "café €5", Unmappable::Replace(b'?')),
+      b"caf\xe9 ?5".to_vec()
+    );
+    assert_eq!(
+      encode(&Latin1, "café €5", Unmappable::Skip),
+      b"caf\xe9 5".to_vec()
+    );
+  }
+
+  #[test]
+  fn test_latin1_decode() {
+    assert_eq!(decode(&Latin1, b"caf\xe9"), "café");
+  }
+
+  #[cfg(feature = "encodings")]
+  #[test]
+  fn test_cp437() {
+    let art = "╔═╗ 25°";
+    let encoded = encode(&Cp437, art, Unmappable::Skip);
+    assert_eq!(encoded, b"\xc9\xcd\xbb 25\xf8".to_vec());
+    assert_eq!(decode(&Cp437, &encoded), art);
+    assert!(Cp437::is_name("cp437"));
+    assert!(!Cp437::is_name("UTF-8"));
+  }
+
+  #[cfg(feature = "encodings")]
+  #[test]
+  fn test_charset() {
+    let koi8 = Charset::for_label("koi8-r").unwrap();
+    assert_eq!(TextEncoder::name(&koi8), "KOI8-R");
+    let encoded = encode(&koi8, "Привет €", Unmappable::Replace(b'?'));
+    assert_eq!(encoded, b"\xf0\xd2\xc9\xd7\xc5\xd4 ?".to_vec());
+    assert_eq!(decode(&koi8, &encoded), "Привет ?");
+    let latin1 = Charset::for_label("ISO-8859-1").unwrap();
+    assert_eq!(decode(&latin1, b"caf\xe9 \x80"), "café €");
+    assert_eq!(Charset::for_label("Shift_JIS"), None);
+    assert_eq!(Charset::for_label("UTF-8"), None);
+    assert_eq!(Charset::for_label("no such charset"), None);
+  }
+
+  #[test]
+  fn test_utf8_decoder() {
+    let text = |s: &str| Some(Decoded::Text(String::from(s)));
+    let mut decoder = Utf8Decoder::new(InvalidUtf8::Replace);
+    // "é€" split inside each character.
+    assert_eq!(decoder.decode(b"caf\xc3"), text("caf"));
+    assert_eq!(decoder.decode(b"\xa9 \xe2\x82"), text("\u{e9} "));
+    assert_eq!(decoder.decode(b"\xac"), text("\u{20ac}"));
+    assert_eq!(decoder.decode(b"\xe2"), None);
+    assert_eq!(decoder.take_partial(), text("\u{fffd}"));
+    assert_eq!(decoder.decode(b"a\xffb\xc3"), text("a\u{fffd}b"));
+    assert_eq!(decoder.decode(b"\xa9"), text("\u{e9}"));
+
+    let mut decoder = Utf8Decoder::new(InvalidUtf8::Raw);
+    assert_eq!(
+      decoder.decode(b"a\xffb"),
+      Some(Decoded::Raw(Bytes::from_static(b"a\xffb")))
+    );
+  }
+}
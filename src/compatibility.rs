@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 /// An expansion of a bitmask contained in `CompatibilityTable`.
 #[derive(Clone, Copy)]
 pub struct CompatibilityEntry {
@@ -20,6 +22,10 @@ impl CompatibilityEntry {
       remote_state,
     }
   }
+  /// Whether this option is supported and currently enabled on either side of the connection.
+  pub fn enabled(&self) -> bool {
+    (self.local && self.local_state) || (self.remote && self.remote_state)
+  }
   /// Creates a u8 bitmask from this entry.
   pub fn into_u8(self) -> u8 {
     let mut res: u8 = 0;
@@ -88,32 +94,74 @@ impl CompatibilityTable {
     }
     Self { options }
   }
+  /// Get the option values that are set, in the form taken by `from_options`, to save and later restore the table.
+  pub fn to_options(&self) -> Vec<(u8, u8)> {
+    (0..=255u8)
+      .zip(self.options.iter())
+      .filter(|(_, value)| **value != 0)
+      .map(|(option, value)| (option, *value))
+      .collect()
+  }
+  /// Iterate over the options that have any support or state set, in order of option code.
+  pub fn iter(&self) -> impl Iterator<Item = (u8, CompatibilityEntry)> + '_ {
+    (0..=255u8)
+      .zip(self.options.iter())
+      .filter(|(_, value)| **value != 0)
+      .map(|(option, value)| (option, CompatibilityEntry::from(*value)))
+  }
+  /// Get the options that are supported locally and currently enabled locally.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<u8>` - The option codes, in ascending order.
+  pub fn enabled_local(&self) -> Vec<u8> {
+    self
+      .iter()
+      .filter(|(_, entry)| entry.local && entry.local_state)
+      .map(|(option, _)| option)
+      .collect()
+  }
+  /// Get the options that are supported remotely and currently enabled remotely.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<u8>` - The option codes, in ascending order.
+  pub fn enabled_remote(&self) -> Vec<u8> {
+    self
+      .iter()
+      .filter(|(_, entry)| entry.remote && entry.remote_state)
+      .map(|(option, _)| option)
+      .collect()
+  }
   /// Enable local support for an option.
-  pub fn support_local(&mut self, option: u8) {
+  pub fn support_local(&mut self, option: impl Into<u8>) {
+    let option = option.into();
     let mut opt = CompatibilityEntry::from(self.options[option as usize]);
     opt.local = true;
     self.set_option(option, opt);
   }
   /// Enable remote support for an option.
-  pub fn support_remote(&mut self, option: u8) {
+  pub fn support_remote(&mut self, option: impl Into<u8>) {
+    let option = option.into();
     let mut opt = CompatibilityEntry::from(self.options[option as usize]);
     opt.remote = true;
     self.set_option(option, opt);
   }
   /// Enable both remote and local support for an option.
-  pub fn support(&mut self, option: u8) {
+  pub fn support(&mut self, option: impl Into<u8>) {
+    let option = option.into();
     let mut opt = CompatibilityEntry::from(self.options[option as usize]);
     opt.local = true;
     opt.remote = true;
     self.set_option(option, opt);
   }
   /// Retrieve a `CompatbilityEntry` generated from the current state of the option value.
-  pub fn get_option(&self, option: u8) -> CompatibilityEntry {
-    CompatibilityEntry::from(self.options[option as usize])
+  pub fn get_option(&self, option: impl Into<u8>) -> CompatibilityEntry {
+    CompatibilityEntry::from(self.options[option.into() as usize])
   }
   /// Set an option value by getting the bitmask from a `CompatibilityEntry`.
-  pub fn set_option(&mut self, option: u8, entry: CompatibilityEntry) {
-    self.options[option as usize] = entry.clone().into_u8();
+  pub fn set_option(&mut self, option: impl Into<u8>, entry: CompatibilityEntry) {
+    self.options[option.into() as usize] = entry.into_u8();
   }
 
   /// Reset all negotiated states
@@ -127,6 +175,33 @@ impl CompatibilityTable {
   }
 }
 
+/// Serialized as the list of `(option, bitmask)` pairs from `to_options`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for CompatibilityTable {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(self.to_options())
+  }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CompatibilityTable {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let values = <Vec<(u8, u8)>>::deserialize(deserializer)?;
+    Ok(Self::from_options(&values))
+  }
+}
+
+/// The negotiation state of a parser, taken with `Parser::negotiation_snapshot` and put back with
+/// `Parser::restore_negotiation`, such as when a proxy hands a connection over to another process.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NegotiationSnapshot {
+  /// The supported options and their states.
+  pub options: CompatibilityTable,
+  /// The requests still waiting for an answer, as `(option, flags)`.
+  pub(crate) pending: Vec<(u8, u8)>,
+}
+
 #[cfg(test)]
 mod test_compat {
   use super::*;
@@ -135,16 +210,48 @@ mod test_compat {
   fn test_reset() {
     let mut table = CompatibilityTable::default();
     let entry = CompatibilityEntry::new(true, true, true, true);
-    assert_eq!(entry.remote, true);
-    assert_eq!(entry.local, true);
-    assert_eq!(entry.remote_state, true);
-    assert_eq!(entry.local_state, true);
+    assert!(entry.remote);
+    assert!(entry.local);
+    assert!(entry.remote_state);
+    assert!(entry.local_state);
     table.set_option(201, entry);
     table.reset_states();
     let entry = table.get_option(201);
-    assert_eq!(entry.remote, true);
-    assert_eq!(entry.local, true);
-    assert_eq!(entry.remote_state, false);
-    assert_eq!(entry.local_state, false);
+    assert!(entry.remote);
+    assert!(entry.local);
+    assert!(!entry.remote_state);
+    assert!(!entry.local_state);
+  }
+
+  #[test]
+  fn test_to_options() {
+    let mut table = CompatibilityTable::new();
+    table.support_local(1);
+    table.set_option(201, CompatibilityEntry::new(true, true, false, true));
+    let values = table.to_options();
+    assert_eq!(values, [(1, 1), (201, 11)]);
+    assert_eq!(
+      CompatibilityTable::from_options(&values).to_options(),
+      values
+    );
+  }
+
+  #[test]
+  fn test_iter() {
+    let mut table = CompatibilityTable::new();
+    table.support(24);
+    table.set_option(31, CompatibilityEntry::new(true, true, true, false));
+    table.set_option(201, CompatibilityEntry::new(false, true, false, true));
+    table.set_option(86, CompatibilityEntry::new(false, false, true, true));
+    let options: Vec<u8> = table.iter().map(|(option, _)| option).collect();
+    assert_eq!(options, [24, 31, 86, 201]);
+    let (_, entry) = table.iter().nth(1).unwrap();
+    assert!(entry.local_state);
+    assert!(!entry.remote_state);
+    assert_eq!(table.enabled_local(), [31]);
+    assert_eq!(table.enabled_remote(), [201]);
+    table.reset_states();
+    assert!(table.enabled_local().is_empty());
+    assert_eq!(table.iter().count(), 3);
   }
 }
@@ -0,0 +1,66 @@
+/// Which way data travels, relative to this end of the connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+  /// Data received from the remote end.
+  Inbound,
+  /// Data sent to the remote end.
+  Outbound,
+}
+
+impl Direction {
+  /// The arrow a trace line starts with: `<-` for inbound and `->` for outbound.
+  pub fn arrow(self) -> &'static str {
+    match self {
+      Direction::Inbound => "<-",
+      Direction::Outbound => "->",
+    }
+  }
+}
+
+/// Byte counts for one direction of an MCCP compressed stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DirectionStats {
+  /// Bytes as they travelled over the connection.
+  pub compressed: u64,
+  /// Bytes before compression, or after decompression.
+  pub uncompressed: u64,
+}
+
+impl DirectionStats {
+  /// The compression ratio, as uncompressed bytes per compressed byte, or None if nothing has been recorded.
+  pub fn ratio(&self) -> Option<f64> {
+    if self.compressed == 0 {
+      None
+    } else {
+      Some(self.uncompressed as f64 / self.compressed as f64)
+    }
+  }
+}
+
+/// Byte counts for both directions of MCCP compression on a connection.
+///
+/// The parser does not own the zlib streams, so the counts are whatever has been reported with
+/// `Parser::record_compression`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressionStats {
+  pub inbound: DirectionStats,
+  pub outbound: DirectionStats,
+}
+
+impl CompressionStats {
+  /// Get the counts for one direction.
+  pub fn direction(&self, direction: Direction) -> &DirectionStats {
+    match direction {
+      Direction::Inbound => &self.inbound,
+      Direction::Outbound => &self.outbound,
+    }
+  }
+  pub(crate) fn direction_mut(&mut self, direction: Direction) -> &mut DirectionStats {
+    match direction {
+      Direction::Inbound => &mut self.inbound,
+      Direction::Outbound => &mut self.outbound,
+    }
+  }
+}
@@ -0,0 +1,114 @@
+#[cfg(feature = "defmt")]
+use crate::events::Hexdump;
+use crate::events::TelnetErrorKind;
+use bytes::Bytes;
+use core::fmt;
+
+/// The reason a subnegotiation could not be sent by `Parser::try_subnegotiation`.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SubnegotiationRefusal {
+  /// The option is not supported on either side via the `CompatibilityTable`.
+  Unsupported,
+  /// We have asked to enable the option, but the remote end has not answered yet.
+  Pending,
+  /// We have asked to enable the option, but the remote end has not answered yet. The subnegotiation has been queued
+  /// and will be sent once the option is enabled, or dropped if it is refused.
+  ///
+  /// Only returned when the parser was built with `ParserBuilder::queue_subnegotiations`.
+  Queued,
+  /// The option is supported, but is not currently enabled on either side.
+  Disabled,
+}
+
+impl fmt::Display for SubnegotiationRefusal {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SubnegotiationRefusal::Unsupported => write!(f, "option is not supported"),
+      SubnegotiationRefusal::Pending => write!(f, "option negotiation is still pending"),
+      SubnegotiationRefusal::Queued => write!(f, "option negotiation is still pending, queued"),
+      SubnegotiationRefusal::Disabled => write!(f, "option is disabled"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SubnegotiationRefusal {}
+
+/// A name given to `TelnetOption::from_str` or `TelnetCommand::from_str` that is not a known name or a code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ParseNameError;
+
+impl fmt::Display for ParseNameError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "unknown telnet option or command name")
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseNameError {}
+
+/// The reason `Parser::try_receive` failed.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TelnetError {
+  /// Received data went over a configured size limit, such as `ParserBuilder::max_subnegotiation_len` or
+  /// `SanitizePolicy::max_line_len`.
+  BufferOverflow(TelnetErrorKind, Bytes),
+  /// The remote end sent something that breaks the protocol or is refused by the parser's configuration.
+  ProtocolViolation(TelnetErrorKind, Bytes),
+}
+
+impl TelnetError {
+  /// Get the kind of problem, as it would be reported by a `TelnetEvents::Error`.
+  pub fn kind(&self) -> TelnetErrorKind {
+    match self {
+      TelnetError::BufferOverflow(kind, _) | TelnetError::ProtocolViolation(kind, _) => *kind,
+    }
+  }
+  /// Get the bytes involved.
+  pub fn data(&self) -> &Bytes {
+    match self {
+      TelnetError::BufferOverflow(_, data) | TelnetError::ProtocolViolation(_, data) => data,
+    }
+  }
+}
+
+impl From<(TelnetErrorKind, Bytes)> for TelnetError {
+  fn from((kind, data): (TelnetErrorKind, Bytes)) -> Self {
+    match kind {
+      TelnetErrorKind::Overflow | TelnetErrorKind::LineTooLong => {
+        TelnetError::BufferOverflow(kind, data)
+      }
+      _ => TelnetError::ProtocolViolation(kind, data),
+    }
+  }
+}
+
+impl fmt::Display for TelnetError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TelnetError::BufferOverflow(kind, _) => write!(f, "buffer overflow: {}", kind),
+      TelnetError::ProtocolViolation(kind, _) => write!(f, "protocol violation: {}", kind),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TelnetError {}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for TelnetError {
+  fn format(&self, f: defmt::Formatter<'_>) {
+    match self {
+      TelnetError::BufferOverflow(kind, data) => {
+        defmt::write!(f, "BufferOverflow({}, {})", kind, Hexdump(data))
+      }
+      TelnetError::ProtocolViolation(kind, data) => {
+        defmt::write!(f, "ProtocolViolation({}, {})", kind, Hexdump(data))
+      }
+    }
+  }
+}
@@ -1,32 +1,58 @@
+use crate::compression::CompressionStats;
+use crate::fingerprint::PeerStack;
+use crate::telnet::aard102::Aard102;
+use crate::telnet::auth::AuthPair;
+use crate::telnet::comport::ComPortMessage;
+use crate::telnet::encrypt::EncryptMessage;
+use crate::telnet::environ::EnvironVar;
+use crate::telnet::gmcp::GmcpMessage;
+use crate::telnet::lflow::LflowState;
+use crate::telnet::msdp::{self, MsdpValue};
+use crate::telnet::msp::MspTrigger;
+use crate::telnet::mssp::MsspData;
+use crate::telnet::naws::Naws;
+use crate::telnet::status::StateMismatch;
+use crate::telnet::tn3270e::Tn3270eMessage;
+use crate::telnet::tspeed::TspeedMessage;
+use crate::telnet::xdisploc::XdisplocMessage;
+use crate::telnet::{TelnetCommand, TelnetOption};
 use crate::Parser;
-use alloc::vec::Vec;
+use alloc::{boxed::Box, string::String, vec::Vec};
 use bytes::{BufMut, Bytes, BytesMut};
+use core::fmt;
 
 /// A struct representing a 2 byte IAC sequence.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TelnetIAC {
-  pub command: u8,
+  pub command: TelnetCommand,
 }
 
-impl Into<Bytes> for TelnetIAC {
-  fn into(self) -> Bytes {
+impl From<TelnetIAC> for Bytes {
+  fn from(value: TelnetIAC) -> Self {
     let mut buf = BytesMut::with_capacity(2);
     buf.put_u8(255);
-    buf.put_u8(self.command);
+    buf.put_u8(value.command.into());
     buf.freeze()
   }
 }
 
-impl Into<Vec<u8>> for TelnetIAC {
-  fn into(self) -> Vec<u8> {
-    let b: Bytes = self.into();
+impl From<TelnetIAC> for Vec<u8> {
+  fn from(value: TelnetIAC) -> Self {
+    let b: Bytes = value.into();
     b.to_vec()
   }
 }
 
 impl TelnetIAC {
-  pub fn new(command: u8) -> Self {
-    Self { command }
+  pub fn new(command: impl Into<u8>) -> Self {
+    Self {
+      command: TelnetCommand::from(command.into()),
+    }
+  }
+  /// Get the command's code, such as 249 for `GA`.
+  pub fn raw_command(&self) -> u8 {
+    self.command.into()
   }
   /// Consume the sequence struct and return the bytes.
   pub fn into_bytes(self) -> Vec<u8> {
@@ -35,15 +61,16 @@ impl TelnetIAC {
 }
 
 /// A struct representing a 3 byte IAC sequence.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TelnetNegotiation {
-  pub command: u8,
+  pub command: TelnetCommand,
   pub option: u8,
 }
 
-impl Into<Bytes> for TelnetNegotiation {
-  fn into(self) -> Bytes {
-    let data = [self.command, self.option];
+impl From<TelnetNegotiation> for Bytes {
+  fn from(value: TelnetNegotiation) -> Self {
+    let data = [value.command.into(), value.option];
     let mut buf = BytesMut::with_capacity(3);
     buf.put_u8(255);
     buf.put(&data[..]);
@@ -51,16 +78,27 @@ impl Into<Bytes> for TelnetNegotiation {
   }
 }
 
-impl Into<Vec<u8>> for TelnetNegotiation {
-  fn into(self) -> Vec<u8> {
-    let b: Bytes = self.into();
+impl From<TelnetNegotiation> for Vec<u8> {
+  fn from(value: TelnetNegotiation) -> Self {
+    let b: Bytes = value.into();
     b.to_vec()
   }
 }
 
 impl TelnetNegotiation {
-  pub fn new(command: u8, option: u8) -> Self {
-    Self { command, option }
+  pub fn new(command: impl Into<u8>, option: impl Into<u8>) -> Self {
+    Self {
+      command: TelnetCommand::from(command.into()),
+      option: option.into(),
+    }
+  }
+  /// Get the command's code: 251 to 254 for `WILL`, `WONT`, `DO` or `DONT`.
+  pub fn raw_command(&self) -> u8 {
+    self.command.into()
+  }
+  /// Get the option as a `TelnetOption`, for matching symbolically.
+  pub fn option_kind(&self) -> TelnetOption {
+    TelnetOption::from(self.option)
   }
   /// Consume the sequence struct and return the bytes.
   pub fn into_bytes(self) -> Vec<u8> {
@@ -68,73 +106,348 @@ impl TelnetNegotiation {
   }
 }
 
+/// A struct representing the answer to a negotiation request made with `Parser::_will`, `_wont`, `_do` or `_dont`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TelnetNegotiationComplete {
+  pub option: u8,
+  /// Whether the remote end agreed to the request.
+  pub accepted: bool,
+  /// Whether the request was about our side of the option (WILL/WONT) rather than theirs (DO/DONT).
+  pub local: bool,
+}
+
+impl TelnetNegotiationComplete {
+  pub fn new(option: impl Into<u8>, accepted: bool, local: bool) -> Self {
+    Self {
+      option: option.into(),
+      accepted,
+      local,
+    }
+  }
+  /// Get the option as a `TelnetOption`, for matching symbolically.
+  pub fn option_kind(&self) -> TelnetOption {
+    TelnetOption::from(self.option)
+  }
+}
+
 /// A struct representing an arbitrary length IAC subnegotiation sequence.
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TelnetSubnegotiation {
   pub option: u8,
   pub buffer: Bytes,
 }
 
-impl Into<Bytes> for TelnetSubnegotiation {
-  fn into(self) -> Bytes {
-    let head: [u8; 3] = [255, 250, self.option];
-    let parsed = &Parser::escape_iac(self.buffer)[..];
+impl From<TelnetSubnegotiation> for Bytes {
+  fn from(value: TelnetSubnegotiation) -> Self {
+    let head: [u8; 3] = [255, 250, value.option];
+    let parsed = &Parser::escape_iac(value.buffer)[..];
     let tail: [u8; 2] = [255, 240];
     let mut buf = BytesMut::with_capacity(head.len() + parsed.len() + tail.len());
     buf.put(&head[..]);
-    buf.put(&parsed[..]);
+    buf.put(parsed);
     buf.put(&tail[..]);
     buf.freeze()
   }
 }
 
-impl Into<Vec<u8>> for TelnetSubnegotiation {
-  fn into(self) -> Vec<u8> {
-    let b: Bytes = self.into();
+impl From<TelnetSubnegotiation> for Vec<u8> {
+  fn from(value: TelnetSubnegotiation) -> Self {
+    let b: Bytes = value.into();
     b.to_vec()
   }
 }
 
 impl TelnetSubnegotiation {
-  pub fn new(option: u8, buffer: Bytes) -> Self {
-    Self { option, buffer }
+  pub fn new(option: impl Into<u8>, buffer: Bytes) -> Self {
+    Self {
+      option: option.into(),
+      buffer,
+    }
+  }
+  /// Get the option as a `TelnetOption`, for matching symbolically.
+  pub fn option_kind(&self) -> TelnetOption {
+    TelnetOption::from(self.option)
   }
   /// Consume the sequence struct and return the bytes.
   pub fn into_bytes(self) -> Vec<u8> {
     self.into()
   }
+  /// Decode the window size carried by a NAWS subnegotiation.
+  ///
+  /// # Returns
+  ///
+  /// `Option<Naws>` - The window size, or None if this is not a well-formed NAWS subnegotiation.
+  pub fn naws(&self) -> Option<Naws> {
+    if self.option != crate::telnet::op_option::NAWS {
+      return None;
+    }
+    Naws::decode(&self.buffer)
+  }
+  /// Decode the variables carried by an MSSP subnegotiation.
+  ///
+  /// # Returns
+  ///
+  /// `Option<MsspData>` - The variables, or None if this is not a well-formed MSSP subnegotiation.
+  pub fn mssp(&self) -> Option<MsspData> {
+    if self.option != crate::telnet::op_option::MSSP {
+      return None;
+    }
+    MsspData::decode(&self.buffer)
+  }
+  /// Decode the message carried by a GMCP subnegotiation.
+  ///
+  /// # Returns
+  ///
+  /// `Option<GmcpMessage>` - The message, or None if this is not a well-formed GMCP subnegotiation.
+  pub fn gmcp(&self) -> Option<GmcpMessage> {
+    if self.option != crate::telnet::op_option::GMCP {
+      return None;
+    }
+    GmcpMessage::decode(&self.buffer)
+  }
+  /// Decode the variables carried by an MSDP subnegotiation.
+  ///
+  /// # Returns
+  ///
+  /// `Option<Vec<(String, MsdpValue)>>` - The variables, or None if this is not a well-formed MSDP subnegotiation.
+  pub fn msdp(&self) -> Option<Vec<(String, MsdpValue)>> {
+    if self.option != crate::telnet::op_option::MSDP {
+      return None;
+    }
+    msdp::decode(&self.buffer)
+  }
+}
+
+/// The kinds of problem reported by a `TelnetEvents::Error` event.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TelnetErrorKind {
+  /// The remote end kept repeating a negotiation that we kept refusing, so further replies were suppressed.
+  NegotiationLoop,
+  /// A queued subnegotiation was dropped because the remote end refused to enable its option.
+  SubnegotiationDropped,
+  /// A received subnegotiation was longer than the configured `ParserBuilder::max_subnegotiation_len`.
+  Overflow,
+  /// An incomplete sequence was still buffered when `Parser::flush` was called.
+  Truncated,
+  /// A subnegotiation was interrupted by another command (such as `IAC SB` or `IAC WILL`) before its `IAC SE`, and
+  /// was dropped.
+  InterruptedSubnegotiation,
+  /// The remote end sent an IAC command refused by `ParserBuilder::deny_commands` or `ParserBuilder::allow_commands`.
+  DeniedCommand,
+  /// A sequence that breaks the protocol framing, such as an `IAC SE` with no subnegotiation to end, or a command
+  /// inside a subnegotiation. Only reported in `ParserMode::Strict`.
+  Malformed,
+  /// The remote end sent an IAC command that is not defined. Only reported in `ParserMode::Strict`.
+  UnknownCommand,
+  /// A received line went over the limit set by `SanitizePolicy::max_line_len` or `ParserBuilder::line_mode`. The
+  /// event carries the bytes dropped from the line, or with `line_mode`, the bytes kept before it was cut off.
+  LineTooLong,
+  /// A subnegotiation was received for an option that is not enabled in either direction, and was ignored.
+  OptionNotEnabled,
+}
+
+impl fmt::Display for TelnetErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TelnetErrorKind::NegotiationLoop => write!(f, "negotiation loop suppressed"),
+      TelnetErrorKind::SubnegotiationDropped => write!(f, "queued subnegotiation dropped"),
+      TelnetErrorKind::Overflow => write!(f, "subnegotiation too long"),
+      TelnetErrorKind::Truncated => write!(f, "incomplete sequence flushed"),
+      TelnetErrorKind::InterruptedSubnegotiation => {
+        write!(f, "subnegotiation interrupted by another command")
+      }
+      TelnetErrorKind::DeniedCommand => write!(f, "command denied"),
+      TelnetErrorKind::Malformed => write!(f, "malformed sequence"),
+      TelnetErrorKind::UnknownCommand => write!(f, "unknown command"),
+      TelnetErrorKind::LineTooLong => write!(f, "line too long"),
+      TelnetErrorKind::OptionNotEnabled => {
+        write!(f, "subnegotiation for an option that is not enabled")
+      }
+    }
+  }
 }
 
 /// An enum representing various telnet events.
-#[derive(Clone, Debug)]
+///
+/// New kinds of event are added as more options are handled, so matches on it need a wildcard arm.
+#[non_exhaustive]
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TelnetEvents {
   /// An IAC command sequence.
   IAC(TelnetIAC),
+  /// An `IAC DM` (Data Mark). Ends the discard started by `Parser::synch` or `Parser::mark_urgent`.
+  DataMark,
   /// An IAC negotiation sequence.
   Negotiation(TelnetNegotiation),
-  /// An IAC subnegotiation sequence.
+  /// The remote end answered a negotiation request that we made.
+  NegotiationComplete(TelnetNegotiationComplete),
+  /// An IAC subnegotiation sequence. Received data has escaped IAC bytes collapsed and any embedded commands removed.
   Subnegotiation(TelnetSubnegotiation),
   /// Regular data received from the remote end.
   DataReceive(Bytes),
   /// Any data to be sent to the remote end.
   DataSend(Bytes),
-  /// MCCP2/3 compatibility. MUST DECOMPRESS THIS DATA BEFORE PARSING
+  /// A complete line received from the remote end, without its line ending. Only emitted, in place of
+  /// `DataReceive`, when enabled with `ParserBuilder::line_mode`.
+  Line(Bytes),
+  /// Text received from the remote end. Only emitted, in place of `DataReceive`, when enabled with
+  /// `ParserBuilder::utf8_text`.
+  Text(String),
+  /// A structured view of the `DataSend` immediately before it, as the remote end will parse it.
+  ///
+  /// Only emitted when the parser was built with `ParserBuilder::mirror_outbound`.
+  Outbound(Box<TelnetEvents>),
+  /// A prompt from the remote end: the unterminated line before an `IAC GA` or `IAC EOR`. Only emitted when enabled
+  /// with `ParserBuilder::detect_prompts`.
+  Prompt(Bytes),
+  /// An MSP sound or music trigger, removed from the received text. Only emitted when enabled with
+  /// `ParserBuilder::msp_triggers` and MSP is enabled by the remote end.
+  Sound(MspTrigger),
+  /// An update from Aardwolf's option 102 status channel, following the `Subnegotiation` that carried it.
+  Aard102(Aard102),
+  /// A TERMINAL-SPEED request or report, following the `Subnegotiation` that carried it.
+  TerminalSpeed(TspeedMessage),
+  /// An X-DISPLAY-LOCATION request or report, following the `Subnegotiation` that carried it.
+  DisplayLocation(XdisplocMessage),
+  /// The local flow control requested by the remote end has changed, following the `Subnegotiation` that carried
+  /// the command.
+  FlowControl(LflowState),
+  /// The name of the account the remote end wants to authenticate as, sent before its authentication data.
+  AuthName(String),
+  /// An authentication exchange has ended. A `kind` of `telnet::auth::NULL` means no mechanism was agreed.
+  AuthComplete { pair: AuthPair, accepted: bool },
+  /// An ENCRYPT message from the remote end, following the `Subnegotiation` that carried it.
+  Encrypt(EncryptMessage),
+  /// A TN3270E device type or functions message, following the `Subnegotiation` that carried it.
+  Tn3270e(Tn3270eMessage),
+  /// A COM-PORT-OPTION command from the remote end, following the `Subnegotiation` that carried it.
+  ComPort(ComPortMessage),
+  /// A renegotiation started with `Parser::resync_option` for this option has finished.
+  Resynced(u8),
+  /// A STATUS report from the remote end, such as the answer to `Parser::verify_states`, disagrees with our own view
+  /// of these option states.
+  StateMismatch(Vec<StateMismatch>),
+  /// NEW-ENVIRON variables reported by the remote end that are new or have changed value.
+  EnvironChange(Vec<EnvironVar>),
+  /// A problem was detected with the data received, along with the bytes involved.
+  Error(TelnetErrorKind, Bytes),
+  /// A new guess at the remote end's telnet stack. Only emitted when enabled with `ParserBuilder::fingerprint`.
+  PeerStack(PeerStack),
+  /// Our outbound compression for this option (MCCP1, MCCP2 or MCCP3) must be ended cleanly, by finishing the
+  /// compressed stream, before anything that follows is sent.
+  EndCompression(u8),
+  /// The remote end's compressed stream for this option (MCCP1, MCCP2 or MCCP3), started with the last
+  /// `DecompressImmediate` or `DecompressClientStream`, has ended, either because it switched the option off or
+  /// because `Parser::end_decompression` was called. Data received from now on is not compressed.
+  DecompressionEnded(u8),
+  /// Periodic compression statistics, enabled with `ParserBuilder::compression_stats_interval`, for the data
+  /// reported with `Parser::record_compression`.
+  CompressionStats(CompressionStats),
+  /// A received subnegotiation for this option had an unusually large payload of this many bytes (as received,
+  /// including any IAC escapes). Only emitted when enabled with `ParserBuilder::subnegotiation_anomalies`, just
+  /// before the subnegotiation itself is handled.
+  AnomalousSubnegotiation(u8, usize),
+  /// The remote end answered a probe sent with `Parser::keepalive_probe`.
+  KeepaliveAck,
+  /// The remote end answered a probe sent with `Parser::ping`.
+  Pong {
+    /// The time between the ping and the latest time given to `Parser::ping` or `Parser::set_time`.
+    rtt_hint: u64,
+  },
+  /// The remote end asked to be logged out with `DO LOGOUT` (RFC 727). A server agrees with
+  /// `Parser::acknowledge_logout` and then closes the connection.
+  LogoutRequested,
+  /// The remote end is logging us out with `WILL LOGOUT` (RFC 727), either because we asked with
+  /// `Parser::request_logout` or to force a logout. The connection is about to be closed.
+  LogoutAnnounced,
+  /// The end of a `Parser::shutdown_sequence`. Nothing more should be sent, and the connection can be closed.
+  Shutdown,
+  /// MCCP2 compatibility. The remote end has started compressing the data it sends, and may do so again after a
+  /// `DecompressionEnded`. MUST DECOMPRESS THIS DATA BEFORE PARSING
   DecompressImmediate(Bytes),
+  /// MCCP3 compatibility. The client has started compressing the data it sends to us (the server). MUST DECOMPRESS THIS DATA BEFORE PARSING
+  DecompressClientStream(Bytes),
+  /// START_TLS compatibility. Both ends have sent `FOLLOWS`, and the TLS handshake starts with this data, which
+  /// MUST BE PASSED TO THE TLS LIBRARY. The parser is suspended until `Parser::tls_established` is called.
+  StartTls(Bytes),
+  /// ENCRYPT compatibility. The remote end is encrypting the data it sends, and no cipher was set with
+  /// `Parser::set_cipher`. MUST DECRYPT THIS DATA and pass it to `Parser::receive_decrypted`.
+  Encrypted(Bytes),
 }
 
-impl Into<Bytes> for TelnetEvents {
-  fn into(self) -> Bytes {
-    match self {
+impl From<TelnetEvents> for Bytes {
+  fn from(value: TelnetEvents) -> Self {
+    match value {
       TelnetEvents::IAC(iac) => iac.into(),
+      TelnetEvents::DataMark => TelnetIAC::new(crate::telnet::op_command::DM).into(),
       TelnetEvents::Negotiation(neg) => neg.into(),
+      TelnetEvents::NegotiationComplete(_) => Bytes::new(),
       TelnetEvents::Subnegotiation(sub) => sub.into(),
       TelnetEvents::DataReceive(data) => data,
       TelnetEvents::DataSend(data) => data,
+      TelnetEvents::Line(data) => data,
+      TelnetEvents::Text(text) => Bytes::from(text),
+      TelnetEvents::Outbound(_) => Bytes::new(),
+      TelnetEvents::Prompt(data) => data,
+      TelnetEvents::Sound(_) => Bytes::new(),
+      TelnetEvents::Aard102(_) => Bytes::new(),
+      TelnetEvents::TerminalSpeed(_) => Bytes::new(),
+      TelnetEvents::DisplayLocation(_) => Bytes::new(),
+      TelnetEvents::FlowControl(_) => Bytes::new(),
+      TelnetEvents::AuthName(_) => Bytes::new(),
+      TelnetEvents::AuthComplete { .. } => Bytes::new(),
+      TelnetEvents::Encrypt(_) => Bytes::new(),
+      TelnetEvents::ComPort(_) => Bytes::new(),
+      TelnetEvents::Tn3270e(_) => Bytes::new(),
+      TelnetEvents::Resynced(_) => Bytes::new(),
+      TelnetEvents::StateMismatch(_) => Bytes::new(),
+      TelnetEvents::EnvironChange(_) => Bytes::new(),
+      TelnetEvents::Error(_, data) => data,
+      TelnetEvents::PeerStack(_) => Bytes::new(),
+      TelnetEvents::EndCompression(_) => Bytes::new(),
+      TelnetEvents::DecompressionEnded(_) => Bytes::new(),
+      TelnetEvents::CompressionStats(_) => Bytes::new(),
+      TelnetEvents::AnomalousSubnegotiation(..) => Bytes::new(),
+      TelnetEvents::KeepaliveAck => Bytes::new(),
+      TelnetEvents::Pong { .. } => Bytes::new(),
+      TelnetEvents::LogoutRequested => Bytes::new(),
+      TelnetEvents::LogoutAnnounced => Bytes::new(),
+      TelnetEvents::Shutdown => Bytes::new(),
       TelnetEvents::DecompressImmediate(data) => data,
+      TelnetEvents::DecompressClientStream(data) => data,
+      TelnetEvents::StartTls(data) => data,
+      TelnetEvents::Encrypted(data) => data,
     }
   }
 }
 
+/// A `DataSend` of the text's bytes, with IAC bytes escaped. No line ending is added.
+impl From<&str> for TelnetEvents {
+  fn from(value: &str) -> Self {
+    TelnetEvents::build_send(Parser::escape_iac(Bytes::copy_from_slice(value.as_bytes())))
+  }
+}
+
+/// A `DataSend` of the text's bytes, with IAC bytes escaped. No line ending is added.
+impl From<String> for TelnetEvents {
+  fn from(value: String) -> Self {
+    TelnetEvents::build_send(Parser::escape_iac(value.into_bytes()))
+  }
+}
+
+/// A `DataSend` of the bytes, with IAC bytes escaped.
+impl From<Vec<u8>> for TelnetEvents {
+  fn from(value: Vec<u8>) -> Self {
+    TelnetEvents::build_send(Parser::escape_iac(value))
+  }
+}
+
 impl TelnetEvents {
   /// Helper method to generate a TelnetEvents::DataSend.
   pub fn build_send(buffer: Bytes) -> Self {
@@ -145,15 +458,437 @@ impl TelnetEvents {
     TelnetEvents::DataReceive(buffer)
   }
   /// Helper method to generate a TelnetEvents::IAC.
-  pub fn build_iac(command: u8) -> TelnetEvents {
+  pub fn build_iac(command: impl Into<u8>) -> TelnetEvents {
     TelnetEvents::IAC(TelnetIAC::new(command))
   }
   /// Helper method to generate a TelnetEvents::Negotiation.
-  pub fn build_negotiation(command: u8, option: u8) -> Self {
+  pub fn build_negotiation(command: impl Into<u8>, option: impl Into<u8>) -> Self {
     TelnetEvents::Negotiation(TelnetNegotiation::new(command, option))
   }
   /// Helper method to generate a TelnetEvents::Subnegotiation.
-  pub fn build_subnegotiation(option: u8, buffer: Bytes) -> Self {
+  pub fn build_subnegotiation(option: impl Into<u8>, buffer: Bytes) -> Self {
     TelnetEvents::Subnegotiation(TelnetSubnegotiation::new(option, buffer))
   }
 }
+
+/// A view of a `TelnetEvents` whose data is borrowed, passed to the callback of `Parser::receive_borrowed`.
+///
+/// The borrowed data is only valid for the duration of the callback. Use `to_owned` to keep an event beyond it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TelnetEventRef<'a> {
+  /// An IAC command sequence.
+  IAC(TelnetIAC),
+  /// An `IAC DM` (Data Mark).
+  DataMark,
+  /// An IAC negotiation sequence.
+  Negotiation(TelnetNegotiation),
+  /// An IAC subnegotiation, with its option and unescaped payload.
+  Subnegotiation(u8, &'a [u8]),
+  /// Regular data received from the remote end.
+  DataReceive(&'a [u8]),
+  /// Any data to be sent to the remote end.
+  DataSend(&'a [u8]),
+  /// A complete line received from the remote end, without its line ending.
+  Line(&'a [u8]),
+  /// A problem was detected with the data received, along with the bytes involved.
+  Error(TelnetErrorKind, &'a [u8]),
+  /// Any other event.
+  Other(&'a TelnetEvents),
+}
+
+impl<'a> From<&'a TelnetEvents> for TelnetEventRef<'a> {
+  fn from(value: &'a TelnetEvents) -> Self {
+    match value {
+      TelnetEvents::IAC(iac) => TelnetEventRef::IAC(*iac),
+      TelnetEvents::DataMark => TelnetEventRef::DataMark,
+      TelnetEvents::Negotiation(neg) => TelnetEventRef::Negotiation(*neg),
+      TelnetEvents::Subnegotiation(sub) => TelnetEventRef::Subnegotiation(sub.option, &sub.buffer),
+      TelnetEvents::DataReceive(data) => TelnetEventRef::DataReceive(data),
+      TelnetEvents::DataSend(data) => TelnetEventRef::DataSend(data),
+      TelnetEvents::Line(data) => TelnetEventRef::Line(data),
+      TelnetEvents::Error(kind, data) => TelnetEventRef::Error(*kind, data),
+      event => TelnetEventRef::Other(event),
+    }
+  }
+}
+
+impl TelnetEventRef<'_> {
+  /// Copy the event into an owned `TelnetEvents`.
+  pub fn to_owned(&self) -> TelnetEvents {
+    match *self {
+      TelnetEventRef::IAC(iac) => TelnetEvents::IAC(iac),
+      TelnetEventRef::DataMark => TelnetEvents::DataMark,
+      TelnetEventRef::Negotiation(neg) => TelnetEvents::Negotiation(neg),
+      TelnetEventRef::Subnegotiation(option, data) => {
+        TelnetEvents::build_subnegotiation(option, Bytes::copy_from_slice(data))
+      }
+      TelnetEventRef::DataReceive(data) => {
+        TelnetEvents::build_receive(Bytes::copy_from_slice(data))
+      }
+      TelnetEventRef::DataSend(data) => TelnetEvents::build_send(Bytes::copy_from_slice(data)),
+      TelnetEventRef::Line(data) => TelnetEvents::Line(Bytes::copy_from_slice(data)),
+      TelnetEventRef::Error(kind, data) => TelnetEvents::Error(kind, Bytes::copy_from_slice(data)),
+      TelnetEventRef::Other(event) => event.clone(),
+    }
+  }
+}
+
+/// The most payload bytes written out by the `Debug` format of an event.
+const DEBUG_BYTES: usize = 16;
+
+/// Writes a payload as its length and the first `DEBUG_BYTES` bytes in hex, as in `27 bytes: 43 6f 72 ...`.
+pub(crate) struct Hexdump<'a>(pub(crate) &'a [u8]);
+
+impl fmt::Debug for Hexdump<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let len = self.0.len();
+    write!(f, "{} byte{}", len, if len == 1 { "" } else { "s" })?;
+    for (index, byte) in self.0.iter().take(DEBUG_BYTES).enumerate() {
+      f.write_str(if index == 0 { ": " } else { " " })?;
+      write!(f, "{:02x}", byte)?;
+    }
+    if len > DEBUG_BYTES {
+      f.write_str(" ...")?;
+    }
+    Ok(())
+  }
+}
+
+/// Writes an option by name, or as its code if it has none.
+struct OptionName(u8);
+
+impl fmt::Debug for OptionName {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", TelnetOption::from(self.0))
+  }
+}
+
+/// Writes a command by name, or as its code if it has none.
+struct CommandName(TelnetCommand);
+
+impl fmt::Debug for CommandName {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl fmt::Debug for TelnetIAC {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("TelnetIAC")
+      .field(&CommandName(self.command))
+      .finish()
+  }
+}
+
+impl fmt::Debug for TelnetNegotiation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("TelnetNegotiation")
+      .field(&CommandName(self.command))
+      .field(&OptionName(self.option))
+      .finish()
+  }
+}
+
+impl fmt::Debug for TelnetNegotiationComplete {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("TelnetNegotiationComplete")
+      .field("option", &OptionName(self.option))
+      .field("accepted", &self.accepted)
+      .field("local", &self.local)
+      .finish()
+  }
+}
+
+impl fmt::Debug for TelnetSubnegotiation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("TelnetSubnegotiation")
+      .field(&OptionName(self.option))
+      .field(&Hexdump(&self.buffer))
+      .finish()
+  }
+}
+
+/// Commands and options are written by name, and payloads as a bounded hexdump, as in
+/// `Subnegotiation(GMCP, 27 bytes: 43 6f 72 ...)`, so that large payloads don't flood logs.
+impl fmt::Debug for TelnetEvents {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TelnetEvents::IAC(iac) => f
+        .debug_tuple("IAC")
+        .field(&CommandName(iac.command))
+        .finish(),
+      TelnetEvents::DataMark => f.write_str("DataMark"),
+      TelnetEvents::Negotiation(neg) => f
+        .debug_tuple("Negotiation")
+        .field(&CommandName(neg.command))
+        .field(&OptionName(neg.option))
+        .finish(),
+      TelnetEvents::NegotiationComplete(complete) => f
+        .debug_struct("NegotiationComplete")
+        .field("option", &OptionName(complete.option))
+        .field("accepted", &complete.accepted)
+        .field("local", &complete.local)
+        .finish(),
+      TelnetEvents::Subnegotiation(sub) => f
+        .debug_tuple("Subnegotiation")
+        .field(&OptionName(sub.option))
+        .field(&Hexdump(&sub.buffer))
+        .finish(),
+      TelnetEvents::DataReceive(data) => {
+        f.debug_tuple("DataReceive").field(&Hexdump(data)).finish()
+      }
+      TelnetEvents::DataSend(data) => f.debug_tuple("DataSend").field(&Hexdump(data)).finish(),
+      TelnetEvents::Line(data) => f.debug_tuple("Line").field(&Hexdump(data)).finish(),
+      TelnetEvents::Text(text) => f.debug_tuple("Text").field(text).finish(),
+      TelnetEvents::Outbound(event) => f.debug_tuple("Outbound").field(event).finish(),
+      TelnetEvents::Prompt(data) => f.debug_tuple("Prompt").field(&Hexdump(data)).finish(),
+      TelnetEvents::Sound(trigger) => f.debug_tuple("Sound").field(trigger).finish(),
+      TelnetEvents::Aard102(update) => f.debug_tuple("Aard102").field(update).finish(),
+      TelnetEvents::TerminalSpeed(message) => {
+        f.debug_tuple("TerminalSpeed").field(message).finish()
+      }
+      TelnetEvents::DisplayLocation(message) => {
+        f.debug_tuple("DisplayLocation").field(message).finish()
+      }
+      TelnetEvents::FlowControl(state) => f.debug_tuple("FlowControl").field(state).finish(),
+      TelnetEvents::AuthName(name) => f.debug_tuple("AuthName").field(name).finish(),
+      TelnetEvents::AuthComplete { pair, accepted } => f
+        .debug_struct("AuthComplete")
+        .field("pair", pair)
+        .field("accepted", accepted)
+        .finish(),
+      TelnetEvents::Encrypt(message) => f.debug_tuple("Encrypt").field(message).finish(),
+      TelnetEvents::Tn3270e(message) => f.debug_tuple("Tn3270e").field(message).finish(),
+      TelnetEvents::ComPort(message) => f.debug_tuple("ComPort").field(message).finish(),
+      TelnetEvents::Resynced(option) => f
+        .debug_tuple("Resynced")
+        .field(&OptionName(*option))
+        .finish(),
+      TelnetEvents::StateMismatch(mismatches) => {
+        f.debug_tuple("StateMismatch").field(mismatches).finish()
+      }
+      TelnetEvents::EnvironChange(vars) => f.debug_tuple("EnvironChange").field(vars).finish(),
+      TelnetEvents::Error(kind, data) => f
+        .debug_tuple("Error")
+        .field(kind)
+        .field(&Hexdump(data))
+        .finish(),
+      TelnetEvents::PeerStack(stack) => f.debug_tuple("PeerStack").field(stack).finish(),
+      TelnetEvents::EndCompression(option) => f
+        .debug_tuple("EndCompression")
+        .field(&OptionName(*option))
+        .finish(),
+      TelnetEvents::DecompressionEnded(option) => f
+        .debug_tuple("DecompressionEnded")
+        .field(&OptionName(*option))
+        .finish(),
+      TelnetEvents::CompressionStats(stats) => {
+        f.debug_tuple("CompressionStats").field(stats).finish()
+      }
+      TelnetEvents::AnomalousSubnegotiation(option, len) => f
+        .debug_tuple("AnomalousSubnegotiation")
+        .field(&OptionName(*option))
+        .field(len)
+        .finish(),
+      TelnetEvents::KeepaliveAck => f.write_str("KeepaliveAck"),
+      TelnetEvents::Pong { rtt_hint } => {
+        f.debug_struct("Pong").field("rtt_hint", rtt_hint).finish()
+      }
+      TelnetEvents::LogoutRequested => f.write_str("LogoutRequested"),
+      TelnetEvents::LogoutAnnounced => f.write_str("LogoutAnnounced"),
+      TelnetEvents::Shutdown => f.write_str("Shutdown"),
+      TelnetEvents::DecompressImmediate(data) => f
+        .debug_tuple("DecompressImmediate")
+        .field(&Hexdump(data))
+        .finish(),
+      TelnetEvents::DecompressClientStream(data) => f
+        .debug_tuple("DecompressClientStream")
+        .field(&Hexdump(data))
+        .finish(),
+      TelnetEvents::StartTls(data) => f.debug_tuple("StartTls").field(&Hexdump(data)).finish(),
+      TelnetEvents::Encrypted(data) => f.debug_tuple("Encrypted").field(&Hexdump(data)).finish(),
+    }
+  }
+}
+
+/// Written as its length and the first `DEBUG_BYTES` bytes, like its `Debug` format.
+#[cfg(feature = "defmt")]
+impl defmt::Format for Hexdump<'_> {
+  fn format(&self, f: defmt::Formatter<'_>) {
+    let len = self.0.len();
+    defmt::write!(
+      f,
+      "{=usize} bytes: {=[u8]:02x}",
+      len,
+      &self.0[..len.min(DEBUG_BYTES)]
+    );
+    if len > DEBUG_BYTES {
+      defmt::write!(f, " ...");
+    }
+  }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for TelnetIAC {
+  fn format(&self, f: defmt::Formatter<'_>) {
+    defmt::write!(f, "TelnetIAC({})", self.command)
+  }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for TelnetNegotiation {
+  fn format(&self, f: defmt::Formatter<'_>) {
+    defmt::write!(
+      f,
+      "TelnetNegotiation({}, {})",
+      self.command,
+      TelnetOption::from(self.option)
+    )
+  }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for TelnetNegotiationComplete {
+  fn format(&self, f: defmt::Formatter<'_>) {
+    defmt::write!(
+      f,
+      "TelnetNegotiationComplete {{ option: {}, accepted: {=bool}, local: {=bool} }}",
+      TelnetOption::from(self.option),
+      self.accepted,
+      self.local
+    )
+  }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for TelnetSubnegotiation {
+  fn format(&self, f: defmt::Formatter<'_>) {
+    defmt::write!(
+      f,
+      "TelnetSubnegotiation({}, {})",
+      TelnetOption::from(self.option),
+      Hexdump(&self.buffer)
+    )
+  }
+}
+
+/// Written like the `Debug` format, except that events carrying a protocol message of their own, such as `Sound`
+/// or `ComPort`, are written by name only.
+#[cfg(feature = "defmt")]
+impl defmt::Format for TelnetEvents {
+  fn format(&self, f: defmt::Formatter<'_>) {
+    match self {
+      TelnetEvents::IAC(iac) => defmt::write!(f, "IAC({})", iac.command),
+      TelnetEvents::DataMark => defmt::write!(f, "DataMark"),
+      TelnetEvents::Negotiation(neg) => defmt::write!(
+        f,
+        "Negotiation({}, {})",
+        neg.command,
+        TelnetOption::from(neg.option)
+      ),
+      TelnetEvents::NegotiationComplete(complete) => defmt::write!(
+        f,
+        "NegotiationComplete {{ option: {}, accepted: {=bool}, local: {=bool} }}",
+        TelnetOption::from(complete.option),
+        complete.accepted,
+        complete.local
+      ),
+      TelnetEvents::Subnegotiation(sub) => defmt::write!(
+        f,
+        "Subnegotiation({}, {})",
+        TelnetOption::from(sub.option),
+        Hexdump(&sub.buffer)
+      ),
+      TelnetEvents::DataReceive(data) => defmt::write!(f, "DataReceive({})", Hexdump(data)),
+      TelnetEvents::DataSend(data) => defmt::write!(f, "DataSend({})", Hexdump(data)),
+      TelnetEvents::Line(data) => defmt::write!(f, "Line({})", Hexdump(data)),
+      TelnetEvents::Text(text) => defmt::write!(f, "Text({=str})", text.as_str()),
+      TelnetEvents::Outbound(event) => defmt::write!(f, "Outbound({})", event.as_ref()),
+      TelnetEvents::Prompt(data) => defmt::write!(f, "Prompt({})", Hexdump(data)),
+      TelnetEvents::Sound(_) => defmt::write!(f, "Sound(..)"),
+      TelnetEvents::Aard102(update) => {
+        defmt::write!(f, "Aard102({=u8}, {=u8})", update.channel, update.value)
+      }
+      TelnetEvents::TerminalSpeed(_) => defmt::write!(f, "TerminalSpeed(..)"),
+      TelnetEvents::DisplayLocation(_) => defmt::write!(f, "DisplayLocation(..)"),
+      TelnetEvents::FlowControl(_) => defmt::write!(f, "FlowControl(..)"),
+      TelnetEvents::AuthName(_) => defmt::write!(f, "AuthName(..)"),
+      TelnetEvents::AuthComplete { accepted, .. } => {
+        defmt::write!(f, "AuthComplete {{ accepted: {=bool} }}", accepted)
+      }
+      TelnetEvents::Encrypt(_) => defmt::write!(f, "Encrypt(..)"),
+      TelnetEvents::Tn3270e(_) => defmt::write!(f, "Tn3270e(..)"),
+      TelnetEvents::ComPort(_) => defmt::write!(f, "ComPort(..)"),
+      TelnetEvents::Resynced(option) => {
+        defmt::write!(f, "Resynced({})", TelnetOption::from(*option))
+      }
+      TelnetEvents::StateMismatch(mismatches) => {
+        defmt::write!(f, "StateMismatch({=usize} options)", mismatches.len())
+      }
+      TelnetEvents::EnvironChange(vars) => {
+        defmt::write!(f, "EnvironChange({=usize} variables)", vars.len())
+      }
+      TelnetEvents::Error(kind, data) => defmt::write!(f, "Error({}, {})", kind, Hexdump(data)),
+      TelnetEvents::PeerStack(_) => defmt::write!(f, "PeerStack(..)"),
+      TelnetEvents::EndCompression(option) => {
+        defmt::write!(f, "EndCompression({})", TelnetOption::from(*option))
+      }
+      TelnetEvents::DecompressionEnded(option) => {
+        defmt::write!(f, "DecompressionEnded({})", TelnetOption::from(*option))
+      }
+      TelnetEvents::CompressionStats(_) => defmt::write!(f, "CompressionStats(..)"),
+      TelnetEvents::AnomalousSubnegotiation(option, len) => defmt::write!(
+        f,
+        "AnomalousSubnegotiation({}, {=usize})",
+        TelnetOption::from(*option),
+        len
+      ),
+      TelnetEvents::KeepaliveAck => defmt::write!(f, "KeepaliveAck"),
+      TelnetEvents::Pong { rtt_hint } => defmt::write!(f, "Pong {{ rtt_hint: {=u64} }}", rtt_hint),
+      TelnetEvents::LogoutRequested => defmt::write!(f, "LogoutRequested"),
+      TelnetEvents::LogoutAnnounced => defmt::write!(f, "LogoutAnnounced"),
+      TelnetEvents::Shutdown => defmt::write!(f, "Shutdown"),
+      TelnetEvents::DecompressImmediate(data) => {
+        defmt::write!(f, "DecompressImmediate({})", Hexdump(data))
+      }
+      TelnetEvents::DecompressClientStream(data) => {
+        defmt::write!(f, "DecompressClientStream({})", Hexdump(data))
+      }
+      TelnetEvents::StartTls(data) => defmt::write!(f, "StartTls({})", Hexdump(data)),
+      TelnetEvents::Encrypted(data) => defmt::write!(f, "Encrypted({})", Hexdump(data)),
+    }
+  }
+}
+
+impl fmt::Debug for TelnetEventRef<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TelnetEventRef::IAC(iac) => f
+        .debug_tuple("IAC")
+        .field(&CommandName(iac.command))
+        .finish(),
+      TelnetEventRef::DataMark => f.write_str("DataMark"),
+      TelnetEventRef::Negotiation(neg) => f
+        .debug_tuple("Negotiation")
+        .field(&CommandName(neg.command))
+        .field(&OptionName(neg.option))
+        .finish(),
+      TelnetEventRef::Subnegotiation(option, data) => f
+        .debug_tuple("Subnegotiation")
+        .field(&OptionName(*option))
+        .field(&Hexdump(data))
+        .finish(),
+      TelnetEventRef::DataReceive(data) => {
+        f.debug_tuple("DataReceive").field(&Hexdump(data)).finish()
+      }
+      TelnetEventRef::DataSend(data) => f.debug_tuple("DataSend").field(&Hexdump(data)).finish(),
+      TelnetEventRef::Line(data) => f.debug_tuple("Line").field(&Hexdump(data)).finish(),
+      TelnetEventRef::Error(kind, data) => f
+        .debug_tuple("Error")
+        .field(kind)
+        .field(&Hexdump(data))
+        .finish(),
+      TelnetEventRef::Other(event) => f.debug_tuple("Other").field(event).finish(),
+    }
+  }
+}
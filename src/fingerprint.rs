@@ -0,0 +1,192 @@
+//! Best-effort identification of the telnet stack on the other end of a connection.
+//!
+//! A `Fingerprinter` watches the negotiations a peer opens with, how quickly it sends them, and any terminal type it
+//! reports, and guesses which client or server it is talking to. The guess is a heuristic: it is good enough for
+//! analytics and for switching on known quirk workarounds, but it should never be trusted for anything that matters.
+
+use crate::telnet::{op_command::*, op_option::*};
+use alloc::{string::String, vec::Vec};
+
+/// The number of negotiations kept as the peer's opening sequence.
+const OPENING_LEN: usize = 16;
+
+/// A telnet implementation recognised by a `Fingerprinter`.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PeerStack {
+  /// The PuTTY terminal emulator.
+  PuTTY,
+  /// The telnet client that ships with Windows.
+  WindowsTelnet,
+  /// The TinTin++ MUD client.
+  TinTin,
+  /// The Mudlet MUD client.
+  Mudlet,
+  /// A server built on the C libtelnet library.
+  Libtelnet,
+}
+
+/// How long the peer took to send something, measured by the caller from the previous thing it sent (or from the
+/// connection opening).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimingBucket {
+  /// Under 50ms, as part of the same burst.
+  Immediate,
+  /// Under a second.
+  Fast,
+  /// A second or more.
+  Slow,
+}
+
+impl TimingBucket {
+  /// Bucket a delay given in milliseconds.
+  pub fn from_millis(millis: u64) -> Self {
+    match millis {
+      0..=49 => TimingBucket::Immediate,
+      50..=999 => TimingBucket::Fast,
+      _ => TimingBucket::Slow,
+    }
+  }
+}
+
+/// Collects what a peer sends at the start of a connection and guesses its telnet stack.
+#[derive(Clone, Debug, Default)]
+pub struct Fingerprinter {
+  opening: Vec<(u8, u8)>,
+  opening_closed: bool,
+  terminal_types: Vec<String>,
+  guess: Option<PeerStack>,
+}
+
+impl Fingerprinter {
+  /// Create an empty fingerprinter.
+  pub fn new() -> Self {
+    Self::default()
+  }
+  /// Record a negotiation received from the peer.
+  ///
+  /// # Returns
+  ///
+  /// `Option<PeerStack>` - The new guess, if this negotiation changed it.
+  pub fn observe_negotiation(
+    &mut self,
+    command: impl Into<u8>,
+    option: impl Into<u8>,
+  ) -> Option<PeerStack> {
+    let command = command.into();
+    let option = option.into();
+    if !self.opening_closed && self.opening.len() < OPENING_LEN {
+      self.opening.push((command, option));
+    }
+    self.update()
+  }
+  /// Record a terminal type reported by the peer with TTYPE.
+  ///
+  /// # Returns
+  ///
+  /// `Option<PeerStack>` - The new guess, if this terminal type changed it.
+  pub fn observe_terminal_type(&mut self, name: &str) -> Option<PeerStack> {
+    self.terminal_types.push(name.to_ascii_uppercase());
+    self.update()
+  }
+  /// Record the delay before the peer's next transmission.
+  ///
+  /// # Notes
+  ///
+  /// A `Slow` delay ends the peer's opening sequence, so that negotiations sent later in the session (usually in
+  /// answer to something) are not mistaken for its unprompted opening.
+  pub fn observe_delay(&mut self, delay: TimingBucket) {
+    if delay == TimingBucket::Slow && !self.opening.is_empty() {
+      self.opening_closed = true;
+    }
+  }
+  /// The current guess, or None if nothing recognisable has been seen yet.
+  pub fn guess(&self) -> Option<PeerStack> {
+    self.guess
+  }
+  /// The negotiations the peer opened with, in the order received.
+  pub fn opening(&self) -> &[(u8, u8)] {
+    &self.opening
+  }
+
+  fn update(&mut self) -> Option<PeerStack> {
+    let guess = self.classify();
+    if guess.is_some() && guess != self.guess {
+      self.guess = guess;
+      guess
+    } else {
+      None
+    }
+  }
+
+  fn classify(&self) -> Option<PeerStack> {
+    // A self-reported name beats any pattern.
+    for name in self.terminal_types.iter() {
+      if name.starts_with("MUDLET") {
+        return Some(PeerStack::Mudlet);
+      }
+      if name.starts_with("TINTIN") {
+        return Some(PeerStack::TinTin);
+      }
+      if name.starts_with("PUTTY") {
+        return Some(PeerStack::PuTTY);
+      }
+    }
+    let opened = |command: u8, option: u8| self.opening.contains(&(command, option));
+    if opened(WILL, AUTHENTICATION) {
+      // Offering NTLM authentication unprompted is characteristic of the Windows client.
+      return Some(PeerStack::WindowsTelnet);
+    }
+    if self.opening.starts_with(&[
+      (WILL, NAWS),
+      (WILL, TSPEED),
+      (WILL, TTYPE),
+      (WILL, NEWENVIRON),
+    ]) {
+      return Some(PeerStack::PuTTY);
+    }
+    if self.opening.first() == Some(&(WILL, MCCP2)) && opened(DO, TTYPE) {
+      return Some(PeerStack::Libtelnet);
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod test_fingerprint {
+  use super::*;
+
+  #[test]
+  fn test_opening_patterns() {
+    let mut fp = Fingerprinter::new();
+    assert_eq!(fp.observe_negotiation(WILL, NAWS), None);
+    fp.observe_negotiation(WILL, TSPEED);
+    fp.observe_negotiation(WILL, TTYPE);
+    assert_eq!(
+      fp.observe_negotiation(WILL, NEWENVIRON),
+      Some(PeerStack::PuTTY)
+    );
+    // Unchanged guesses are only reported once.
+    assert_eq!(fp.observe_negotiation(DO, ECHO), None);
+    assert_eq!(fp.guess(), Some(PeerStack::PuTTY));
+
+    let mut fp = Fingerprinter::new();
+    fp.observe_negotiation(DO, SGA);
+    fp.observe_delay(TimingBucket::from_millis(5000));
+    fp.observe_negotiation(WILL, AUTHENTICATION);
+    assert_eq!(fp.guess(), None);
+    assert_eq!(fp.opening(), &[(DO, SGA)]);
+  }
+
+  #[test]
+  fn test_terminal_type() {
+    let mut fp = Fingerprinter::new();
+    fp.observe_negotiation(WILL, AUTHENTICATION);
+    assert_eq!(fp.guess(), Some(PeerStack::WindowsTelnet));
+    assert_eq!(
+      fp.observe_terminal_type("Mudlet 4.17"),
+      Some(PeerStack::Mudlet)
+    );
+  }
+}
@@ -0,0 +1,99 @@
+//! Tricky byte sequences for exercising telnet stacks.
+//!
+//! These are the inputs the parser's own tests lean on. Downstream applications can feed them through their full
+//! stack (parser, handlers and whatever sits behind them) to make sure nothing panics or desynchronizes.
+
+use crate::telnet::{op_command::*, op_option::*};
+
+/// A named input sequence, as it would arrive from the remote end of a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fixture {
+  /// A short, unique identifier.
+  pub name: &'static str,
+  /// What makes this input interesting.
+  pub description: &'static str,
+  /// The raw bytes to receive.
+  pub input: &'static [u8],
+}
+
+static FIXTURES: &[Fixture] = &[
+  Fixture {
+    name: "truncated_subnegotiation",
+    description: "A subnegotiation that never reaches IAC SE.",
+    input: &[IAC, SB, GMCP, b'C', b'o', b'r', b'e'],
+  },
+  Fixture {
+    name: "truncated_negotiation",
+    description: "A negotiation cut off before its option byte.",
+    input: &[b'h', b'i', IAC, WILL],
+  },
+  Fixture {
+    name: "lone_iac",
+    description: "Data ending in a single IAC.",
+    input: &[b'h', b'i', IAC],
+  },
+  Fixture {
+    name: "empty_subnegotiation",
+    description: "IAC SB IAC SE with no option byte at all.",
+    input: &[IAC, SB, IAC, SE],
+  },
+  Fixture {
+    name: "crossed_negotiation",
+    description: "Both sides asking for the same option at once, followed by a change of mind.",
+    input: &[
+      IAC, WILL, ECHO, IAC, DO, ECHO, IAC, WONT, ECHO, IAC, DONT, ECHO,
+    ],
+  },
+  Fixture {
+    name: "negotiation_storm",
+    description: "The same option toggled on and off repeatedly.",
+    input: &[
+      IAC, WILL, SGA, IAC, WONT, SGA, IAC, WILL, SGA, IAC, WONT, SGA, IAC, WILL, SGA, IAC, WONT,
+      SGA,
+    ],
+  },
+  Fixture {
+    name: "iac_heavy_data",
+    description: "Data made almost entirely of escaped IAC bytes.",
+    input: &[IAC, IAC, IAC, IAC, b'x', IAC, IAC, IAC, IAC, IAC, IAC],
+  },
+  Fixture {
+    name: "iac_heavy_subnegotiation",
+    description: "A subnegotiation payload containing escaped IAC bytes.",
+    input: &[IAC, SB, GMCP, IAC, IAC, b'a', IAC, IAC, IAC, SE],
+  },
+  Fixture {
+    name: "unknown_command",
+    description: "IAC followed by a byte that is not a known command.",
+    input: &[IAC, 7, b'o', b'k'],
+  },
+  Fixture {
+    name: "se_without_sb",
+    description: "An IAC SE that does not close anything.",
+    input: &[b'a', IAC, SE, b'b'],
+  },
+  Fixture {
+    name: "mccp2_start_with_trailing_data",
+    description: "An MCCP2 start sequence immediately followed by bytes that would be compressed.",
+    input: &[IAC, SB, MCCP2, IAC, SE, 0x78, 0x9c, 0x01],
+  },
+  Fixture {
+    name: "mccp2_copyover",
+    description: "What a client sees after a server copyover ends its MCCP2 stream: MCCP2 switched off, a message, \
+                  then MCCP2 offered twice and restarted.",
+    input: &[
+      IAC, WONT, MCCP2, b'C', b'o', b'p', b'y', b'o', b'v', b'e', b'r', b'\r', b'\n', IAC, WILL, MCCP2, IAC, WILL,
+      MCCP2, IAC, SB, MCCP2, IAC, SE,
+    ],
+  },
+];
+
+/// Get every built-in fixture.
+pub fn fixtures() -> &'static [Fixture] {
+  FIXTURES
+}
+
+/// Look up a built-in fixture by name.
+pub fn fixture(name: &str) -> Option<&'static Fixture> {
+  FIXTURES.iter().find(|fixture| fixture.name == name)
+}
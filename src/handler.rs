@@ -0,0 +1,44 @@
+use crate::events::{TelnetEvents, TelnetIAC, TelnetNegotiation, TelnetSubnegotiation};
+use crate::sink::EventSink;
+use alloc::string::String;
+use bytes::Bytes;
+
+/// Callbacks for handling parsed events directly, used with `Parser::receive_with`.
+///
+/// Every method has an empty default implementation, so only the events of interest need to be handled.
+pub trait TelnetHandler {
+  /// Regular data received from the remote end.
+  fn on_data(&mut self, _data: Bytes) {}
+  /// A complete line received from the remote end, when `ParserBuilder::line_mode` is enabled.
+  fn on_line(&mut self, _line: Bytes) {}
+  /// Text received from the remote end, when `ParserBuilder::utf8_text` is enabled.
+  fn on_text(&mut self, _text: String) {}
+  /// An IAC command sequence.
+  fn on_iac(&mut self, _iac: TelnetIAC) {}
+  /// An IAC negotiation sequence.
+  fn on_negotiation(&mut self, _negotiation: TelnetNegotiation) {}
+  /// An IAC subnegotiation sequence.
+  fn on_subnegotiation(&mut self, _subnegotiation: TelnetSubnegotiation) {}
+  /// Data that should be sent to the remote end.
+  fn on_send(&mut self, _data: Bytes) {}
+  /// Any other event, such as `NegotiationComplete`, `Error` or `DecompressImmediate`.
+  fn on_event(&mut self, _event: TelnetEvents) {}
+}
+
+/// Adapts a `TelnetHandler` into an `EventSink`.
+pub(crate) struct HandlerSink<'a, H: ?Sized>(pub(crate) &'a mut H);
+
+impl<H: TelnetHandler + ?Sized> EventSink for HandlerSink<'_, H> {
+  fn push(&mut self, event: TelnetEvents) {
+    match event {
+      TelnetEvents::DataReceive(data) => self.0.on_data(data),
+      TelnetEvents::Line(line) => self.0.on_line(line),
+      TelnetEvents::Text(text) => self.0.on_text(text),
+      TelnetEvents::IAC(iac) => self.0.on_iac(iac),
+      TelnetEvents::Negotiation(neg) => self.0.on_negotiation(neg),
+      TelnetEvents::Subnegotiation(sub) => self.0.on_subnegotiation(sub),
+      TelnetEvents::DataSend(data) => self.0.on_send(data),
+      event => self.0.on_event(event),
+    }
+  }
+}
@@ -0,0 +1,170 @@
+use crate::events;
+use crate::Parser;
+use alloc::vec::Vec;
+use bytes::Bytes;
+
+/// A 2 byte IAC sequence with a `u8` command, as in versions before 2.0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TelnetIAC {
+  pub command: u8,
+}
+
+impl TelnetIAC {
+  pub fn new(command: u8) -> Self {
+    Self { command }
+  }
+  /// Consume the sequence struct and return the bytes.
+  pub fn into_bytes(self) -> Vec<u8> {
+    events::TelnetIAC::from(self).into()
+  }
+}
+
+impl From<events::TelnetIAC> for TelnetIAC {
+  fn from(value: events::TelnetIAC) -> Self {
+    Self::new(value.raw_command())
+  }
+}
+
+impl From<TelnetIAC> for events::TelnetIAC {
+  fn from(value: TelnetIAC) -> Self {
+    Self::new(value.command)
+  }
+}
+
+/// A 3 byte IAC sequence with a `u8` command, as in versions before 2.0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TelnetNegotiation {
+  pub command: u8,
+  pub option: u8,
+}
+
+impl TelnetNegotiation {
+  pub fn new(command: u8, option: u8) -> Self {
+    Self { command, option }
+  }
+  /// Consume the sequence struct and return the bytes.
+  pub fn into_bytes(self) -> Vec<u8> {
+    events::TelnetNegotiation::from(self).into()
+  }
+}
+
+impl From<events::TelnetNegotiation> for TelnetNegotiation {
+  fn from(value: events::TelnetNegotiation) -> Self {
+    Self::new(value.raw_command(), value.option)
+  }
+}
+
+impl From<TelnetNegotiation> for events::TelnetNegotiation {
+  fn from(value: TelnetNegotiation) -> Self {
+    Self::new(value.command, value.option)
+  }
+}
+
+/// A subnegotiation sequence with a `Vec<u8>` buffer, as in versions before 2.0.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TelnetSubnegotiation {
+  pub option: u8,
+  pub buffer: Vec<u8>,
+}
+
+impl From<events::TelnetSubnegotiation> for TelnetSubnegotiation {
+  fn from(value: events::TelnetSubnegotiation) -> Self {
+    Self {
+      option: value.option,
+      buffer: value.buffer.to_vec(),
+    }
+  }
+}
+
+impl From<TelnetSubnegotiation> for events::TelnetSubnegotiation {
+  fn from(value: TelnetSubnegotiation) -> Self {
+    Self::new(value.option, Bytes::from(value.buffer))
+  }
+}
+
+/// Telnet events with `Vec<u8>` buffers, as in versions before 2.0.
+///
+/// Events that did not exist before 2.0 are passed through unchanged as `Other`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TelnetEvents {
+  /// An IAC command sequence.
+  IAC(TelnetIAC),
+  /// An IAC negotiation sequence.
+  Negotiation(TelnetNegotiation),
+  /// An IAC subnegotiation sequence.
+  Subnegotiation(TelnetSubnegotiation),
+  /// Regular data received from the remote end.
+  DataReceive(Vec<u8>),
+  /// Any data to be sent to the remote end.
+  DataSend(Vec<u8>),
+  /// MCCP2 compatibility. MUST DECOMPRESS THIS DATA BEFORE PARSING
+  DecompressImmediate(Vec<u8>),
+  /// An event that has no pre-2.0 equivalent.
+  Other(events::TelnetEvents),
+}
+
+impl From<events::TelnetEvents> for TelnetEvents {
+  fn from(value: events::TelnetEvents) -> Self {
+    match value {
+      events::TelnetEvents::IAC(iac) => TelnetEvents::IAC(iac.into()),
+      events::TelnetEvents::Negotiation(neg) => TelnetEvents::Negotiation(neg.into()),
+      events::TelnetEvents::Subnegotiation(sub) => TelnetEvents::Subnegotiation(sub.into()),
+      events::TelnetEvents::DataReceive(data) => TelnetEvents::DataReceive(data.to_vec()),
+      events::TelnetEvents::DataSend(data) => TelnetEvents::DataSend(data.to_vec()),
+      events::TelnetEvents::DecompressImmediate(data) => {
+        TelnetEvents::DecompressImmediate(data.to_vec())
+      }
+      event => TelnetEvents::Other(event),
+    }
+  }
+}
+
+impl From<TelnetEvents> for events::TelnetEvents {
+  fn from(value: TelnetEvents) -> Self {
+    match value {
+      TelnetEvents::IAC(iac) => events::TelnetEvents::IAC(iac.into()),
+      TelnetEvents::Negotiation(neg) => events::TelnetEvents::Negotiation(neg.into()),
+      TelnetEvents::Subnegotiation(sub) => events::TelnetEvents::Subnegotiation(sub.into()),
+      TelnetEvents::DataReceive(data) => events::TelnetEvents::DataReceive(Bytes::from(data)),
+      TelnetEvents::DataSend(data) => events::TelnetEvents::DataSend(Bytes::from(data)),
+      TelnetEvents::DecompressImmediate(data) => {
+        events::TelnetEvents::DecompressImmediate(Bytes::from(data))
+      }
+      TelnetEvents::Other(event) => event,
+    }
+  }
+}
+
+impl From<TelnetEvents> for Vec<u8> {
+  fn from(value: TelnetEvents) -> Self {
+    let b: Bytes = events::TelnetEvents::from(value).into();
+    b.to_vec()
+  }
+}
+
+impl Parser {
+  /// Receive bytes into the internal buffer, returning events with `Vec<u8>` buffers as in versions before 2.0.
+  ///
+  /// # Arguments
+  ///
+  /// * `data` - The bytes to be received. This should be sourced from the remote side of a connection.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<legacy::TelnetEvents>` - Any events parsed from the internal buffer with the new bytes.
+  pub fn receive_legacy(&mut self, data: &[u8]) -> Vec<TelnetEvents> {
+    self
+      .receive(data)
+      .into_iter()
+      .map(TelnetEvents::from)
+      .collect()
+  }
+  /// Escape IAC bytes in data, returning a `Vec<u8>` as in versions before 2.0.
+  pub fn escape_iac_vec(data: &[u8]) -> Vec<u8> {
+    Parser::escape_iac(data.to_vec()).to_vec()
+  }
+  /// Reverse escaped IAC bytes, returning a `Vec<u8>` as in versions before 2.0.
+  pub fn unescape_iac_vec(data: &[u8]) -> Vec<u8> {
+    Parser::unescape_iac(data.to_vec()).to_vec()
+  }
+}
@@ -5,25 +5,111 @@ extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std as alloc;
 
+pub mod anomaly;
+mod builder;
+pub mod charset;
 pub mod compatibility;
+pub mod compression;
+pub mod error;
 pub mod events;
+pub mod fingerprint;
+pub mod fixtures;
+pub mod handler;
+#[cfg(feature = "legacy-vec-api")]
+pub mod legacy;
+pub mod newline;
+pub mod outbound;
+pub mod pool;
+pub mod protocol;
+pub mod replay;
+pub mod sanitize;
+pub mod simulate;
+pub mod sink;
 pub mod telnet;
+pub mod trace;
+pub mod websocket;
 
-use alloc::{format, vec::Vec};
+use alloc::{boxed::Box, collections::VecDeque, string::String, vec::Vec};
 pub use bytes;
 
+use crate::telnet::aard102::Aard102;
+use crate::telnet::auth::{self, AuthMechanism, AuthMessage, AuthPair, AuthStep};
+use crate::telnet::comport::{ComPortCommand, ComPortMessage};
+use crate::telnet::encrypt::{Cipher, EncryptMessage, EncryptState};
+use crate::telnet::environ::{
+  EnvironCommand, EnvironKind, EnvironMessage, EnvironVar, LegacyEnviron,
+};
+use crate::telnet::gmcp::GmcpMessage;
+use crate::telnet::lflow::{LflowCommand, LflowState};
+use crate::telnet::linemode::{self, LinemodeMode, SlcEntry, SlcTable};
+use crate::telnet::mnes::Mnes;
+use crate::telnet::msdp::{MsdpCommand, MsdpValue};
+use crate::telnet::msp::{MspScanner, MspSegment};
+use crate::telnet::mtts::Mtts;
+use crate::telnet::naws::Naws;
 use crate::telnet::op_command::*;
+use crate::telnet::status::StatusReport;
+use crate::telnet::tn3270e::Tn3270eMessage;
+use crate::telnet::tspeed::TspeedMessage;
+use crate::telnet::ttype::TtypeMessage;
+use crate::telnet::xdisploc::XdisplocMessage;
 
-use bytes::{BufMut, Bytes, BytesMut};
+use anomaly::SubnegotiationSizes;
+use builder::ParserConfig;
+pub use builder::{ParserBuilder, ParserMode};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use charset::{Decoded, TextDecoder, TextEncoder, Unmappable, Utf8Decoder};
 use compatibility::*;
+use compression::{CompressionStats, Direction};
+use error::{SubnegotiationRefusal, TelnetError};
+use fingerprint::{Fingerprinter, PeerStack, TimingBucket};
+use handler::{HandlerSink, TelnetHandler};
+use newline::{Assembled, LineAssembler, NewlineTranslator, TextFormat};
+use pool::{BufferPool, PooledBuffer};
+use protocol::Protocol;
+use sanitize::{Sanitized, Sanitizer};
+use sink::EventSink;
 
 pub enum EventType {
   None(Bytes),
   IAC(Bytes),
   SubNegotiation(Bytes, Option<Bytes>),
+  PartialSub(Bytes),
+  SubAborted(Bytes),
   Neg(Bytes),
 }
 
+/// Encode an environment message for NEW-ENVIRON, or for old ENVIRON with the given type codes.
+fn encode_environ(message: &EnvironMessage, legacy: Option<LegacyEnviron>) -> Bytes {
+  match legacy {
+    Some(legacy) => message.encode_legacy(legacy),
+    None => message.encode(),
+  }
+}
+
+/// Find the first IAC byte in `data`.
+///
+/// Scans a machine word at a time, which is much faster than a byte loop for long runs of plain text.
+fn find_iac(data: &[u8]) -> Option<usize> {
+  const LO: u64 = u64::from_ne_bytes([0x01; 8]);
+  const HI: u64 = u64::from_ne_bytes([0x80; 8]);
+  let mut chunks = data.chunks_exact(8);
+  for (i, chunk) in chunks.by_ref().enumerate() {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(chunk);
+    // IAC bytes become zero, then the classic "has a zero byte" test.
+    let word = !u64::from_ne_bytes(word);
+    if word.wrapping_sub(LO) & !word & HI != 0 {
+      return chunk.iter().position(|&b| b == IAC).map(|p| i * 8 + p);
+    }
+  }
+  let tail = chunks.remainder();
+  tail
+    .iter()
+    .position(|&b| b == IAC)
+    .map(|p| data.len() - tail.len() + p)
+}
+
 #[macro_export]
 /// Macro for calling `Bytes::copy_from_slice()`
 macro_rules! vbytes {
@@ -32,48 +118,221 @@ macro_rules! vbytes {
   };
 }
 
+#[doc(hidden)]
+pub use alloc::format as __format;
+
+#[macro_export]
+/// Macro for building a `TelnetEvents::DataSend` from `format!()` arguments, with IAC bytes escaped.
+///
+/// # Example
+///
+/// ```
+/// use libtelnet_rs::text;
+///
+/// let event = text!("You have {} gold.\r\n", 5);
+/// ```
+macro_rules! text {
+  ($($arg:tt)*) => {
+    $crate::events::TelnetEvents::from($crate::__format!($($arg)*))
+  };
+}
+
+/// An iterator over the events parsed from data supplied with `Parser::feed()`.
+///
+/// Created by `Parser::events()`.
+pub struct Events<'a> {
+  parser: &'a mut Parser,
+}
+
+impl Iterator for Events<'_> {
+  type Item = events::TelnetEvents;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.parser.next_event()
+  }
+}
+
 /// A telnet parser that handles the main parts of the protocol.
 pub struct Parser {
   pub options: CompatibilityTable,
-  buffer: BytesMut,
+  buffer: PooledBuffer,
+  prompt: Option<Bytes>,
+  pending: [u8; 256],
+  environ: Vec<EnvironVar>,
+  remote_environ: Vec<EnvironVar>,
+  repeated: [u8; 256],
+  config: ParserConfig,
+  queued: VecDeque<events::TelnetEvents>,
+  outbound: Vec<events::TelnetSubnegotiation>,
+  scratch: Vec<EventType>,
+  urgent: Option<usize>,
+  overflowed: bool,
+  fingerprinter: Option<Fingerprinter>,
+  encoder: Option<(Box<dyn TextEncoder>, Unmappable)>,
+  decoder: Option<Box<dyn TextDecoder>>,
+  compression: CompressionStats,
+  compression_reported: u64,
+  sanitizer: Option<Sanitizer>,
+  newline: Option<NewlineTranslator>,
+  lines: Option<LineAssembler>,
+  utf8: Option<Utf8Decoder>,
+  msp: Option<MspScanner>,
+  sizes: SubnegotiationSizes,
+  decompressing: Option<u8>,
+  /// The TIMING-MARK probes not yet answered, in the order they were sent, with the time each `ping` was sent.
+  keepalives: VecDeque<Option<u64>>,
+  /// The latest time given to `ping` or `set_time`.
+  clock: u64,
+  /// Whether we asked the remote end to log us out with `request_logout`.
+  logout_requested: bool,
+  prompt_line: BytesMut,
+  /// Our window size, reported to the remote end over NAWS.
+  window: Option<Naws>,
+  /// The window size last reported by the remote end over NAWS.
+  remote_window: Option<Naws>,
+  /// Our terminal types, reported in turn to the remote end's TERMINAL-TYPE requests.
+  terminal_names: Vec<String>,
+  /// How many of `terminal_names` have been reported in the current cycle.
+  terminal_index: usize,
+  /// The terminal types reported by the remote end so far.
+  terminal_types: Vec<String>,
+  /// Whether we are asking the remote end for terminal types until its list repeats.
+  cycling_terminal_types: bool,
+  /// The last STATUS report received from the remote end.
+  remote_status: Option<StatusReport>,
+  /// The LINEMODE mode agreed with the remote end, without its `MODE_ACK` bit.
+  linemode: Option<LinemodeMode>,
+  /// Our LINEMODE local characters, as agreed with the remote end.
+  slc: SlcTable,
+  /// The local flow control requested by the remote end with TOGGLE-FLOW-CONTROL.
+  lflow: LflowState,
+  /// The AUTHENTICATION mechanisms we support, in order of preference.
+  auth: Vec<Box<dyn AuthMechanism>>,
+  /// The index in `auth` of the mechanism whose exchange is in progress.
+  auth_active: Option<usize>,
+  /// The cipher for ENCRYPT, used in whichever directions are encrypted.
+  cipher: Option<Box<dyn Cipher>>,
+  encryption: EncryptState,
+  /// How much of the ENCRYPT `END` sequence has been seen in the decrypted input.
+  end_matched: usize,
+  /// Whether we sent START_TLS `FOLLOWS` with `request_tls`, and are waiting for the client's.
+  tls_requested: bool,
+  /// Whether parsing is suspended for a TLS handshake, until `tls_established`.
+  tls_suspended: bool,
 }
 
 impl Default for Parser {
   fn default() -> Parser {
-    Parser {
-      options: CompatibilityTable::new(),
-      buffer: BytesMut::with_capacity(128),
-    }
+    Parser::with_support_and_capacity(128, CompatibilityTable::new())
   }
 }
 
 impl Parser {
+  /// We are waiting for a DO/DONT answer to our WILL/WONT.
+  const PENDING_LOCAL: u8 = 1;
+  /// The outstanding local request was WILL.
+  const WANT_LOCAL: u8 = 1 << 1;
+  /// We are waiting for a WILL/WONT answer to our DO/DONT.
+  const PENDING_REMOTE: u8 = 1 << 2;
+  /// The outstanding remote request was DO.
+  const WANT_REMOTE: u8 = 1 << 3;
+  /// Our side of the option is being disabled and re-enabled by `resync_option`.
+  const RESYNC_LOCAL: u8 = 1 << 4;
+  /// Their side of the option is being disabled and re-enabled by `resync_option`.
+  const RESYNC_REMOTE: u8 = 1 << 5;
+  /// The most bytes of an unterminated line kept for a `TelnetEvents::Prompt`. Only the end of a longer line is kept.
+  const PROMPT_LIMIT: usize = 4096;
+
   /// Create a default, empty Parser with an internal buffer capacity of 128 bytes.
   pub fn new() -> Self {
     Self::default()
   }
+  /// Create a `ParserBuilder` for configuring optional parser behavior.
+  pub fn builder() -> ParserBuilder {
+    ParserBuilder::new()
+  }
   /// Create an empty parser, setting the initial internal buffer capcity.
   pub fn with_capacity(size: usize) -> Self {
-    Self {
-      options: CompatibilityTable::new(),
-      buffer: BytesMut::with_capacity(size),
-    }
+    Self::with_support_and_capacity(size, CompatibilityTable::new())
   }
   /// Create an parser, setting the initial internal buffer capacity and directly supplying a CompatibilityTable.
   pub fn with_support_and_capacity(size: usize, table: CompatibilityTable) -> Self {
     Self {
       options: table,
-      buffer: BytesMut::with_capacity(size),
+      buffer: PooledBuffer::new(size),
+      prompt: None,
+      pending: [0; 256],
+      environ: Vec::new(),
+      remote_environ: Vec::new(),
+      repeated: [0; 256],
+      config: ParserConfig::default(),
+      queued: VecDeque::new(),
+      outbound: Vec::new(),
+      scratch: Vec::new(),
+      urgent: None,
+      overflowed: false,
+      fingerprinter: None,
+      encoder: None,
+      decoder: None,
+      compression: CompressionStats::default(),
+      compression_reported: 0,
+      sanitizer: None,
+      newline: None,
+      lines: None,
+      utf8: None,
+      msp: None,
+      sizes: SubnegotiationSizes::default(),
+      decompressing: None,
+      keepalives: VecDeque::new(),
+      clock: 0,
+      logout_requested: false,
+      prompt_line: BytesMut::new(),
+      window: None,
+      remote_window: None,
+      terminal_names: Vec::new(),
+      terminal_index: 0,
+      terminal_types: Vec::new(),
+      cycling_terminal_types: false,
+      remote_status: None,
+      linemode: None,
+      slc: SlcTable::default(),
+      lflow: LflowState::default(),
+      auth: Vec::new(),
+      auth_active: None,
+      cipher: None,
+      encryption: EncryptState::default(),
+      end_matched: 0,
+      tls_requested: false,
+      tls_suspended: false,
     }
   }
   /// Create a parser, directly supplying a CompatibilityTable.
   ///
   /// Uses the default initial buffer capacity of 128 bytes.
   pub fn with_support(table: CompatibilityTable) -> Self {
-    Self {
-      options: table,
-      buffer: BytesMut::with_capacity(128),
-    }
+    Self::with_support_and_capacity(128, table)
+  }
+  /// Create a parser supporting a set of protocols, on both sides of the connection.
+  ///
+  /// # Arguments
+  ///
+  /// `protocols` - The protocols to support, such as `&[Protocol::Gmcp, Protocol::Naws, Protocol::Mccp2]`.
+  ///
+  /// # Notes
+  ///
+  /// Uses the default initial buffer capacity of 128 bytes. Use `ParserBuilder::protocols` to combine this with other
+  /// settings.
+  pub fn with_protocols(protocols: &[Protocol]) -> Self {
+    Self::builder().protocols(protocols).build()
+  }
+  /// Create a parser whose internal buffer is acquired from, and released back to, a `BufferPool`.
+  ///
+  /// Uses the default initial buffer capacity of 128 bytes. The buffer is returned to the pool when the parser is dropped.
+  pub fn with_pool<P>(pool: P) -> Self
+  where
+    P: BufferPool + 'static,
+  {
+    Self::builder().pool(pool).build()
   }
   /// Receive bytes into the internal buffer.
   ///
@@ -86,14 +345,429 @@ impl Parser {
   /// `Vec<events::TelnetEvents>` - Any events parsed from the internal buffer with the new bytes.
   ///
   pub fn receive(&mut self, data: &[u8]) -> Vec<events::TelnetEvents> {
+    let mut event_list: Vec<events::TelnetEvents> = Vec::with_capacity(2);
+    self.receive_into(data, &mut event_list);
+    event_list
+  }
+  /// Receive bytes into the internal buffer, failing on the first problem with the received data.
+  ///
+  /// # Arguments
+  ///
+  /// * `data` - The bytes to be received. This should be sourced from the remote side of a connection.
+  ///
+  /// # Returns
+  ///
+  /// `Result<Vec<events::TelnetEvents>, TelnetError>` - The events parsed from the internal buffer with the new
+  /// bytes, or the first `TelnetEvents::Error` among them.
+  ///
+  /// # Notes
+  ///
+  /// This is for callers that close the connection on any error. Events parsed before the error are discarded,
+  /// including any replies that would have been sent.
+  pub fn try_receive(&mut self, data: &[u8]) -> Result<Vec<events::TelnetEvents>, TelnetError> {
+    let event_list = self.receive(data);
+    for event in event_list.iter() {
+      if let events::TelnetEvents::Error(kind, data) = event {
+        return Err(TelnetError::from((*kind, data.clone())));
+      }
+    }
+    Ok(event_list)
+  }
+  /// Receive bytes into the internal buffer, pushing any parsed events into a caller-provided `EventSink`.
+  ///
+  /// # Arguments
+  ///
+  /// * `data` - The bytes to be received. This should be sourced from the remote side of a connection.
+  ///
+  /// * `sink` - The destination for any events parsed from the internal buffer with the new bytes.
+  ///
+  /// # Notes
+  ///
+  /// Events are pushed in the same order `receive()` would return them. Nothing already in the sink is removed.
+  ///
+  /// Passing the same `Vec` (cleared between calls) lets hot loops reuse one allocation instead of getting a fresh
+  /// `Vec` from every `receive()` call.
+  pub fn receive_into<S: EventSink + ?Sized>(&mut self, data: &[u8], sink: &mut S) {
+    if self.tls_suspended {
+      self.buffer.put(data);
+      return;
+    }
+    if self.encryption.input {
+      match self.decrypt_input(data) {
+        Some(plain) => {
+          self.buffer.put(&plain[..]);
+          self.process(sink);
+        }
+        None => sink.push(events::TelnetEvents::Encrypted(Bytes::copy_from_slice(
+          data,
+        ))),
+      }
+      return;
+    }
+    if self.can_bypass(data) {
+      // Fast path: plain data with nothing buffered from earlier calls needs no parsing.
+      self.receive_data(vbytes!(data), sink);
+      return;
+    }
     self.buffer.put(data);
-    self.process()
+    self.process(sink);
+  }
+  /// Whether received data can skip the parser: it holds no IAC, and nothing is buffered, encrypted or suspended.
+  fn can_bypass(&self, data: &[u8]) -> bool {
+    self.buffer.is_empty()
+      && self.urgent.is_none()
+      && !self.overflowed
+      && !self.encryption.input
+      && !self.tls_suspended
+      && !data.is_empty()
+      && find_iac(data).is_none()
+  }
+  /// Append bytes to the internal buffer without parsing them.
+  ///
+  /// Use `next_event()` or `events()` to parse the buffered data one event at a time.
+  ///
+  /// # Arguments
+  ///
+  /// * `data` - The bytes to be received. This should be sourced from the remote side of a connection.
+  pub fn feed(&mut self, data: &[u8]) {
+    if self.tls_suspended {
+      self.buffer.put(data);
+      return;
+    }
+    match self.decrypt_input(data) {
+      Some(plain) => self.buffer.put(&plain[..]),
+      None => self.buffer.put(data),
+    }
+  }
+  /// Mark the position of the TCP urgent pointer in the inbound stream, starting a SYNCH (RFC 854).
+  ///
+  /// Until the matching Data Mark (`IAC DM`) is parsed, received data is discarded while telnet commands are still
+  /// processed as normal.
+  ///
+  /// # Arguments
+  ///
+  /// * `offset` - The number of bytes, counted from the next byte passed to the parser, up to and including the
+  ///   byte the urgent pointer refers to.
+  ///
+  /// # Notes
+  ///
+  /// A Data Mark that ends before the urgent pointer belongs to an earlier SYNCH and does not stop the discard. If the
+  /// urgent pointer is passed without a Data Mark, data continues to be discarded until the next one.
+  pub fn mark_urgent(&mut self, offset: usize) {
+    self.urgent = Some(self.buffer.len() + offset);
+  }
+  /// Start a SYNCH (RFC 854) when the transport reports urgent data without saying where it is.
+  ///
+  /// Input that is buffered or received from now on is discarded up to the next Data Mark (`IAC DM`), while telnet
+  /// commands are still processed as normal. Interactive clients use this to drop output the remote end sent before an
+  /// interrupt, such as `IAC IP`, took effect. Use `mark_urgent` instead if the urgent pointer is known.
+  pub fn synch(&mut self) {
+    self.urgent = Some(0);
+  }
+  /// Get the next event from data supplied with `feed()`, or None if no complete event is available yet.
+  ///
+  /// # Notes
+  ///
+  /// Buffered data is only parsed once the events already queued have been taken, so a consumer that stops calling
+  /// this method applies backpressure to the parser.
+  pub fn next_event(&mut self) -> Option<events::TelnetEvents> {
+    if self.queued.is_empty() && !self.buffer.is_empty() {
+      let mut queued = core::mem::take(&mut self.queued);
+      self.process(&mut queued);
+      self.queued = queued;
+    }
+    self.queued.pop_front()
+  }
+  /// Get an iterator that yields events from data supplied with `feed()`, by repeatedly calling `next_event()`.
+  pub fn events(&mut self) -> Events<'_> {
+    Events { parser: self }
+  }
+  /// Force out everything the parser is holding on to, for use on idle timeouts or when the connection closes.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<events::TelnetEvents>` - Any events still queued from `feed()`, followed by events for any data left in
+  /// the internal buffer.
+  ///
+  /// # Notes
+  ///
+  /// Trailing data is emitted as `DataReceive`. An incomplete command sequence, such as a subnegotiation missing its
+  /// `IAC SE`, is emitted as a `TelnetEvents::Error` with `TelnetErrorKind::Truncated`. The internal buffer is empty
+  /// afterwards, unless parsing is suspended for a TLS handshake, in which case the data received since is left in
+  /// it for `tls_established()`.
+  pub fn flush(&mut self) -> Vec<events::TelnetEvents> {
+    let mut event_list: Vec<events::TelnetEvents> = self.queued.drain(..).collect();
+    if !self.tls_suspended {
+      if !self.buffer.is_empty() && !self.overflowed {
+        self.process(&mut event_list);
+      }
+      let rest = self.buffer.take();
+      if !rest.is_empty() && !self.overflowed {
+        if rest[0] == IAC {
+          event_list.push(events::TelnetEvents::Error(
+            events::TelnetErrorKind::Truncated,
+            rest,
+          ));
+        } else {
+          self.receive_data(rest, &mut event_list);
+        }
+      }
+    }
+    if let Some(cr) = self
+      .newline
+      .as_mut()
+      .and_then(NewlineTranslator::take_pending)
+    {
+      self.deliver_data(cr, &mut event_list);
+    }
+    if let Some(held) = self.msp.as_mut().and_then(MspScanner::take_partial) {
+      self.deliver_text(held, &mut event_list);
+    }
+    if let Some(line) = self.lines.as_mut().and_then(LineAssembler::take_partial) {
+      event_list.push(events::TelnetEvents::Line(line));
+    }
+    match self.utf8.as_mut().and_then(Utf8Decoder::take_partial) {
+      Some(Decoded::Text(text)) => event_list.push(events::TelnetEvents::Text(text)),
+      Some(Decoded::Raw(data)) => event_list.push(events::TelnetEvents::build_receive(data)),
+      None => (),
+    }
+    self.overflowed = false;
+    event_list
+  }
+  /// Clear the internal buffer and anything held back from it, such as when reusing a parser for a new connection.
+  ///
+  /// # Arguments
+  ///
+  /// `states` - Whether to also reset the state negotiated with the remote end: the enabled options, requests still
+  /// waiting for an answer, and anything the remote end has reported over them.
+  ///
+  /// # Notes
+  ///
+  /// Nothing is emitted, so data that should not be lost is best taken with `flush()` first. The configuration is
+  /// kept, including the options supported by the `CompatibilityTable` and anything set with the `ParserBuilder`.
+  pub fn reset(&mut self, states: bool) {
+    self.buffer.clear();
+    self.queued.clear();
+    self.urgent = None;
+    self.overflowed = false;
+    self.sanitizer = self.config.sanitize.map(Sanitizer::new);
+    self.newline = self.config.line_ending.map(NewlineTranslator::new);
+    self.lines = self.config.line_mode.map(LineAssembler::new);
+    self.prompt_line.clear();
+    self.utf8 = self.config.utf8_text.map(Utf8Decoder::new);
+    if self.msp.is_some() {
+      self.msp = Some(MspScanner::new());
+    }
+    self.tls_suspended = false;
+    if !states {
+      return;
+    }
+    self.options.reset_states();
+    self.pending = [0; 256];
+    self.repeated = [0; 256];
+    self.outbound.clear();
+    self.prompt = None;
+    self.remote_environ.clear();
+    if self.fingerprinter.is_some() {
+      self.fingerprinter = Some(Fingerprinter::new());
+    }
+    self.compression = CompressionStats::default();
+    self.compression_reported = 0;
+    self.sizes = SubnegotiationSizes::default();
+    self.decompressing = None;
+    self.keepalives.clear();
+    self.remote_window = None;
+    self.terminal_index = 0;
+    self.terminal_types.clear();
+    self.cycling_terminal_types = false;
+    self.remote_status = None;
+    self.linemode = None;
+    self.slc = SlcTable::default();
+    self.lflow = LflowState::default();
+    self.logout_requested = false;
+    self.auth_active = None;
+    self.encryption = EncryptState::default();
+    self.end_matched = 0;
+    self.tls_requested = false;
+  }
+  /// Get the number of received bytes held in the internal buffer, waiting for the rest of a command sequence or to
+  /// be parsed by `next_event()`.
+  pub fn pending_len(&self) -> usize {
+    self.buffer.len()
+  }
+  /// Get whether the internal buffer holds the start of a subnegotiation whose `IAC SE` has not been received yet.
+  pub fn has_pending_subnegotiation(&self) -> bool {
+    self.overflowed || self.buffer.starts_with(&[IAC, SB])
+  }
+  /// Get whether the parser holds no received data: the internal buffer is empty and no events from `feed()` are
+  /// waiting for `next_event()`.
+  pub fn is_idle(&self) -> bool {
+    self.buffer.is_empty() && self.queued.is_empty() && !self.overflowed
+  }
+  /// Receive bytes into the internal buffer, passing each parsed event to `f` with its data borrowed.
+  ///
+  /// # Arguments
+  ///
+  /// * `data` - The bytes to be received. This should be sourced from the remote side of a connection.
+  ///
+  /// * `f` - Called with each event, in order. The borrowed data is only valid until it returns.
+  ///
+  /// # Notes
+  ///
+  /// Only the fast path is zero-copy: data with no IAC, arriving with nothing buffered from earlier calls and no
+  /// received text processing configured, is passed to `f` straight from `data`. Anything else is parsed into owned
+  /// events as `receive` does, and `f` borrows from those.
+  pub fn receive_borrowed<F>(&mut self, data: &[u8], mut f: F)
+  where
+    F: FnMut(events::TelnetEventRef<'_>),
+  {
+    if self.can_bypass(data)
+      && self.newline.is_none()
+      && self.sanitizer.is_none()
+      && self.lines.is_none()
+      && self.utf8.is_none()
+      && self.msp.is_none()
+      && self.decoder.is_none()
+      && self.config.prompt_markers.is_empty()
+    {
+      if data.iter().any(|&b| b == b'\r' || b == b'\n') {
+        // The remote end has answered any outstanding prompt.
+        self.prompt = None;
+      }
+      f(events::TelnetEventRef::DataReceive(data));
+      return;
+    }
+    let mut event_list: Vec<events::TelnetEvents> = Vec::with_capacity(2);
+    self.receive_into(data, &mut event_list);
+    for event in event_list.iter() {
+      f(events::TelnetEventRef::from(event));
+    }
+  }
+  /// Receive bytes into the internal buffer, dispatching any parsed events to a `TelnetHandler`.
+  ///
+  /// # Arguments
+  ///
+  /// * `data` - The bytes to be received. This should be sourced from the remote side of a connection.
+  ///
+  /// * `handler` - The handler whose callbacks will be invoked for each parsed event, in order.
+  pub fn receive_with<H: TelnetHandler + ?Sized>(&mut self, data: &[u8], handler: &mut H) {
+    self.receive_into(data, &mut HandlerSink(handler));
   }
   /// Get whether the remote end supports and is using linemode.
   pub fn linemode_enabled(&mut self) -> bool {
     let opt = self.options.get_option(telnet::op_option::LINEMODE);
     opt.remote && opt.remote_state
   }
+  /// Get the LINEMODE mode agreed with the remote end.
+  ///
+  /// # Returns
+  ///
+  /// `Option<LinemodeMode>` - The mode, without its `MODE_ACK` bit, or None if LINEMODE is not enabled or no mode
+  /// has been agreed yet.
+  pub fn linemode(&self) -> Option<LinemodeMode> {
+    let opt = self.options.get_option(telnet::op_option::LINEMODE);
+    if (opt.local && opt.local_state) || (opt.remote && opt.remote_state) {
+      self.linemode
+    } else {
+      None
+    }
+  }
+  /// Ask the client to switch to a LINEMODE mode, as a server.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A `MODE` subnegotiation to be processed, or None if LINEMODE is not
+  /// enabled remotely.
+  ///
+  /// # Notes
+  ///
+  /// The mode returned by `linemode` changes once the client acknowledges it.
+  pub fn set_linemode(&mut self, mode: LinemodeMode) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::LINEMODE);
+    if !(opt.remote && opt.remote_state) {
+      return None;
+    }
+    self.subnegotiation(telnet::op_option::LINEMODE, mode.without_ack().encode())
+  }
+  /// Get our LINEMODE local characters, such as the character that interrupts a process.
+  ///
+  /// # Notes
+  ///
+  /// The table starts with the usual characters of a Unix terminal, and follows the `SLC` subnegotiations of the
+  /// remote end, which are answered automatically.
+  pub fn slc(&self) -> &SlcTable {
+    &self.slc
+  }
+  /// Change local characters and tell the remote end.
+  ///
+  /// # Arguments
+  ///
+  /// `entries` - The new entries, such as `SlcEntry::new(slc::EC, slc::VALUE, 8)` for backspace.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - An `SLC` subnegotiation to be processed, or None if LINEMODE is not
+  /// enabled.
+  pub fn set_slc(&mut self, entries: &[SlcEntry]) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::LINEMODE);
+    if !((opt.local && opt.local_state) || (opt.remote && opt.remote_state)) {
+      return None;
+    }
+    for entry in entries {
+      self.slc.set(*entry);
+    }
+    self.subnegotiation(telnet::op_option::LINEMODE, linemode::encode_slc(entries))
+  }
+  /// Get the local flow control requested by the remote end.
+  ///
+  /// # Returns
+  ///
+  /// `Option<LflowState>` - The flow control to use, or None if TOGGLE-FLOW-CONTROL is not enabled locally.
+  ///
+  /// # Notes
+  ///
+  /// Changes are also emitted as `FlowControl` events.
+  pub fn flow_control(&self) -> Option<LflowState> {
+    let opt = self.options.get_option(telnet::op_option::LFLOW);
+    if opt.local && opt.local_state {
+      Some(self.lflow)
+    } else {
+      None
+    }
+  }
+  /// Tell the client how to handle flow control, as a server.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A TOGGLE-FLOW-CONTROL command to be processed, or None if
+  /// TOGGLE-FLOW-CONTROL is not enabled remotely.
+  pub fn send_flow_control(&mut self, command: LflowCommand) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::LFLOW);
+    if !(opt.remote && opt.remote_state) {
+      return None;
+    }
+    self.subnegotiation(
+      telnet::op_option::LFLOW,
+      Bytes::copy_from_slice(&command.encode()),
+    )
+  }
+  /// Get whether BINARY (RFC 856) transmission is in effect in one direction.
+  ///
+  /// # Arguments
+  ///
+  /// `local` - True for the data we send, false for the data the remote end sends.
+  ///
+  /// # Notes
+  ///
+  /// Data sent in binary is not NVT text, so no newline translation is applied to it in that direction.
+  pub fn binary_enabled(&self, local: bool) -> bool {
+    let opt = self.options.get_option(telnet::op_option::BINARY);
+    if local {
+      opt.local && opt.local_state
+    } else {
+      opt.remote && opt.remote_state
+    }
+  }
   /// Escape IAC bytes in data that is to be transmitted and treated as a non-IAC sequence.
   ///
   /// # Example
@@ -143,13 +817,84 @@ impl Parser {
 
     res.freeze()
   }
+  /// Get whether a request made with `_will`, `_wont`, `_do` or `_dont` is still waiting for an answer from the remote end.
+  ///
+  /// # Arguments
+  ///
+  /// `option` - A `u8` or `TelnetOption` representing the telnet option code to check.
+  ///
+  /// `local` - `true` to check a `_will`/`_wont` request, `false` to check a `_do`/`_dont` request.
+  pub fn negotiation_pending(&self, option: impl Into<u8>, local: bool) -> bool {
+    let option = option.into();
+    let flag = if local {
+      Self::PENDING_LOCAL
+    } else {
+      Self::PENDING_REMOTE
+    };
+    self.pending[option as usize] & flag == flag
+  }
+  /// Take a copy of the negotiation state: the `CompatibilityTable` and the requests still waiting for an answer.
+  ///
+  /// # Notes
+  ///
+  /// Only the state of the options is kept. Anything carried over them, such as compression, encryption or
+  /// buffered data, is not, so a snapshot is best taken while none of those are active.
+  pub fn negotiation_snapshot(&self) -> NegotiationSnapshot {
+    NegotiationSnapshot {
+      options: self.options.clone(),
+      pending: (0..=255u8)
+        .zip(self.pending.iter())
+        .filter(|(_, flags)| **flags != 0)
+        .map(|(option, flags)| (option, *flags))
+        .collect(),
+    }
+  }
+  /// Put back the negotiation state taken with `negotiation_snapshot`, such as in a new process taking over the
+  /// connection.
+  pub fn restore_negotiation(&mut self, snapshot: NegotiationSnapshot) {
+    self.options = snapshot.options;
+    self.pending = [0; 256];
+    for (option, flags) in snapshot.pending {
+      self.pending[option as usize] = flags;
+    }
+  }
+  /// Record an outstanding request for an option.
+  fn set_pending(&mut self, option: u8, local: bool, enable: bool) {
+    let (pending, want) = if local {
+      (Self::PENDING_LOCAL, Self::WANT_LOCAL)
+    } else {
+      (Self::PENDING_REMOTE, Self::WANT_REMOTE)
+    };
+    self.repeated[option as usize] = 0;
+    let flags = &mut self.pending[option as usize];
+    *flags = (*flags & !want) | pending;
+    if enable {
+      *flags |= want;
+    }
+  }
+  /// Clear an outstanding request for an option, returning whether the request was to enable it.
+  fn take_pending(&mut self, option: u8, local: bool) -> Option<bool> {
+    let (pending, want) = if local {
+      (Self::PENDING_LOCAL, Self::WANT_LOCAL)
+    } else {
+      (Self::PENDING_REMOTE, Self::WANT_REMOTE)
+    };
+    let flags = &mut self.pending[option as usize];
+    if *flags & pending == pending {
+      let wanted = *flags & want == want;
+      *flags &= !(pending | want);
+      Some(wanted)
+    } else {
+      None
+    }
+  }
   /// Negotiate an option.
   ///
   /// # Arguments
   ///
-  /// `command` - A `u8` representing the telnet command code to be negotiated with. Example: WILL (251), WONT (252), DO (253), DONT (254)
+  /// `command` - A `u8` or `TelnetCommand` representing the telnet command code to be negotiated with. Example: WILL (251), WONT (252), DO (253), DONT (254)
   ///
-  /// `option` - A `u8` representing the telnet option code that is being negotiated.
+  /// `option` - A `u8` or `TelnetOption` representing the telnet option code that is being negotiated.
   ///
   /// # Returns
   ///
@@ -160,14 +905,18 @@ impl Parser {
   /// This and other methods meant for sending data to the remote end will generate a `TelnetEvents::Send(DataEvent)` event.
   ///
   /// These Send events contain a buffer that should be sent directly to the remote end, as it will have already been encoded properly.
-  pub fn negotiate(&mut self, command: u8, option: u8) -> events::TelnetEvents {
+  pub fn negotiate(
+    &mut self,
+    command: impl Into<u8>,
+    option: impl Into<u8>,
+  ) -> events::TelnetEvents {
     events::TelnetEvents::build_send(events::TelnetNegotiation::new(command, option).into())
   }
   /// Indicate to the other side that you are able and wanting to utilize an option.
   ///
   /// # Arguments
   ///
-  /// `option` - A `u8` representing the telnet option code that you want to enable locally.
+  /// `option` - A `u8` or `TelnetOption` representing the telnet option code that you want to enable locally.
   ///
   /// # Returns
   ///
@@ -176,11 +925,13 @@ impl Parser {
   /// # Notes
   ///
   /// This method will do nothing if the option is not "supported" locally via the `CompatibilityTable`.
-  pub fn _will(&mut self, option: u8) -> Option<events::TelnetEvents> {
+  pub fn _will(&mut self, option: impl Into<u8>) -> Option<events::TelnetEvents> {
+    let option = option.into();
     let mut opt = self.options.get_option(option);
     if opt.local && !opt.local_state {
       opt.local_state = true;
       self.options.set_option(option, opt);
+      self.set_pending(option, true, true);
       Some(self.negotiate(251, option))
     } else {
       None
@@ -190,17 +941,19 @@ impl Parser {
   ///
   /// # Arguments
   ///
-  /// `option` - A `u8` representing the telnet option code that you want to disable locally.
+  /// `option` - A `u8` or `TelnetOption` representing the telnet option code that you want to disable locally.
   ///
   /// # Returns
   ///
   /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if the option is already disabled.
   ///
-  pub fn _wont(&mut self, option: u8) -> Option<events::TelnetEvents> {
+  pub fn _wont(&mut self, option: impl Into<u8>) -> Option<events::TelnetEvents> {
+    let option = option.into();
     let mut opt = self.options.get_option(option);
     if opt.local_state {
       opt.local_state = false;
       self.options.set_option(option, opt);
+      self.set_pending(option, true, false);
       Some(self.negotiate(252, option))
     } else {
       None
@@ -210,7 +963,7 @@ impl Parser {
   ///
   /// # Arguments
   ///
-  /// `option` - A `u8` representing the telnet option code that you want to enable remotely.
+  /// `option` - A `u8` or `TelnetOption` representing the telnet option code that you want to enable remotely.
   ///
   /// # Returns
   ///
@@ -219,9 +972,11 @@ impl Parser {
   /// # Notes
   ///
   /// This method will do nothing if the option is not "supported" remotely via the `CompatibilityTable`.
-  pub fn _do(&mut self, option: u8) -> Option<events::TelnetEvents> {
+  pub fn _do(&mut self, option: impl Into<u8>) -> Option<events::TelnetEvents> {
+    let option = option.into();
     let opt = self.options.get_option(option);
     if opt.remote && !opt.remote_state {
+      self.set_pending(option, false, true);
       Some(self.negotiate(253, option))
     } else {
       None
@@ -231,272 +986,2373 @@ impl Parser {
   ///
   /// # Arguments
   ///
-  /// `option` - A `u8` representing the telnet option code that you want to disable remotely.
+  /// `option` - A `u8` or `TelnetOption` representing the telnet option code that you want to disable remotely.
   ///
   /// # Returns
   ///
   /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if the option is already disabled.
   ///
-  pub fn _dont(&mut self, option: u8) -> Option<events::TelnetEvents> {
+  pub fn _dont(&mut self, option: impl Into<u8>) -> Option<events::TelnetEvents> {
+    let option = option.into();
     let opt = self.options.get_option(option);
     if opt.remote_state {
+      self.set_pending(option, false, false);
       Some(self.negotiate(254, option))
     } else {
       None
     }
   }
-  /// Send a subnegotiation for a locally supported option.
-  ///
-  /// # Arguments
-  ///
-  /// `option` - A `u8` representing the telnet option code for the negotiation.
-  ///
-  /// `data` - A `Bytes` containing the data to be sent in the subnegotiation. This data will have all IAC (255) byte values escaped.
+  /// Start echoing the remote end's input ourselves, such as before a password prompt, so that the remote end stops
+  /// echoing it locally.
   ///
   /// # Returns
   ///
-  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if the option is not supported or is currently disabled.
+  /// `Option<events::TelnetEvents::DataSend>` - `WILL ECHO`, with `WILL SGA` if SUPPRESS-GO-AHEAD is not already
+  /// enabled, or None if we are already echoing.
   ///
   /// # Notes
   ///
-  /// This method will do nothing if the option is not "supported" locally via the `CompatibilityTable`.
-  pub fn subnegotiation<T>(&mut self, option: u8, data: T) -> Option<events::TelnetEvents>
-  where
-    Bytes: From<T>,
-  {
-    let opt = self.options.get_option(option);
-    if opt.local && opt.local_state {
-      Some(events::TelnetEvents::build_send(
-        events::TelnetSubnegotiation::new(option, Bytes::from(data)).into(),
-      ))
-    } else {
+  /// ECHO and SGA are marked as supported locally in the `CompatibilityTable` if they were not already. Nothing needs
+  /// to be echoed to mask a password: just don't send the input back.
+  pub fn begin_server_echo(&mut self) -> Option<events::TelnetEvents> {
+    let mut buf = BytesMut::new();
+    for &option in [telnet::op_option::ECHO, telnet::op_option::SGA].iter() {
+      self.options.support_local(option);
+      if let Some(event) = self._will(option) {
+        buf.put(Bytes::from(event));
+      }
+    }
+    if buf.is_empty() {
       None
+    } else {
+      Some(events::TelnetEvents::build_send(buf.freeze()))
     }
   }
-  /// Send a subnegotiation for a locally supported option, using a string instead of raw byte values.
+  /// Stop echoing the remote end's input, handing echo back to the remote end, such as after a password prompt.
   ///
-  /// # Arguments
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - `WONT ECHO`, or None if we are not echoing. SGA is left enabled.
+  pub fn end_server_echo(&mut self) -> Option<events::TelnetEvents> {
+    self._wont(telnet::op_option::ECHO)
+  }
+  /// Get whether the connection is in character mode: ECHO and SUPPRESS-GO-AHEAD both enabled by the same end.
   ///
-  /// `option` - A `u8` representing the telnet option code for the negotiation.
+  /// On a server, this is after `begin_server_echo` has been agreed to. On a client, it is when the server echoes.
+  pub fn character_mode(&self) -> bool {
+    let echo = self.options.get_option(telnet::op_option::ECHO);
+    let sga = self.options.get_option(telnet::op_option::SGA);
+    (echo.local && echo.local_state && sga.local && sga.local_state)
+      || (echo.remote && echo.remote_state && sga.remote && sga.remote_state)
+  }
+  /// Disable and re-enable an option, for when both sides may disagree about its state.
   ///
-  /// `text` - A `&str` representing the text to be sent in the subnegotation. This data will have all IAC (255) byte values escaped.
+  /// # Arguments
+  ///
+  /// `option` - A `u8` or `TelnetOption` representing the telnet option code to renegotiate.
   ///
   /// # Returns
   ///
-  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if the option is not supported or is currently disabled.
+  /// `Vec<events::TelnetEvents>` - A `DataSend` disabling each side of the option that is enabled, or nothing if it is
+  /// disabled on both sides or already being negotiated.
   ///
   /// # Notes
   ///
-  /// This method will do nothing if the option is not "supported" locally via the `CompatibilityTable`.
-  pub fn subnegotiation_text(&mut self, option: u8, text: &str) -> Option<events::TelnetEvents> {
-    self.subnegotiation(option, Bytes::copy_from_slice(&text.as_bytes()))
+  /// Each side is re-enabled automatically once the remote end agrees to disable it. When every side has been
+  /// re-enabled (or refused), a `TelnetEvents::Resynced` is emitted. The `NegotiationComplete` events along the way
+  /// tell whether the option ended up enabled.
+  pub fn resync_option(&mut self, option: impl Into<u8>) -> Vec<events::TelnetEvents> {
+    let option = option.into();
+    let opt = self.options.get_option(option);
+    let mut event_list = Vec::new();
+    if opt.local && opt.local_state && !self.negotiation_pending(option, true) {
+      self.pending[option as usize] |= Self::RESYNC_LOCAL;
+      event_list.extend(self._wont(option));
+    }
+    if opt.remote && opt.remote_state && !self.negotiation_pending(option, false) {
+      self.pending[option as usize] |= Self::RESYNC_REMOTE;
+      event_list.extend(self._dont(option));
+    }
+    event_list
   }
-  /// Directly send a string, with appended `\r\n`, to the remote end, along with an `IAC (255) GOAHEAD (249)` sequence.
+  /// Send a subnegotiation for an enabled option.
   ///
-  /// # Returns
+  /// # Arguments
   ///
-  /// `events::TelnetEvents::DataSend` - A DataSend event to be processed.
+  /// `option` - A `u8` or `TelnetOption` representing the telnet option code for the negotiation.
   ///
-  /// # Notes
+  /// `data` - A `Bytes` containing the data to be sent in the subnegotiation. This data will have all IAC (255) byte values escaped.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if the option is not supported, is still being negotiated, or is currently disabled.
+  ///
+  /// # Notes
+  ///
+  /// This method will do nothing unless the option is "supported" and enabled on at least one side via the `CompatibilityTable`.
+  /// Use `try_subnegotiation` to find out why a subnegotiation was refused.
+  pub fn subnegotiation<T>(
+    &mut self,
+    option: impl Into<u8>,
+    data: T,
+  ) -> Option<events::TelnetEvents>
+  where
+    Bytes: From<T>,
+  {
+    self.try_subnegotiation(option, data).ok()
+  }
+  /// Send a subnegotiation for an enabled option, reporting why it was refused if it can't be sent.
+  ///
+  /// # Arguments
+  ///
+  /// `option` - A `u8` or `TelnetOption` representing the telnet option code for the negotiation.
+  ///
+  /// `data` - A `Bytes` containing the data to be sent in the subnegotiation. This data will have all IAC (255) byte values escaped.
+  ///
+  /// # Returns
+  ///
+  /// `Result<events::TelnetEvents::DataSend, SubnegotiationRefusal>` - A DataSend event to be processed, or the reason the subnegotiation was refused.
+  pub fn try_subnegotiation<T>(
+    &mut self,
+    option: impl Into<u8>,
+    data: T,
+  ) -> Result<events::TelnetEvents, SubnegotiationRefusal>
+  where
+    Bytes: From<T>,
+  {
+    let option = option.into();
+    let opt = self.options.get_option(option);
+    let flags = self.pending[option as usize];
+    let pending_local = flags & Self::PENDING_LOCAL != 0;
+    let pending_remote = flags & Self::PENDING_REMOTE != 0;
+    if (opt.local && opt.local_state && !pending_local)
+      || (opt.remote && opt.remote_state && !pending_remote)
+    {
+      Ok(self.force_subnegotiation(option, data))
+    } else if (pending_local && flags & Self::WANT_LOCAL != 0)
+      || (pending_remote && flags & Self::WANT_REMOTE != 0)
+    {
+      if self.config.queue_subnegotiations {
+        self
+          .outbound
+          .push(events::TelnetSubnegotiation::new(option, Bytes::from(data)));
+        Err(SubnegotiationRefusal::Queued)
+      } else {
+        Err(SubnegotiationRefusal::Pending)
+      }
+    } else if !opt.local && !opt.remote {
+      Err(SubnegotiationRefusal::Unsupported)
+    } else {
+      Err(SubnegotiationRefusal::Disabled)
+    }
+  }
+  /// Send a subnegotiation regardless of the state of the option.
+  ///
+  /// This is mostly useful for testing, as the remote end is free to ignore subnegotiations for options that are not enabled.
+  ///
+  /// # Returns
+  ///
+  /// `events::TelnetEvents::DataSend` - A DataSend event to be processed.
+  pub fn force_subnegotiation<T>(&mut self, option: impl Into<u8>, data: T) -> events::TelnetEvents
+  where
+    Bytes: From<T>,
+  {
+    events::TelnetEvents::build_send(
+      events::TelnetSubnegotiation::new(option, Bytes::from(data)).into(),
+    )
+  }
+  /// Send a subnegotiation for a locally supported option, using a string instead of raw byte values.
+  ///
+  /// # Arguments
+  ///
+  /// `option` - A `u8` or `TelnetOption` representing the telnet option code for the negotiation.
+  ///
+  /// `text` - A `&str` representing the text to be sent in the subnegotation. This data will have all IAC (255) byte values escaped.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if the option is not supported or is currently disabled.
+  ///
+  /// # Notes
+  ///
+  /// This method will do nothing if the option is not "supported" locally via the `CompatibilityTable`.
+  pub fn subnegotiation_text(
+    &mut self,
+    option: impl Into<u8>,
+    text: &str,
+  ) -> Option<events::TelnetEvents> {
+    self.subnegotiation(option, Bytes::copy_from_slice(text.as_bytes()))
+  }
+  /// Tell the client to start MXP, by sending `IAC SB MXP IAC SE`.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if MXP is not enabled.
+  ///
+  /// # Notes
+  ///
+  /// Received text can be split into lines of each MXP mode with `telnet::mxp::MxpScanner`.
+  pub fn start_mxp(&mut self) -> Option<events::TelnetEvents> {
+    self.subnegotiation(telnet::op_option::MXP, Bytes::new())
+  }
+  /// Turn an Aardwolf option 102 feature, such as `telnet::aard102::TELL`, on or off.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if option 102 is not
+  /// enabled.
+  pub fn send_aard102(&mut self, option: u8, on: bool) -> Option<events::TelnetEvents> {
+    self.subnegotiation(
+      telnet::op_option::AARD102,
+      Aard102::toggle(option, on).encode(),
+    )
+  }
+  /// Send a GMCP message.
+  ///
+  /// # Arguments
+  ///
+  /// `name` - The full name of the message, such as `Core.Hello`.
+  ///
+  /// `data` - The JSON payload as text, or an empty string for a message without one.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if GMCP is not enabled.
+  pub fn send_gmcp(&mut self, name: &str, data: &str) -> Option<events::TelnetEvents> {
+    self.subnegotiation(
+      telnet::op_option::GMCP,
+      GmcpMessage::new(name, data).encode(),
+    )
+  }
+  /// Send an MSDP variable.
+  ///
+  /// # Arguments
+  ///
+  /// `name` - The name of the variable, such as `HEALTH`.
+  ///
+  /// `value` - The value, which can be a string, array or table.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if MSDP is not enabled.
+  pub fn send_msdp(&mut self, name: &str, value: &MsdpValue) -> Option<events::TelnetEvents> {
+    let vars = [(String::from(name), value.clone())];
+    self.subnegotiation(telnet::op_option::MSDP, telnet::msdp::encode(&vars))
+  }
+  /// Send an MSDP command, such as `MsdpCommand::Report`, to the server.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if MSDP is not enabled.
+  pub fn send_msdp_command(&mut self, command: &MsdpCommand) -> Option<events::TelnetEvents> {
+    self.subnegotiation(telnet::op_option::MSDP, command.encode())
+  }
+  /// Send a GMCP message with a payload serialized to JSON, such as a `serde_json::Value` or a user type.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if GMCP is not enabled or
+  /// the value could not be serialized.
+  #[cfg(feature = "json")]
+  pub fn send_gmcp_json<T: serde::Serialize + ?Sized>(
+    &mut self,
+    name: &str,
+    value: &T,
+  ) -> Option<events::TelnetEvents> {
+    self.send_gmcp(name, &serde_json::to_string(value).ok()?)
+  }
+  /// Set a local NEW-ENVIRON variable, which will be reported when the remote end asks for it with a SEND request.
+  ///
+  /// # Arguments
+  ///
+  /// `kind` - Whether this is a well-known `VAR` or a `USERVAR`.
+  ///
+  /// `name` - The name of the variable.
+  ///
+  /// `value` - The new value of the variable, or None if it is undefined.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - An `INFO` update to be processed, or None if the value is unchanged or neither NEW-ENVIRON nor a `legacy_environ` ENVIRON is enabled locally.
+  pub fn set_environ_var(
+    &mut self,
+    kind: EnvironKind,
+    name: &str,
+    value: Option<&str>,
+  ) -> Option<events::TelnetEvents> {
+    let var = EnvironVar::new(kind, name, value);
+    match self
+      .environ
+      .iter_mut()
+      .find(|v| v.kind == kind && v.name == name)
+    {
+      Some(existing) if *existing == var => return None,
+      Some(existing) => *existing = var.clone(),
+      None => self.environ.push(var.clone()),
+    }
+    let (option, legacy) = self.environ_option(true)?;
+    let info = EnvironMessage::new(EnvironCommand::Info, alloc::vec![var]);
+    Some(events::TelnetEvents::build_send(
+      events::TelnetSubnegotiation::new(option, encode_environ(&info, legacy)).into(),
+    ))
+  }
+  /// Set every known MNES variable at once, to be reported when the remote end asks for them.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - `INFO` updates for the variables that changed, or None if nothing
+  /// changed or NEW-ENVIRON is not enabled locally.
+  ///
+  /// # Notes
+  ///
+  /// NEW-ENVIRON is marked as supported locally in the `CompatibilityTable`. Variables that are None in `mnes` are
+  /// left as they were.
+  pub fn set_mnes(&mut self, mnes: &Mnes) -> Option<events::TelnetEvents> {
+    self.options.support_local(telnet::op_option::NEWENVIRON);
+    let mut buf = BytesMut::new();
+    for var in mnes.to_vars() {
+      if let Some(event) = self.set_environ_var(var.kind, &var.name, var.value.as_deref()) {
+        buf.put(Bytes::from(event));
+      }
+    }
+    if buf.is_empty() {
+      None
+    } else {
+      Some(events::TelnetEvents::build_send(buf.freeze()))
+    }
+  }
+  /// Ask the remote end for every MNES variable.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if NEW-ENVIRON is not enabled remotely.
+  pub fn request_mnes(&mut self) -> Option<events::TelnetEvents> {
+    let vars: Vec<(EnvironKind, &str)> = telnet::mnes::VARIABLES
+      .iter()
+      .map(|name| (EnvironKind::Var, *name))
+      .collect();
+    self.request_environ(&vars)
+  }
+  /// Get the MNES variables reported by the remote end so far.
+  pub fn remote_mnes(&self) -> Mnes {
+    Mnes::from_vars(&self.remote_environ)
+  }
+  /// Ask the remote end for NEW-ENVIRON variables.
+  ///
+  /// # Arguments
+  ///
+  /// `vars` - The kinds and names of the variables to request. An empty slice requests every variable.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if neither NEW-ENVIRON nor a `legacy_environ` ENVIRON is enabled remotely.
+  pub fn request_environ(&mut self, vars: &[(EnvironKind, &str)]) -> Option<events::TelnetEvents> {
+    let (option, legacy) = self.environ_option(false)?;
+    let request = EnvironMessage::new(
+      EnvironCommand::Send,
+      vars
+        .iter()
+        .map(|(kind, name)| EnvironVar::new(*kind, name, None))
+        .collect(),
+    );
+    self.subnegotiation(option, encode_environ(&request, legacy))
+  }
+  /// Get the value of a NEW-ENVIRON variable reported by the remote end.
+  pub fn remote_environ_var(&self, kind: EnvironKind, name: &str) -> Option<&str> {
+    self
+      .remote_environ
+      .iter()
+      .find(|v| v.kind == kind && v.name == name)
+      .and_then(|v| v.value.as_deref())
+  }
+  /// Get every NEW-ENVIRON variable reported by the remote end so far.
+  pub fn remote_environ(&self) -> &[EnvironVar] {
+    &self.remote_environ
+  }
+  /// Directly send a string to the remote end, finished as set with `ParserBuilder::text_format`. By default, `\r\n`
+  /// is appended.
+  ///
+  /// # Returns
+  ///
+  /// `events::TelnetEvents::DataSend` - A DataSend event to be processed.
+  ///
+  /// # Notes
   ///
   /// The string will have IAC (255) bytes escaped before being sent.
   pub fn send_text(&mut self, text: &str) -> events::TelnetEvents {
-    events::TelnetEvents::build_send(Bytes::copy_from_slice(&Parser::escape_iac(
-      format!("{}\r\n", text).into_bytes(),
-    )))
+    self.send_text_with(text, self.config.text_format)
+  }
+  /// Directly send a string to the remote end, overriding the parser's `TextFormat` for this call.
+  ///
+  /// # Arguments
+  ///
+  /// `text` - The text to send.
+  ///
+  /// `format` - The line ending and terminating command to send after the text.
+  ///
+  /// # Returns
+  ///
+  /// `events::TelnetEvents::DataSend` - A DataSend event to be processed.
+  ///
+  /// # Notes
+  ///
+  /// The string will have IAC (255) bytes escaped before being sent. Outside of BINARY mode the line ending is sent as
+  /// NVT requires, so `LineEnding::Lf` only produces a bare `\n` once BINARY is enabled on our side.
+  pub fn send_text_with(&mut self, text: &str, format: TextFormat) -> events::TelnetEvents {
+    let text = Parser::escape_iac(self.encode_text(text));
+    let suffix = format.suffix(self.binary_enabled(true), self.prompt_marker());
+    let mut buf = BytesMut::with_capacity(text.len() + suffix.len());
+    buf.put(&text[..]);
+    buf.put(&suffix[..]);
+    events::TelnetEvents::build_send(buf.freeze())
+  }
+  /// Send raw data to the remote end, such as a binary payload or part of a line.
+  ///
+  /// # Returns
+  ///
+  /// `events::TelnetEvents::DataSend` - A DataSend event to be processed.
+  ///
+  /// # Notes
+  ///
+  /// Only IAC (255) bytes are escaped. No line ending is added and no newline or charset translation is applied.
+  pub fn send_data(&self, data: &[u8]) -> events::TelnetEvents {
+    events::TelnetEvents::build_send(Parser::escape_iac(Bytes::copy_from_slice(data)))
+  }
+  /// Send a prompt to the remote end, without a trailing newline.
+  ///
+  /// The prompt is terminated by an `IAC (255) EOR (239)` sequence if the END-OF-RECORD option is enabled on our side,
+  /// otherwise by an `IAC (255) GOAHEAD (249)` sequence.
+  ///
+  /// # Returns
+  ///
+  /// `events::TelnetEvents::DataSend` - A DataSend event to be processed.
+  ///
+  /// # Notes
+  ///
+  /// The prompt is remembered until the remote end sends a line, so that it can be repainted by `send_async_text`.
+  pub fn send_prompt(&mut self, text: &str) -> events::TelnetEvents {
+    let prompt = Parser::escape_iac(self.encode_text(text));
+    let mut buf = BytesMut::with_capacity(prompt.len() + 2);
+    buf.put(&prompt[..]);
+    buf.put(&[IAC, self.prompt_marker()][..]);
+    self.prompt = Some(prompt);
+    events::TelnetEvents::build_send(buf.freeze())
+  }
+  /// Send a line of text that was not requested by the remote end, such as a chat message or combat update.
+  ///
+  /// If a prompt sent with `send_prompt` is still waiting for input, the text is moved onto its own line and the prompt
+  /// is repainted after it. Otherwise this behaves like `send_text`.
+  ///
+  /// # Returns
+  ///
+  /// `events::TelnetEvents::DataSend` - A DataSend event to be processed.
+  pub fn send_async_text(&mut self, text: &str) -> events::TelnetEvents {
+    let prompt = match &self.prompt {
+      Some(prompt) => prompt.clone(),
+      None => return self.send_text(text),
+    };
+    let text = Parser::escape_iac(self.encode_text(text));
+    let mut buf = BytesMut::with_capacity(text.len() + prompt.len() + 6);
+    buf.put(&b"\r\n"[..]);
+    buf.put(&text[..]);
+    buf.put(&b"\r\n"[..]);
+    buf.put(&prompt[..]);
+    buf.put(&[IAC, self.prompt_marker()][..]);
+    events::TelnetEvents::build_send(buf.freeze())
+  }
+  /// Forget the prompt recorded by `send_prompt`, so that `send_async_text` no longer repaints it.
+  pub fn clear_prompt(&mut self) {
+    self.prompt = None;
+  }
+  /// Generate a keepalive for an idle connection, which the remote end ignores.
+  ///
+  /// # Returns
+  ///
+  /// `events::TelnetEvents::DataSend` - An `IAC NOP` to be sent.
+  pub fn keepalive(&self) -> events::TelnetEvents {
+    events::TelnetEvents::build_send(events::TelnetIAC::new(NOP).into())
+  }
+  /// Generate a keepalive probe that the remote end answers, for measuring round trip time or detecting a dead
+  /// connection.
+  ///
+  /// # Returns
+  ///
+  /// `events::TelnetEvents::DataSend` - An `IAC DO TIMING-MARK` (RFC 860) to be sent.
+  ///
+  /// # Notes
+  ///
+  /// Each answer, `WILL` or `WONT TIMING-MARK`, produces a `TelnetEvents::KeepaliveAck` instead of being handled as a
+  /// negotiation. Use `keepalives_outstanding` to find out how many probes are still unanswered.
+  pub fn keepalive_probe(&mut self) -> events::TelnetEvents {
+    self.keepalives.push_back(None);
+    self.negotiate(DO, telnet::op_option::TM)
+  }
+  /// Send a probe for measuring latency, like `keepalive_probe`, recording when it was sent.
+  ///
+  /// # Arguments
+  ///
+  /// `now` - The current time, in whatever unit the caller likes, such as milliseconds since the connection opened.
+  ///
+  /// # Returns
+  ///
+  /// `events::TelnetEvents::DataSend` - An `IAC DO TIMING-MARK` to be sent.
+  ///
+  /// # Notes
+  ///
+  /// The answer produces a `TelnetEvents::Pong`, whose `rtt_hint` is measured against the latest time given to `ping`
+  /// or `set_time`. Call `set_time` before passing received data to the parser for an accurate round trip time.
+  pub fn ping(&mut self, now: u64) -> events::TelnetEvents {
+    self.set_time(now);
+    self.keepalives.push_back(Some(now));
+    self.negotiate(DO, telnet::op_option::TM)
+  }
+  /// Tell the parser the current time, in the unit used with `ping`, for measuring the round trip time of pings.
+  pub fn set_time(&mut self, now: u64) {
+    self.clock = self.clock.max(now);
+  }
+  /// Ask the remote end to log us out, as a client.
+  ///
+  /// # Returns
+  ///
+  /// `events::TelnetEvents::DataSend` - An `IAC DO LOGOUT` (RFC 727) to be sent.
+  ///
+  /// # Notes
+  ///
+  /// A server that agrees answers `WILL LOGOUT`, emitted as `TelnetEvents::LogoutAnnounced`, and closes the
+  /// connection. A refusal, `WONT LOGOUT`, is emitted as an ordinary `Negotiation`. A `WILL LOGOUT` we did not ask
+  /// for is only taken as a logout if LOGOUT is supported remotely by the `CompatibilityTable`, and is refused with
+  /// `DONT` otherwise.
+  pub fn request_logout(&mut self) -> events::TelnetEvents {
+    self.logout_requested = true;
+    self.negotiate(DO, telnet::op_option::LOGOUT)
+  }
+  /// Tell the remote end that we are logging it out, as a server, either in answer to a
+  /// `TelnetEvents::LogoutRequested` or to force a logout, such as after a timeout.
+  ///
+  /// # Returns
+  ///
+  /// `events::TelnetEvents::DataSend` - An `IAC WILL LOGOUT` (RFC 727) to be sent before closing the connection.
+  ///
+  /// # Notes
+  ///
+  /// `DO LOGOUT` is only emitted as `TelnetEvents::LogoutRequested` if LOGOUT is supported locally by the
+  /// `CompatibilityTable`. Otherwise it is refused with `WONT` like any other unsupported option.
+  pub fn acknowledge_logout(&mut self) -> events::TelnetEvents {
+    self.negotiate(WILL, telnet::op_option::LOGOUT)
+  }
+  /// Set our window size, to be reported to the remote end over NAWS (RFC 1073).
+  ///
+  /// # Arguments
+  ///
+  /// `width` - The width in characters, or 0 if unknown.
+  ///
+  /// `height` - The height in characters, or 0 if unknown.
+  ///
+  /// # Notes
+  ///
+  /// NAWS is marked as supported locally in the `CompatibilityTable`, so a `DO NAWS` from the remote end is accepted
+  /// and answered with the window size straight away. Use `resize` when the size changes later.
+  pub fn set_window_size(&mut self, width: u16, height: u16) {
+    self.options.support_local(telnet::op_option::NAWS);
+    self.window = Some(Naws::new(width, height));
+  }
+  /// Update our window size, reporting it to the remote end if NAWS is enabled.
+  ///
+  /// # Arguments
+  ///
+  /// `width` - The width in characters, or 0 if unknown.
+  ///
+  /// `height` - The height in characters, or 0 if unknown.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - The NAWS subnegotiation to be sent, or None if NAWS is not enabled
+  /// locally or the size has not changed.
+  pub fn resize(&mut self, width: u16, height: u16) -> Option<events::TelnetEvents> {
+    let window = Naws::new(width, height);
+    if self.window.replace(window) == Some(window) {
+      return None;
+    }
+    let opt = self.options.get_option(telnet::op_option::NAWS);
+    if !(opt.local && opt.local_state) {
+      return None;
+    }
+    self.subnegotiation(telnet::op_option::NAWS, window.encode())
+  }
+  /// Set the terminal types to report when the remote end asks with TERMINAL-TYPE (RFC 1091), most specific first.
+  ///
+  /// # Arguments
+  ///
+  /// `names` - The terminal types, such as `&["MUDLET", "XTERM-256COLOR", "MTTS 2825"]`.
+  ///
+  /// # Notes
+  ///
+  /// TERMINAL-TYPE is marked as supported locally in the `CompatibilityTable`. Each `SEND` from the remote end is
+  /// answered with the next name. The last name is sent twice to mark the end of the list, then the list starts over.
+  pub fn set_terminal_types(&mut self, names: &[&str]) {
+    self.options.support_local(telnet::op_option::TTYPE);
+    self.terminal_names = names.iter().map(|name| String::from(*name)).collect();
+    self.terminal_index = 0;
+  }
+  /// Ask the remote end for every terminal type it supports.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - The first TERMINAL-TYPE `SEND` request to be sent, or None if
+  /// TERMINAL-TYPE is not enabled remotely.
+  ///
+  /// # Notes
+  ///
+  /// Each reply is added to `terminal_types` and answered with another `SEND`, until the remote end repeats a type to
+  /// show that its list is finished.
+  pub fn request_terminal_types(&mut self) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::TTYPE);
+    if !(opt.remote && opt.remote_state) {
+      return None;
+    }
+    self.terminal_types.clear();
+    self.cycling_terminal_types = true;
+    self.subnegotiation(telnet::op_option::TTYPE, TtypeMessage::Send.encode())
+  }
+  /// Get the terminal types reported by the remote end, in the order it sent them.
+  pub fn terminal_types(&self) -> &[String] {
+    &self.terminal_types
+  }
+  /// Ask the remote end for its terminal speeds.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A TERMINAL-SPEED `SEND` request to be processed, or None if
+  /// TERMINAL-SPEED is not enabled remotely. The answer is emitted as a `TerminalSpeed` event.
+  pub fn request_terminal_speed(&mut self) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::TSPEED);
+    if !(opt.remote && opt.remote_state) {
+      return None;
+    }
+    self.subnegotiation(telnet::op_option::TSPEED, TspeedMessage::Send.encode())
+  }
+  /// Report our terminal speeds, usually in answer to a `TerminalSpeed` request.
+  ///
+  /// # Arguments
+  ///
+  /// `transmit` - The speed we send at, in bits per second.
+  ///
+  /// `receive` - The speed we receive at, in bits per second.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A TERMINAL-SPEED `IS` to be processed, or None if TERMINAL-SPEED is
+  /// not enabled locally.
+  pub fn send_terminal_speed(
+    &mut self,
+    transmit: u32,
+    receive: u32,
+  ) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::TSPEED);
+    if !(opt.local && opt.local_state) {
+      return None;
+    }
+    self.subnegotiation(
+      telnet::op_option::TSPEED,
+      TspeedMessage::Is { transmit, receive }.encode(),
+    )
+  }
+  /// Ask the remote end for its X display location.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - An X-DISPLAY-LOCATION `SEND` request to be processed, or None if
+  /// X-DISPLAY-LOCATION is not enabled remotely. The answer is emitted as a `DisplayLocation` event.
+  pub fn request_display_location(&mut self) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::XDISPLOC);
+    if !(opt.remote && opt.remote_state) {
+      return None;
+    }
+    self.subnegotiation(telnet::op_option::XDISPLOC, XdisplocMessage::Send.encode())
+  }
+  /// Report our X display location, such as `localhost:0.0`, usually in answer to a `DisplayLocation` request.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - An X-DISPLAY-LOCATION `IS` to be processed, or None if
+  /// X-DISPLAY-LOCATION is not enabled locally.
+  pub fn send_display_location(&mut self, display: &str) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::XDISPLOC);
+    if !(opt.local && opt.local_state) {
+      return None;
+    }
+    self.subnegotiation(
+      telnet::op_option::XDISPLOC,
+      XdisplocMessage::Is(String::from(display)).encode(),
+    )
+  }
+  /// Record the delay before the remote end's next transmission, for guessing its telnet stack.
+  ///
+  /// # Notes
+  ///
+  /// This does nothing unless `ParserBuilder::fingerprint` was enabled. See `Fingerprinter::observe_delay`.
+  pub fn observe_delay(&mut self, delay: TimingBucket) {
+    if let Some(fingerprinter) = self.fingerprinter.as_mut() {
+      fingerprinter.observe_delay(delay);
+    }
+  }
+  /// Get the current guess at the remote end's telnet stack, or None if fingerprinting is not enabled or nothing
+  /// recognisable has been seen yet.
+  pub fn peer_stack(&self) -> Option<PeerStack> {
+    self.fingerprinter.as_ref().and_then(Fingerprinter::guess)
+  }
+  /// Get the remote end's MTTS report, once `request_terminal_types` has collected at least three terminal types.
+  pub fn mtts(&self) -> Option<Mtts> {
+    Mtts::from_terminal_types(&self.terminal_types)
+  }
+  /// Get the window size last reported by the remote end over NAWS, as `(width, height)`.
+  ///
+  /// A width or height of 0 means the remote end does not know that dimension.
+  pub fn remote_window_size(&self) -> Option<(u16, u16)> {
+    self.remote_window.map(|naws| (naws.width, naws.height))
+  }
+  /// Ask the remote end for its view of the option states, to check that both ends agree.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A `STATUS SEND` request to be sent, or None if STATUS is not enabled
+  /// remotely.
+  ///
+  /// # Notes
+  ///
+  /// The remote end's `IS` report is compared with the compatibility table as it arrives, and any disagreement is
+  /// reported with a `TelnetEvents::StateMismatch`. Nothing is emitted when the states match. The report itself is
+  /// kept, for `remote_status`.
+  ///
+  /// When STATUS is enabled locally, `SEND` requests from the remote end are answered automatically with an `IS`
+  /// report built from the compatibility table.
+  pub fn verify_states(&mut self) -> Option<events::TelnetEvents> {
+    if !self
+      .options
+      .get_option(telnet::op_option::STATUS)
+      .remote_state
+    {
+      return None;
+    }
+    self.subnegotiation(
+      telnet::op_option::STATUS,
+      Bytes::from_static(&[telnet::status::SEND]),
+    )
+  }
+  /// Get the last STATUS `IS` report received from the remote end, such as in answer to `verify_states`.
+  pub fn remote_status(&self) -> Option<&StatusReport> {
+    self.remote_status.as_ref()
+  }
+  /// Get the number of probes sent with `keepalive_probe` or `ping` that the remote end has not answered yet.
+  pub fn keepalives_outstanding(&self) -> u32 {
+    self.keepalives.len() as u32
+  }
+  /// Record data passing through an MCCP compressed stream.
+  ///
+  /// The parser does not own the zlib streams: it hands compressed data off with `TelnetEvents::DecompressImmediate`
+  /// and `TelnetEvents::DecompressClientStream` and never sees how much it inflates to. Nothing is counted
+  /// automatically, so call this after compressing outbound data or decompressing inbound data to keep
+  /// `compression_stats` up to date.
+  ///
+  /// # Arguments
+  ///
+  /// * `direction` - Which stream the data passed through.
+  ///
+  /// * `compressed` - The number of bytes on the wire.
+  ///
+  /// * `uncompressed` - The number of bytes before compression, or after decompression.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::CompressionStats>` - The current statistics, if the interval set with
+  /// `ParserBuilder::compression_stats_interval` has been reached.
+  pub fn record_compression(
+    &mut self,
+    direction: Direction,
+    compressed: usize,
+    uncompressed: usize,
+  ) -> Option<events::TelnetEvents> {
+    let stats = self.compression.direction_mut(direction);
+    stats.compressed += compressed as u64;
+    stats.uncompressed += uncompressed as u64;
+    let interval = self.config.compression_stats_interval?;
+    let total = self.compression.inbound.uncompressed + self.compression.outbound.uncompressed;
+    if total - self.compression_reported >= interval {
+      self.compression_reported = total;
+      Some(events::TelnetEvents::CompressionStats(self.compression))
+    } else {
+      None
+    }
+  }
+  /// Get the payload size histograms of the subnegotiations received so far.
+  pub fn subnegotiation_sizes(&self) -> &SubnegotiationSizes {
+    &self.sizes
+  }
+  /// Get the option of the compressed stream the remote end is currently sending, if any.
+  pub fn decompressing(&self) -> Option<u8> {
+    self.decompressing
+  }
+  /// Report that the remote end's compressed stream has ended, such as when zlib reports the end of the stream.
+  ///
+  /// Servers end their MCCP stream without switching the option off in some cases, for example around a copyover.
+  /// Anything left over after the end of the stream should then be passed to `receive` as ordinary data.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DecompressionEnded>` - The transition, or None if no compressed stream was active.
+  pub fn end_decompression(&mut self) -> Option<events::TelnetEvents> {
+    self
+      .decompressing
+      .take()
+      .map(events::TelnetEvents::DecompressionEnded)
+  }
+  /// Get the compressed and uncompressed byte counts recorded with `record_compression`, which are all zero if the
+  /// caller never reports any.
+  pub fn compression_stats(&self) -> CompressionStats {
+    self.compression
+  }
+  /// Generate a polite teardown for a connection that is about to be closed.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<events::TelnetEvents>` - An `EndCompression` for any compressed stream we are sending, then a `DataSend`
+  /// disabling each option that is enabled in either direction, then a final `Shutdown` marker.
+  ///
+  /// # Notes
+  ///
+  /// Subnegotiations still queued by `ParserBuilder::queue_subnegotiations` are discarded, as is any prompt waiting to
+  /// be repainted.
+  pub fn shutdown_sequence(&mut self) -> Vec<events::TelnetEvents> {
+    use telnet::op_option::{MCCP1, MCCP2, MCCP3};
+
+    let mut event_list = Vec::new();
+    // The compressed stream has to end before the remote end can read anything else we send.
+    for &option in [MCCP1, MCCP2].iter() {
+      if self.options.get_option(option).local_state {
+        event_list.push(events::TelnetEvents::EndCompression(option));
+      }
+    }
+    if self.options.get_option(MCCP3).remote_state {
+      // MCCP3 compresses client to server, and the client enables it with DO.
+      event_list.push(events::TelnetEvents::EndCompression(MCCP3));
+    }
+    for option in 0..=255 {
+      event_list.extend(self._wont(option));
+      if self.options.get_option(option).remote && !self.negotiation_pending(option, false) {
+        event_list.extend(self._dont(option));
+      }
+    }
+    self.outbound.clear();
+    self.prompt = None;
+    event_list.push(events::TelnetEvents::Shutdown);
+    event_list
+  }
+  /// Replace the supported options with another profile, such as a fuller set once a user has logged in.
+  ///
+  /// # Arguments
+  ///
+  /// * `profile` - The options to support from now on. Only which options are supported is taken from it, not their
+  ///   states.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<events::TelnetEvents>` - An `EndCompression` for any compressed stream we are sending for an option that is
+  /// no longer supported, then a single `DataSend` disabling options the profile drops and offering or requesting
+  /// options it adds. Empty if nothing needs to be sent.
+  ///
+  /// # Notes
+  ///
+  /// Options that stay supported keep their current state. The negotiations are sent as one `DataSend`, so the
+  /// remote end never sees the connection halfway between profiles.
+  pub fn switch_profile(&mut self, profile: &CompatibilityTable) -> Vec<events::TelnetEvents> {
+    use telnet::op_option::{MCCP1, MCCP2, MCCP3};
+
+    let mut event_list = Vec::new();
+    let mut negotiations = BytesMut::new();
+    let previous = self.options.clone();
+    // Disable everything being dropped before offering anything new.
+    for option in 0..=255 {
+      let old = previous.get_option(option);
+      let new = profile.get_option(option);
+      if old.local && !new.local && old.local_state {
+        if option == MCCP1 || option == MCCP2 {
+          event_list.push(events::TelnetEvents::EndCompression(option));
+        }
+        negotiations.extend(self._wont(option).map(Bytes::from));
+      }
+      if old.remote && !new.remote && old.remote_state {
+        if option == MCCP3 {
+          event_list.push(events::TelnetEvents::EndCompression(option));
+        }
+        negotiations.extend(self._dont(option).map(Bytes::from));
+      }
+      let mut entry = self.options.get_option(option);
+      entry.local = new.local;
+      entry.remote = new.remote;
+      self.options.set_option(option, entry);
+    }
+    for option in 0..=255 {
+      let old = previous.get_option(option);
+      let new = profile.get_option(option);
+      if new.local && !old.local {
+        negotiations.extend(self._will(option).map(Bytes::from));
+      }
+      if new.remote && !old.remote {
+        negotiations.extend(self._do(option).map(Bytes::from));
+      }
+    }
+    if !negotiations.is_empty() {
+      event_list.push(events::TelnetEvents::DataSend(negotiations.freeze()));
+    }
+    event_list
+  }
+  /// Transcode text sent with `send_text`, `send_prompt` and `send_async_text` into another character set.
+  ///
+  /// # Arguments
+  ///
+  /// * `encoder` - The encoder for the character set agreed with the remote end.
+  ///
+  /// * `unmappable` - What to do with characters the character set cannot represent.
+  pub fn set_text_encoder<E>(&mut self, encoder: E, unmappable: Unmappable)
+  where
+    E: TextEncoder + 'static,
+  {
+    self.encoder = Some((Box::new(encoder), unmappable));
+  }
+  /// Support an AUTHENTICATION (RFC 2941) mechanism. Mechanisms added first are preferred.
+  ///
+  /// # Notes
+  ///
+  /// As the server, the mechanisms are offered with `request_authentication`. As the client, the first offered
+  /// mechanism that was added here is used, and `IS NULL` is sent if there is none.
+  pub fn add_auth_mechanism<M>(&mut self, mechanism: M)
+  where
+    M: AuthMechanism + 'static,
+  {
+    self.auth.push(Box::new(mechanism));
+  }
+  /// Offer our authentication mechanisms to the client, as the server.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - An AUTHENTICATION `SEND` to be processed, or None if AUTHENTICATION
+  /// is not enabled remotely or no mechanisms have been added. The result is emitted as `TelnetEvents::AuthComplete`.
+  pub fn request_authentication(&mut self) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::AUTHENTICATION);
+    if !(opt.remote && opt.remote_state) || self.auth.is_empty() {
+      return None;
+    }
+    let pairs = self.auth.iter().map(|mechanism| mechanism.pair()).collect();
+    self.subnegotiation(
+      telnet::op_option::AUTHENTICATION,
+      AuthMessage::Send(pairs).encode(),
+    )
+  }
+  /// Tell the server which account we want to authenticate as, as the client, before answering its `SEND`.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - An AUTHENTICATION `NAME` to be processed, or None if AUTHENTICATION
+  /// is not enabled locally.
+  pub fn send_auth_name(&mut self, name: &str) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::AUTHENTICATION);
+    if !(opt.local && opt.local_state) {
+      return None;
+    }
+    self.subnegotiation(
+      telnet::op_option::AUTHENTICATION,
+      AuthMessage::Name(String::from(name)).encode(),
+    )
+  }
+  /// Ask the client to switch the connection to TLS, as the server.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A START_TLS `FOLLOWS` to be processed, or None if START_TLS is not
+  /// enabled remotely.
+  ///
+  /// # Notes
+  ///
+  /// Once the client answers with its own `FOLLOWS`, a `TelnetEvents::StartTls` is emitted and parsing is suspended
+  /// for the TLS handshake. A client answers the server's `FOLLOWS` automatically.
+  pub fn request_tls(&mut self) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::START_TLS);
+    if !(opt.remote && opt.remote_state) {
+      return None;
+    }
+    let event = self.subnegotiation(
+      telnet::op_option::START_TLS,
+      Bytes::from_static(&[telnet::tls::FOLLOWS]),
+    )?;
+    self.tls_requested = true;
+    Some(event)
+  }
+  /// Get whether parsing is suspended for a TLS handshake.
+  pub fn tls_suspended(&self) -> bool {
+    self.tls_suspended
+  }
+  /// Resume parsing once the TLS handshake started by `TelnetEvents::StartTls` has completed. Data received from
+  /// now on should be the cleartext from inside TLS.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<events::TelnetEvents>` - The events for any data received while parsing was suspended.
+  pub fn tls_established(&mut self) -> Vec<events::TelnetEvents> {
+    let mut event_list: Vec<events::TelnetEvents> = Vec::new();
+    if self.tls_suspended {
+      self.tls_suspended = false;
+      self.process(&mut event_list);
+    }
+    event_list
+  }
+  /// Send a COM-PORT-OPTION (RFC 2217) command, such as `ComPortCommand::SetBaudRate(9600)`.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if COM-PORT-OPTION is not
+  /// enabled.
+  ///
+  /// # Notes
+  ///
+  /// The command is sent with the client's code if COM-PORT-OPTION is enabled locally, and with the server's
+  /// otherwise.
+  pub fn send_com_port(&mut self, command: ComPortCommand) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::COM_PORT_OPTION);
+    let from_server = !(opt.local && opt.local_state);
+    self.subnegotiation(
+      telnet::op_option::COM_PORT_OPTION,
+      ComPortMessage::new(from_server, command).encode(),
+    )
+  }
+  /// Send a TN3270E (RFC 2355) device type or functions message.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if TN3270E is not enabled.
+  ///
+  /// # Notes
+  ///
+  /// Records are framed by `telnet::tn3270e::Tn3270eSession` rather than sent as subnegotiations.
+  pub fn send_tn3270e(&mut self, message: &Tn3270eMessage) -> Option<events::TelnetEvents> {
+    self.subnegotiation(telnet::op_option::TN3270E, message.encode())
+  }
+  /// Set the cipher for ENCRYPT (RFC 2946), once a key has been agreed through `IS` and `REPLY`.
+  ///
+  /// # Notes
+  ///
+  /// Without a cipher, data received while the remote end is encrypting is passed through untouched as
+  /// `TelnetEvents::Encrypted`, so it is never misparsed as telnet.
+  pub fn set_cipher<C>(&mut self, cipher: C)
+  where
+    C: Cipher + 'static,
+  {
+    self.cipher = Some(Box::new(cipher));
+  }
+  /// Get which directions of the connection are encrypted.
+  pub fn encryption(&self) -> EncryptState {
+    self.encryption
+  }
+  /// Send an ENCRYPT message, such as `Support` or `Is`, for the key exchange.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - A DataSend event to be processed, or None if ENCRYPT is not enabled.
+  pub fn send_encrypt(&mut self, message: &EncryptMessage) -> Option<events::TelnetEvents> {
+    self.subnegotiation(telnet::op_option::ENCRYPT, message.encode())
+  }
+  /// Start encrypting what we send, with the cipher set by `set_cipher`.
+  ///
+  /// # Arguments
+  ///
+  /// `keyid` - The key ID agreed with the remote end, usually `[0]`.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - The `START` to be sent, itself unencrypted, or None if ENCRYPT is not
+  /// enabled locally or there is no cipher.
+  ///
+  /// # Notes
+  ///
+  /// Everything sent after it must be passed through `encrypt`, until `end_encryption`.
+  pub fn start_encryption(&mut self, keyid: &[u8]) -> Option<events::TelnetEvents> {
+    let opt = self.options.get_option(telnet::op_option::ENCRYPT);
+    if !(opt.local && opt.local_state) || self.cipher.is_none() {
+      return None;
+    }
+    let start = EncryptMessage::Start(Bytes::copy_from_slice(keyid));
+    let event = self.subnegotiation(telnet::op_option::ENCRYPT, start.encode())?;
+    self.encryption.output = true;
+    Some(event)
+  }
+  /// Encrypt data to be sent, such as the contents of a `DataSend` event, once `start_encryption` has been called.
+  /// Data is returned unchanged while we are not encrypting.
+  pub fn encrypt(&mut self, data: Bytes) -> Bytes {
+    match self.cipher.as_mut() {
+      Some(cipher) if self.encryption.output => {
+        let mut data = BytesMut::from(&data[..]);
+        cipher.encrypt(&mut data);
+        data.freeze()
+      }
+      _ => data,
+    }
+  }
+  /// Stop encrypting what we send.
+  ///
+  /// # Returns
+  ///
+  /// `Option<events::TelnetEvents::DataSend>` - The `END`, already encrypted, or None if we are not encrypting.
+  /// Anything sent after it is unencrypted.
+  pub fn end_encryption(&mut self) -> Option<events::TelnetEvents> {
+    if !self.encryption.output {
+      return None;
+    }
+    let end =
+      events::TelnetSubnegotiation::new(telnet::op_option::ENCRYPT, EncryptMessage::End.encode());
+    let end = self.encrypt(end.into());
+    self.encryption.output = false;
+    Some(events::TelnetEvents::build_send(end))
+  }
+  /// Parse data that was passed through as `TelnetEvents::Encrypted`, once the application has decrypted it.
+  ///
+  /// # Notes
+  ///
+  /// Once the remote end's `END` is parsed, received data is no longer passed through. Any data after the `END` in
+  /// the same `Encrypted` event was not encrypted, so it must not have been decrypted.
+  pub fn receive_decrypted(&mut self, data: &[u8]) -> Vec<events::TelnetEvents> {
+    let mut event_list: Vec<events::TelnetEvents> = Vec::with_capacity(2);
+    self.buffer.put(data);
+    self.process(&mut event_list);
+    event_list
+  }
+  /// Go back to sending text as UTF-8.
+  pub fn clear_text_encoder(&mut self) {
+    self.encoder = None;
+  }
+  /// Get the name of the character set outbound text is transcoded into, or None for UTF-8.
+  pub fn text_encoding(&self) -> Option<&str> {
+    self.encoder.as_ref().map(|(encoder, _)| encoder.name())
+  }
+  /// Transcode received data from another character set into UTF-8 before it is delivered.
+  ///
+  /// # Arguments
+  ///
+  /// * `decoder` - The decoder for the character set agreed with the remote end.
+  ///
+  /// # Notes
+  ///
+  /// Data received while the remote end has BINARY enabled is not transcoded.
+  pub fn set_text_decoder<D>(&mut self, decoder: D)
+  where
+    D: TextDecoder + 'static,
+  {
+    self.decoder = Some(Box::new(decoder));
+  }
+  /// Go back to delivering received data as it arrives.
+  pub fn clear_text_decoder(&mut self) {
+    self.decoder = None;
+  }
+  /// Transcode text in both directions for a character set given by name, such as one agreed in CHARSET negotiation.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The name of the character set, such as `CP437`, `ISO-8859-1` or `KOI8-R`: code page 437 or any
+  ///   single-byte character set in `encoding_rs`. UTF-8 clears any transcoding.
+  ///
+  /// * `unmappable` - What to do with outbound characters the character set cannot represent.
+  ///
+  /// # Returns
+  ///
+  /// `bool` - Whether the character set is supported. Transcoding is left unchanged when it is not.
+  #[cfg(feature = "encodings")]
+  pub fn set_encoding(&mut self, name: &str, unmappable: Unmappable) -> bool {
+    use charset::{Charset, Cp437};
+    match Charset::for_label(name) {
+      Some(charset) => {
+        self.set_text_encoder(charset, unmappable);
+        self.set_text_decoder(charset);
+      }
+      None if Cp437::is_name(name) => {
+        self.set_text_encoder(Cp437, unmappable);
+        self.set_text_decoder(Cp437);
+      }
+      None if name.eq_ignore_ascii_case("UTF-8") => {
+        self.clear_text_encoder();
+        self.clear_text_decoder();
+      }
+      None => return false,
+    }
+    true
+  }
+
+  /// The command that ends a prompt: EOR once the remote end has agreed to END-OF-RECORD, otherwise GA.
+  fn prompt_marker(&self) -> u8 {
+    let eor = self.options.get_option(telnet::op_option::EOR);
+    if eor.local && eor.local_state {
+      EOR
+    } else {
+      GA
+    }
+  }
+
+  /// Convert outbound text into the configured character set.
+  fn encode_text(&self, text: &str) -> Vec<u8> {
+    let encoded = match &self.encoder {
+      Some((encoder, unmappable)) => charset::encode(encoder.as_ref(), text, *unmappable),
+      None => text.as_bytes().to_vec(),
+    };
+    if self.newline.is_some() && !self.binary_enabled(true) {
+      newline::encode(&encoded)
+    } else {
+      encoded
+    }
   }
 
-  /// Extract sub-buffers from the current buffer
-  fn extract_event_data(&mut self) -> Vec<EventType> {
+  /// Extract sub-buffers from the current buffer into `events`
+  fn extract_event_data(&mut self, events: &mut Vec<EventType>) {
     enum State {
       Normal,
-      IAC,
+      Iac,
       Neg,
       Sub,
+      SubIac,
     }
     let mut iter_state = State::Normal;
 
-    let mut events: Vec<EventType> = Vec::with_capacity(4);
-    let iter = self.buffer.iter().enumerate();
+    // Take the buffered bytes as a shared buffer, so every region below is a cheap refcounted slice of it.
+    let buffer = self.buffer.take();
     let mut cmd_begin: usize = 0;
+    // Where the IAC that moved us into the Iac state is.
+    let mut iac_at: usize = 0;
+    let mut index: usize = 0;
 
-    for (index, &val) in iter {
+    while index < buffer.len() {
+      let val = buffer[index];
+      let mut sub_end = false;
       match iter_state {
         State::Normal => {
-          if val == IAC {
-            if cmd_begin < index {
-              events.push(EventType::None(vbytes!(&self.buffer[cmd_begin..index])));
-            }
-            cmd_begin = index;
-            iter_state = State::IAC;
+          // Jump straight to the next IAC, plain data needs no per-byte handling.
+          match find_iac(&buffer[index..]) {
+            Some(offset) => index += offset,
+            None => break,
           }
+          iac_at = index;
+          iter_state = State::Iac;
         }
-        State::IAC => {
+        State::Iac => {
+          if val == IAC {
+            // Double IAC, an escaped 255 that stays part of the surrounding data
+            iter_state = State::Normal;
+            index += 1;
+            continue;
+          }
+          if cmd_begin < iac_at {
+            events.push(EventType::None(buffer.slice(cmd_begin..iac_at)));
+          }
+          cmd_begin = iac_at;
           match val {
-            IAC => iter_state = State::Normal, // Double IAC, ignore
-            GA | EOR | NOP => {
-              events.push(EventType::IAC(vbytes!(&self.buffer[cmd_begin..index + 1])));
+            WILL | WONT | DO | DONT => iter_state = State::Neg,
+            SB => iter_state = State::Sub,
+            _ => {
+              // Any other command is two bytes long, such as GA, NOP or DM.
+              events.push(EventType::IAC(buffer.slice(cmd_begin..index + 1)));
               cmd_begin = index + 1;
               iter_state = State::Normal;
             }
-            SB => iter_state = State::Sub,
-            _ => iter_state = State::Neg, // WILL | WONT | DO | DONT | IS | SEND
           }
         }
         State::Neg => {
-          events.push(EventType::Neg(vbytes!(&self.buffer[cmd_begin..index + 1])));
+          events.push(EventType::Neg(buffer.slice(cmd_begin..index + 1)));
           cmd_begin = index + 1;
           iter_state = State::Normal;
         }
         State::Sub => {
           // Every sub negotiation should be of the form:
           //   IAC SB <option> <optional data> IAC SE
-          // Meaning it must:
-          //  * Be at least 5 bytes long.
-          //  * Start with IAC SB
-          //  * End with IAC SE
-          let long_enough = index - cmd_begin >= 4;
-          let has_prefix = self.buffer[cmd_begin] == IAC && self.buffer[cmd_begin + 1] == SB;
-          let has_suffix = val == SE && self.buffer[index - 1] == IAC;
-          if long_enough && has_prefix && has_suffix {
-            let opt = &self.buffer[cmd_begin + 2];
-            if *opt == telnet::op_option::MCCP2 || *opt == telnet::op_option::MCCP3 {
-              // MCCP2/MCCP3 MUST DECOMPRESS DATA AFTER THIS!
-              events.push(EventType::SubNegotiation(
-                vbytes!(&self.buffer[cmd_begin..index + 1]),
-                Some(vbytes!(&self.buffer[index + 1..])),
-              ));
-              cmd_begin = self.buffer.len();
-              break;
-            } else {
-              events.push(EventType::SubNegotiation(
-                vbytes!(&self.buffer[cmd_begin..index + 1]),
-                None,
-              ));
-              cmd_begin = index + 1;
-              iter_state = State::Normal;
-            }
+          // with any IAC in the data escaped as IAC IAC.
+          //
+          // The exception is legacy MCCP v1 (COMPRESS), whose start sequence ends with
+          // WILL SE (or IAC WILL SE) instead of IAC SE.
+          if index == cmd_begin + 2 {
+            // The option byte is taken as is.
+          } else if val == IAC {
+            iter_state = State::SubIac;
+          } else if val == SE
+            && buffer[index - 1] == WILL
+            && buffer[cmd_begin + 2] == telnet::op_option::MCCP1
+          {
+            sub_end = true;
           }
         }
+        State::SubIac => match val {
+          // An escaped IAC in the data
+          IAC => iter_state = State::Sub,
+          SE => sub_end = true,
+          WILL if buffer[cmd_begin + 2] == telnet::op_option::MCCP1 => iter_state = State::Sub,
+          SB | WILL | WONT | DO | DONT => {
+            // A new command before IAC SE, the subnegotiation was cut short. Resync on the new command.
+            events.push(EventType::SubAborted(buffer.slice(cmd_begin..index - 1)));
+            cmd_begin = index - 1;
+            iter_state = if val == SB { State::Sub } else { State::Neg };
+          }
+          // Any other command, such as a NOP keepalive, is left out of the data.
+          _ => iter_state = State::Sub,
+        },
+      }
+      if sub_end {
+        let opt = buffer[cmd_begin + 2];
+        // Whatever follows these is encrypted, or part of a TLS handshake, so it must not be parsed yet.
+        let switches_stream = (opt == telnet::op_option::ENCRYPT
+          && buffer.get(cmd_begin + 3) == Some(&telnet::encrypt::START))
+          || (opt == telnet::op_option::START_TLS
+            && buffer.get(cmd_begin + 3) == Some(&telnet::tls::FOLLOWS));
+        if opt == telnet::op_option::MCCP1
+          || opt == telnet::op_option::MCCP2
+          || opt == telnet::op_option::MCCP3
+          || switches_stream
+        {
+          // MCCP MUST DECOMPRESS DATA AFTER THIS!
+          events.push(EventType::SubNegotiation(
+            buffer.slice(cmd_begin..index + 1),
+            Some(buffer.slice(index + 1..)),
+          ));
+          cmd_begin = buffer.len();
+          break;
+        } else {
+          events.push(EventType::SubNegotiation(
+            buffer.slice(cmd_begin..index + 1),
+            None,
+          ));
+          cmd_begin = index + 1;
+          iter_state = State::Normal;
+        }
       }
+      index += 1;
     }
-    if cmd_begin < self.buffer.len() {
+    if cmd_begin < buffer.len() {
       match iter_state {
-        State::Sub => events.push(EventType::SubNegotiation(
-          vbytes!(&self.buffer[cmd_begin..]),
-          None,
+        State::Sub | State::SubIac => events.push(EventType::PartialSub(buffer.slice(cmd_begin..))),
+        State::Iac => {
+          if cmd_begin < iac_at {
+            events.push(EventType::None(buffer.slice(cmd_begin..iac_at)));
+          }
+          // A lone IAC, keep it until the rest of the command arrives.
+          self.buffer.put(&buffer[iac_at..]);
+        }
+        // An incomplete command, keep it until the rest arrives.
+        State::Neg => self.buffer.put(&buffer[cmd_begin..]),
+        State::Normal => events.push(EventType::None(buffer.slice(cmd_begin..))),
+      }
+    }
+  }
+
+  /// Whether a received subnegotiation payload of `len` bytes is over the configured limit.
+  fn exceeds_subnegotiation_limit(&self, len: usize) -> bool {
+    matches!(self.config.max_subnegotiation_len, Some(limit) if len > limit)
+  }
+
+  /// Whether the raw data of a received subnegotiation contains an IAC command other than an escaped IAC.
+  fn has_embedded_command(raw: &[u8]) -> bool {
+    let mut iter = raw.iter();
+    while let Some(&byte) = iter.next() {
+      if byte == IAC && iter.next() != Some(&IAC) {
+        return true;
+      }
+    }
+    false
+  }
+
+  /// Get the data of a received subnegotiation, with escaped IAC bytes collapsed and any embedded commands left out.
+  fn subnegotiation_payload(raw: Bytes) -> Bytes {
+    if find_iac(&raw).is_none() {
+      return raw;
+    }
+    let mut payload = BytesMut::with_capacity(raw.len());
+    let mut iter = raw.iter();
+    while let Some(&byte) = iter.next() {
+      if byte != IAC {
+        payload.put_u8(byte);
+      } else if iter.next() == Some(&IAC) {
+        payload.put_u8(IAC);
+      }
+    }
+    payload.freeze()
+  }
+
+  /// Discard the rest of an overflowed subnegotiation from the internal buffer, up to and including its `IAC SE`.
+  ///
+  /// Returns the number of bytes discarded.
+  fn skip_overflowed(&mut self) -> usize {
+    let mut index = 0;
+    while index < self.buffer.len() {
+      if self.buffer[index] == IAC {
+        match self.buffer.get(index + 1) {
+          Some(&SE) => {
+            self.overflowed = false;
+            self.buffer.discard(index + 2);
+            return index + 2;
+          }
+          Some(_) => index += 2,
+          // Keep a trailing IAC, it may be the start of the IAC SE.
+          None => break,
+        }
+      } else {
+        index += 1;
+      }
+    }
+    self.buffer.discard(index);
+    index
+  }
+
+  /// Emit received data.
+  fn receive_data<S: EventSink + ?Sized>(&mut self, data: Bytes, sink: &mut S) {
+    let binary = self.binary_enabled(false);
+    let data = match self.newline.as_mut() {
+      Some(newline) if !binary => newline.translate(data),
+      Some(newline) => {
+        // A CR held back before the remote end switched to binary is still NVT text.
+        if let Some(cr) = newline.take_pending() {
+          self.deliver_data(cr, sink);
+        }
+        data
+      }
+      None => data,
+    };
+    let data = match &self.decoder {
+      Some(decoder) if !binary => {
+        Bytes::from(charset::decode(decoder.as_ref(), &data).into_bytes())
+      }
+      _ => data,
+    };
+    self.deliver_data(data, sink);
+  }
+
+  /// Emit received data that has had its line endings translated.
+  fn deliver_data<S: EventSink + ?Sized>(&mut self, data: Bytes, sink: &mut S) {
+    let opt = self.options.get_option(telnet::op_option::MSP);
+    let segments = match self.msp.as_mut() {
+      Some(msp) if opt.remote && opt.remote_state => msp.scan(&data),
+      _ => {
+        self.deliver_text(data, sink);
+        return;
+      }
+    };
+    for segment in segments {
+      match segment {
+        MspSegment::Data(data) => self.deliver_text(data, sink),
+        MspSegment::Trigger(trigger) => sink.push(events::TelnetEvents::Sound(trigger)),
+      }
+    }
+  }
+  /// Deliver received text, after any MSP triggers have been removed.
+  fn deliver_text<S: EventSink + ?Sized>(&mut self, data: Bytes, sink: &mut S) {
+    if data.iter().any(|&b| b == b'\r' || b == b'\n') {
+      // The remote end has answered any outstanding prompt.
+      self.prompt = None;
+    }
+    let pieces = match self.sanitizer.as_mut() {
+      Some(sanitizer) => sanitizer.apply(data),
+      None => return self.deliver_clean(data, sink),
+    };
+    for piece in pieces {
+      match piece {
+        Sanitized::Data(data) => self.deliver_clean(data, sink),
+        Sanitized::Dropped(dropped) => sink.push(events::TelnetEvents::Error(
+          events::TelnetErrorKind::LineTooLong,
+          dropped,
         )),
-        _ => events.push(EventType::None(vbytes!(&self.buffer[cmd_begin..]))),
       }
     }
+  }
+  /// Deliver received text that has been sanitized.
+  fn deliver_clean<S: EventSink + ?Sized>(&mut self, data: Bytes, sink: &mut S) {
+    if !self.config.prompt_markers.is_empty() && self.lines.is_none() {
+      match data.iter().rposition(|&b| b == b'\r' || b == b'\n') {
+        Some(end) => {
+          self.prompt_line.clear();
+          self.prompt_line.put(&data[end + 1..]);
+        }
+        None => self.prompt_line.put(&data[..]),
+      }
+      if self.prompt_line.len() > Self::PROMPT_LIMIT {
+        let excess = self.prompt_line.len() - Self::PROMPT_LIMIT;
+        self.prompt_line.advance(excess);
+      }
+    }
+    if let Some(lines) = self.lines.as_mut() {
+      let mut assembled = Vec::new();
+      lines.push(&data, &mut assembled);
+      for assembled in assembled {
+        sink.push(match assembled {
+          Assembled::Line(line) => events::TelnetEvents::Line(line),
+          Assembled::TooLong(line) => {
+            events::TelnetEvents::Error(events::TelnetErrorKind::LineTooLong, line)
+          }
+        });
+      }
+    } else if let Some(utf8) = self.utf8.as_mut() {
+      match utf8.decode(&data) {
+        Some(Decoded::Text(text)) => sink.push(events::TelnetEvents::Text(text)),
+        Some(Decoded::Raw(data)) => sink.push(events::TelnetEvents::build_receive(data)),
+        None => (),
+      }
+    } else if !data.is_empty() {
+      sink.push(events::TelnetEvents::build_receive(data));
+    }
+  }
 
-    // Empty the buffer when we are done
-    self.buffer.clear();
-    events
+  /// Handle a received negotiation sequence, updating option state and generating any replies.
+  fn process_negotiation<S: EventSink + ?Sized>(&mut self, command: u8, option: u8, sink: &mut S) {
+    let mut opt = self.options.get_option(option);
+    let event = events::TelnetNegotiation::new(command, option);
+    let local = command == DO || command == DONT;
+    if let Some(wanted) = self.take_pending(option, local) {
+      // This is the answer to a request we made, so it must not be answered again.
+      let enabled = command == WILL || command == DO;
+      if local {
+        opt.local_state = enabled && wanted;
+      } else {
+        opt.remote_state = enabled && wanted;
+      }
+      self.options.set_option(option, opt);
+      sink.push(events::TelnetEvents::Negotiation(event));
+      sink.push(events::TelnetEvents::NegotiationComplete(
+        events::TelnetNegotiationComplete::new(option, enabled == wanted, local),
+      ));
+      if wanted {
+        self.flush_outbound(option, sink);
+      }
+      self.continue_resync(option, local, wanted, sink);
+      return;
+    }
+    match command {
+      WILL => {
+        if opt.remote && !opt.remote_state {
+          opt.remote_state = true;
+          self.send_reply(DO, option, true, sink);
+          self.options.set_option(option, opt);
+          sink.push(events::TelnetEvents::Negotiation(event));
+        } else if !opt.remote {
+          self.send_reply(DONT, option, false, sink);
+        }
+      }
+      WONT => {
+        if opt.remote_state {
+          opt.remote_state = false;
+          self.options.set_option(option, opt);
+          self.send_reply(DONT, option, true, sink);
+        }
+        sink.push(events::TelnetEvents::Negotiation(event));
+      }
+      DO => {
+        if opt.local && !opt.local_state {
+          opt.local_state = true;
+          opt.remote_state = true;
+          self.send_reply(WILL, option, true, sink);
+          self.options.set_option(option, opt);
+          sink.push(events::TelnetEvents::Negotiation(event));
+        } else if !opt.local {
+          self.send_reply(WONT, option, false, sink);
+        }
+      }
+      DONT => {
+        if opt.local_state {
+          opt.local_state = false;
+          self.options.set_option(option, opt);
+          self.send_reply(WONT, option, true, sink);
+        }
+        sink.push(events::TelnetEvents::Negotiation(event));
+      }
+      _ => (),
+    }
+  }
+
+  /// Emit the compression transitions caused by a received negotiation, before it changes any option state.
+  fn compression_negotiated<S: EventSink + ?Sized>(
+    &mut self,
+    command: u8,
+    option: u8,
+    sink: &mut S,
+  ) {
+    use telnet::op_option::{MCCP1, MCCP2, MCCP3};
+
+    let opt = self.options.get_option(option);
+    match (command, option) {
+      // Our compressed stream has to end before the WONT reply is sent.
+      (DONT, MCCP1) | (DONT, MCCP2) if opt.local_state => {
+        sink.push(events::TelnetEvents::EndCompression(option));
+      }
+      (WONT, MCCP3) if opt.remote_state => {
+        sink.push(events::TelnetEvents::EndCompression(option));
+      }
+      _ => (),
+    }
+    // The remote end switching off the option it was compressing with means its stream is over.
+    let stops = match option {
+      MCCP1 | MCCP2 => command == WONT,
+      MCCP3 => command == DONT,
+      _ => false,
+    };
+    if stops && self.decompressing == Some(option) {
+      self.decompressing = None;
+      sink.push(events::TelnetEvents::DecompressionEnded(option));
+    }
+  }
+
+  /// Take the next step of a `resync_option` once a request for one side of the option has been answered.
+  fn continue_resync<S: EventSink + ?Sized>(
+    &mut self,
+    option: u8,
+    local: bool,
+    wanted: bool,
+    sink: &mut S,
+  ) {
+    let flag = if local {
+      Self::RESYNC_LOCAL
+    } else {
+      Self::RESYNC_REMOTE
+    };
+    if self.pending[option as usize] & flag == 0 {
+      return;
+    }
+    if !wanted {
+      // The side is now disabled, so enable it again.
+      let event = if local {
+        self._will(option)
+      } else {
+        self._do(option)
+      };
+      if let Some(event) = event {
+        self.push_send(event, sink);
+        return;
+      }
+    }
+    self.pending[option as usize] &= !flag;
+    if self.pending[option as usize] & (Self::RESYNC_LOCAL | Self::RESYNC_REMOTE) == 0 {
+      sink.push(events::TelnetEvents::Resynced(option));
+    }
+  }
+
+  /// Send or drop the subnegotiations queued for an option whose negotiation has completed.
+  fn flush_outbound<S: EventSink + ?Sized>(&mut self, option: u8, sink: &mut S) {
+    if self.outbound.iter().all(|sub| sub.option != option) {
+      return;
+    }
+    let enabled = self.options.get_option(option).enabled();
+    let mut outbound = core::mem::take(&mut self.outbound);
+    outbound.retain(|sub| {
+      if sub.option != option {
+        return true;
+      }
+      if enabled {
+        self.push_send(events::TelnetEvents::Subnegotiation(sub.clone()), sink);
+      } else {
+        sink.push(events::TelnetEvents::Error(
+          events::TelnetErrorKind::SubnegotiationDropped,
+          sub.clone().into(),
+        ));
+      }
+      false
+    });
+    self.outbound = outbound;
+  }
+
+  /// Send an automatic negotiation reply, unless the negotiation loop guard suppresses it.
+  ///
+  /// `changed` is whether the reply accompanies a change in the option's state. Replies that don't are counted, and
+  /// once the configured limit is exceeded they are dropped and a single `NegotiationLoop` error is emitted.
+  fn send_reply<S: EventSink + ?Sized>(
+    &mut self,
+    command: u8,
+    option: u8,
+    changed: bool,
+    sink: &mut S,
+  ) {
+    let reply = vbytes!(&[IAC, command, option]);
+    if let Some(limit) = self.config.negotiation_limit {
+      let count = &mut self.repeated[option as usize];
+      if changed {
+        *count = 0;
+      } else {
+        *count = count.saturating_add(1);
+        if *count > limit {
+          if *count - 1 == limit {
+            sink.push(events::TelnetEvents::Error(
+              events::TelnetErrorKind::NegotiationLoop,
+              reply,
+            ));
+          }
+          return;
+        }
+      }
+    }
+    self.push_send(
+      events::TelnetEvents::build_negotiation(command, option),
+      sink,
+    );
+  }
+
+  /// Push the wire bytes of a generated event as a `DataSend`, mirroring the event itself if configured to.
+  fn push_send<S: EventSink + ?Sized>(&self, event: events::TelnetEvents, sink: &mut S) {
+    sink.push(events::TelnetEvents::build_send(event.clone().into()));
+    if self.config.mirror_outbound {
+      sink.push(events::TelnetEvents::Outbound(Box::new(event)));
+    }
+  }
+
+  /// Report the window size set with `set_window_size` once the remote end has asked for NAWS.
+  fn send_window_size<S: EventSink + ?Sized>(&mut self, sink: &mut S) {
+    let opt = self.options.get_option(telnet::op_option::NAWS);
+    if let (Some(window), true) = (self.window, opt.local && opt.local_state) {
+      let naws = events::TelnetSubnegotiation::new(telnet::op_option::NAWS, window.encode());
+      self.push_send(events::TelnetEvents::Subnegotiation(naws), sink);
+    }
+  }
+
+  /// Handle the payload of a received subnegotiation for the options the parser understands.
+  fn process_subnegotiation<S: EventSink + ?Sized>(
+    &mut self,
+    option: u8,
+    data: &Bytes,
+    sink: &mut S,
+  ) {
+    if option == telnet::op_option::NEWENVIRON {
+      if let Some(message) = EnvironMessage::decode(data) {
+        self.process_environ(option, message, sink);
+      }
+    } else if let (telnet::op_option::ENVIRON, Some(legacy)) = (option, self.config.legacy_environ)
+    {
+      if let Some(message) = EnvironMessage::decode_legacy(data, legacy) {
+        self.process_environ(option, message, sink);
+      }
+    } else if option == telnet::op_option::AARD102 {
+      if let Some(update) = Aard102::decode(data) {
+        sink.push(events::TelnetEvents::Aard102(update));
+      }
+    } else if option == telnet::op_option::AUTHENTICATION {
+      if let Some(message) = AuthMessage::decode(data) {
+        self.process_auth(message, sink);
+      }
+    } else if option == telnet::op_option::ENCRYPT {
+      if let Some(message) = EncryptMessage::decode(data) {
+        let opt = self.options.get_option(option);
+        match message {
+          EncryptMessage::Start(_) if opt.remote && opt.remote_state => {
+            self.encryption.input = true;
+            self.end_matched = 0;
+          }
+          EncryptMessage::End => self.encryption.input = false,
+          _ => (),
+        }
+        sink.push(events::TelnetEvents::Encrypt(message));
+      }
+    } else if option == telnet::op_option::START_TLS {
+      let opt = self.options.get_option(option);
+      let follows = telnet::tls::is_follows(data);
+      if follows && opt.local && opt.local_state {
+        // The client answers with its own FOLLOWS, then starts the handshake.
+        let follows =
+          events::TelnetSubnegotiation::new(option, Bytes::from_static(&[telnet::tls::FOLLOWS]));
+        self.push_send(events::TelnetEvents::Subnegotiation(follows), sink);
+        self.tls_suspended = true;
+      } else if follows
+        && opt.remote
+        && opt.remote_state
+        && core::mem::take(&mut self.tls_requested)
+      {
+        self.tls_suspended = true;
+      }
+    } else if option == telnet::op_option::COM_PORT_OPTION {
+      if let Some(message) = ComPortMessage::decode(data) {
+        sink.push(events::TelnetEvents::ComPort(message));
+      }
+    } else if option == telnet::op_option::LFLOW {
+      let opt = self.options.get_option(option);
+      if let (true, Some(command)) = (opt.local && opt.local_state, LflowCommand::decode(data)) {
+        self.lflow.apply(command);
+        sink.push(events::TelnetEvents::FlowControl(self.lflow));
+      }
+    } else if option == telnet::op_option::LINEMODE {
+      if let Some(mode) = LinemodeMode::decode(data) {
+        self.process_linemode_mode(mode, sink);
+      } else if let Some(entries) = linemode::decode_slc(data) {
+        let replies = self.slc.merge(&entries);
+        if !replies.is_empty() {
+          let reply = events::TelnetSubnegotiation::new(option, linemode::encode_slc(&replies));
+          self.push_send(events::TelnetEvents::Subnegotiation(reply), sink);
+        }
+      }
+    } else if option == telnet::op_option::NAWS {
+      if let Some(naws) = Naws::decode(data) {
+        self.remote_window = Some(naws);
+      }
+    } else if option == telnet::op_option::STATUS {
+      if data.first() == Some(&telnet::status::SEND) {
+        let opt = self.options.get_option(option);
+        if opt.local && opt.local_state {
+          let report = StatusReport::from_table(&self.options);
+          let is = events::TelnetSubnegotiation::new(option, report.encode());
+          self.push_send(events::TelnetEvents::Subnegotiation(is), sink);
+        }
+      } else if let Some(report) = StatusReport::decode(data) {
+        let mismatches = report.compare(&self.options);
+        if !mismatches.is_empty() {
+          sink.push(events::TelnetEvents::StateMismatch(mismatches));
+        }
+        self.remote_status = Some(report);
+      }
+    } else if option == telnet::op_option::TSPEED {
+      if let Some(message) = TspeedMessage::decode(data) {
+        sink.push(events::TelnetEvents::TerminalSpeed(message));
+      }
+    } else if option == telnet::op_option::TN3270E {
+      if let Some(message) = Tn3270eMessage::decode(data) {
+        sink.push(events::TelnetEvents::Tn3270e(message));
+      }
+    } else if option == telnet::op_option::XDISPLOC {
+      if let Some(message) = XdisplocMessage::decode(data) {
+        sink.push(events::TelnetEvents::DisplayLocation(message));
+      }
+    } else if option == telnet::op_option::TTYPE {
+      match TtypeMessage::decode(data) {
+        Some(TtypeMessage::Send) => self.answer_terminal_type(sink),
+        Some(TtypeMessage::Is(name)) => {
+          if let Some(fingerprinter) = self.fingerprinter.as_mut() {
+            if let Some(guess) = fingerprinter.observe_terminal_type(&name) {
+              sink.push(events::TelnetEvents::PeerStack(guess));
+            }
+          }
+          self.record_terminal_type(name, sink);
+        }
+        None => (),
+      }
+    }
+  }
+
+  /// Follow an AUTHENTICATION message, passing the data of the exchange to the mechanism in use.
+  fn process_auth<S: EventSink + ?Sized>(&mut self, message: AuthMessage, sink: &mut S) {
+    let opt = self.options.get_option(telnet::op_option::AUTHENTICATION);
+    let client = opt.local && opt.local_state;
+    let server = opt.remote && opt.remote_state;
+    match message {
+      AuthMessage::Send(pairs) if client => {
+        let found = pairs.iter().find_map(|pair| {
+          self
+            .auth
+            .iter()
+            .position(|mechanism| mechanism.pair() == *pair)
+        });
+        match found {
+          Some(index) => {
+            self.auth_active = Some(index);
+            let step = self.auth[index].start();
+            self.auth_step(index, step, true, sink);
+          }
+          None => {
+            let pair = AuthPair::new(auth::NULL, 0);
+            self.send_auth(AuthMessage::Is(pair, Bytes::new()), sink);
+            sink.push(events::TelnetEvents::AuthComplete {
+              pair,
+              accepted: false,
+            });
+          }
+        }
+      }
+      AuthMessage::Is(pair, data) if server => {
+        let index = self
+          .auth
+          .iter()
+          .position(|mechanism| pair.kind != auth::NULL && mechanism.pair() == pair);
+        match index {
+          Some(index) => {
+            self.auth_active = Some(index);
+            let step = self.auth[index].step(&data);
+            self.auth_step(index, step, false, sink);
+          }
+          None => sink.push(events::TelnetEvents::AuthComplete {
+            pair,
+            accepted: false,
+          }),
+        }
+      }
+      AuthMessage::Reply(pair, data) if client => {
+        if let Some(index) = self
+          .auth_active
+          .filter(|&index| self.auth[index].pair() == pair)
+        {
+          let step = self.auth[index].step(&data);
+          self.auth_step(index, step, true, sink);
+        }
+      }
+      AuthMessage::Name(name) if server => sink.push(events::TelnetEvents::AuthName(name)),
+      _ => (),
+    }
+  }
+  /// Send the data produced by a mechanism, as an `IS` from the client or a `REPLY` from the server, and report the
+  /// end of the exchange.
+  fn auth_step<S: EventSink + ?Sized>(
+    &mut self,
+    index: usize,
+    step: AuthStep,
+    client: bool,
+    sink: &mut S,
+  ) {
+    let pair = self.auth[index].pair();
+    let wrap = |data| {
+      if client {
+        AuthMessage::Is(pair, data)
+      } else {
+        AuthMessage::Reply(pair, data)
+      }
+    };
+    match step {
+      AuthStep::Continue(data) => self.send_auth(wrap(data), sink),
+      AuthStep::Complete { data, accepted } => {
+        if let Some(data) = data {
+          self.send_auth(wrap(data), sink);
+        }
+        self.auth_active = None;
+        sink.push(events::TelnetEvents::AuthComplete { pair, accepted });
+      }
+    }
+  }
+  fn send_auth<S: EventSink + ?Sized>(&mut self, message: AuthMessage, sink: &mut S) {
+    let sub =
+      events::TelnetSubnegotiation::new(telnet::op_option::AUTHENTICATION, message.encode());
+    self.push_send(events::TelnetEvents::Subnegotiation(sub), sink);
+  }
+  /// Decrypt received data while the remote end is encrypting, stopping after its `END`.
+  ///
+  /// # Returns
+  ///
+  /// `Option<Vec<u8>>` - The data, decrypted up to and including any `END`, or None if there is no cipher.
+  fn decrypt_input(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+    if !self.encryption.input {
+      return None;
+    }
+    let cipher = self.cipher.as_mut()?;
+    let mut plain = data.to_vec();
+    for index in 0..plain.len() {
+      // One byte at a time, as whatever follows the END is not encrypted.
+      cipher.decrypt(&mut plain[index..=index]);
+      let byte = plain[index];
+      if byte == telnet::encrypt::END_SEQUENCE[self.end_matched] {
+        self.end_matched += 1;
+      } else {
+        self.end_matched = (byte == telnet::encrypt::END_SEQUENCE[0]) as usize;
+      }
+      if self.end_matched == telnet::encrypt::END_SEQUENCE.len() {
+        self.end_matched = 0;
+        self.encryption.input = false;
+        break;
+      }
+    }
+    Some(plain)
+  }
+  /// Handle the data received in the same call after a subnegotiation that can change the stream, such as ENCRYPT
+  /// `START`, START_TLS `FOLLOWS` or an MCCP start, when it is not part of a TLS handshake or compressed.
+  fn receive_after_start<S: EventSink + ?Sized>(&mut self, data: &Bytes, sink: &mut S) {
+    if data.is_empty() {
+      return;
+    }
+    if !self.encryption.input {
+      // The start was not accepted, so the data is parsed as it is.
+      self.buffer.put(&data[..]);
+      self.process(sink);
+    } else if let Some(plain) = self.decrypt_input(data) {
+      self.buffer.put(&plain[..]);
+      self.process(sink);
+    } else {
+      sink.push(events::TelnetEvents::Encrypted(data.clone()));
+    }
+  }
+  /// Follow a LINEMODE `MODE` from the remote end. As the client, a new mode is adopted and acknowledged, as RFC 1184
+  /// requires. As the server, the client's mode is recorded.
+  fn process_linemode_mode<S: EventSink + ?Sized>(&mut self, mode: LinemodeMode, sink: &mut S) {
+    let opt = self.options.get_option(telnet::op_option::LINEMODE);
+    if opt.local && opt.local_state {
+      // An acknowledgement, or a mode we already have, is not answered.
+      if mode.contains(LinemodeMode::MODE_ACK) || self.linemode == Some(mode) {
+        return;
+      }
+      self.linemode = Some(mode);
+      let ack = events::TelnetSubnegotiation::new(
+        telnet::op_option::LINEMODE,
+        (mode | LinemodeMode::MODE_ACK).encode(),
+      );
+      self.push_send(events::TelnetEvents::Subnegotiation(ack), sink);
+    } else if opt.remote && opt.remote_state {
+      self.linemode = Some(mode.without_ack());
+    }
+  }
+  /// Report the next of our terminal types. After the last one, it is repeated once before starting over, so the
+  /// remote end knows it has seen the whole list.
+  fn answer_terminal_type<S: EventSink + ?Sized>(&mut self, sink: &mut S) {
+    let opt = self.options.get_option(telnet::op_option::TTYPE);
+    if !(opt.local && opt.local_state) || self.terminal_names.is_empty() {
+      return;
+    }
+    let name = match self.terminal_names.get(self.terminal_index) {
+      Some(name) => {
+        self.terminal_index += 1;
+        name.clone()
+      }
+      None => {
+        self.terminal_index = 0;
+        self.terminal_names[self.terminal_names.len() - 1].clone()
+      }
+    };
+    let is =
+      events::TelnetSubnegotiation::new(telnet::op_option::TTYPE, TtypeMessage::Is(name).encode());
+    self.push_send(events::TelnetEvents::Subnegotiation(is), sink);
+  }
+
+  /// Record a terminal type reported by the remote end, asking for the next one until the list repeats.
+  fn record_terminal_type<S: EventSink + ?Sized>(&mut self, name: String, sink: &mut S) {
+    if !self.cycling_terminal_types {
+      if !self.terminal_types.contains(&name) {
+        self.terminal_types.push(name);
+      }
+      return;
+    }
+    if self.terminal_types.last() == Some(&name) || self.terminal_types.first() == Some(&name) {
+      self.cycling_terminal_types = false;
+      return;
+    }
+    self.terminal_types.push(name);
+    let send =
+      events::TelnetSubnegotiation::new(telnet::op_option::TTYPE, TtypeMessage::Send.encode());
+    self.push_send(events::TelnetEvents::Subnegotiation(send), sink);
+  }
+
+  /// Pick the option to exchange environment variables over in one direction: NEW-ENVIRON if it is enabled, otherwise
+  /// old ENVIRON if it is enabled and `ParserBuilder::legacy_environ` was set.
+  fn environ_option(&self, local: bool) -> Option<(u8, Option<LegacyEnviron>)> {
+    let enabled = |option| {
+      let opt = self.options.get_option(option);
+      if local {
+        opt.local_state
+      } else {
+        opt.remote_state
+      }
+    };
+    if enabled(telnet::op_option::NEWENVIRON) {
+      return Some((telnet::op_option::NEWENVIRON, None));
+    }
+    match self.config.legacy_environ {
+      Some(legacy) if enabled(telnet::op_option::ENVIRON) => {
+        Some((telnet::op_option::ENVIRON, Some(legacy)))
+      }
+      _ => None,
+    }
+  }
+
+  /// Answer NEW-ENVIRON (or old ENVIRON) requests, and record the variables reported by the remote end.
+  fn process_environ<S: EventSink + ?Sized>(
+    &mut self,
+    option: u8,
+    message: EnvironMessage,
+    sink: &mut S,
+  ) {
+    match message.command {
+      EnvironCommand::Send => {
+        if !self.options.get_option(option).local_state {
+          return;
+        }
+        let mut vars = Vec::new();
+        for request in message.vars.iter() {
+          if request.name.is_empty() {
+            // A bare VAR or USERVAR asks for every variable of that kind.
+            vars.extend(
+              self
+                .environ
+                .iter()
+                .filter(|v| v.kind == request.kind)
+                .cloned(),
+            );
+          } else {
+            match self
+              .environ
+              .iter()
+              .find(|v| v.kind == request.kind && v.name == request.name)
+            {
+              Some(var) => vars.push(var.clone()),
+              None => vars.push(EnvironVar::new(request.kind, &request.name, None)),
+            }
+          }
+        }
+        if message.vars.is_empty() {
+          vars = self.environ.clone();
+        }
+        let reply = EnvironMessage::new(EnvironCommand::Is, vars);
+        let legacy = self
+          .config
+          .legacy_environ
+          .filter(|_| option == telnet::op_option::ENVIRON);
+        self.push_send(
+          events::TelnetEvents::build_subnegotiation(option, encode_environ(&reply, legacy)),
+          sink,
+        );
+      }
+      EnvironCommand::Is | EnvironCommand::Info => {
+        let mut changed = Vec::new();
+        for var in message.vars {
+          match self
+            .remote_environ
+            .iter_mut()
+            .find(|v| v.kind == var.kind && v.name == var.name)
+          {
+            Some(existing) if existing.value == var.value => continue,
+            Some(existing) => existing.value = var.value.clone(),
+            None => self.remote_environ.push(var.clone()),
+          }
+          changed.push(var);
+        }
+        if !changed.is_empty() {
+          sink.push(events::TelnetEvents::EnvironChange(changed));
+        }
+      }
+    }
   }
 
   /// The internal parser method that takes the current buffer and generates the corresponding events.
-  fn process(&mut self) -> Vec<events::TelnetEvents> {
-    let mut event_list: Vec<events::TelnetEvents> = Vec::with_capacity(2);
-    for event in self.extract_event_data() {
+  fn process<S: EventSink + ?Sized>(&mut self, sink: &mut S) {
+    if self.tls_suspended {
+      return;
+    }
+    let strict = self.config.mode == ParserMode::Strict;
+    // Bytes of the stream parsed so far by this call, used to locate the urgent pointer.
+    let mut position: usize = 0;
+    if self.overflowed {
+      let skipped = self.skip_overflowed();
+      if self.overflowed {
+        if let Some(urgent) = self.urgent.as_mut() {
+          *urgent = urgent.saturating_sub(skipped);
+        }
+        return;
+      }
+      position += skipped;
+    }
+    // Reuse the same scratch list between calls, so steady-state parsing does not allocate for it.
+    let mut extracted = core::mem::take(&mut self.scratch);
+    self.extract_event_data(&mut extracted);
+    for event in extracted.drain(..) {
       match event {
-        EventType::None(buffer) | EventType::IAC(buffer) | EventType::Neg(buffer) => {
-          if buffer.is_empty() {
+        EventType::None(buffer) => {
+          position += buffer.len();
+          if buffer.is_empty() || self.urgent.is_some() {
+            // Nothing to deliver, or discarded by a SYNCH in progress.
             continue;
           }
-          if buffer[0] == IAC {
-            match buffer.len() {
-              2 => {
-                if buffer[1] != SE {
-                  // IAC command
-                  event_list.push(events::TelnetEvents::build_iac(buffer[1]));
-                }
+          if find_iac(&buffer).is_some() {
+            // Collapse escaped IAC bytes into literal 255s.
+            self.receive_data(Parser::unescape_iac(buffer), sink);
+          } else {
+            self.receive_data(buffer, sink);
+          }
+        }
+        EventType::IAC(buffer) | EventType::Neg(buffer) => {
+          position += buffer.len();
+          match buffer.len() {
+            2 if self.config.denied_commands.contains(&buffer[1]) => {
+              sink.push(events::TelnetEvents::Error(
+                events::TelnetErrorKind::DeniedCommand,
+                buffer,
+              ));
+            }
+            2 if strict && buffer[1] == SE => {
+              // IAC SE without a subnegotiation to end.
+              sink.push(events::TelnetEvents::Error(
+                events::TelnetErrorKind::Malformed,
+                buffer,
+              ));
+            }
+            // No command below EOF (236) is defined.
+            2 if strict && buffer[1] < 236 => {
+              sink.push(events::TelnetEvents::Error(
+                events::TelnetErrorKind::UnknownCommand,
+                buffer,
+              ));
+            }
+            2 if buffer[1] == DM => {
+              if matches!(self.urgent, Some(urgent) if position >= urgent) {
+                // This is the Data Mark of the current SYNCH.
+                self.urgent = None;
+              }
+              sink.push(events::TelnetEvents::DataMark);
+            }
+            2 if buffer[1] != SE => {
+              // IAC command
+              sink.push(events::TelnetEvents::build_iac(buffer[1]));
+              if self.config.prompt_markers.contains(&buffer[1]) {
+                let line = match self.lines.as_mut() {
+                  Some(lines) => lines.take_partial().unwrap_or_default(),
+                  None => self.prompt_line.split().freeze(),
+                };
+                sink.push(events::TelnetEvents::Prompt(line));
+              }
+              let reply = self
+                .config
+                .auto_responses
+                .iter()
+                .find(|(command, _)| *command == buffer[1])
+                .map(|(_, reply)| reply.clone());
+              if let Some(reply) = reply {
+                self.push_send(events::TelnetEvents::build_send(reply), sink);
+              }
+            }
+            3 if !self.keepalives.is_empty()
+              && buffer[2] == telnet::op_option::TM
+              && (buffer[1] == WILL || buffer[1] == WONT) =>
+            {
+              // The answer to a keepalive probe or ping.
+              match self.keepalives.pop_front() {
+                Some(Some(sent)) => sink.push(events::TelnetEvents::Pong {
+                  rtt_hint: self.clock.saturating_sub(sent),
+                }),
+                _ => sink.push(events::TelnetEvents::KeepaliveAck),
               }
-              3 => {
-                // Negotiation
-                let mut opt = self.options.get_option(buffer[2]);
-                let event = events::TelnetNegotiation::new(buffer[1], buffer[2]);
-                match buffer[1] {
-                  WILL => {
-                    if opt.remote && !opt.remote_state {
-                      opt.remote_state = true;
-                      event_list.push(events::TelnetEvents::build_send(vbytes!(&[
-                        IAC, DO, buffer[2]
-                      ])));
-                      self.options.set_option(buffer[2], opt);
-                      event_list.push(events::TelnetEvents::Negotiation(event));
-                    } else if !opt.remote {
-                      event_list.push(events::TelnetEvents::build_send(vbytes!(&[
-                        IAC, DONT, buffer[2]
-                      ])));
-                    }
-                  }
-                  WONT => {
-                    if opt.remote_state {
-                      opt.remote_state = false;
-                      self.options.set_option(buffer[2], opt);
-                      event_list.push(events::TelnetEvents::build_send(vbytes!(&[
-                        IAC, DONT, buffer[2]
-                      ])));
-                    }
-                    event_list.push(events::TelnetEvents::Negotiation(event));
-                  }
-                  DO => {
-                    if opt.local && !opt.local_state {
-                      opt.local_state = true;
-                      opt.remote_state = true;
-                      event_list.push(events::TelnetEvents::build_send(vbytes!(&[
-                        IAC, WILL, buffer[2]
-                      ])));
-                      self.options.set_option(buffer[2], opt);
-                      event_list.push(events::TelnetEvents::Negotiation(event));
-                    } else if !opt.local {
-                      event_list.push(events::TelnetEvents::build_send(vbytes!(&[
-                        IAC, WONT, buffer[2]
-                      ])));
-                    }
-                  }
-                  DONT => {
-                    if opt.local_state {
-                      opt.local_state = false;
-                      self.options.set_option(buffer[2], opt);
-                      event_list.push(events::TelnetEvents::build_send(vbytes!(&[
-                        IAC, WONT, buffer[2]
-                      ])));
-                    }
-                    event_list.push(events::TelnetEvents::Negotiation(event));
-                  }
-                  _ => (),
+            }
+            3 if buffer[2] == telnet::op_option::LOGOUT
+              && buffer[1] == DO
+              && self.options.get_option(telnet::op_option::LOGOUT).local =>
+            {
+              // Not an option to enable, but a request to be logged out.
+              sink.push(events::TelnetEvents::LogoutRequested);
+            }
+            3 if buffer[2] == telnet::op_option::LOGOUT
+              && buffer[1] == WILL
+              && (self.logout_requested
+                || self.options.get_option(telnet::op_option::LOGOUT).remote) =>
+            {
+              // The remote end is logging us out. Unless we asked for it, this is answered to show that we know.
+              if !core::mem::take(&mut self.logout_requested) {
+                let reply = self.negotiate(DO, telnet::op_option::LOGOUT);
+                self.push_send(reply, sink);
+              }
+              sink.push(events::TelnetEvents::LogoutAnnounced);
+            }
+            3 => {
+              // Negotiation
+              self.compression_negotiated(buffer[1], buffer[2], sink);
+              self.process_negotiation(buffer[1], buffer[2], sink);
+              if buffer[1] == DO && buffer[2] == telnet::op_option::NAWS {
+                self.send_window_size(sink);
+              }
+              if let Some(fingerprinter) = self.fingerprinter.as_mut() {
+                if let Some(guess) = fingerprinter.observe_negotiation(buffer[1], buffer[2]) {
+                  sink.push(events::TelnetEvents::PeerStack(guess));
                 }
               }
-              _ => (),
             }
-          } else {
-            // Not an iac sequence, it's data!
-            event_list.push(events::TelnetEvents::build_receive(buffer));
+            _ => (),
           }
         }
         EventType::SubNegotiation(buffer, remaining) => {
           let len: usize = buffer.len();
-          if buffer[len - 2] == IAC && buffer[len - 1] == SE {
-            // Valid ending
-            let opt = self.options.get_option(buffer[2]);
-            if opt.local && opt.local_state && len - 2 >= 3 {
-              let dbuffer = vbytes!(&buffer[3..len - 2]);
-              event_list.push(events::TelnetEvents::build_subnegotiation(
-                buffer[2], dbuffer,
-              ));
-              if let Some(rbuf) = remaining {
-                event_list.push(events::TelnetEvents::DecompressImmediate(rbuf));
+          position += len;
+          if self.exceeds_subnegotiation_limit(len - 5) {
+            sink.push(events::TelnetEvents::Error(
+              events::TelnetErrorKind::Overflow,
+              buffer,
+            ));
+            if let Some(rbuf) = remaining.as_ref() {
+              self.receive_after_start(rbuf, sink);
+            }
+            continue;
+          }
+          // The legacy MCCP1 start, `IAC SB 85 IAC WILL SE`, holds a command by design.
+          let mccp1_start = buffer[2] == telnet::op_option::MCCP1 && buffer[len - 2] == WILL;
+          if strict && !mccp1_start && Parser::has_embedded_command(&buffer[3..len - 2]) {
+            // Drop the whole subnegotiation rather than guess at what was meant.
+            sink.push(events::TelnetEvents::Error(
+              events::TelnetErrorKind::Malformed,
+              buffer,
+            ));
+            if let Some(rbuf) = remaining.as_ref() {
+              self.receive_after_start(rbuf, sink);
+            }
+            continue;
+          }
+          let option = buffer[2];
+          if self
+            .sizes
+            .record(option, len - 5, self.config.anomalies.as_ref())
+          {
+            sink.push(events::TelnetEvents::AnomalousSubnegotiation(
+              option,
+              len - 5,
+            ));
+          }
+          let opt = self.options.get_option(option);
+          if opt.enabled() {
+            let dbuffer = if buffer[len - 2] == WILL {
+              // Legacy MCCP v1 start sequence, with no data.
+              Bytes::new()
+            } else {
+              Parser::subnegotiation_payload(buffer.slice(3..len - 2))
+            };
+            sink.push(events::TelnetEvents::build_subnegotiation(
+              option,
+              dbuffer.clone(),
+            ));
+            self.process_subnegotiation(option, &dbuffer, sink);
+            if let (telnet::op_option::ENCRYPT, Some(rbuf)) = (option, remaining.as_ref()) {
+              self.receive_after_start(rbuf, sink);
+            } else if let (telnet::op_option::START_TLS, Some(rbuf)) = (option, remaining.as_ref())
+            {
+              if self.tls_suspended {
+                sink.push(events::TelnetEvents::StartTls(rbuf.clone()));
+              } else {
+                self.receive_after_start(rbuf, sink);
+              }
+            } else if let Some(rbuf) = remaining {
+              if let Some(previous) = self.decompressing.replace(option) {
+                // A restart without the end of the previous stream having been reported.
+                sink.push(events::TelnetEvents::DecompressionEnded(previous));
               }
+              if option == telnet::op_option::MCCP3 {
+                // MCCP3 is only ever started by the client, so everything after it is client -> server.
+                sink.push(events::TelnetEvents::DecompressClientStream(rbuf));
+              } else {
+                sink.push(events::TelnetEvents::DecompressImmediate(rbuf));
+              }
+            }
+          } else {
+            sink.push(events::TelnetEvents::Error(
+              events::TelnetErrorKind::OptionNotEnabled,
+              buffer,
+            ));
+            // Whatever the option, a start that was not accepted leaves the rest of the stream as it was.
+            if let Some(rbuf) = remaining.as_ref() {
+              self.receive_after_start(rbuf, sink);
             }
+          }
+        }
+        EventType::PartialSub(buffer) => {
+          if self.exceeds_subnegotiation_limit(buffer.len().saturating_sub(3)) {
+            position += buffer.len();
+            self.overflowed = true;
+            sink.push(events::TelnetEvents::Error(
+              events::TelnetErrorKind::Overflow,
+              buffer,
+            ));
           } else {
             // Missing the rest
             self.buffer.put(&buffer[..]);
           }
         }
+        EventType::SubAborted(buffer) => {
+          position += buffer.len();
+          sink.push(events::TelnetEvents::Error(
+            events::TelnetErrorKind::InterruptedSubnegotiation,
+            buffer,
+          ));
+        }
       }
     }
-    event_list
+    if let Some(urgent) = self.urgent.as_mut() {
+      *urgent = urgent.saturating_sub(position);
+    }
+    self.scratch = extracted;
   }
 }
@@ -0,0 +1,305 @@
+//! Translation between telnet's network line endings and the application's own.
+//!
+//! Outside of BINARY mode, RFC 854 requires a newline to be sent as `CR LF`, and a carriage return on its own as
+//! `CR NUL`. Once a `LineEnding` is set with `ParserBuilder::line_ending`, received data is translated into that line
+//! ending, and text sent with `Parser::send_text` and friends is translated into the network form.
+
+use crate::telnet::op_command::{EOR, GA, IAC};
+use alloc::vec::Vec;
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// The line ending applications see in received data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LineEnding {
+  /// `\r\n`, as sent over the network.
+  CrLf,
+  /// `\n`.
+  Lf,
+  /// `\r`.
+  Cr,
+}
+
+impl LineEnding {
+  fn as_bytes(self) -> &'static [u8] {
+    match self {
+      LineEnding::CrLf => b"\r\n",
+      LineEnding::Lf => b"\n",
+      LineEnding::Cr => b"\r",
+    }
+  }
+  /// The line ending as sent over the network. Outside of BINARY mode, a newline is always `CR LF` and a carriage
+  /// return is `CR NUL`.
+  fn wire_bytes(self, binary: bool) -> &'static [u8] {
+    match self {
+      _ if binary => self.as_bytes(),
+      LineEnding::Cr => b"\r\0",
+      _ => b"\r\n",
+    }
+  }
+}
+
+/// The command `Parser::send_text` sends after the text and its line ending.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Terminator {
+  /// Nothing.
+  #[default]
+  None,
+  /// `IAC GA`.
+  GoAhead,
+  /// `IAC EOR`.
+  EndOfRecord,
+  /// `IAC EOR` once the remote end has agreed to END-OF-RECORD, otherwise `IAC GA`, as used by `Parser::send_prompt`.
+  Prompt,
+}
+
+/// How `Parser::send_text` finishes the text it sends.
+///
+/// Set a default for a parser with `ParserBuilder::text_format`, or use `Parser::send_text_with` for a single call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextFormat {
+  /// The line ending appended to the text, or None for no line ending. Outside of BINARY mode, it is sent as `CR LF`
+  /// (or `CR NUL` for `LineEnding::Cr`) whatever is chosen here. Defaults to `LineEnding::CrLf`.
+  pub line_ending: Option<LineEnding>,
+  /// The command sent after the line ending. Defaults to `Terminator::None`.
+  pub terminator: Terminator,
+}
+
+impl Default for TextFormat {
+  fn default() -> Self {
+    Self {
+      line_ending: Some(LineEnding::CrLf),
+      terminator: Terminator::None,
+    }
+  }
+}
+
+impl TextFormat {
+  /// The bytes to send after the text.
+  pub(crate) fn suffix(&self, binary: bool, prompt_marker: u8) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4);
+    if let Some(ending) = self.line_ending {
+      out.extend_from_slice(ending.wire_bytes(binary));
+    }
+    let command = match self.terminator {
+      Terminator::None => return out,
+      Terminator::GoAhead => GA,
+      Terminator::EndOfRecord => EOR,
+      Terminator::Prompt => prompt_marker,
+    };
+    out.push(IAC);
+    out.push(command);
+    out
+  }
+}
+
+/// Translates received data, holding back a trailing CR until the byte after it arrives.
+#[derive(Clone, Debug)]
+pub(crate) struct NewlineTranslator {
+  ending: LineEnding,
+  pending_cr: bool,
+}
+
+impl NewlineTranslator {
+  pub(crate) fn new(ending: LineEnding) -> Self {
+    Self {
+      ending,
+      pending_cr: false,
+    }
+  }
+
+  /// Translate a piece of received data. `CR NUL` becomes `CR`, and `CR LF` becomes the configured line ending.
+  pub(crate) fn translate(&mut self, data: Bytes) -> Bytes {
+    if !self.pending_cr && !data.contains(&b'\r') {
+      return data;
+    }
+    let mut out = Vec::with_capacity(data.len() + 1);
+    let mut cr = self.pending_cr;
+    for &byte in data.iter() {
+      if cr {
+        cr = false;
+        match byte {
+          b'\n' => {
+            out.extend_from_slice(self.ending.as_bytes());
+            continue;
+          }
+          0 => {
+            out.push(b'\r');
+            continue;
+          }
+          // A bare CR is not valid NVT, but pass it through rather than lose data.
+          _ => out.push(b'\r'),
+        }
+      }
+      if byte == b'\r' {
+        cr = true;
+      } else {
+        out.push(byte);
+      }
+    }
+    self.pending_cr = cr;
+    Bytes::from(out)
+  }
+
+  /// Take a CR held back at the end of the data so far, such as when the connection is closing.
+  pub(crate) fn take_pending(&mut self) -> Option<Bytes> {
+    if core::mem::take(&mut self.pending_cr) {
+      Some(Bytes::from_static(b"\r"))
+    } else {
+      None
+    }
+  }
+}
+
+/// Buffers received data into complete lines, for `ParserBuilder::line_mode`.
+#[derive(Clone, Debug)]
+pub(crate) struct LineAssembler {
+  line: BytesMut,
+  max_len: usize,
+  /// Whether the last byte ended a line with CR, so that a following LF or NUL belongs to the same line ending.
+  after_cr: bool,
+  /// Whether the current line went over `max_len` and is being discarded.
+  discarding: bool,
+}
+
+/// Something a `LineAssembler` produced.
+pub(crate) enum Assembled {
+  /// A complete line, without its line ending.
+  Line(Bytes),
+  /// The start of a line that went over the size limit. The rest of the line is discarded.
+  TooLong(Bytes),
+}
+
+impl LineAssembler {
+  pub(crate) fn new(max_len: usize) -> Self {
+    Self {
+      line: BytesMut::new(),
+      max_len,
+      after_cr: false,
+      discarding: false,
+    }
+  }
+
+  /// Add received data, pushing any lines it completes to `out`.
+  ///
+  /// A line ends at `LF`, `CR LF`, `CR NUL` or a lone `CR`.
+  pub(crate) fn push(&mut self, data: &[u8], out: &mut Vec<Assembled>) {
+    for &byte in data {
+      if core::mem::take(&mut self.after_cr) && (byte == b'\n' || byte == 0) {
+        continue;
+      }
+      match byte {
+        b'\r' | b'\n' => {
+          self.after_cr = byte == b'\r';
+          if self.discarding {
+            self.discarding = false;
+          } else {
+            out.push(Assembled::Line(self.line.split().freeze()));
+          }
+        }
+        _ if self.discarding => (),
+        _ if self.line.len() >= self.max_len => {
+          self.discarding = true;
+          out.push(Assembled::TooLong(self.line.split().freeze()));
+        }
+        _ => self.line.put_u8(byte),
+      }
+    }
+  }
+
+  /// Take an unfinished line, such as when the connection is closing.
+  pub(crate) fn take_partial(&mut self) -> Option<Bytes> {
+    self.discarding = false;
+    if self.line.is_empty() {
+      None
+    } else {
+      Some(self.line.split().freeze())
+    }
+  }
+}
+
+/// Translate outbound text into NVT form, sending a lone LF as `CR LF` and a lone CR as `CR NUL`.
+pub(crate) fn encode(text: &[u8]) -> Vec<u8> {
+  let mut out = Vec::with_capacity(text.len() + 2);
+  let mut bytes = text.iter().copied().peekable();
+  while let Some(byte) = bytes.next() {
+    match byte {
+      b'\r' if bytes.peek() == Some(&b'\n') => {
+        out.push(b'\r');
+        out.push(b'\n');
+        bytes.next();
+      }
+      b'\r' => out.extend_from_slice(b"\r\0"),
+      b'\n' => out.extend_from_slice(b"\r\n"),
+      byte => out.push(byte),
+    }
+  }
+  out
+}
+
+#[cfg(test)]
+mod test_newline {
+  use super::*;
+
+  #[test]
+  fn test_translate() {
+    let mut translator = NewlineTranslator::new(LineEnding::Lf);
+    assert_eq!(
+      translator.translate(Bytes::from_static(b"a\r\0b\r\nc\r")),
+      Bytes::from_static(b"a\rb\nc")
+    );
+    assert_eq!(
+      translator.translate(Bytes::from_static(b"\nd")),
+      Bytes::from_static(b"\nd")
+    );
+    assert_eq!(
+      translator.translate(Bytes::from_static(b"e\r")),
+      Bytes::from_static(b"e")
+    );
+    assert_eq!(translator.take_pending(), Some(Bytes::from_static(b"\r")));
+  }
+
+  #[test]
+  fn test_line_assembler() {
+    let mut assembler = LineAssembler::new(4);
+    let mut out = Vec::new();
+    assembler.push(b"ab\r", &mut out);
+    assembler.push(b"\ncd\r\0\nabcdef\nx", &mut out);
+    let lines: Vec<(bool, Bytes)> = out
+      .into_iter()
+      .map(|assembled| match assembled {
+        Assembled::Line(line) => (true, line),
+        Assembled::TooLong(line) => (false, line),
+      })
+      .collect();
+    assert_eq!(
+      lines,
+      alloc::vec![
+        (true, Bytes::from_static(b"ab")),
+        (true, Bytes::from_static(b"cd")),
+        (true, Bytes::new()),
+        (false, Bytes::from_static(b"abcd")),
+      ]
+    );
+    assert_eq!(assembler.take_partial(), Some(Bytes::from_static(b"x")));
+  }
+
+  #[test]
+  fn test_suffix() {
+    let format = TextFormat {
+      line_ending: Some(LineEnding::Lf),
+      terminator: Terminator::Prompt,
+    };
+    assert_eq!(format.suffix(false, GA), alloc::vec![b'\r', b'\n', IAC, GA]);
+    assert_eq!(format.suffix(true, EOR), alloc::vec![b'\n', IAC, EOR]);
+    let format = TextFormat {
+      line_ending: Some(LineEnding::Cr),
+      terminator: Terminator::None,
+    };
+    assert_eq!(format.suffix(false, GA), b"\r\0".to_vec());
+  }
+
+  #[test]
+  fn test_encode() {
+    assert_eq!(encode(b"a\nb\r\nc\rd"), b"a\r\nb\r\nc\r\0d".to_vec());
+  }
+}
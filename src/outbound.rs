@@ -0,0 +1,96 @@
+use crate::events::TelnetEvents;
+use crate::telnet::op_command::{EOR, GA, IAC};
+use alloc::collections::VecDeque;
+use bytes::Bytes;
+
+/// How urgently a piece of outbound data should be written.
+///
+/// Variants are ordered from most to least urgent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+  /// Negotiations, subnegotiations and other commands.
+  Control,
+  /// Prompts, which the remote end is waiting on.
+  Prompt,
+  /// Everything else, such as room descriptions and chat.
+  Bulk,
+}
+
+impl Priority {
+  /// Guess the priority of outbound bytes produced by a `Parser`.
+  ///
+  /// Data starting with an `IAC` command is control traffic, data ending with `IAC GA` or `IAC EOR` is a prompt,
+  /// and anything else is bulk data.
+  pub fn classify(data: &[u8]) -> Self {
+    match data {
+      [IAC, command, ..] if *command != IAC => Priority::Control,
+      [.., IAC, GA] | [.., IAC, EOR] => Priority::Prompt,
+      _ => Priority::Bulk,
+    }
+  }
+}
+
+/// A queue of outbound data that drains higher priority data first.
+///
+/// Under backpressure, a server can keep writing from the front of this queue so that negotiations and prompts are
+/// not starved behind large amounts of bulk data. Data of the same priority is always written in the order it was
+/// queued.
+#[derive(Clone, Debug, Default)]
+pub struct OutboundQueue {
+  queues: [VecDeque<Bytes>; 3],
+  len: usize,
+}
+
+impl OutboundQueue {
+  /// Create an empty queue.
+  pub fn new() -> Self {
+    Self::default()
+  }
+  /// Queue data with an explicit priority.
+  pub fn push(&mut self, priority: Priority, data: Bytes) {
+    if data.is_empty() {
+      return;
+    }
+    self.len += data.len();
+    self.queues[priority as usize].push_back(data);
+  }
+  /// Queue the data of a `TelnetEvents::DataSend`, classified with `Priority::classify`.
+  ///
+  /// # Returns
+  ///
+  /// `Option<TelnetEvents>` - The event, if it was not a `DataSend` and so was not queued.
+  pub fn push_event(&mut self, event: TelnetEvents) -> Option<TelnetEvents> {
+    match event {
+      TelnetEvents::DataSend(data) => {
+        self.push(Priority::classify(&data), data);
+        None
+      }
+      event => Some(event),
+    }
+  }
+  /// Take the most urgent queued data.
+  pub fn pop(&mut self) -> Option<(Priority, Bytes)> {
+    for (priority, queue) in [Priority::Control, Priority::Prompt, Priority::Bulk]
+      .iter()
+      .zip(self.queues.iter_mut())
+    {
+      if let Some(data) = queue.pop_front() {
+        self.len -= data.len();
+        return Some((*priority, data));
+      }
+    }
+    None
+  }
+  /// Take queued data in drain order, most urgent first.
+  pub fn drain(&mut self) -> impl Iterator<Item = (Priority, Bytes)> + '_ {
+    core::iter::from_fn(move || self.pop())
+  }
+  /// The total number of bytes queued.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+  /// Whether nothing is queued.
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+}
@@ -0,0 +1,92 @@
+use alloc::{boxed::Box, sync::Arc};
+use bytes::{Buf, Bytes, BytesMut};
+use core::ops::{Deref, DerefMut};
+
+/// A source of internal buffers for a `Parser`.
+///
+/// Servers handling many connections can supply a pool (or a per-connection arena) so that
+/// parser buffers are recycled instead of being returned to the global allocator on disconnect.
+pub trait BufferPool: Send + Sync {
+  /// Acquire a buffer with at least `capacity` bytes of capacity.
+  fn acquire(&self, capacity: usize) -> BytesMut;
+  /// Return a buffer to the pool once the parser no longer needs it.
+  ///
+  /// The buffer may still contain data; implementations should clear it before reuse.
+  fn release(&self, buffer: BytesMut);
+}
+
+impl<T: BufferPool + ?Sized> BufferPool for Arc<T> {
+  fn acquire(&self, capacity: usize) -> BytesMut {
+    (**self).acquire(capacity)
+  }
+  fn release(&self, buffer: BytesMut) {
+    (**self).release(buffer)
+  }
+}
+
+/// A parser's internal buffer, released back to the pool it was acquired from when dropped.
+pub(crate) struct PooledBuffer {
+  buffer: BytesMut,
+  pool: Option<Box<dyn BufferPool>>,
+}
+
+impl PooledBuffer {
+  pub(crate) fn new(capacity: usize) -> Self {
+    Self {
+      buffer: BytesMut::with_capacity(capacity),
+      pool: None,
+    }
+  }
+  pub(crate) fn pooled(pool: Box<dyn BufferPool>, capacity: usize) -> Self {
+    Self {
+      buffer: pool.acquire(capacity),
+      pool: Some(pool),
+    }
+  }
+  /// Take everything buffered, leaving the buffer empty.
+  ///
+  /// Without a pool, the result shares the buffer's allocation so it needs no copy. With one, the data is copied out
+  /// so the allocation acquired from the pool stays whole and is what gets released.
+  pub(crate) fn take(&mut self) -> Bytes {
+    if self.pool.is_some() {
+      let data = Bytes::copy_from_slice(&self.buffer);
+      self.buffer.clear();
+      data
+    } else {
+      self.buffer.split().freeze()
+    }
+  }
+  /// Discard `count` bytes from the front of the buffer, moving the rest down rather than advancing past them if
+  /// the buffer came from a pool.
+  pub(crate) fn discard(&mut self, count: usize) {
+    if self.pool.is_some() {
+      let len = self.buffer.len();
+      self.buffer.copy_within(count.., 0);
+      self.buffer.truncate(len - count);
+    } else {
+      self.buffer.advance(count);
+    }
+  }
+}
+
+impl Deref for PooledBuffer {
+  type Target = BytesMut;
+
+  fn deref(&self) -> &BytesMut {
+    &self.buffer
+  }
+}
+
+impl DerefMut for PooledBuffer {
+  fn deref_mut(&mut self) -> &mut BytesMut {
+    &mut self.buffer
+  }
+}
+
+impl Drop for PooledBuffer {
+  fn drop(&mut self) {
+    if let Some(pool) = &self.pool {
+      pool.release(core::mem::take(&mut self.buffer));
+    }
+  }
+}
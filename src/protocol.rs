@@ -0,0 +1,109 @@
+//! The telnet options and MUD protocols the parser knows how to enable.
+//!
+//! `Parser::with_protocols` and `ParserBuilder::protocols` take a list of `Protocol`s and set up the compatibility
+//! table for them in one call, instead of looking up option codes and calling `CompatibilityTable::support` for each.
+
+use crate::compatibility::CompatibilityTable;
+use crate::telnet::op_option;
+
+/// A protocol that can be enabled on a parser.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Protocol {
+  /// BINARY (RFC 856): 8-bit data without NVT newline translation.
+  Binary,
+  /// ECHO (RFC 857).
+  Echo,
+  /// SUPPRESS-GO-AHEAD (RFC 858).
+  SuppressGoAhead,
+  /// STATUS (RFC 859), used by `Parser::verify_states`.
+  Status,
+  /// TIMING-MARK (RFC 860).
+  TimingMark,
+  /// TERMINAL-TYPE (RFC 1091), also carrying MTTS.
+  TerminalType,
+  /// END-OF-RECORD (RFC 885), for marking prompts with `IAC EOR`.
+  EndOfRecord,
+  /// NAWS (RFC 1073): window size reports.
+  Naws,
+  /// TERMINAL-SPEED (RFC 1079).
+  TerminalSpeed,
+  /// TOGGLE-FLOW-CONTROL (RFC 1372): remote control of local XON/XOFF handling.
+  FlowControl,
+  /// LINEMODE (RFC 1184).
+  Linemode,
+  /// X-DISPLAY-LOCATION (RFC 1096).
+  XDisplayLocation,
+  /// AUTHENTICATION (RFC 2941), with mechanisms added by `Parser::add_auth_mechanism`.
+  Authentication,
+  /// ENCRYPT (RFC 2946), with a cipher set by `Parser::set_cipher`.
+  Encrypt,
+  /// NEW-ENVIRON (RFC 1572).
+  NewEnviron,
+  /// TN3270E (RFC 2355): 3270 terminal and printer sessions, with records framed by `telnet::tn3270e::Tn3270eSession`.
+  Tn3270e,
+  /// COM-PORT-OPTION (RFC 2217): control of a serial port shared over telnet.
+  ComPort,
+  /// START_TLS: switching the connection to TLS, with `Parser::request_tls`.
+  StartTls,
+  /// MSSP: MUD server status.
+  Mssp,
+  /// MSDP: the MUD Server Data Protocol.
+  Msdp,
+  /// MXP: the MUD eXtension Protocol.
+  Mxp,
+  /// MSP: the MUD Sound Protocol.
+  Msp,
+  /// Aardwolf's option 102 status channel.
+  Aard102,
+  /// MCCP1: the original, deprecated, MUD compression protocol.
+  Mccp1,
+  /// MCCP2: compression of the data sent by the server.
+  Mccp2,
+  /// MCCP3: compression of the data sent by the client.
+  Mccp3,
+  /// GMCP: the Generic MUD Communication Protocol.
+  Gmcp,
+}
+
+impl Protocol {
+  /// Get the telnet option code used to negotiate the protocol.
+  pub fn option(self) -> u8 {
+    match self {
+      Protocol::Binary => op_option::BINARY,
+      Protocol::Echo => op_option::ECHO,
+      Protocol::SuppressGoAhead => op_option::SGA,
+      Protocol::Status => op_option::STATUS,
+      Protocol::TimingMark => op_option::TM,
+      Protocol::TerminalType => op_option::TTYPE,
+      Protocol::EndOfRecord => op_option::EOR,
+      Protocol::Naws => op_option::NAWS,
+      Protocol::TerminalSpeed => op_option::TSPEED,
+      Protocol::FlowControl => op_option::LFLOW,
+      Protocol::Linemode => op_option::LINEMODE,
+      Protocol::XDisplayLocation => op_option::XDISPLOC,
+      Protocol::Authentication => op_option::AUTHENTICATION,
+      Protocol::Encrypt => op_option::ENCRYPT,
+      Protocol::NewEnviron => op_option::NEWENVIRON,
+      Protocol::Tn3270e => op_option::TN3270E,
+      Protocol::ComPort => op_option::COM_PORT_OPTION,
+      Protocol::StartTls => op_option::START_TLS,
+      Protocol::Mssp => op_option::MSSP,
+      Protocol::Msdp => op_option::MSDP,
+      Protocol::Mxp => op_option::MXP,
+      Protocol::Msp => op_option::MSP,
+      Protocol::Aard102 => op_option::AARD102,
+      Protocol::Mccp1 => op_option::MCCP1,
+      Protocol::Mccp2 => op_option::MCCP2,
+      Protocol::Mccp3 => op_option::MCCP3,
+      Protocol::Gmcp => op_option::GMCP,
+    }
+  }
+}
+
+/// Mark every protocol in `protocols` as supported on both sides of the connection.
+pub(crate) fn support(table: &mut CompatibilityTable, protocols: &[Protocol]) {
+  for protocol in protocols {
+    table.support(protocol.option());
+  }
+}
@@ -0,0 +1,120 @@
+use crate::events::TelnetEvents;
+use crate::telnet::op_command::*;
+use crate::Parser;
+use alloc::{collections::VecDeque, vec::Vec};
+
+/// How much output a `ReplayBuffer` keeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReplayLimit {
+  /// Keep at most this many bytes.
+  Bytes(usize),
+  /// Keep at most this many complete lines, plus any unfinished line after them.
+  Lines(usize),
+}
+
+/// A record of recent outbound data, for replaying to a client that reconnects to an existing session.
+///
+/// Only data is kept. Negotiations, subnegotiations and other commands are stripped as output is recorded, since they
+/// belong to the old connection and would confuse the new one.
+#[derive(Clone, Debug)]
+pub struct ReplayBuffer {
+  limit: ReplayLimit,
+  data: VecDeque<u8>,
+  lines: usize,
+}
+
+impl ReplayBuffer {
+  /// Create an empty buffer that keeps output up to `limit`.
+  pub fn new(limit: ReplayLimit) -> Self {
+    Self {
+      limit,
+      data: VecDeque::new(),
+      lines: 0,
+    }
+  }
+  /// Record an outbound event. Anything other than a `DataSend` is ignored.
+  pub fn record(&mut self, event: &TelnetEvents) {
+    if let TelnetEvents::DataSend(data) = event {
+      for byte in strip_commands(data) {
+        if byte == b'\n' {
+          self.lines += 1;
+        }
+        self.data.push_back(byte);
+      }
+      self.trim();
+    }
+  }
+  /// Get a `DataSend` event that replays the recorded output to a new connection, or None if nothing was recorded.
+  pub fn replay(&self) -> Option<TelnetEvents> {
+    if self.data.is_empty() {
+      return None;
+    }
+    let data: Vec<u8> = self.data.iter().copied().collect();
+    Some(TelnetEvents::build_send(Parser::escape_iac(data)))
+  }
+  /// The number of data bytes held.
+  pub fn len(&self) -> usize {
+    self.data.len()
+  }
+  /// Whether nothing is held.
+  pub fn is_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+  /// Forget everything recorded.
+  pub fn clear(&mut self) {
+    self.data.clear();
+    self.lines = 0;
+  }
+
+  fn trim(&mut self) {
+    match self.limit {
+      ReplayLimit::Bytes(limit) => {
+        while self.data.len() > limit {
+          if self.data.pop_front() == Some(b'\n') {
+            self.lines -= 1;
+          }
+        }
+      }
+      ReplayLimit::Lines(limit) => {
+        while self.lines > limit {
+          while let Some(byte) = self.data.pop_front() {
+            if byte == b'\n' {
+              break;
+            }
+          }
+          self.lines -= 1;
+        }
+      }
+    }
+  }
+}
+
+/// Get the data in escaped outbound bytes, leaving out every telnet command.
+fn strip_commands(data: &[u8]) -> Vec<u8> {
+  let mut out = Vec::with_capacity(data.len());
+  let mut index = 0;
+  while index < data.len() {
+    if data[index] != IAC {
+      out.push(data[index]);
+      index += 1;
+      continue;
+    }
+    match data.get(index + 1) {
+      Some(&IAC) => {
+        out.push(IAC);
+        index += 2;
+      }
+      Some(&SB) => {
+        // Skip to just after the closing IAC SE.
+        index += 2;
+        while index < data.len() && !(data[index] == IAC && data.get(index + 1) == Some(&SE)) {
+          index += if data[index] == IAC { 2 } else { 1 };
+        }
+        index += 2;
+      }
+      Some(&WILL) | Some(&WONT) | Some(&DO) | Some(&DONT) => index += 3,
+      _ => index += 2,
+    }
+  }
+  out
+}
@@ -0,0 +1,225 @@
+//! Cleaning of received data before it reaches application logic.
+//!
+//! A malicious client can send terminal escape sequences that rewrite the screen of anyone its text is shown to, or
+//! lines long enough to exhaust a line-based game loop. A `SanitizePolicy` set with `ParserBuilder::sanitize` is
+//! applied to every `DataReceive` to guard against both.
+
+use alloc::vec::Vec;
+use bytes::Bytes;
+
+/// What to do with a C0 control character other than CR, LF and TAB.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ControlChars {
+  /// Pass them through unchanged.
+  #[default]
+  Keep,
+  /// Leave them out.
+  Strip,
+  /// Replace them with caret notation, so ESC becomes the two characters `^[`.
+  Escape,
+}
+
+/// How received data is cleaned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SanitizePolicy {
+  /// Handling of C0 control characters other than CR, LF and TAB.
+  pub control_chars: ControlChars,
+  /// The most bytes a single line may hold, not counting its line ending, or None for no limit.
+  ///
+  /// Bytes past the limit are dropped until the next line ending, and a `TelnetEvents::Error` with
+  /// `TelnetErrorKind::LineTooLong` is emitted once for the line.
+  pub max_line_len: Option<usize>,
+}
+
+/// A piece of sanitized data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Sanitized {
+  /// Cleaned data, to be delivered as usual.
+  Data(Bytes),
+  /// The bytes dropped from a line that went over the limit, which follows the data ending that line.
+  Dropped(Bytes),
+}
+
+/// A `SanitizePolicy` along with the state of the current line.
+#[derive(Clone, Debug)]
+pub(crate) struct Sanitizer {
+  policy: SanitizePolicy,
+  line_len: usize,
+  truncating: bool,
+}
+
+impl Sanitizer {
+  pub(crate) fn new(policy: SanitizePolicy) -> Self {
+    Self {
+      policy,
+      line_len: 0,
+      truncating: false,
+    }
+  }
+
+  /// Clean a piece of received data.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<Sanitized>` - The cleaned data, split after each line that went over the limit in this piece so the bytes
+  /// dropped from it can be reported straight after. Bytes dropped from a line already reported are not returned
+  /// again.
+  pub(crate) fn apply(&mut self, data: Bytes) -> Vec<Sanitized> {
+    if self.is_clean(&data) {
+      for &byte in data.iter() {
+        self.advance(byte);
+      }
+      return alloc::vec![Sanitized::Data(data)];
+    }
+    let mut pieces = Vec::new();
+    let mut out = Vec::with_capacity(data.len());
+    // The bytes dropped from the line that went over the limit in this piece, if any.
+    let mut dropped: Option<Vec<u8>> = None;
+    for &byte in data.iter() {
+      if byte == b'\r' || byte == b'\n' {
+        out.push(byte);
+        self.advance(byte);
+        continue;
+      }
+      if !self.truncating {
+        if let Some(dropped) = dropped.take() {
+          // The line that went over the limit has ended, along with its line ending.
+          pieces.push(Sanitized::Data(Bytes::from(core::mem::take(&mut out))));
+          pieces.push(Sanitized::Dropped(Bytes::from(dropped)));
+        }
+      }
+      let escaped = is_control(byte) && self.policy.control_chars == ControlChars::Escape;
+      if is_control(byte) && self.policy.control_chars == ControlChars::Strip {
+        continue;
+      }
+      let width = if escaped { 2 } else { 1 };
+      if matches!(self.policy.max_line_len, Some(limit) if self.line_len + width > limit) {
+        if !self.truncating {
+          self.truncating = true;
+          dropped = Some(Vec::new());
+        }
+        if let Some(dropped) = dropped.as_mut() {
+          dropped.push(byte);
+        }
+        continue;
+      }
+      self.line_len += width;
+      if escaped {
+        out.push(b'^');
+        out.push(byte + 0x40);
+      } else {
+        out.push(byte);
+      }
+    }
+    if !out.is_empty() {
+      pieces.push(Sanitized::Data(Bytes::from(out)));
+    }
+    if let Some(dropped) = dropped {
+      pieces.push(Sanitized::Dropped(Bytes::from(dropped)));
+    }
+    pieces
+  }
+
+  /// Whether data passes through unchanged, with no control characters to handle and no line over the limit.
+  fn is_clean(&self, data: &[u8]) -> bool {
+    if self.policy.control_chars != ControlChars::Keep && data.iter().any(|&b| is_control(b)) {
+      return false;
+    }
+    let limit = match self.policy.max_line_len {
+      Some(limit) => limit,
+      None => return true,
+    };
+    let mut line_len = self.line_len;
+    let mut truncating = self.truncating;
+    for &byte in data {
+      if byte == b'\r' || byte == b'\n' {
+        line_len = 0;
+        truncating = false;
+      } else if truncating {
+        return false;
+      } else {
+        line_len += 1;
+        if line_len > limit {
+          return false;
+        }
+      }
+    }
+    true
+  }
+
+  /// Update the line state for a byte that was kept as is.
+  fn advance(&mut self, byte: u8) {
+    if byte == b'\r' || byte == b'\n' {
+      self.line_len = 0;
+      self.truncating = false;
+    } else {
+      self.line_len += 1;
+    }
+  }
+}
+
+/// Whether a byte is a C0 control character that a policy applies to.
+fn is_control(byte: u8) -> bool {
+  byte < 0x20 && byte != b'\r' && byte != b'\n' && byte != b'\t'
+}
+
+#[cfg(test)]
+mod test_sanitize {
+  use super::*;
+
+  fn sanitizer(control_chars: ControlChars, max_line_len: Option<usize>) -> Sanitizer {
+    Sanitizer::new(SanitizePolicy {
+      control_chars,
+      max_line_len,
+    })
+  }
+
+  #[test]
+  fn test_control_chars() {
+    let data = Bytes::from_static(b"a\x1b[2Jb\tc\r\n");
+    let mut keep = sanitizer(ControlChars::Keep, None);
+    assert_eq!(keep.apply(data.clone()), [Sanitized::Data(data.clone())]);
+    let mut strip = sanitizer(ControlChars::Strip, None);
+    assert_eq!(
+      strip.apply(data.clone()),
+      [Sanitized::Data(Bytes::from_static(b"a[2Jb\tc\r\n"))]
+    );
+    let mut escape = sanitizer(ControlChars::Escape, None);
+    assert_eq!(
+      escape.apply(data),
+      [Sanitized::Data(Bytes::from_static(b"a^[[2Jb\tc\r\n"))]
+    );
+  }
+
+  #[test]
+  fn test_max_line_len() {
+    let mut sanitizer = sanitizer(ControlChars::Keep, Some(4));
+    assert_eq!(
+      sanitizer.apply(Bytes::from_static(b"abc")),
+      [Sanitized::Data(Bytes::from_static(b"abc"))]
+    );
+    assert_eq!(
+      sanitizer.apply(Bytes::from_static(b"def")),
+      [
+        Sanitized::Data(Bytes::from_static(b"d")),
+        Sanitized::Dropped(Bytes::from_static(b"ef"))
+      ]
+    );
+    // The rest of the line is dropped without being reported again.
+    assert_eq!(
+      sanitizer.apply(Bytes::from_static(b"gh\r\nij")),
+      [Sanitized::Data(Bytes::from_static(b"\r\nij"))]
+    );
+    // Each line over the limit is reported after its own line ending.
+    assert_eq!(
+      sanitizer.apply(Bytes::from_static(b"\r\nabcdef\r\nghijkl\r\nmn")),
+      [
+        Sanitized::Data(Bytes::from_static(b"\r\nabcd\r\n")),
+        Sanitized::Dropped(Bytes::from_static(b"ef")),
+        Sanitized::Data(Bytes::from_static(b"ghij\r\n")),
+        Sanitized::Dropped(Bytes::from_static(b"kl")),
+        Sanitized::Data(Bytes::from_static(b"mn")),
+      ]
+    );
+  }
+}
@@ -0,0 +1,282 @@
+//! Deterministic two-endpoint simulations for testing option handling without a network.
+//!
+//! A `Simulation` scripts what two connected parsers do, runs the script with every packet boundary arrangement it
+//! knows, and checks that both ends see the same events every time. This catches bugs that only show up when a
+//! sequence is split across reads, in the parser as well as in the caller's own handling of its events.
+//!
+//! # Example
+//!
+//! ```
+//! use libtelnet_rs::compatibility::CompatibilityTable;
+//! use libtelnet_rs::simulate::{Action, Peer, Simulation};
+//! use libtelnet_rs::telnet::op_option::GMCP;
+//!
+//! let mut server = CompatibilityTable::new();
+//! server.support_local(GMCP);
+//! let mut client = CompatibilityTable::new();
+//! client.support_remote(GMCP);
+//!
+//! let transcript = Simulation::new(server, client)
+//!   .action(Action::Will(Peer::A, GMCP))
+//!   .action(Action::Subnegotiate(Peer::A, GMCP, b"Core.Hello {}".to_vec()))
+//!   .action(Action::SendText(Peer::A, "Welcome!".into()))
+//!   .run_all()
+//!   .unwrap();
+//! assert!(!transcript.b.is_empty());
+//! ```
+
+use crate::compatibility::CompatibilityTable;
+use crate::events::TelnetEvents;
+use crate::Parser;
+use alloc::{collections::VecDeque, format, string::String, vec::Vec};
+use bytes::Bytes;
+
+/// The number of transmissions a single action may cause before the simulation gives up.
+const MAX_EXCHANGES: usize = 1024;
+
+/// One of the two simulated endpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Peer {
+  A,
+  B,
+}
+
+impl Peer {
+  fn other(self) -> Self {
+    match self {
+      Peer::A => Peer::B,
+      Peer::B => Peer::A,
+    }
+  }
+}
+
+/// A scripted step, taken by one endpoint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+  /// Send a line of text with `Parser::send_text`.
+  SendText(Peer, String),
+  /// Offer to enable an option locally with `Parser::_will`.
+  Will(Peer, u8),
+  /// Disable a local option with `Parser::_wont`.
+  Wont(Peer, u8),
+  /// Ask the other end to enable an option with `Parser::_do`.
+  Do(Peer, u8),
+  /// Ask the other end to disable an option with `Parser::_dont`.
+  Dont(Peer, u8),
+  /// Send a subnegotiation with `Parser::subnegotiation`.
+  Subnegotiate(Peer, u8, Vec<u8>),
+  /// Send raw bytes, exactly as given.
+  Raw(Peer, Vec<u8>),
+  /// Lose the next `n` bytes sent by this endpoint.
+  Drop(Peer, usize),
+  /// Split the next transmission from this endpoint at these offsets, in addition to any boundaries being tried.
+  Split(Peer, Vec<usize>),
+}
+
+/// Where packet boundaries were placed in a run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Boundaries {
+  /// Every transmission arrives in one piece.
+  Whole,
+  /// Every transmission long enough is split in two at this offset.
+  At(usize),
+  /// Every transmission arrives one byte at a time.
+  EveryByte,
+}
+
+/// Something an endpoint observed while receiving.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Observed {
+  /// Received data. Consecutive `DataReceive` events are joined, since their boundaries depend on packet boundaries.
+  Data(Vec<u8>),
+  /// Any other event, in its `Debug` form.
+  Event(String),
+}
+
+/// Everything each endpoint observed during a run.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Transcript {
+  pub a: Vec<Observed>,
+  pub b: Vec<Observed>,
+}
+
+impl Transcript {
+  fn record(&mut self, peer: Peer, event: TelnetEvents) {
+    let log = match peer {
+      Peer::A => &mut self.a,
+      Peer::B => &mut self.b,
+    };
+    match event {
+      TelnetEvents::DataReceive(data) => match log.last_mut() {
+        Some(Observed::Data(last)) => last.extend_from_slice(&data),
+        _ => log.push(Observed::Data(data.to_vec())),
+      },
+      event => log.push(Observed::Event(format!("{:?}", event))),
+    }
+  }
+}
+
+/// Why a simulation failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SimulationError {
+  /// Two runs with different packet boundaries produced different transcripts.
+  Mismatch {
+    /// The boundaries used for the run that differed from the `Whole` run.
+    boundaries: Boundaries,
+    /// The transcript of the `Whole` run.
+    expected: Transcript,
+    /// The transcript of the run that differed.
+    actual: Transcript,
+  },
+  /// The endpoints kept answering each other without settling, at the action with this index.
+  Livelock(usize),
+}
+
+/// A scripted exchange between two parsers.
+#[derive(Clone)]
+pub struct Simulation {
+  a: CompatibilityTable,
+  b: CompatibilityTable,
+  actions: Vec<Action>,
+}
+
+impl Simulation {
+  /// Create a simulation between endpoints supporting the given options.
+  pub fn new(a: CompatibilityTable, b: CompatibilityTable) -> Self {
+    Self {
+      a,
+      b,
+      actions: Vec::new(),
+    }
+  }
+  /// Append a step to the script.
+  pub fn action(mut self, action: Action) -> Self {
+    self.actions.push(action);
+    self
+  }
+  /// Run the script once, with the given packet boundaries.
+  pub fn run(&self, boundaries: Boundaries) -> Result<Transcript, SimulationError> {
+    let mut a = Parser::with_support(self.a.clone());
+    let mut b = Parser::with_support(self.b.clone());
+    let mut transcript = Transcript::default();
+    let mut dropping = [0usize; 2];
+    let mut splits: [Vec<usize>; 2] = [Vec::new(), Vec::new()];
+    let mut wire: VecDeque<(Peer, Bytes)> = VecDeque::new();
+
+    for (index, action) in self.actions.iter().enumerate() {
+      let (peer, sent) = match action {
+        Action::SendText(peer, text) => {
+          (*peer, Some(endpoint(&mut a, &mut b, *peer).send_text(text)))
+        }
+        Action::Will(peer, option) => (*peer, endpoint(&mut a, &mut b, *peer)._will(*option)),
+        Action::Wont(peer, option) => (*peer, endpoint(&mut a, &mut b, *peer)._wont(*option)),
+        Action::Do(peer, option) => (*peer, endpoint(&mut a, &mut b, *peer)._do(*option)),
+        Action::Dont(peer, option) => (*peer, endpoint(&mut a, &mut b, *peer)._dont(*option)),
+        Action::Subnegotiate(peer, option, data) => (
+          *peer,
+          endpoint(&mut a, &mut b, *peer).subnegotiation(*option, data.clone()),
+        ),
+        Action::Raw(peer, data) => (
+          *peer,
+          Some(TelnetEvents::DataSend(Bytes::from(data.clone()))),
+        ),
+        Action::Drop(peer, count) => {
+          dropping[*peer as usize] += count;
+          continue;
+        }
+        Action::Split(peer, offsets) => {
+          splits[*peer as usize] = offsets.clone();
+          continue;
+        }
+      };
+      if let Some(TelnetEvents::DataSend(data)) = sent {
+        wire.push_back((peer, data));
+      }
+
+      let mut exchanges = 0;
+      while let Some((from, mut data)) = wire.pop_front() {
+        exchanges += 1;
+        if exchanges > MAX_EXCHANGES {
+          return Err(SimulationError::Livelock(index));
+        }
+        let lost = dropping[from as usize].min(data.len());
+        dropping[from as usize] -= lost;
+        let _ = data.split_to(lost);
+
+        let to = from.other();
+        let receiver = endpoint(&mut a, &mut b, to);
+        let forced = core::mem::take(&mut splits[from as usize]);
+        for chunk in chunks(&data, boundaries, &forced) {
+          for event in receiver.receive(chunk) {
+            if let TelnetEvents::DataSend(reply) = &event {
+              wire.push_back((to, reply.clone()));
+            }
+            transcript.record(to, event);
+          }
+        }
+      }
+    }
+    Ok(transcript)
+  }
+  /// Run the script with every supported arrangement of packet boundaries, and check that the transcripts agree.
+  ///
+  /// # Returns
+  ///
+  /// `Result<Transcript, SimulationError>` - The transcript shared by every run, or how they differed.
+  pub fn run_all(&self) -> Result<Transcript, SimulationError> {
+    let expected = self.run(Boundaries::Whole)?;
+    let longest = self
+      .actions
+      .iter()
+      .map(|action| match action {
+        Action::SendText(_, text) => text.len() + 2,
+        Action::Subnegotiate(_, _, data) => data.len() + 5,
+        Action::Raw(_, data) => data.len(),
+        _ => 3,
+      })
+      .max()
+      .unwrap_or(0);
+    let runs = (1..longest)
+      .map(Boundaries::At)
+      .chain(core::iter::once(Boundaries::EveryByte));
+    for boundaries in runs {
+      let actual = self.run(boundaries)?;
+      if actual != expected {
+        return Err(SimulationError::Mismatch {
+          boundaries,
+          expected,
+          actual,
+        });
+      }
+    }
+    Ok(expected)
+  }
+}
+
+fn endpoint<'p>(a: &'p mut Parser, b: &'p mut Parser, peer: Peer) -> &'p mut Parser {
+  match peer {
+    Peer::A => a,
+    Peer::B => b,
+  }
+}
+
+/// Cut a transmission into the pieces it arrives in.
+fn chunks<'a>(data: &'a [u8], boundaries: Boundaries, forced: &[usize]) -> Vec<&'a [u8]> {
+  let mut cuts: Vec<usize> = match boundaries {
+    Boundaries::Whole => Vec::new(),
+    Boundaries::At(offset) => alloc::vec![offset],
+    Boundaries::EveryByte => (1..data.len()).collect(),
+  };
+  cuts.extend_from_slice(forced);
+  cuts.retain(|&cut| cut > 0 && cut < data.len());
+  cuts.sort_unstable();
+  cuts.dedup();
+  let mut pieces = Vec::with_capacity(cuts.len() + 1);
+  let mut start = 0;
+  for cut in cuts {
+    pieces.push(&data[start..cut]);
+    start = cut;
+  }
+  pieces.push(&data[start..]);
+  pieces
+}
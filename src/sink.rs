@@ -0,0 +1,69 @@
+use crate::events::TelnetEvents;
+use alloc::{collections::VecDeque, vec::Vec};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// A destination for the events produced by `Parser::receive_into`.
+///
+/// Implementing this allows callers to choose how events are stored or dispatched, such as reusing a `Vec` between
+/// calls, pushing into a ring buffer, or handling each event directly as it is parsed.
+pub trait EventSink {
+  /// Accept a single event from the parser.
+  fn push(&mut self, event: TelnetEvents);
+}
+
+impl EventSink for Vec<TelnetEvents> {
+  fn push(&mut self, event: TelnetEvents) {
+    Vec::push(self, event);
+  }
+}
+
+impl EventSink for VecDeque<TelnetEvents> {
+  fn push(&mut self, event: TelnetEvents) {
+    self.push_back(event);
+  }
+}
+
+impl<S: EventSink + ?Sized> EventSink for &mut S {
+  fn push(&mut self, event: TelnetEvents) {
+    (**self).push(event);
+  }
+}
+
+/// An event along with the moment it was parsed or generated.
+///
+/// Collecting into a `Vec<Timestamped>` or `VecDeque<Timestamped>` with `Parser::receive_into` stamps each event as
+/// the parser pushes it, which allows measuring how long events wait before being handled, or replaying a transcript
+/// with its original timing.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct Timestamped {
+  /// When the event was parsed or generated.
+  pub at: Instant,
+  pub event: TelnetEvents,
+}
+
+#[cfg(feature = "std")]
+impl Timestamped {
+  /// Stamp an event with the current time, such as one returned by `Parser::_will`.
+  pub fn now(event: TelnetEvents) -> Self {
+    Self {
+      at: Instant::now(),
+      event,
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl EventSink for Vec<Timestamped> {
+  fn push(&mut self, event: TelnetEvents) {
+    Vec::push(self, Timestamped::now(event));
+  }
+}
+
+#[cfg(feature = "std")]
+impl EventSink for VecDeque<Timestamped> {
+  fn push(&mut self, event: TelnetEvents) {
+    self.push_back(Timestamped::now(event));
+  }
+}
@@ -1,3 +1,8 @@
+use crate::error::ParseNameError;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+
 /// Module containing constants for Telnet Command codes.
 pub mod op_command {
   pub const IAC: u8 = 255;
@@ -12,6 +17,128 @@ pub mod op_command {
   pub const SEND: u8 = 1;
   pub const GA: u8 = 249;
   pub const EOR: u8 = 239;
+  pub const DM: u8 = 242;
+  pub const BREAK: u8 = 243;
+  pub const IP: u8 = 244;
+  pub const AO: u8 = 245;
+  pub const AYT: u8 = 246;
+  pub const EC: u8 = 247;
+  pub const EL: u8 = 248;
+  pub const EOF: u8 = 236;
+  pub const SUSP: u8 = 237;
+  pub const ABORT: u8 = 238;
+}
+
+/// A command following IAC, for matching `TelnetIAC` and `TelnetNegotiation` events symbolically. Serialized as its
+/// code with the `serde` feature.
+///
+/// Commands compare and hash by their code, so `Unknown(246)` is equal to `Break`. `From<u8>` only produces
+/// `Unknown` for codes with no symbolic name.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(from = "u8", into = "u8")
+)]
+pub enum TelnetCommand {
+  Will,
+  Wont,
+  Do,
+  Dont,
+  /// Start of subnegotiation.
+  SubnegotiationBegin,
+  /// End of Record (RFC 885).
+  EndOfRecord,
+  /// End of subnegotiation.
+  SubnegotiationEnd,
+  NoOperation,
+  /// The data stream portion of a SYNCH.
+  DataMark,
+  Break,
+  InterruptProcess,
+  AbortOutput,
+  AreYouThere,
+  EraseCharacter,
+  EraseLine,
+  GoAhead,
+  /// End of file, for LINEMODE (RFC 1184).
+  EndOfFile,
+  /// Suspend the current process, for LINEMODE (RFC 1184).
+  Suspend,
+  /// Abort the current process, for LINEMODE (RFC 1184).
+  Abort,
+  /// A command byte with no symbolic name.
+  Unknown(u8),
+}
+
+impl PartialEq for TelnetCommand {
+  fn eq(&self, other: &Self) -> bool {
+    u8::from(*self) == u8::from(*other)
+  }
+}
+
+impl Eq for TelnetCommand {}
+
+impl Hash for TelnetCommand {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    u8::from(*self).hash(state);
+  }
+}
+
+impl From<u8> for TelnetCommand {
+  fn from(value: u8) -> Self {
+    use op_command::*;
+    match value {
+      WILL => TelnetCommand::Will,
+      WONT => TelnetCommand::Wont,
+      DO => TelnetCommand::Do,
+      DONT => TelnetCommand::Dont,
+      SB => TelnetCommand::SubnegotiationBegin,
+      EOR => TelnetCommand::EndOfRecord,
+      SE => TelnetCommand::SubnegotiationEnd,
+      NOP => TelnetCommand::NoOperation,
+      DM => TelnetCommand::DataMark,
+      BREAK => TelnetCommand::Break,
+      IP => TelnetCommand::InterruptProcess,
+      AO => TelnetCommand::AbortOutput,
+      AYT => TelnetCommand::AreYouThere,
+      EC => TelnetCommand::EraseCharacter,
+      EL => TelnetCommand::EraseLine,
+      GA => TelnetCommand::GoAhead,
+      EOF => TelnetCommand::EndOfFile,
+      SUSP => TelnetCommand::Suspend,
+      ABORT => TelnetCommand::Abort,
+      other => TelnetCommand::Unknown(other),
+    }
+  }
+}
+
+impl From<TelnetCommand> for u8 {
+  fn from(value: TelnetCommand) -> Self {
+    use op_command::*;
+    match value {
+      TelnetCommand::Will => WILL,
+      TelnetCommand::Wont => WONT,
+      TelnetCommand::Do => DO,
+      TelnetCommand::Dont => DONT,
+      TelnetCommand::SubnegotiationBegin => SB,
+      TelnetCommand::EndOfRecord => EOR,
+      TelnetCommand::SubnegotiationEnd => SE,
+      TelnetCommand::NoOperation => NOP,
+      TelnetCommand::DataMark => DM,
+      TelnetCommand::Break => BREAK,
+      TelnetCommand::InterruptProcess => IP,
+      TelnetCommand::AbortOutput => AO,
+      TelnetCommand::AreYouThere => AYT,
+      TelnetCommand::EraseCharacter => EC,
+      TelnetCommand::EraseLine => EL,
+      TelnetCommand::GoAhead => GA,
+      TelnetCommand::EndOfFile => EOF,
+      TelnetCommand::Suspend => SUSP,
+      TelnetCommand::Abort => ABORT,
+      TelnetCommand::Unknown(other) => other,
+    }
+  }
 }
 
 /// Module containing constants for Telnet Option codes.
@@ -56,10 +183,558 @@ pub mod op_option {
   pub const AUTHENTICATION: u8 = 37;
   pub const ENCRYPT: u8 = 38;
   pub const NEWENVIRON: u8 = 39;
+  pub const TN3270E: u8 = 40;
+  pub const XAUTH: u8 = 41;
+  pub const CHARSET: u8 = 42;
+  pub const RSP: u8 = 43;
+  pub const COM_PORT_OPTION: u8 = 44;
+  pub const SUPPRESS_LOCAL_ECHO: u8 = 45;
+  pub const START_TLS: u8 = 46;
+  pub const KERMIT: u8 = 47;
+  pub const SEND_URL: u8 = 48;
+  pub const FORWARD_X: u8 = 49;
+  pub const MSDP: u8 = 69;
   pub const MSSP: u8 = 70;
+  pub const MSP: u8 = 90;
+  pub const MXP: u8 = 91;
   pub const ZMP: u8 = 93;
+  pub const AARD102: u8 = 102;
+  pub const PRAGMA_LOGON: u8 = 138;
+  pub const SSPI_LOGON: u8 = 139;
+  pub const PRAGMA_HEARTBEAT: u8 = 140;
   pub const EXOPL: u8 = 255;
+  pub const MCCP1: u8 = 85;
   pub const MCCP2: u8 = 86;
   pub const MCCP3: u8 = 87;
   pub const GMCP: u8 = 201;
 }
+
+/// A telnet option, for matching negotiations and subnegotiations symbolically.
+///
+/// Every method taking an option code accepts either this or a `u8` from `op_option`.
+///
+/// Options compare and hash by their code, so `Unknown(86)` is equal to `Mccp2`. `From<u8>` only produces
+/// `Unknown` for codes with no symbolic name.
+#[derive(Clone, Copy, Debug)]
+pub enum TelnetOption {
+  /// Binary transmission (RFC 856).
+  Binary,
+  Echo,
+  /// Reconnection.
+  Rcp,
+  /// Suppress go ahead (RFC 858).
+  Sga,
+  /// Approximate message size negotiation.
+  Nams,
+  Status,
+  /// Timing mark (RFC 860).
+  Tm,
+  /// Remote controlled transmission and echo.
+  Rcte,
+  Naol,
+  Naop,
+  Naocrd,
+  Naohts,
+  Naohtd,
+  Naoffd,
+  Naovts,
+  Naovtd,
+  Naolfd,
+  /// Extended ASCII.
+  Xascii,
+  Logout,
+  /// Byte macro.
+  Bm,
+  /// Data entry terminal.
+  Det,
+  Supdup,
+  SupdupOutput,
+  /// Send location.
+  Sndloc,
+  /// Terminal type (RFC 1091).
+  Ttype,
+  /// End of record (RFC 885).
+  Eor,
+  /// TACACS user identification.
+  Tuid,
+  /// Output marking.
+  Outmrk,
+  /// Terminal location number.
+  Ttyloc,
+  /// Telnet 3270 regime.
+  Regime3270,
+  /// X.3 PAD.
+  X3pad,
+  /// Negotiate about window size (RFC 1073).
+  Naws,
+  /// Terminal speed (RFC 1079).
+  Tspeed,
+  /// Remote flow control (RFC 1372).
+  Lflow,
+  Linemode,
+  /// X display location (RFC 1096).
+  Xdisploc,
+  /// The old environment option (RFC 1408).
+  Environ,
+  Authentication,
+  Encrypt,
+  /// New environment option (RFC 1572).
+  NewEnviron,
+  Tn3270e,
+  /// X Window System authentication.
+  Xauth,
+  /// Character set negotiation (RFC 2066).
+  Charset,
+  /// Remote serial port.
+  Rsp,
+  ComPortOption,
+  SuppressLocalEcho,
+  StartTls,
+  Kermit,
+  SendUrl,
+  /// X11 forwarding.
+  ForwardX,
+  Msdp,
+  Mssp,
+  Mccp1,
+  Mccp2,
+  Mccp3,
+  Msp,
+  Mxp,
+  Zmp,
+  Aard102,
+  PragmaLogon,
+  SspiLogon,
+  PragmaHeartbeat,
+  Gmcp,
+  /// Extended options list.
+  Exopl,
+  /// An option code with no symbolic name.
+  Unknown(u8),
+}
+
+impl PartialEq for TelnetOption {
+  fn eq(&self, other: &Self) -> bool {
+    u8::from(*self) == u8::from(*other)
+  }
+}
+
+impl Eq for TelnetOption {}
+
+impl Hash for TelnetOption {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    u8::from(*self).hash(state);
+  }
+}
+
+impl From<u8> for TelnetOption {
+  fn from(value: u8) -> Self {
+    use op_option::*;
+    match value {
+      BINARY => TelnetOption::Binary,
+      ECHO => TelnetOption::Echo,
+      RCP => TelnetOption::Rcp,
+      SGA => TelnetOption::Sga,
+      NAMS => TelnetOption::Nams,
+      STATUS => TelnetOption::Status,
+      TM => TelnetOption::Tm,
+      RCTE => TelnetOption::Rcte,
+      NAOL => TelnetOption::Naol,
+      NAOP => TelnetOption::Naop,
+      NAOCRD => TelnetOption::Naocrd,
+      NAOHTS => TelnetOption::Naohts,
+      NAOHTD => TelnetOption::Naohtd,
+      NAOFFD => TelnetOption::Naoffd,
+      NAOVTS => TelnetOption::Naovts,
+      NAOVTD => TelnetOption::Naovtd,
+      NAOLFD => TelnetOption::Naolfd,
+      XASCII => TelnetOption::Xascii,
+      LOGOUT => TelnetOption::Logout,
+      BM => TelnetOption::Bm,
+      DET => TelnetOption::Det,
+      SUPDUP => TelnetOption::Supdup,
+      SUPDUPOUTPUT => TelnetOption::SupdupOutput,
+      SNDLOC => TelnetOption::Sndloc,
+      TTYPE => TelnetOption::Ttype,
+      EOR => TelnetOption::Eor,
+      TUID => TelnetOption::Tuid,
+      OUTMRK => TelnetOption::Outmrk,
+      TTYLOC => TelnetOption::Ttyloc,
+      _3270REGIME => TelnetOption::Regime3270,
+      X3PAD => TelnetOption::X3pad,
+      NAWS => TelnetOption::Naws,
+      TSPEED => TelnetOption::Tspeed,
+      LFLOW => TelnetOption::Lflow,
+      LINEMODE => TelnetOption::Linemode,
+      XDISPLOC => TelnetOption::Xdisploc,
+      ENVIRON => TelnetOption::Environ,
+      AUTHENTICATION => TelnetOption::Authentication,
+      ENCRYPT => TelnetOption::Encrypt,
+      NEWENVIRON => TelnetOption::NewEnviron,
+      TN3270E => TelnetOption::Tn3270e,
+      XAUTH => TelnetOption::Xauth,
+      CHARSET => TelnetOption::Charset,
+      RSP => TelnetOption::Rsp,
+      COM_PORT_OPTION => TelnetOption::ComPortOption,
+      SUPPRESS_LOCAL_ECHO => TelnetOption::SuppressLocalEcho,
+      START_TLS => TelnetOption::StartTls,
+      KERMIT => TelnetOption::Kermit,
+      SEND_URL => TelnetOption::SendUrl,
+      FORWARD_X => TelnetOption::ForwardX,
+      MSDP => TelnetOption::Msdp,
+      MSSP => TelnetOption::Mssp,
+      MCCP1 => TelnetOption::Mccp1,
+      MCCP2 => TelnetOption::Mccp2,
+      MCCP3 => TelnetOption::Mccp3,
+      MSP => TelnetOption::Msp,
+      MXP => TelnetOption::Mxp,
+      ZMP => TelnetOption::Zmp,
+      AARD102 => TelnetOption::Aard102,
+      PRAGMA_LOGON => TelnetOption::PragmaLogon,
+      SSPI_LOGON => TelnetOption::SspiLogon,
+      PRAGMA_HEARTBEAT => TelnetOption::PragmaHeartbeat,
+      GMCP => TelnetOption::Gmcp,
+      EXOPL => TelnetOption::Exopl,
+      other => TelnetOption::Unknown(other),
+    }
+  }
+}
+
+impl From<TelnetOption> for u8 {
+  fn from(value: TelnetOption) -> Self {
+    use op_option::*;
+    match value {
+      TelnetOption::Binary => BINARY,
+      TelnetOption::Echo => ECHO,
+      TelnetOption::Rcp => RCP,
+      TelnetOption::Sga => SGA,
+      TelnetOption::Nams => NAMS,
+      TelnetOption::Status => STATUS,
+      TelnetOption::Tm => TM,
+      TelnetOption::Rcte => RCTE,
+      TelnetOption::Naol => NAOL,
+      TelnetOption::Naop => NAOP,
+      TelnetOption::Naocrd => NAOCRD,
+      TelnetOption::Naohts => NAOHTS,
+      TelnetOption::Naohtd => NAOHTD,
+      TelnetOption::Naoffd => NAOFFD,
+      TelnetOption::Naovts => NAOVTS,
+      TelnetOption::Naovtd => NAOVTD,
+      TelnetOption::Naolfd => NAOLFD,
+      TelnetOption::Xascii => XASCII,
+      TelnetOption::Logout => LOGOUT,
+      TelnetOption::Bm => BM,
+      TelnetOption::Det => DET,
+      TelnetOption::Supdup => SUPDUP,
+      TelnetOption::SupdupOutput => SUPDUPOUTPUT,
+      TelnetOption::Sndloc => SNDLOC,
+      TelnetOption::Ttype => TTYPE,
+      TelnetOption::Eor => EOR,
+      TelnetOption::Tuid => TUID,
+      TelnetOption::Outmrk => OUTMRK,
+      TelnetOption::Ttyloc => TTYLOC,
+      TelnetOption::Regime3270 => _3270REGIME,
+      TelnetOption::X3pad => X3PAD,
+      TelnetOption::Naws => NAWS,
+      TelnetOption::Tspeed => TSPEED,
+      TelnetOption::Lflow => LFLOW,
+      TelnetOption::Linemode => LINEMODE,
+      TelnetOption::Xdisploc => XDISPLOC,
+      TelnetOption::Environ => ENVIRON,
+      TelnetOption::Authentication => AUTHENTICATION,
+      TelnetOption::Encrypt => ENCRYPT,
+      TelnetOption::NewEnviron => NEWENVIRON,
+      TelnetOption::Tn3270e => TN3270E,
+      TelnetOption::Xauth => XAUTH,
+      TelnetOption::Charset => CHARSET,
+      TelnetOption::Rsp => RSP,
+      TelnetOption::ComPortOption => COM_PORT_OPTION,
+      TelnetOption::SuppressLocalEcho => SUPPRESS_LOCAL_ECHO,
+      TelnetOption::StartTls => START_TLS,
+      TelnetOption::Kermit => KERMIT,
+      TelnetOption::SendUrl => SEND_URL,
+      TelnetOption::ForwardX => FORWARD_X,
+      TelnetOption::Msdp => MSDP,
+      TelnetOption::Mssp => MSSP,
+      TelnetOption::Mccp1 => MCCP1,
+      TelnetOption::Mccp2 => MCCP2,
+      TelnetOption::Mccp3 => MCCP3,
+      TelnetOption::Msp => MSP,
+      TelnetOption::Mxp => MXP,
+      TelnetOption::Zmp => ZMP,
+      TelnetOption::Aard102 => AARD102,
+      TelnetOption::PragmaLogon => PRAGMA_LOGON,
+      TelnetOption::SspiLogon => SSPI_LOGON,
+      TelnetOption::PragmaHeartbeat => PRAGMA_HEARTBEAT,
+      TelnetOption::Gmcp => GMCP,
+      TelnetOption::Exopl => EXOPL,
+      TelnetOption::Unknown(other) => other,
+    }
+  }
+}
+
+/// The names of the telnet options, as their RFCs write them, from the IANA registry along with the MUD protocols.
+const OPTION_NAMES: &[(u8, &str)] = &[
+  (op_option::BINARY, "TRANSMIT-BINARY"),
+  (op_option::ECHO, "ECHO"),
+  (op_option::RCP, "RCP"),
+  (op_option::SGA, "SUPPRESS-GO-AHEAD"),
+  (op_option::NAMS, "NAMS"),
+  (op_option::STATUS, "STATUS"),
+  (op_option::TM, "TIMING-MARK"),
+  (op_option::RCTE, "RCTE"),
+  (op_option::NAOL, "NAOL"),
+  (op_option::NAOP, "NAOP"),
+  (op_option::NAOCRD, "NAOCRD"),
+  (op_option::NAOHTS, "NAOHTS"),
+  (op_option::NAOHTD, "NAOHTD"),
+  (op_option::NAOFFD, "NAOFFD"),
+  (op_option::NAOVTS, "NAOVTS"),
+  (op_option::NAOVTD, "NAOVTD"),
+  (op_option::NAOLFD, "NAOLFD"),
+  (op_option::XASCII, "EXTEND-ASCII"),
+  (op_option::LOGOUT, "LOGOUT"),
+  (op_option::BM, "BM"),
+  (op_option::DET, "DET"),
+  (op_option::SUPDUP, "SUPDUP"),
+  (op_option::SUPDUPOUTPUT, "SUPDUP-OUTPUT"),
+  (op_option::SNDLOC, "SEND-LOCATION"),
+  (op_option::TTYPE, "TERMINAL-TYPE"),
+  (op_option::EOR, "END-OF-RECORD"),
+  (op_option::TUID, "TUID"),
+  (op_option::OUTMRK, "OUTMRK"),
+  (op_option::TTYLOC, "TTYLOC"),
+  (op_option::_3270REGIME, "3270-REGIME"),
+  (op_option::X3PAD, "X.3-PAD"),
+  (op_option::NAWS, "NAWS"),
+  (op_option::TSPEED, "TERMINAL-SPEED"),
+  (op_option::LFLOW, "TOGGLE-FLOW-CONTROL"),
+  (op_option::LINEMODE, "LINEMODE"),
+  (op_option::XDISPLOC, "X-DISPLAY-LOCATION"),
+  (op_option::ENVIRON, "ENVIRON"),
+  (op_option::AUTHENTICATION, "AUTHENTICATION"),
+  (op_option::ENCRYPT, "ENCRYPT"),
+  (op_option::NEWENVIRON, "NEW-ENVIRON"),
+  (op_option::TN3270E, "TN3270E"),
+  (op_option::XAUTH, "XAUTH"),
+  (op_option::CHARSET, "CHARSET"),
+  (op_option::RSP, "RSP"),
+  (op_option::COM_PORT_OPTION, "COM-PORT-OPTION"),
+  (op_option::SUPPRESS_LOCAL_ECHO, "SUPPRESS-LOCAL-ECHO"),
+  (op_option::START_TLS, "START-TLS"),
+  (op_option::KERMIT, "KERMIT"),
+  (op_option::SEND_URL, "SEND-URL"),
+  (op_option::FORWARD_X, "FORWARD-X"),
+  (op_option::MSDP, "MSDP"),
+  (op_option::MSSP, "MSSP"),
+  (op_option::MCCP1, "MCCP1"),
+  (op_option::MCCP2, "MCCP2"),
+  (op_option::MCCP3, "MCCP3"),
+  (op_option::MSP, "MSP"),
+  (op_option::MXP, "MXP"),
+  (op_option::ZMP, "ZMP"),
+  (op_option::AARD102, "AARD102"),
+  (op_option::PRAGMA_LOGON, "PRAGMA-LOGON"),
+  (op_option::SSPI_LOGON, "SSPI-LOGON"),
+  (op_option::PRAGMA_HEARTBEAT, "PRAGMA-HEARTBEAT"),
+  (op_option::GMCP, "GMCP"),
+  (op_option::EXOPL, "EXTENDED-OPTIONS-LIST"),
+];
+
+/// The `op_option` constants whose names differ from `OPTION_NAMES`, without a leading `_`, also accepted when parsing.
+const OPTION_CONSTANTS: &[(u8, &str)] = &[
+  (op_option::BINARY, "BINARY"),
+  (op_option::SGA, "SGA"),
+  (op_option::TM, "TM"),
+  (op_option::XASCII, "XASCII"),
+  (op_option::SUPDUPOUTPUT, "SUPDUPOUTPUT"),
+  (op_option::SNDLOC, "SNDLOC"),
+  (op_option::TTYPE, "TTYPE"),
+  (op_option::EOR, "EOR"),
+  (op_option::_3270REGIME, "3270REGIME"),
+  (op_option::X3PAD, "X3PAD"),
+  (op_option::TSPEED, "TSPEED"),
+  (op_option::LFLOW, "LFLOW"),
+  (op_option::XDISPLOC, "XDISPLOC"),
+  (op_option::NEWENVIRON, "NEWENVIRON"),
+  (op_option::EXOPL, "EXOPL"),
+];
+
+/// The names of the telnet commands, as in the IANA registry.
+const COMMAND_NAMES: &[(u8, &str)] = &[
+  (op_command::EOF, "EOF"),
+  (op_command::SUSP, "SUSP"),
+  (op_command::ABORT, "ABORT"),
+  (op_command::EOR, "EOR"),
+  (op_command::SE, "SE"),
+  (op_command::NOP, "NOP"),
+  (op_command::DM, "DM"),
+  (op_command::BREAK, "BRK"),
+  (op_command::IP, "IP"),
+  (op_command::AO, "AO"),
+  (op_command::AYT, "AYT"),
+  (op_command::EC, "EC"),
+  (op_command::EL, "EL"),
+  (op_command::GA, "GA"),
+  (op_command::SB, "SB"),
+  (op_command::WILL, "WILL"),
+  (op_command::WONT, "WONT"),
+  (op_command::DO, "DO"),
+  (op_command::DONT, "DONT"),
+  (op_command::IAC, "IAC"),
+];
+
+/// The `op_command` constants whose names differ from `COMMAND_NAMES`, also accepted when parsing.
+const COMMAND_CONSTANTS: &[(u8, &str)] = &[(op_command::BREAK, "BREAK")];
+
+fn name_of(names: &[(u8, &'static str)], code: u8) -> Option<&'static str> {
+  names
+    .iter()
+    .find(|(c, _)| *c == code)
+    .map(|(_, name)| *name)
+}
+
+/// Look up a name, ignoring case and treating `_` as `-`, or parse a decimal code.
+fn code_of(names: &[(u8, &str)], name: &str) -> Result<u8, ParseNameError> {
+  let name = name.trim();
+  if let Ok(code) = name.parse::<u8>() {
+    return Ok(code);
+  }
+  let normalize = |b: u8| {
+    if b == b'_' {
+      b'-'
+    } else {
+      b.to_ascii_uppercase()
+    }
+  };
+  names
+    .iter()
+    .find(|(_, n)| {
+      n.len() == name.len()
+        && n
+          .bytes()
+          .zip(name.bytes())
+          .all(|(a, b)| normalize(a) == normalize(b))
+    })
+    .map(|(code, _)| *code)
+    .ok_or(ParseNameError)
+}
+
+impl TelnetOption {
+  /// Get the name of the option, such as `NAWS`, or None for a code with no registered name.
+  pub fn name(self) -> Option<&'static str> {
+    name_of(OPTION_NAMES, self.into())
+  }
+}
+
+/// Written as the option's name, or its code if it has none.
+impl fmt::Display for TelnetOption {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.name() {
+      Some(name) => f.write_str(name),
+      None => write!(f, "{}", u8::from(*self)),
+    }
+  }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for TelnetOption {
+  fn format(&self, f: defmt::Formatter<'_>) {
+    match self.name() {
+      Some(name) => defmt::write!(f, "{=str}", name),
+      None => defmt::write!(f, "{=u8}", u8::from(*self)),
+    }
+  }
+}
+
+/// Parsed from a name or the `op_option` constant's name, in any case and with `_` or `-`, or from a decimal code.
+impl FromStr for TelnetOption {
+  type Err = ParseNameError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    code_of(OPTION_NAMES, s)
+      .or_else(|_| code_of(OPTION_CONSTANTS, s))
+      .map(TelnetOption::from)
+  }
+}
+
+impl TelnetCommand {
+  /// Get the name of the command, such as `WILL`, or None for a code with no registered name.
+  pub fn name(self) -> Option<&'static str> {
+    name_of(COMMAND_NAMES, self.into())
+  }
+}
+
+/// Written as the command's name, or its code if it has none.
+impl fmt::Display for TelnetCommand {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.name() {
+      Some(name) => f.write_str(name),
+      None => write!(f, "{}", u8::from(*self)),
+    }
+  }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for TelnetCommand {
+  fn format(&self, f: defmt::Formatter<'_>) {
+    match self.name() {
+      Some(name) => defmt::write!(f, "{=str}", name),
+      None => defmt::write!(f, "{=u8}", u8::from(*self)),
+    }
+  }
+}
+
+/// Parsed from a name or the `op_command` constant's name, in any case, or from a decimal code.
+impl FromStr for TelnetCommand {
+  type Err = ParseNameError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    code_of(COMMAND_NAMES, s)
+      .or_else(|_| code_of(COMMAND_CONSTANTS, s))
+      .map(TelnetCommand::from)
+  }
+}
+
+/// Codec for Aardwolf's option 102 status subnegotiations.
+pub mod aard102;
+/// Codec for AUTHENTICATION (RFC 2941) subnegotiations, and the trait for plugging in mechanisms.
+pub mod auth;
+/// Codec for COM-PORT-OPTION (RFC 2217) subnegotiations, and a client session for serial ports shared over telnet.
+pub mod comport;
+/// Codec for ENCRYPT (RFC 2946) subnegotiations, and the trait for plugging in a cipher.
+pub mod encrypt;
+/// Codec for NEW-ENVIRON (RFC 1572) subnegotiations.
+pub mod environ;
+/// Codec for GMCP (Generic MUD Communication Protocol) messages, and the `Core` package handshake.
+pub mod gmcp;
+/// Codec for TOGGLE-FLOW-CONTROL (RFC 1372) subnegotiations.
+pub mod lflow;
+/// Codec for LINEMODE (RFC 1184) subnegotiations.
+pub mod linemode;
+/// The MUD NEW-ENVIRON Standard variables, carried over NEW-ENVIRON.
+pub mod mnes;
+/// Codec for MSDP (MUD Server Data Protocol) subnegotiations.
+pub mod msdp;
+/// Triggers for MSP (MUD Sound Protocol), found in received text.
+pub mod msp;
+/// Codec for MSSP (MUD Server Status Protocol) subnegotiations.
+pub mod mssp;
+/// The MUD Terminal Type Standard, carried over TERMINAL-TYPE.
+pub mod mtts;
+/// Line modes for MXP (MUD eXtension Protocol) text.
+pub mod mxp;
+/// Codec for NAWS (RFC 1073) subnegotiations.
+pub mod naws;
+/// Codec for STATUS (RFC 859) subnegotiations.
+pub mod status;
+/// The START_TLS subnegotiation, which switches the connection to TLS.
+pub mod tls;
+/// Codec for TN3270E (RFC 2355) subnegotiations, and the framing of 3270 records.
+pub mod tn3270e;
+/// Codec for TERMINAL-SPEED (RFC 1079) subnegotiations.
+pub mod tspeed;
+/// Codec for TERMINAL-TYPE (RFC 1091) subnegotiations.
+pub mod ttype;
+/// Codec for X-DISPLAY-LOCATION (RFC 1096) subnegotiations.
+pub mod xdisploc;
@@ -0,0 +1,122 @@
+use bytes::Bytes;
+
+/// The channels of the status updates sent by an Aardwolf server.
+pub const STATUS: u8 = 100;
+pub const TICK: u8 = 101;
+
+/// The options a client can turn on or off, sent as the channel of its own updates.
+pub const STATMON: u8 = 1;
+pub const BIGMAP: u8 = 2;
+pub const HELP: u8 = 3;
+pub const MAP: u8 = 4;
+pub const CHANNELS: u8 = 5;
+pub const TELL: u8 = 6;
+pub const SPELLUPS: u8 = 7;
+pub const SKILLGAINS: u8 = 8;
+pub const SAY: u8 = 9;
+pub const SCORE: u8 = 11;
+pub const ROOM_NAMES: u8 = 12;
+pub const EXITS: u8 = 14;
+pub const EDITOR: u8 = 15;
+pub const EQUIP: u8 = 16;
+pub const INVENTORY: u8 = 17;
+pub const QUIET: u8 = 50;
+pub const AUTOTICK: u8 = 51;
+pub const PROMPT: u8 = 52;
+pub const PAGING: u8 = 53;
+pub const AUTOMAP: u8 = 54;
+pub const SHORTMAP: u8 = 55;
+
+/// The values of a client option update.
+pub const ON: u8 = 1;
+pub const OFF: u8 = 2;
+
+/// What the player is doing, as reported on the `STATUS` channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AardStatus {
+  Login,
+  Motd,
+  Playing,
+  Afk,
+  Note,
+  Editing,
+  Paged,
+}
+
+impl AardStatus {
+  pub fn from_value(value: u8) -> Option<Self> {
+    match value {
+      1 => Some(AardStatus::Login),
+      2 => Some(AardStatus::Motd),
+      3 => Some(AardStatus::Playing),
+      4 => Some(AardStatus::Afk),
+      5 => Some(AardStatus::Note),
+      6 => Some(AardStatus::Editing),
+      7 => Some(AardStatus::Paged),
+      _ => None,
+    }
+  }
+}
+
+/// An Aardwolf option 102 subnegotiation: a channel and its value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Aard102 {
+  pub channel: u8,
+  pub value: u8,
+}
+
+impl Aard102 {
+  pub fn new(channel: u8, value: u8) -> Self {
+    Self { channel, value }
+  }
+  /// Create a client update turning an option, such as `TELL`, on or off.
+  pub fn toggle(option: u8, on: bool) -> Self {
+    Self::new(option, if on { ON } else { OFF })
+  }
+  /// Decode the payload of an option 102 subnegotiation.
+  ///
+  /// # Returns
+  ///
+  /// `Option<Aard102>` - The update, or None if the payload is not 2 bytes.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    match *data {
+      [channel, value] => Some(Self { channel, value }),
+      _ => None,
+    }
+  }
+  /// Encode the update as the payload of an option 102 subnegotiation.
+  pub fn encode(&self) -> Bytes {
+    Bytes::copy_from_slice(&[self.channel, self.value])
+  }
+  /// Get the player's status, if this is an update on the `STATUS` channel.
+  pub fn status(&self) -> Option<AardStatus> {
+    if self.channel == STATUS {
+      AardStatus::from_value(self.value)
+    } else {
+      None
+    }
+  }
+  /// Whether this is the update sent on each game tick.
+  pub fn is_tick(&self) -> bool {
+    self.channel == TICK
+  }
+}
+
+#[cfg(test)]
+mod test_aard102 {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let update = Aard102::decode(&[STATUS, 3]).unwrap();
+    assert_eq!(update.status(), Some(AardStatus::Playing));
+    assert!(!update.is_tick());
+    assert_eq!(Aard102::decode(&update.encode()), Some(update));
+    assert!(Aard102::new(TICK, 1).is_tick());
+    assert_eq!(Aard102::new(TICK, 1).status(), None);
+    assert_eq!(&Aard102::toggle(TELL, false).encode()[..], &[TELL, OFF]);
+    assert_eq!(Aard102::decode(&[STATUS]), None);
+    assert_eq!(Aard102::decode(&[STATUS, 3, 0]), None);
+  }
+}
@@ -0,0 +1,193 @@
+use alloc::{string::String, vec::Vec};
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// AUTHENTICATION (RFC 2941) subnegotiation commands.
+pub const IS: u8 = 0;
+pub const SEND: u8 = 1;
+pub const REPLY: u8 = 2;
+pub const NAME: u8 = 3;
+
+/// Authentication types, as assigned by IANA.
+pub const NULL: u8 = 0;
+pub const KERBEROS_V4: u8 = 1;
+pub const KERBEROS_V5: u8 = 2;
+pub const SPX: u8 = 3;
+pub const MINK: u8 = 4;
+pub const SRP: u8 = 5;
+pub const RSA: u8 = 6;
+pub const SSL: u8 = 7;
+pub const LOKI: u8 = 10;
+pub const SSA: u8 = 11;
+pub const KEA_SJ: u8 = 12;
+pub const KEA_SJ_INTEG: u8 = 13;
+pub const DSS: u8 = 14;
+pub const NTLM: u8 = 15;
+
+/// Modifier bits, sent with each authentication type.
+pub const WHO_MASK: u8 = 1;
+pub const WHO_CLIENT_TO_SERVER: u8 = 0;
+pub const WHO_SERVER_TO_CLIENT: u8 = 1;
+pub const HOW_MASK: u8 = 2;
+pub const HOW_ONE_WAY: u8 = 0;
+pub const HOW_MUTUAL: u8 = 2;
+pub const ENCRYPT_MASK: u8 = 20;
+pub const ENCRYPT_OFF: u8 = 0;
+pub const ENCRYPT_USING_TELOPT: u8 = 4;
+pub const ENCRYPT_AFTER_EXCHANGE: u8 = 16;
+pub const CRED_FWD_MASK: u8 = 8;
+pub const CRED_FWD_OFF: u8 = 0;
+pub const CRED_FWD_ON: u8 = 8;
+
+/// An authentication type and its modifiers, such as `SRP` with `HOW_MUTUAL`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuthPair {
+  pub kind: u8,
+  pub modifiers: u8,
+}
+
+impl AuthPair {
+  pub fn new(kind: u8, modifiers: u8) -> Self {
+    Self { kind, modifiers }
+  }
+  /// Whether both ends prove who they are, rather than only the client.
+  pub fn is_mutual(&self) -> bool {
+    self.modifiers & HOW_MASK == HOW_MUTUAL
+  }
+}
+
+/// A decoded AUTHENTICATION subnegotiation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AuthMessage {
+  /// The server offers these authentication types, in order of preference.
+  Send(Vec<AuthPair>),
+  /// Authentication data from the client, for the type it chose. `NULL` means it supports none of those offered.
+  Is(AuthPair, Bytes),
+  /// Authentication data from the server.
+  Reply(AuthPair, Bytes),
+  /// The name of the account the client wants to use.
+  Name(String),
+}
+
+impl AuthMessage {
+  /// Decode the payload of an AUTHENTICATION subnegotiation (without the leading option byte).
+  ///
+  /// # Returns
+  ///
+  /// `Option<AuthMessage>` - The message, or None if the command is unknown or an `IS` or `REPLY` has no
+  /// authentication type. A trailing odd byte of a `SEND` is ignored.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    let (&command, rest) = data.split_first()?;
+    match command {
+      SEND => Some(AuthMessage::Send(
+        rest
+          .chunks_exact(2)
+          .map(|pair| AuthPair::new(pair[0], pair[1]))
+          .collect(),
+      )),
+      IS | REPLY => {
+        if rest.len() < 2 {
+          return None;
+        }
+        let pair = AuthPair::new(rest[0], rest[1]);
+        let data = Bytes::copy_from_slice(&rest[2..]);
+        if command == IS {
+          Some(AuthMessage::Is(pair, data))
+        } else {
+          Some(AuthMessage::Reply(pair, data))
+        }
+      }
+      NAME => Some(AuthMessage::Name(
+        String::from_utf8_lossy(rest).into_owned(),
+      )),
+      _ => None,
+    }
+  }
+  /// Encode this message as the payload of an AUTHENTICATION subnegotiation. IAC bytes are not escaped, as
+  /// `Parser::subnegotiation` does that when sending.
+  pub fn encode(&self) -> Bytes {
+    let mut buf = BytesMut::new();
+    match self {
+      AuthMessage::Send(pairs) => {
+        buf.put_u8(SEND);
+        for pair in pairs {
+          buf.put_u8(pair.kind);
+          buf.put_u8(pair.modifiers);
+        }
+      }
+      AuthMessage::Is(pair, data) | AuthMessage::Reply(pair, data) => {
+        buf.put_u8(if let AuthMessage::Is(..) = self {
+          IS
+        } else {
+          REPLY
+        });
+        buf.put_u8(pair.kind);
+        buf.put_u8(pair.modifiers);
+        buf.put(&data[..]);
+      }
+      AuthMessage::Name(name) => {
+        buf.put_u8(NAME);
+        buf.put(name.as_bytes());
+      }
+    }
+    buf.freeze()
+  }
+}
+
+/// The next step of an authentication exchange, decided by an `AuthMechanism`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AuthStep {
+  /// Send this data to the remote end, as an `IS` from the client or a `REPLY` from the server, and wait for its
+  /// answer.
+  Continue(Bytes),
+  /// The exchange is over, after sending this data if there is any.
+  Complete { data: Option<Bytes>, accepted: bool },
+}
+
+/// An authentication mechanism, such as SRP or Kerberos, plugged into a parser with `Parser::add_auth_mechanism`.
+///
+/// The parser handles the AUTHENTICATION framing and picks the mechanism. The mechanism only deals with its own data,
+/// including the meaning of any accept or reject codes within it.
+pub trait AuthMechanism: Send + Sync {
+  /// The authentication type and modifiers this mechanism handles.
+  fn pair(&self) -> AuthPair;
+  /// Start an exchange as the client, once the server has offered this mechanism.
+  ///
+  /// # Returns
+  ///
+  /// `AuthStep` - Usually `Continue` with the data of the first `IS`.
+  fn start(&mut self) -> AuthStep;
+  /// Handle the data of an `IS` from the client, as the server, or of a `REPLY` from the server, as the client.
+  fn step(&mut self, data: &[u8]) -> AuthStep;
+}
+
+#[cfg(test)]
+mod test_auth {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let offer = AuthMessage::Send(alloc::vec![
+      AuthPair::new(SRP, HOW_MUTUAL),
+      AuthPair::new(KERBEROS_V5, HOW_ONE_WAY),
+    ]);
+    assert_eq!(
+      &offer.encode()[..],
+      &[SEND, SRP, HOW_MUTUAL, KERBEROS_V5, 0]
+    );
+    assert_eq!(AuthMessage::decode(&offer.encode()), Some(offer));
+    let is = AuthMessage::Is(
+      AuthPair::new(SRP, HOW_MUTUAL),
+      Bytes::from_static(b"\x00abc"),
+    );
+    assert_eq!(&is.encode()[..], b"\x00\x05\x02\x00abc");
+    assert_eq!(AuthMessage::decode(&is.encode()), Some(is));
+    let reply = AuthMessage::Reply(AuthPair::new(SRP, HOW_MUTUAL), Bytes::new());
+    assert_eq!(AuthMessage::decode(&reply.encode()), Some(reply));
+    let name = AuthMessage::Name(String::from("admin"));
+    assert_eq!(AuthMessage::decode(&name.encode()), Some(name));
+    assert_eq!(AuthMessage::decode(&[IS, SRP]), None);
+    assert_eq!(AuthMessage::decode(&[4]), None);
+    assert!(AuthPair::new(SRP, HOW_MUTUAL | CRED_FWD_ON).is_mutual());
+  }
+}
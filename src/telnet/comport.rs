@@ -0,0 +1,501 @@
+use crate::events::TelnetEvents;
+use crate::Parser;
+use alloc::string::String;
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// COM-PORT-OPTION (RFC 2217) commands, as sent by the client. The server answers with the same command plus
+/// `SERVER_OFFSET`.
+pub const SIGNATURE: u8 = 0;
+pub const SET_BAUDRATE: u8 = 1;
+pub const SET_DATASIZE: u8 = 2;
+pub const SET_PARITY: u8 = 3;
+pub const SET_STOPSIZE: u8 = 4;
+pub const SET_CONTROL: u8 = 5;
+pub const NOTIFY_LINESTATE: u8 = 6;
+pub const NOTIFY_MODEMSTATE: u8 = 7;
+pub const FLOWCONTROL_SUSPEND: u8 = 8;
+pub const FLOWCONTROL_RESUME: u8 = 9;
+pub const SET_LINESTATE_MASK: u8 = 10;
+pub const SET_MODEMSTATE_MASK: u8 = 11;
+pub const PURGE_DATA: u8 = 12;
+pub const SERVER_OFFSET: u8 = 100;
+
+/// Values of `SetParity`. 0 asks for the current setting.
+pub mod parity {
+  pub const REQUEST: u8 = 0;
+  pub const NONE: u8 = 1;
+  pub const ODD: u8 = 2;
+  pub const EVEN: u8 = 3;
+  pub const MARK: u8 = 4;
+  pub const SPACE: u8 = 5;
+}
+
+/// Values of `SetStopSize`. 0 asks for the current setting.
+pub mod stopsize {
+  pub const REQUEST: u8 = 0;
+  pub const ONE: u8 = 1;
+  pub const TWO: u8 = 2;
+  pub const ONE_AND_HALF: u8 = 3;
+}
+
+/// Values of `SetControl`.
+pub mod control {
+  pub const FLOW_REQUEST: u8 = 0;
+  pub const FLOW_NONE: u8 = 1;
+  pub const FLOW_XON_XOFF: u8 = 2;
+  pub const FLOW_HARDWARE: u8 = 3;
+  pub const BREAK_REQUEST: u8 = 4;
+  pub const BREAK_ON: u8 = 5;
+  pub const BREAK_OFF: u8 = 6;
+  pub const DTR_REQUEST: u8 = 7;
+  pub const DTR_ON: u8 = 8;
+  pub const DTR_OFF: u8 = 9;
+  pub const RTS_REQUEST: u8 = 10;
+  pub const RTS_ON: u8 = 11;
+  pub const RTS_OFF: u8 = 12;
+  pub const INBOUND_FLOW_REQUEST: u8 = 13;
+  pub const INBOUND_FLOW_NONE: u8 = 14;
+  pub const INBOUND_FLOW_XON_XOFF: u8 = 15;
+  pub const INBOUND_FLOW_HARDWARE: u8 = 16;
+  pub const FLOW_DCD: u8 = 17;
+  pub const INBOUND_FLOW_DTR: u8 = 18;
+  pub const FLOW_DSR: u8 = 19;
+}
+
+/// Bits of `NotifyLineState` and `SetLineStateMask`.
+pub mod linestate {
+  pub const DATA_READY: u8 = 1;
+  pub const OVERRUN_ERROR: u8 = 2;
+  pub const PARITY_ERROR: u8 = 4;
+  pub const FRAMING_ERROR: u8 = 8;
+  pub const BREAK_DETECT: u8 = 16;
+  pub const TRANSFER_HOLDING_EMPTY: u8 = 32;
+  pub const TRANSFER_SHIFT_EMPTY: u8 = 64;
+  pub const TIMEOUT_ERROR: u8 = 128;
+}
+
+/// Bits of `NotifyModemState` and `SetModemStateMask`.
+pub mod modemstate {
+  pub const DELTA_CTS: u8 = 1;
+  pub const DELTA_DSR: u8 = 2;
+  pub const TRAILING_EDGE_RING: u8 = 4;
+  pub const DELTA_CD: u8 = 8;
+  pub const CTS: u8 = 16;
+  pub const DSR: u8 = 32;
+  pub const RING: u8 = 64;
+  pub const CD: u8 = 128;
+}
+
+/// Values of `PurgeData`.
+pub mod purge {
+  pub const RECEIVE: u8 = 1;
+  pub const TRANSMIT: u8 = 2;
+  pub const BOTH: u8 = 3;
+}
+
+/// A COM-PORT-OPTION command. From the client, a value of 0 asks for the current setting. From the server, the
+/// value is the setting now in effect.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ComPortCommand {
+  /// Text identifying the sender, or an empty request for the other end's.
+  Signature(String),
+  /// The baud rate, such as 9600.
+  SetBaudRate(u32),
+  /// The number of data bits, from 5 to 8.
+  SetDataSize(u8),
+  /// One of the `parity` values.
+  SetParity(u8),
+  /// One of the `stopsize` values.
+  SetStopSize(u8),
+  /// One of the `control` values.
+  SetControl(u8),
+  /// The `linestate` bits.
+  NotifyLineState(u8),
+  /// The `modemstate` bits.
+  NotifyModemState(u8),
+  /// Stop sending data, as the receiver's buffers are full.
+  FlowControlSuspend,
+  /// Start sending data again.
+  FlowControlResume,
+  /// Which `linestate` bits the client wants to be notified about.
+  SetLineStateMask(u8),
+  /// Which `modemstate` bits the client wants to be notified about.
+  SetModemStateMask(u8),
+  /// One of the `purge` values.
+  PurgeData(u8),
+}
+
+/// A decoded COM-PORT-OPTION subnegotiation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComPortMessage {
+  /// Whether the server (the access server with the serial port) sent this, rather than the client.
+  pub from_server: bool,
+  pub command: ComPortCommand,
+}
+
+impl ComPortMessage {
+  pub fn new(from_server: bool, command: ComPortCommand) -> Self {
+    Self {
+      from_server,
+      command,
+    }
+  }
+  /// Decode the payload of a COM-PORT-OPTION subnegotiation (without the leading option byte).
+  ///
+  /// # Returns
+  ///
+  /// `Option<ComPortMessage>` - The message, or None if the command is unknown or its value is the wrong length.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    let (&code, value) = data.split_first()?;
+    let from_server = code >= SERVER_OFFSET;
+    let code = if from_server {
+      code - SERVER_OFFSET
+    } else {
+      code
+    };
+    let byte = || match *value {
+      [b] => Some(b),
+      _ => None,
+    };
+    let command = match code {
+      SIGNATURE => ComPortCommand::Signature(String::from_utf8_lossy(value).into_owned()),
+      SET_BAUDRATE => match *value {
+        [a, b, c, d] => ComPortCommand::SetBaudRate(u32::from_be_bytes([a, b, c, d])),
+        _ => return None,
+      },
+      SET_DATASIZE => ComPortCommand::SetDataSize(byte()?),
+      SET_PARITY => ComPortCommand::SetParity(byte()?),
+      SET_STOPSIZE => ComPortCommand::SetStopSize(byte()?),
+      SET_CONTROL => ComPortCommand::SetControl(byte()?),
+      NOTIFY_LINESTATE => ComPortCommand::NotifyLineState(byte()?),
+      NOTIFY_MODEMSTATE => ComPortCommand::NotifyModemState(byte()?),
+      FLOWCONTROL_SUSPEND if value.is_empty() => ComPortCommand::FlowControlSuspend,
+      FLOWCONTROL_RESUME if value.is_empty() => ComPortCommand::FlowControlResume,
+      SET_LINESTATE_MASK => ComPortCommand::SetLineStateMask(byte()?),
+      SET_MODEMSTATE_MASK => ComPortCommand::SetModemStateMask(byte()?),
+      PURGE_DATA => ComPortCommand::PurgeData(byte()?),
+      _ => return None,
+    };
+    Some(Self::new(from_server, command))
+  }
+  /// Encode this message as the payload of a COM-PORT-OPTION subnegotiation. IAC bytes are not escaped, as
+  /// `Parser::subnegotiation` does that when sending.
+  pub fn encode(&self) -> Bytes {
+    let (code, value): (u8, &[u8]) = match &self.command {
+      ComPortCommand::Signature(text) => (SIGNATURE, text.as_bytes()),
+      ComPortCommand::SetBaudRate(rate) => (SET_BAUDRATE, &rate.to_be_bytes()[..]),
+      ComPortCommand::SetDataSize(size) => (SET_DATASIZE, core::slice::from_ref(size)),
+      ComPortCommand::SetParity(parity) => (SET_PARITY, core::slice::from_ref(parity)),
+      ComPortCommand::SetStopSize(size) => (SET_STOPSIZE, core::slice::from_ref(size)),
+      ComPortCommand::SetControl(control) => (SET_CONTROL, core::slice::from_ref(control)),
+      ComPortCommand::NotifyLineState(state) => (NOTIFY_LINESTATE, core::slice::from_ref(state)),
+      ComPortCommand::NotifyModemState(state) => (NOTIFY_MODEMSTATE, core::slice::from_ref(state)),
+      ComPortCommand::FlowControlSuspend => (FLOWCONTROL_SUSPEND, &[]),
+      ComPortCommand::FlowControlResume => (FLOWCONTROL_RESUME, &[]),
+      ComPortCommand::SetLineStateMask(mask) => (SET_LINESTATE_MASK, core::slice::from_ref(mask)),
+      ComPortCommand::SetModemStateMask(mask) => (SET_MODEMSTATE_MASK, core::slice::from_ref(mask)),
+      ComPortCommand::PurgeData(purge) => (PURGE_DATA, core::slice::from_ref(purge)),
+    };
+    let mut buf = BytesMut::with_capacity(1 + value.len());
+    buf.put_u8(if self.from_server {
+      code + SERVER_OFFSET
+    } else {
+      code
+    });
+    buf.put(value);
+    buf.freeze()
+  }
+}
+
+/// The parity of a serial port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Parity {
+  None,
+  Odd,
+  Even,
+  Mark,
+  Space,
+}
+
+impl Parity {
+  pub fn from_value(value: u8) -> Option<Self> {
+    match value {
+      parity::NONE => Some(Parity::None),
+      parity::ODD => Some(Parity::Odd),
+      parity::EVEN => Some(Parity::Even),
+      parity::MARK => Some(Parity::Mark),
+      parity::SPACE => Some(Parity::Space),
+      _ => None,
+    }
+  }
+  pub fn value(self) -> u8 {
+    match self {
+      Parity::None => parity::NONE,
+      Parity::Odd => parity::ODD,
+      Parity::Even => parity::EVEN,
+      Parity::Mark => parity::MARK,
+      Parity::Space => parity::SPACE,
+    }
+  }
+}
+
+/// The number of stop bits of a serial port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StopBits {
+  One,
+  Two,
+  OneAndHalf,
+}
+
+impl StopBits {
+  pub fn from_value(value: u8) -> Option<Self> {
+    match value {
+      stopsize::ONE => Some(StopBits::One),
+      stopsize::TWO => Some(StopBits::Two),
+      stopsize::ONE_AND_HALF => Some(StopBits::OneAndHalf),
+      _ => None,
+    }
+  }
+  pub fn value(self) -> u8 {
+    match self {
+      StopBits::One => stopsize::ONE,
+      StopBits::Two => stopsize::TWO,
+      StopBits::OneAndHalf => stopsize::ONE_AND_HALF,
+    }
+  }
+}
+
+/// The outbound flow control of a serial port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FlowControl {
+  None,
+  XonXoff,
+  Hardware,
+}
+
+impl FlowControl {
+  pub fn from_value(value: u8) -> Option<Self> {
+    match value {
+      control::FLOW_NONE => Some(FlowControl::None),
+      control::FLOW_XON_XOFF => Some(FlowControl::XonXoff),
+      control::FLOW_HARDWARE => Some(FlowControl::Hardware),
+      _ => None,
+    }
+  }
+  pub fn value(self) -> u8 {
+    match self {
+      FlowControl::None => control::FLOW_NONE,
+      FlowControl::XonXoff => control::FLOW_XON_XOFF,
+      FlowControl::Hardware => control::FLOW_HARDWARE,
+    }
+  }
+}
+
+/// A change reported by the access server, returned by `SerialPortSession::process`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SerialEvent {
+  /// The server confirmed a setting, which is now in effect.
+  Setting(ComPortCommand),
+  /// The `modemstate` bits changed.
+  ModemState { state: u8, previous: u8 },
+  /// The `linestate` bits were reported, usually for an error or a break.
+  LineState(u8),
+  /// The server asked us to stop sending data.
+  FlowSuspended,
+  /// The server asked us to start sending data again.
+  FlowResumed,
+}
+
+/// The client side of a serial port shared over COM-PORT-OPTION (RFC 2217), as with ser2net.
+///
+/// Each setter returns the command to send, and the setting is recorded once the access server confirms it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SerialPortSession {
+  signature: Option<String>,
+  baud: Option<u32>,
+  data_size: Option<u8>,
+  parity: Option<Parity>,
+  stop_bits: Option<StopBits>,
+  flow_control: Option<FlowControl>,
+  modem_state: u8,
+  line_state: u8,
+  suspended: bool,
+}
+
+impl SerialPortSession {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  /// Set the baud rate, such as 115200.
+  pub fn set_baud(&self, parser: &mut Parser, baud: u32) -> Option<TelnetEvents> {
+    parser.send_com_port(ComPortCommand::SetBaudRate(baud))
+  }
+  /// Set the number of data bits, from 5 to 8.
+  pub fn set_data_size(&self, parser: &mut Parser, bits: u8) -> Option<TelnetEvents> {
+    parser.send_com_port(ComPortCommand::SetDataSize(bits))
+  }
+  pub fn set_parity(&self, parser: &mut Parser, parity: Parity) -> Option<TelnetEvents> {
+    parser.send_com_port(ComPortCommand::SetParity(parity.value()))
+  }
+  pub fn set_stop_bits(&self, parser: &mut Parser, stop_bits: StopBits) -> Option<TelnetEvents> {
+    parser.send_com_port(ComPortCommand::SetStopSize(stop_bits.value()))
+  }
+  pub fn set_flow_control(&self, parser: &mut Parser, flow: FlowControl) -> Option<TelnetEvents> {
+    parser.send_com_port(ComPortCommand::SetControl(flow.value()))
+  }
+  /// Raise or lower the DTR line.
+  pub fn set_dtr(&self, parser: &mut Parser, on: bool) -> Option<TelnetEvents> {
+    let value = if on {
+      control::DTR_ON
+    } else {
+      control::DTR_OFF
+    };
+    parser.send_com_port(ComPortCommand::SetControl(value))
+  }
+  /// Raise or lower the RTS line.
+  pub fn set_rts(&self, parser: &mut Parser, on: bool) -> Option<TelnetEvents> {
+    let value = if on {
+      control::RTS_ON
+    } else {
+      control::RTS_OFF
+    };
+    parser.send_com_port(ComPortCommand::SetControl(value))
+  }
+  /// Start or stop sending a break.
+  pub fn set_break(&self, parser: &mut Parser, on: bool) -> Option<TelnetEvents> {
+    let value = if on {
+      control::BREAK_ON
+    } else {
+      control::BREAK_OFF
+    };
+    parser.send_com_port(ComPortCommand::SetControl(value))
+  }
+  /// Choose which `modemstate` bits the server reports, such as `modemstate::CD | modemstate::DELTA_CD`.
+  pub fn watch_modem_state(&self, parser: &mut Parser, mask: u8) -> Option<TelnetEvents> {
+    parser.send_com_port(ComPortCommand::SetModemStateMask(mask))
+  }
+  /// Discard the data buffered by the server's serial port, as with `purge::BOTH`.
+  pub fn purge(&self, parser: &mut Parser, which: u8) -> Option<TelnetEvents> {
+    parser.send_com_port(ComPortCommand::PurgeData(which))
+  }
+  /// Get the server's signature, once it has sent one.
+  pub fn signature(&self) -> Option<&str> {
+    self.signature.as_deref()
+  }
+  pub fn baud(&self) -> Option<u32> {
+    self.baud
+  }
+  pub fn data_size(&self) -> Option<u8> {
+    self.data_size
+  }
+  pub fn parity(&self) -> Option<Parity> {
+    self.parity
+  }
+  pub fn stop_bits(&self) -> Option<StopBits> {
+    self.stop_bits
+  }
+  pub fn flow_control(&self) -> Option<FlowControl> {
+    self.flow_control
+  }
+  /// Get the last `modemstate` bits reported by the server.
+  pub fn modem_state(&self) -> u8 {
+    self.modem_state
+  }
+  /// Get the last `linestate` bits reported by the server.
+  pub fn line_state(&self) -> u8 {
+    self.line_state
+  }
+  /// Whether the server has asked us to stop sending data.
+  pub fn suspended(&self) -> bool {
+    self.suspended
+  }
+  /// Update the session from an event returned by the parser.
+  ///
+  /// # Returns
+  ///
+  /// `Option<SerialEvent>` - What changed, for a `ComPort` event from the server, or None.
+  pub fn process(&mut self, event: &TelnetEvents) -> Option<SerialEvent> {
+    let command = match event {
+      TelnetEvents::ComPort(message) if message.from_server => &message.command,
+      _ => return None,
+    };
+    match *command {
+      ComPortCommand::Signature(ref text) => {
+        self.signature = Some(text.clone());
+        return None;
+      }
+      ComPortCommand::NotifyModemState(state) => {
+        let previous = core::mem::replace(&mut self.modem_state, state);
+        return Some(SerialEvent::ModemState { state, previous });
+      }
+      ComPortCommand::NotifyLineState(state) => {
+        self.line_state = state;
+        return Some(SerialEvent::LineState(state));
+      }
+      ComPortCommand::FlowControlSuspend => {
+        self.suspended = true;
+        return Some(SerialEvent::FlowSuspended);
+      }
+      ComPortCommand::FlowControlResume => {
+        self.suspended = false;
+        return Some(SerialEvent::FlowResumed);
+      }
+      ComPortCommand::SetBaudRate(baud) => self.baud = Some(baud),
+      ComPortCommand::SetDataSize(bits) => self.data_size = Some(bits),
+      ComPortCommand::SetParity(value) => self.parity = Parity::from_value(value),
+      ComPortCommand::SetStopSize(value) => self.stop_bits = StopBits::from_value(value),
+      ComPortCommand::SetControl(value) => {
+        if let Some(flow) = FlowControl::from_value(value) {
+          self.flow_control = Some(flow);
+        }
+      }
+      _ => (),
+    }
+    Some(SerialEvent::Setting(command.clone()))
+  }
+}
+
+#[cfg(test)]
+mod test_comport {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let baud = ComPortMessage::new(false, ComPortCommand::SetBaudRate(115_200));
+    assert_eq!(&baud.encode()[..], &[SET_BAUDRATE, 0, 1, 0xc2, 0]);
+    assert_eq!(ComPortMessage::decode(&baud.encode()), Some(baud));
+    let parity = ComPortMessage::new(true, ComPortCommand::SetParity(parity::EVEN));
+    assert_eq!(&parity.encode()[..], &[103, parity::EVEN]);
+    assert_eq!(ComPortMessage::decode(&parity.encode()), Some(parity));
+    let suspend = ComPortMessage::new(true, ComPortCommand::FlowControlSuspend);
+    assert_eq!(&suspend.encode()[..], &[108]);
+    assert_eq!(ComPortMessage::decode(&suspend.encode()), Some(suspend));
+    let signature = ComPortMessage::new(false, ComPortCommand::Signature(String::from("dev")));
+    assert_eq!(ComPortMessage::decode(&signature.encode()), Some(signature));
+    assert_eq!(ComPortMessage::decode(&[SET_BAUDRATE, 0, 0]), None);
+    assert_eq!(ComPortMessage::decode(&[SET_DATASIZE]), None);
+    assert_eq!(ComPortMessage::decode(&[FLOWCONTROL_RESUME, 1]), None);
+    assert_eq!(ComPortMessage::decode(&[13]), None);
+  }
+
+  #[test]
+  fn test_values() {
+    for parity in [
+      Parity::None,
+      Parity::Odd,
+      Parity::Even,
+      Parity::Mark,
+      Parity::Space,
+    ] {
+      assert_eq!(Parity::from_value(parity.value()), Some(parity));
+    }
+    assert_eq!(Parity::from_value(parity::REQUEST), None);
+    assert_eq!(
+      StopBits::from_value(StopBits::OneAndHalf.value()),
+      Some(StopBits::OneAndHalf)
+    );
+    assert_eq!(FlowControl::from_value(control::DTR_ON), None);
+  }
+}
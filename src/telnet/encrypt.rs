@@ -0,0 +1,175 @@
+use crate::telnet::op_command::{IAC, SB, SE};
+use crate::telnet::op_option::ENCRYPT;
+use alloc::vec::Vec;
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// ENCRYPT (RFC 2946) subnegotiation commands.
+pub const IS: u8 = 0;
+pub const SUPPORT: u8 = 1;
+pub const REPLY: u8 = 2;
+pub const START: u8 = 3;
+pub const END: u8 = 4;
+pub const REQUEST_START: u8 = 5;
+pub const REQUEST_END: u8 = 6;
+pub const ENC_KEYID: u8 = 7;
+pub const DEC_KEYID: u8 = 8;
+
+/// Encryption types, as assigned by IANA.
+pub const NULL: u8 = 0;
+pub const DES_CFB64: u8 = 1;
+pub const DES_OFB64: u8 = 2;
+pub const DES3_CFB64: u8 = 3;
+pub const DES3_OFB64: u8 = 4;
+pub const CAST5_40_CFB64: u8 = 8;
+pub const CAST5_40_OFB64: u8 = 9;
+pub const CAST128_CFB64: u8 = 10;
+pub const CAST128_OFB64: u8 = 11;
+
+/// The subnegotiation that ends an encrypted stream, as it appears once decrypted.
+pub(crate) const END_SEQUENCE: [u8; 6] = [IAC, SB, ENCRYPT, END, IAC, SE];
+
+/// A decoded ENCRYPT subnegotiation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EncryptMessage {
+  /// Data of the encryption type chosen by the side that will encrypt.
+  Is(u8, Bytes),
+  /// The encryption types the side that will decrypt supports, in order of preference.
+  Support(Vec<u8>),
+  /// Data of the encryption type, from the side that will decrypt.
+  Reply(u8, Bytes),
+  /// Everything the sender sends after this, with this key ID, is encrypted.
+  Start(Bytes),
+  /// The sender has stopped encrypting, from the end of this subnegotiation.
+  End,
+  /// A request for the remote end to start encrypting, with a key ID.
+  RequestStart(Bytes),
+  /// A request for the remote end to stop encrypting.
+  RequestEnd,
+  /// The key ID the sender will encrypt with.
+  EncKeyid(Bytes),
+  /// The key ID the sender will decrypt with.
+  DecKeyid(Bytes),
+}
+
+impl EncryptMessage {
+  /// Decode the payload of an ENCRYPT subnegotiation (without the leading option byte).
+  ///
+  /// # Returns
+  ///
+  /// `Option<EncryptMessage>` - The message, or None if the command is unknown or an `IS` or `REPLY` has no
+  /// encryption type.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    let (&command, rest) = data.split_first()?;
+    let bytes = || Bytes::copy_from_slice(rest);
+    match command {
+      IS | REPLY => {
+        let (&kind, rest) = rest.split_first()?;
+        let data = Bytes::copy_from_slice(rest);
+        if command == IS {
+          Some(EncryptMessage::Is(kind, data))
+        } else {
+          Some(EncryptMessage::Reply(kind, data))
+        }
+      }
+      SUPPORT => Some(EncryptMessage::Support(rest.to_vec())),
+      START => Some(EncryptMessage::Start(bytes())),
+      END => Some(EncryptMessage::End),
+      REQUEST_START => Some(EncryptMessage::RequestStart(bytes())),
+      REQUEST_END => Some(EncryptMessage::RequestEnd),
+      ENC_KEYID => Some(EncryptMessage::EncKeyid(bytes())),
+      DEC_KEYID => Some(EncryptMessage::DecKeyid(bytes())),
+      _ => None,
+    }
+  }
+  /// Encode this message as the payload of an ENCRYPT subnegotiation. IAC bytes are not escaped, as
+  /// `Parser::subnegotiation` does that when sending.
+  pub fn encode(&self) -> Bytes {
+    let mut buf = BytesMut::new();
+    match self {
+      EncryptMessage::Is(kind, data) | EncryptMessage::Reply(kind, data) => {
+        buf.put_u8(if let EncryptMessage::Is(..) = self {
+          IS
+        } else {
+          REPLY
+        });
+        buf.put_u8(*kind);
+        buf.put(&data[..]);
+      }
+      EncryptMessage::Support(kinds) => {
+        buf.put_u8(SUPPORT);
+        buf.put(&kinds[..]);
+      }
+      EncryptMessage::Start(keyid) => {
+        buf.put_u8(START);
+        buf.put(&keyid[..]);
+      }
+      EncryptMessage::End => buf.put_u8(END),
+      EncryptMessage::RequestStart(keyid) => {
+        buf.put_u8(REQUEST_START);
+        buf.put(&keyid[..]);
+      }
+      EncryptMessage::RequestEnd => buf.put_u8(REQUEST_END),
+      EncryptMessage::EncKeyid(keyid) => {
+        buf.put_u8(ENC_KEYID);
+        buf.put(&keyid[..]);
+      }
+      EncryptMessage::DecKeyid(keyid) => {
+        buf.put_u8(DEC_KEYID);
+        buf.put(&keyid[..]);
+      }
+    }
+    buf.freeze()
+  }
+}
+
+/// A stream cipher for ENCRYPT, plugged into a parser with `Parser::set_cipher`.
+///
+/// The key exchange, carried by `IS` and `REPLY`, is up to the application. The cipher is only handed the data
+/// sent and received once encryption has started.
+pub trait Cipher: Send + Sync {
+  /// The encryption type, such as `DES3_CFB64`.
+  fn kind(&self) -> u8;
+  /// Encrypt outbound data in place.
+  fn encrypt(&mut self, data: &mut [u8]);
+  /// Decrypt inbound data in place. It may be called with as little as one byte at a time.
+  fn decrypt(&mut self, data: &mut [u8]);
+}
+
+/// Which directions of a connection are encrypted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct EncryptState {
+  /// We have sent `START`, and encrypt what we send.
+  pub output: bool,
+  /// The remote end has sent `START`, and encrypts what it sends.
+  pub input: bool,
+}
+
+#[cfg(test)]
+mod test_encrypt {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let messages = [
+      EncryptMessage::Is(DES3_CFB64, Bytes::from_static(&[1, 2, 3])),
+      EncryptMessage::Support(alloc::vec![DES3_CFB64, DES_CFB64]),
+      EncryptMessage::Reply(DES3_CFB64, Bytes::new()),
+      EncryptMessage::Start(Bytes::from_static(&[0])),
+      EncryptMessage::End,
+      EncryptMessage::RequestStart(Bytes::new()),
+      EncryptMessage::RequestEnd,
+      EncryptMessage::EncKeyid(Bytes::from_static(&[1])),
+      EncryptMessage::DecKeyid(Bytes::from_static(&[1])),
+    ];
+    for message in messages.iter() {
+      assert_eq!(
+        EncryptMessage::decode(&message.encode()).as_ref(),
+        Some(message)
+      );
+    }
+    assert_eq!(&messages[1].encode()[..], &[SUPPORT, DES3_CFB64, DES_CFB64]);
+    assert_eq!(EncryptMessage::decode(&[IS]), None);
+    assert_eq!(EncryptMessage::decode(&[9]), None);
+  }
+}
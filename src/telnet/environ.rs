@@ -0,0 +1,238 @@
+use alloc::{string::String, vec::Vec};
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// NEW-ENVIRON (RFC 1572) subnegotiation command codes.
+pub const IS: u8 = 0;
+pub const SEND: u8 = 1;
+pub const INFO: u8 = 2;
+
+/// NEW-ENVIRON (RFC 1572) type codes used inside a subnegotiation.
+pub const VAR: u8 = 0;
+pub const VALUE: u8 = 1;
+pub const ESC: u8 = 2;
+pub const USERVAR: u8 = 3;
+
+/// The type codes used by a peer for the old ENVIRON option (RFC 1408, option 36).
+///
+/// RFC 1408 defines `VAR` as 0 and `VALUE` as 1, but the BSD implementation that most systems derive from swapped
+/// them (see RFC 1571). Old ENVIRON has no `USERVAR`, so user variables are sent as `VAR`s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LegacyEnviron {
+  /// `VAR` 0 and `VALUE` 1, as written in RFC 1408.
+  Rfc1408,
+  /// `VAR` 1 and `VALUE` 0, as sent by BSD-derived implementations.
+  Swapped,
+}
+
+impl LegacyEnviron {
+  /// The `VAR` and `VALUE` codes.
+  fn codes(self) -> (u8, u8) {
+    match self {
+      LegacyEnviron::Rfc1408 => (0, 1),
+      LegacyEnviron::Swapped => (1, 0),
+    }
+  }
+}
+
+/// The kind of an environment variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EnvironKind {
+  /// A well-known variable, such as `USER` or `DISPLAY`.
+  Var,
+  /// A user defined variable.
+  UserVar,
+}
+
+/// A single environment variable, as sent in an IS/INFO reply or requested in a SEND.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvironVar {
+  pub kind: EnvironKind,
+  pub name: String,
+  /// The value of the variable. `None` if the variable is undefined, or if this is a SEND request.
+  pub value: Option<String>,
+}
+
+impl EnvironVar {
+  pub fn new(kind: EnvironKind, name: &str, value: Option<&str>) -> Self {
+    Self {
+      kind,
+      name: String::from(name),
+      value: value.map(String::from),
+    }
+  }
+}
+
+/// The command carried by a NEW-ENVIRON subnegotiation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EnvironCommand {
+  /// A reply to a SEND request.
+  Is,
+  /// A request for the listed variables, or for all variables if the list is empty.
+  Send,
+  /// An unsolicited update of variables that have changed.
+  Info,
+}
+
+/// A decoded NEW-ENVIRON subnegotiation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EnvironMessage {
+  pub command: EnvironCommand,
+  pub vars: Vec<EnvironVar>,
+}
+
+impl EnvironMessage {
+  pub fn new(command: EnvironCommand, vars: Vec<EnvironVar>) -> Self {
+    Self { command, vars }
+  }
+  /// Decode the payload of a NEW-ENVIRON subnegotiation (without the leading option byte).
+  ///
+  /// The payload is expected to have already had its IAC bytes unescaped.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    Self::decode_with(data, VAR, VALUE)
+  }
+  /// Decode the payload of an old ENVIRON (RFC 1408) subnegotiation, using the type codes of `legacy`.
+  pub fn decode_legacy(data: &[u8], legacy: LegacyEnviron) -> Option<Self> {
+    let (var, value) = legacy.codes();
+    Self::decode_with(data, var, value)
+  }
+  fn decode_with(data: &[u8], var_code: u8, value_code: u8) -> Option<Self> {
+    let command = match *data.first()? {
+      IS => EnvironCommand::Is,
+      SEND => EnvironCommand::Send,
+      INFO => EnvironCommand::Info,
+      _ => return None,
+    };
+    let mut vars: Vec<EnvironVar> = Vec::new();
+    let mut iter = data[1..].iter().copied();
+    // Which part of the current variable we are reading into.
+    let mut in_value = false;
+    let mut current: Option<(EnvironKind, Vec<u8>, Option<Vec<u8>>)> = None;
+    let finish = |current: Option<(EnvironKind, Vec<u8>, Option<Vec<u8>>)>,
+                  vars: &mut Vec<EnvironVar>| {
+      if let Some((kind, name, value)) = current {
+        vars.push(EnvironVar {
+          kind,
+          name: String::from_utf8_lossy(&name).into_owned(),
+          value: value.map(|v| String::from_utf8_lossy(&v).into_owned()),
+        });
+      }
+    };
+    while let Some(byte) = iter.next() {
+      match byte {
+        _ if byte == var_code || byte == USERVAR => {
+          finish(current.take(), &mut vars);
+          let kind = if byte == var_code {
+            EnvironKind::Var
+          } else {
+            EnvironKind::UserVar
+          };
+          current = Some((kind, Vec::new(), None));
+          in_value = false;
+        }
+        _ if byte == value_code => {
+          let (_, _, value) = current.as_mut()?;
+          *value = Some(Vec::new());
+          in_value = true;
+        }
+        _ => {
+          let byte = if byte == ESC { iter.next()? } else { byte };
+          let (_, name, value) = current.as_mut()?;
+          match (in_value, value) {
+            (true, Some(value)) => value.push(byte),
+            _ => name.push(byte),
+          }
+        }
+      }
+    }
+    finish(current, &mut vars);
+    Some(Self { command, vars })
+  }
+  /// Encode this message as the payload of a NEW-ENVIRON subnegotiation.
+  ///
+  /// The returned buffer has not had IAC bytes escaped; that happens when it is wrapped in a subnegotiation.
+  pub fn encode(&self) -> Bytes {
+    self.encode_with(VAR, USERVAR, VALUE)
+  }
+  /// Encode this message as the payload of an old ENVIRON (RFC 1408) subnegotiation, using the type codes of
+  /// `legacy`. User variables are sent as `VAR`s.
+  pub fn encode_legacy(&self, legacy: LegacyEnviron) -> Bytes {
+    let (var, value) = legacy.codes();
+    self.encode_with(var, var, value)
+  }
+  fn encode_with(&self, var_code: u8, uservar_code: u8, value_code: u8) -> Bytes {
+    let mut buf = BytesMut::with_capacity(16);
+    buf.put_u8(match self.command {
+      EnvironCommand::Is => IS,
+      EnvironCommand::Send => SEND,
+      EnvironCommand::Info => INFO,
+    });
+    for var in &self.vars {
+      buf.put_u8(match var.kind {
+        EnvironKind::Var => var_code,
+        EnvironKind::UserVar => uservar_code,
+      });
+      put_escaped(&mut buf, var.name.as_bytes());
+      if let Some(value) = &var.value {
+        buf.put_u8(value_code);
+        put_escaped(&mut buf, value.as_bytes());
+      }
+    }
+    buf.freeze()
+  }
+}
+
+fn put_escaped(buf: &mut BytesMut, data: &[u8]) {
+  for &byte in data {
+    if byte <= USERVAR {
+      buf.put_u8(ESC);
+    }
+    buf.put_u8(byte);
+  }
+}
+
+#[cfg(test)]
+mod test_environ {
+  use super::*;
+  use alloc::vec;
+
+  #[test]
+  fn test_roundtrip() {
+    let message = EnvironMessage::new(
+      EnvironCommand::Info,
+      vec![
+        EnvironVar::new(EnvironKind::Var, "USER", Some("guest")),
+        EnvironVar::new(EnvironKind::UserVar, "ODD\u{1}NAME", None),
+        EnvironVar::new(EnvironKind::UserVar, "EMPTY", Some("")),
+      ],
+    );
+    let encoded = message.encode();
+    assert_eq!(
+      &encoded[..12],
+      &[INFO, VAR, b'U', b'S', b'E', b'R', VALUE, b'g', b'u', b'e', b's', b't']
+    );
+    assert_eq!(EnvironMessage::decode(&encoded), Some(message));
+  }
+
+  #[test]
+  fn test_legacy() {
+    let message = EnvironMessage::new(
+      EnvironCommand::Is,
+      vec![EnvironVar::new(EnvironKind::Var, "USER", Some("guest"))],
+    );
+    let swapped = message.encode_legacy(LegacyEnviron::Swapped);
+    assert_eq!(&swapped[..3], &[IS, 1, b'U']);
+    assert_eq!(swapped[6], 0);
+    assert_eq!(
+      EnvironMessage::decode_legacy(&swapped, LegacyEnviron::Swapped),
+      Some(message.clone())
+    );
+    let standard = message.encode_legacy(LegacyEnviron::Rfc1408);
+    assert_eq!(standard, message.encode());
+    assert_eq!(
+      EnvironMessage::decode_legacy(&standard, LegacyEnviron::Rfc1408),
+      Some(message)
+    );
+  }
+}
@@ -0,0 +1,343 @@
+#[cfg(feature = "json")]
+use crate::events::TelnetEvents;
+#[cfg(feature = "json")]
+use crate::telnet::op_option::GMCP;
+#[cfg(feature = "json")]
+use crate::Parser;
+use alloc::string::String;
+#[cfg(feature = "json")]
+use alloc::{string::ToString, vec::Vec};
+use bytes::{BufMut, Bytes, BytesMut};
+#[cfg(feature = "json")]
+use serde_json::Value;
+
+/// A decoded GMCP (Generic MUD Communication Protocol) message, such as `Char.Vitals {"hp":100}`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct GmcpMessage {
+  /// The package, such as `Char` or `Char.Items`.
+  pub package: String,
+  /// The message within the package, such as `Vitals`.
+  pub message: String,
+  /// The JSON payload as sent, which is empty for a message without one.
+  pub data: String,
+}
+
+impl GmcpMessage {
+  /// Create a message from its full name, such as `Core.Hello`, and its JSON payload.
+  ///
+  /// # Arguments
+  ///
+  /// `name` - The full name. Everything before the last `.` is the package, and a name without a `.` is a package
+  /// with an empty message.
+  ///
+  /// `data` - The JSON payload as text, or an empty string for none.
+  pub fn new(name: &str, data: &str) -> Self {
+    let (package, message) = match name.rfind('.') {
+      Some(index) => (&name[..index], &name[index + 1..]),
+      None => (name, ""),
+    };
+    Self {
+      package: String::from(package),
+      message: String::from(message),
+      data: String::from(data),
+    }
+  }
+  /// Get the full name of the message, such as `Char.Vitals`.
+  pub fn name(&self) -> String {
+    if self.message.is_empty() {
+      self.package.clone()
+    } else {
+      alloc::format!("{}.{}", self.package, self.message)
+    }
+  }
+  /// Decode the payload of a GMCP subnegotiation.
+  ///
+  /// The payload is expected to have already had its IAC bytes unescaped. The name ends at the first space or
+  /// newline and the rest, trimmed, is the JSON payload. GMCP is UTF-8, so invalid bytes are replaced.
+  ///
+  /// # Returns
+  ///
+  /// `Option<GmcpMessage>` - The message, or None if the payload has no name.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    let text = String::from_utf8_lossy(data);
+    let text = text.trim_start();
+    let (name, data) = match text.find(|c: char| c.is_ascii_whitespace()) {
+      Some(index) => (&text[..index], text[index..].trim()),
+      None => (text, ""),
+    };
+    if name.is_empty() {
+      return None;
+    }
+    Some(Self::new(name, data))
+  }
+  /// Encode the message as the payload of a GMCP subnegotiation.
+  pub fn encode(&self) -> Bytes {
+    let name = self.name();
+    let mut buf = BytesMut::with_capacity(name.len() + 1 + self.data.len());
+    buf.put(name.as_bytes());
+    if !self.data.is_empty() {
+      buf.put_u8(b' ');
+      buf.put(self.data.as_bytes());
+    }
+    buf.freeze()
+  }
+  /// Parse the JSON payload.
+  ///
+  /// # Returns
+  ///
+  /// `Option<serde_json::Value>` - The payload, `Value::Null` if the message has none, or None if it is not valid
+  /// JSON.
+  #[cfg(feature = "json")]
+  pub fn json(&self) -> Option<Value> {
+    self.deserialize()
+  }
+  /// Deserialize the JSON payload to a user type. A message without a payload is read as `null`.
+  ///
+  /// # Returns
+  ///
+  /// `Option<T>` - The payload, or None if it is not valid JSON or does not match `T`.
+  #[cfg(feature = "json")]
+  pub fn deserialize<T: serde::de::DeserializeOwned>(&self) -> Option<T> {
+    let data = if self.data.is_empty() {
+      "null"
+    } else {
+      &self.data
+    };
+    serde_json::from_str(data).ok()
+  }
+  /// Create a message from its full name and a JSON value.
+  #[cfg(feature = "json")]
+  pub fn with_json(name: &str, value: &Value) -> Self {
+    Self::new(name, &value.to_string())
+  }
+}
+
+/// Bookkeeping for the GMCP `Core` package: sending `Core.Hello` and our own `Core.Supports.Set` once GMCP is
+/// enabled, and tracking the modules the remote end reports with `Core.Supports.Set`, `.Add` and `.Remove`.
+#[cfg(feature = "json")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GmcpCore {
+  hello: Option<GmcpMessage>,
+  announce: Vec<String>,
+  supports: Vec<(String, u32)>,
+  enabled: bool,
+}
+
+#[cfg(feature = "json")]
+impl GmcpCore {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  /// Send `Core.Hello {"client":..., "version":...}` when GMCP is enabled, as a client does.
+  pub fn hello(mut self, client: &str, version: &str) -> Self {
+    let value = serde_json::json!({ "client": client, "version": version });
+    self.hello = Some(GmcpMessage::with_json("Core.Hello", &value));
+    self
+  }
+  /// Send `Core.Supports.Set` with these modules, such as `"Char 1"`, when GMCP is enabled.
+  pub fn announce(mut self, modules: &[&str]) -> Self {
+    self.announce = modules.iter().map(|module| String::from(*module)).collect();
+    self
+  }
+  /// Whether GMCP was enabled as of the last event processed.
+  pub fn enabled(&self) -> bool {
+    self.enabled
+  }
+  /// Update the handshake from an event returned by `parser`.
+  ///
+  /// # Arguments
+  ///
+  /// `parser` - The parser the event came from, used to check whether GMCP is enabled and to send messages.
+  ///
+  /// `event` - The event. Negotiations of GMCP and GMCP subnegotiations are used, and anything else is ignored.
+  ///
+  /// # Returns
+  ///
+  /// `Option<TelnetEvents::DataSend>` - The `Core.Hello` and `Core.Supports.Set` messages to be processed when GMCP
+  /// has just been enabled, or None.
+  ///
+  /// # Notes
+  ///
+  /// The remote end's modules are forgotten when GMCP is disabled, and the messages are sent again if it is
+  /// re-enabled.
+  pub fn process(&mut self, parser: &mut Parser, event: &TelnetEvents) -> Option<TelnetEvents> {
+    match event {
+      TelnetEvents::Negotiation(neg) if neg.option == GMCP => (),
+      TelnetEvents::NegotiationComplete(done) if done.option == GMCP => (),
+      TelnetEvents::Subnegotiation(sub) => {
+        if let Some(msg) = sub.gmcp() {
+          self.receive(&msg);
+        }
+        return None;
+      }
+      _ => return None,
+    }
+    let opt = parser.options.get_option(GMCP);
+    let enabled = (opt.local && opt.local_state) || (opt.remote && opt.remote_state);
+    if !enabled {
+      self.enabled = false;
+      self.supports.clear();
+      return None;
+    }
+    if self.enabled {
+      return None;
+    }
+    self.enabled = true;
+    let mut buf = BytesMut::new();
+    if let Some(hello) = &self.hello {
+      if let Some(event) = parser.send_gmcp(&hello.name(), &hello.data) {
+        buf.put(Bytes::from(event));
+      }
+    }
+    if !self.announce.is_empty() {
+      if let Some(event) = parser.send_gmcp_json("Core.Supports.Set", &self.announce) {
+        buf.put(Bytes::from(event));
+      }
+    }
+    if buf.is_empty() {
+      None
+    } else {
+      Some(TelnetEvents::build_send(buf.freeze()))
+    }
+  }
+  /// Update the remote end's modules from a received message.
+  ///
+  /// # Returns
+  ///
+  /// `bool` - Whether the message was a `Core.Supports` message.
+  pub fn receive(&mut self, msg: &GmcpMessage) -> bool {
+    if !msg.package.eq_ignore_ascii_case("Core.Supports") {
+      return false;
+    }
+    let modules: Vec<(String, u32)> = match msg.json() {
+      Some(Value::Array(values)) => values
+        .iter()
+        .filter_map(Value::as_str)
+        .filter_map(parse_module)
+        .collect(),
+      _ => Vec::new(),
+    };
+    let message = msg.message.to_ascii_lowercase();
+    if message == "set" {
+      self.supports.clear();
+    }
+    for (name, version) in modules {
+      let existing = self
+        .supports
+        .iter()
+        .position(|(known, _)| known.eq_ignore_ascii_case(&name));
+      match (message.as_str(), existing) {
+        ("set" | "add", Some(index)) => self.supports[index].1 = version,
+        ("set" | "add", None) => self.supports.push((name, version)),
+        ("remove", Some(index)) => {
+          self.supports.remove(index);
+        }
+        _ => (),
+      }
+    }
+    true
+  }
+  /// Whether the remote end supports a module.
+  ///
+  /// # Arguments
+  ///
+  /// `module` - The module, such as `"Char"`, or a module and minimum version, such as `"Char 1"`. Names are not case
+  /// sensitive.
+  pub fn supports(&self, module: &str) -> bool {
+    let (name, version) = match parse_module(module) {
+      Some(module) => module,
+      None => return false,
+    };
+    self
+      .supports
+      .iter()
+      .any(|(known, known_version)| known.eq_ignore_ascii_case(&name) && *known_version >= version)
+  }
+  /// Get the modules the remote end supports, with their versions.
+  pub fn modules(&self) -> &[(String, u32)] {
+    &self.supports
+  }
+}
+
+/// Split a module such as `Char 1` into its name and version. A missing version is 0.
+#[cfg(feature = "json")]
+fn parse_module(module: &str) -> Option<(String, u32)> {
+  let mut parts = module.split_whitespace();
+  let name = parts.next()?;
+  let version = match parts.next() {
+    Some(version) => version.parse().ok()?,
+    None => 0,
+  };
+  Some((String::from(name), version))
+}
+
+#[cfg(test)]
+mod test_gmcp {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let msg = GmcpMessage::decode(b"Char.Items.List {\"location\": \"inv\"}").unwrap();
+    assert_eq!(msg.package, "Char.Items");
+    assert_eq!(msg.message, "List");
+    assert_eq!(msg.data, "{\"location\": \"inv\"}");
+    assert_eq!(msg.name(), "Char.Items.List");
+    assert_eq!(GmcpMessage::decode(&msg.encode()), Some(msg));
+    let hello = GmcpMessage::decode(b"Core.Ping").unwrap();
+    assert_eq!(hello, GmcpMessage::new("Core.Ping", ""));
+    assert_eq!(&hello.encode()[..], b"Core.Ping");
+    assert_eq!(GmcpMessage::decode(b"  "), None);
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn test_supports() {
+    let mut core = GmcpCore::new();
+    let set = GmcpMessage::new(
+      "Core.Supports.Set",
+      r#"["Char 1", "Char.Skills 1", "Room 2"]"#,
+    );
+    assert!(core.receive(&set));
+    assert!(core.supports("Char 1"));
+    assert!(core.supports("char"));
+    assert!(core.supports("Room 1"));
+    assert!(!core.supports("Room 3"));
+    assert!(core.receive(&GmcpMessage::new(
+      "Core.Supports.Add",
+      r#"["Room 3", "Comm 1"]"#
+    )));
+    assert!(core.supports("Room 3"));
+    assert!(core.receive(&GmcpMessage::new("Core.Supports.Remove", r#"["Char"]"#)));
+    assert!(!core.supports("Char"));
+    assert!(core.supports("Char.Skills 1"));
+    assert_eq!(core.modules().len(), 3);
+    assert!(core.receive(&set));
+    assert!(!core.supports("Comm"));
+    assert!(!core.receive(&GmcpMessage::new("Char.Vitals", "{}")));
+  }
+
+  #[cfg(feature = "json")]
+  #[derive(Debug, PartialEq, serde::Deserialize)]
+  struct Vitals {
+    hp: u32,
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn test_json() {
+    let msg = GmcpMessage::new("Char.Vitals", "{\"hp\": 100}");
+    let value = msg.json().unwrap();
+    assert_eq!(value["hp"], 100);
+    assert_eq!(
+      GmcpMessage::with_json("Char.Vitals", &value).data,
+      "{\"hp\":100}"
+    );
+    assert_eq!(GmcpMessage::new("Core.Ping", "").json(), Some(Value::Null));
+    assert_eq!(GmcpMessage::new("Core.Hello", "{").json(), None);
+    assert_eq!(msg.deserialize::<Vitals>(), Some(Vitals { hp: 100 }));
+    assert_eq!(
+      GmcpMessage::new("Char.Vitals", "{}").deserialize::<Vitals>(),
+      None
+    );
+  }
+}
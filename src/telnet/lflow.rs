@@ -0,0 +1,101 @@
+/// TOGGLE-FLOW-CONTROL (RFC 1372) subnegotiation commands.
+pub const OFF: u8 = 0;
+pub const ON: u8 = 1;
+pub const RESTART_ANY: u8 = 2;
+pub const RESTART_XON: u8 = 3;
+
+/// A TOGGLE-FLOW-CONTROL command, sent by the server to the client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LflowCommand {
+  /// Stop handling XON and XOFF locally, passing them to the server.
+  Off,
+  /// Handle XON and XOFF locally.
+  On,
+  /// Restart stopped output on any character.
+  RestartAny,
+  /// Restart stopped output only on XON.
+  RestartXon,
+}
+
+impl LflowCommand {
+  /// Decode the payload of a TOGGLE-FLOW-CONTROL subnegotiation (without the leading option byte).
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    match *data {
+      [OFF] => Some(LflowCommand::Off),
+      [ON] => Some(LflowCommand::On),
+      [RESTART_ANY] => Some(LflowCommand::RestartAny),
+      [RESTART_XON] => Some(LflowCommand::RestartXon),
+      _ => None,
+    }
+  }
+  /// Encode this command as the payload of a TOGGLE-FLOW-CONTROL subnegotiation.
+  pub fn encode(self) -> [u8; 1] {
+    [match self {
+      LflowCommand::Off => OFF,
+      LflowCommand::On => ON,
+      LflowCommand::RestartAny => RESTART_ANY,
+      LflowCommand::RestartXon => RESTART_XON,
+    }]
+  }
+}
+
+/// The local flow control a client has been asked for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LflowState {
+  /// Whether XON and XOFF are handled locally. Flow control starts out enabled.
+  pub enabled: bool,
+  /// Whether any character, rather than only XON, restarts stopped output, or None if the server has not said.
+  pub restart_any: Option<bool>,
+}
+
+impl Default for LflowState {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      restart_any: None,
+    }
+  }
+}
+
+impl LflowState {
+  /// Apply a command from the server.
+  pub fn apply(&mut self, command: LflowCommand) {
+    match command {
+      LflowCommand::Off => self.enabled = false,
+      LflowCommand::On => self.enabled = true,
+      LflowCommand::RestartAny => self.restart_any = Some(true),
+      LflowCommand::RestartXon => self.restart_any = Some(false),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test_lflow {
+  use super::*;
+
+  #[test]
+  fn test_commands() {
+    assert_eq!(
+      LflowCommand::decode(&[RESTART_XON]),
+      Some(LflowCommand::RestartXon)
+    );
+    assert_eq!(
+      LflowCommand::decode(&LflowCommand::Off.encode()),
+      Some(LflowCommand::Off)
+    );
+    assert_eq!(LflowCommand::decode(&[4]), None);
+    assert_eq!(LflowCommand::decode(&[ON, ON]), None);
+    let mut state = LflowState::default();
+    assert!(state.enabled);
+    state.apply(LflowCommand::Off);
+    state.apply(LflowCommand::RestartAny);
+    assert_eq!(
+      state,
+      LflowState {
+        enabled: false,
+        restart_any: Some(true)
+      }
+    );
+  }
+}
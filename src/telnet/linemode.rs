@@ -0,0 +1,381 @@
+use alloc::vec::Vec;
+use bytes::{BufMut, Bytes, BytesMut};
+use core::ops::{BitOr, BitOrAssign};
+
+/// LINEMODE (RFC 1184) subnegotiation types.
+pub const MODE: u8 = 1;
+pub const FORWARDMASK: u8 = 2;
+pub const SLC: u8 = 3;
+
+/// The functions that can be given a local character with `SLC`.
+pub mod slc {
+  pub const SYNCH: u8 = 1;
+  pub const BRK: u8 = 2;
+  pub const IP: u8 = 3;
+  pub const AO: u8 = 4;
+  pub const AYT: u8 = 5;
+  pub const EOR: u8 = 6;
+  pub const ABORT: u8 = 7;
+  pub const EOF: u8 = 8;
+  pub const SUSP: u8 = 9;
+  pub const EC: u8 = 10;
+  pub const EL: u8 = 11;
+  pub const EW: u8 = 12;
+  pub const RP: u8 = 13;
+  pub const LNEXT: u8 = 14;
+  pub const XON: u8 = 15;
+  pub const XOFF: u8 = 16;
+  pub const FORW1: u8 = 17;
+  pub const FORW2: u8 = 18;
+  pub const MCL: u8 = 19;
+  pub const MCR: u8 = 20;
+  pub const MCWL: u8 = 21;
+  pub const MCWR: u8 = 22;
+  pub const MCBOL: u8 = 23;
+  pub const MCEOL: u8 = 24;
+  pub const INSRT: u8 = 25;
+  pub const OVER: u8 = 26;
+  pub const ECR: u8 = 27;
+  pub const EWR: u8 = 28;
+  pub const EBOL: u8 = 29;
+  pub const EEOL: u8 = 30;
+  /// The highest function defined by RFC 1184.
+  pub const MAX: u8 = 30;
+
+  /// The support levels, held in the low bits of the flags.
+  pub const NOSUPPORT: u8 = 0;
+  pub const CANTCHANGE: u8 = 1;
+  pub const VALUE: u8 = 2;
+  pub const DEFAULT: u8 = 3;
+  pub const LEVELBITS: u8 = 3;
+  /// Further flags.
+  pub const FLUSHOUT: u8 = 1 << 5;
+  pub const FLUSHIN: u8 = 1 << 6;
+  pub const ACK: u8 = 1 << 7;
+}
+
+/// One triplet of a LINEMODE `SLC` (Set Local Characters) subnegotiation: the character that performs a function.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SlcEntry {
+  /// The function, such as `slc::IP`.
+  pub function: u8,
+  /// The support level, such as `slc::VALUE`, along with `slc::FLUSHIN`, `slc::FLUSHOUT` and `slc::ACK`.
+  pub flags: u8,
+  /// The character, or 255 (`_POSIX_VDISABLE`) for none.
+  pub value: u8,
+}
+
+impl SlcEntry {
+  pub fn new(function: u8, flags: u8, value: u8) -> Self {
+    Self {
+      function,
+      flags,
+      value,
+    }
+  }
+  /// Get the support level, such as `slc::VALUE`.
+  pub fn level(&self) -> u8 {
+    self.flags & slc::LEVELBITS
+  }
+  /// Whether this acknowledges a value sent by the other end.
+  pub fn is_ack(&self) -> bool {
+    self.flags & slc::ACK != 0
+  }
+  fn with_ack(mut self) -> Self {
+    self.flags |= slc::ACK;
+    self
+  }
+  /// Whether two entries agree on the level and character.
+  fn matches(&self, other: &SlcEntry) -> bool {
+    self.level() == other.level() && self.value == other.value
+  }
+}
+
+/// Decode the payload of a LINEMODE `SLC` subnegotiation into its triplets.
+///
+/// # Returns
+///
+/// `Option<Vec<SlcEntry>>` - The triplets, or None if the payload is not an `SLC` subnegotiation. A trailing partial
+/// triplet is ignored.
+pub fn decode_slc(data: &[u8]) -> Option<Vec<SlcEntry>> {
+  if data.first() != Some(&SLC) {
+    return None;
+  }
+  Some(
+    data[1..]
+      .chunks_exact(3)
+      .map(|triplet| SlcEntry::new(triplet[0], triplet[1], triplet[2]))
+      .collect(),
+  )
+}
+
+/// Encode triplets as the payload of a LINEMODE `SLC` subnegotiation. IAC bytes are not escaped, as
+/// `Parser::subnegotiation` does that when sending.
+pub fn encode_slc(entries: &[SlcEntry]) -> Bytes {
+  let mut buf = BytesMut::with_capacity(1 + 3 * entries.len());
+  buf.put_u8(SLC);
+  for entry in entries {
+    buf.put_u8(entry.function);
+    buf.put_u8(entry.flags);
+    buf.put_u8(entry.value);
+  }
+  buf.freeze()
+}
+
+/// The local characters of one end of a LINEMODE connection, with the defaults they return to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SlcTable {
+  current: [SlcEntry; slc::MAX as usize + 1],
+  defaults: [SlcEntry; slc::MAX as usize + 1],
+}
+
+impl Default for SlcTable {
+  /// The usual characters of a Unix terminal, such as `^C` for `IP` and `DEL` for `EC`.
+  fn default() -> Self {
+    let mut table = Self::new();
+    let defaults = [
+      (slc::IP, slc::VALUE | slc::FLUSHIN | slc::FLUSHOUT, 0x03),
+      (slc::AO, slc::VALUE | slc::FLUSHOUT, 0x0f),
+      (slc::AYT, slc::VALUE, 0x14),
+      (slc::ABORT, slc::VALUE | slc::FLUSHIN | slc::FLUSHOUT, 0x1c),
+      (slc::EOF, slc::VALUE, 0x04),
+      (slc::SUSP, slc::VALUE | slc::FLUSHIN, 0x1a),
+      (slc::EC, slc::VALUE, 0x7f),
+      (slc::EL, slc::VALUE, 0x15),
+      (slc::EW, slc::VALUE, 0x17),
+      (slc::RP, slc::VALUE, 0x12),
+      (slc::LNEXT, slc::VALUE, 0x16),
+      (slc::XON, slc::VALUE, 0x11),
+      (slc::XOFF, slc::VALUE, 0x13),
+    ];
+    for &(function, flags, value) in defaults.iter() {
+      table.set_default(SlcEntry::new(function, flags, value));
+    }
+    table
+  }
+}
+
+impl SlcTable {
+  /// Create a table with no functions supported.
+  pub fn new() -> Self {
+    let mut entries = [SlcEntry::default(); slc::MAX as usize + 1];
+    for (function, entry) in entries.iter_mut().enumerate() {
+      entry.function = function as u8;
+    }
+    Self {
+      current: entries,
+      defaults: entries,
+    }
+  }
+  /// Get the current entry for a function, or None if it is not defined by RFC 1184.
+  pub fn get(&self, function: u8) -> Option<SlcEntry> {
+    match function {
+      1..=slc::MAX => Some(self.current[function as usize]),
+      _ => None,
+    }
+  }
+  /// Set both the default and current entry for a function.
+  pub fn set_default(&mut self, entry: SlcEntry) {
+    if let 1..=slc::MAX = entry.function {
+      let entry = SlcEntry {
+        flags: entry.flags & !slc::ACK,
+        ..entry
+      };
+      self.defaults[entry.function as usize] = entry;
+      self.current[entry.function as usize] = entry;
+    }
+  }
+  /// Set the current entry for a function, leaving its default alone.
+  pub fn set(&mut self, entry: SlcEntry) {
+    if let 1..=slc::MAX = entry.function {
+      self.current[entry.function as usize] = SlcEntry {
+        flags: entry.flags & !slc::ACK,
+        ..entry
+      };
+    }
+  }
+  /// Get the current entries for every function, including unsupported ones.
+  pub fn entries(&self) -> &[SlcEntry] {
+    &self.current[1..]
+  }
+  /// Merge triplets received from the other end, following the rules of RFC 1184.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<SlcEntry>` - The triplets to send back, which is empty when the two ends agree.
+  ///
+  /// # Notes
+  ///
+  /// An acknowledgement, or a value matching our own, is not answered, so the two ends can't loop. A new value is
+  /// adopted and acknowledged unless our level is `CANTCHANGE`, in which case our value is sent back. A function
+  /// 0 request is answered with the whole table: the defaults for `DEFAULT`, or the current entries for `VALUE`.
+  pub fn merge(&mut self, received: &[SlcEntry]) -> Vec<SlcEntry> {
+    let mut replies = Vec::new();
+    for entry in received {
+      if entry.function == 0 {
+        if entry.level() == slc::DEFAULT {
+          self.current = self.defaults;
+        }
+        if let slc::DEFAULT | slc::VALUE = entry.level() {
+          replies.extend(
+            self
+              .entries()
+              .iter()
+              .filter(|e| e.level() != slc::NOSUPPORT),
+          );
+        }
+        continue;
+      }
+      if entry.function > slc::MAX {
+        if !entry.is_ack() && entry.level() != slc::NOSUPPORT {
+          replies.push(SlcEntry::new(entry.function, slc::NOSUPPORT, 0));
+        }
+        continue;
+      }
+      let current = &mut self.current[entry.function as usize];
+      if entry.is_ack() || entry.matches(current) {
+        continue;
+      }
+      match entry.level() {
+        slc::DEFAULT => {
+          *current = self.defaults[entry.function as usize];
+          replies.push(*current);
+        }
+        _ if current.level() == slc::CANTCHANGE => replies.push(*current),
+        _ => {
+          *current = SlcEntry {
+            flags: entry.flags & !slc::ACK,
+            ..*entry
+          };
+          replies.push(current.with_ack());
+        }
+      }
+    }
+    replies
+  }
+}
+
+/// The mode of a LINEMODE connection, as set by the `MODE` subnegotiation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LinemodeMode(pub u8);
+
+impl LinemodeMode {
+  /// The client edits lines locally, sending complete lines.
+  pub const EDIT: LinemodeMode = LinemodeMode(1);
+  /// The client turns interrupt and similar characters into telnet commands such as `IAC IP`.
+  pub const TRAPSIG: LinemodeMode = LinemodeMode(1 << 1);
+  /// Set by the client when it acknowledges a mode sent by the server.
+  pub const MODE_ACK: LinemodeMode = LinemodeMode(1 << 2);
+  /// The client expands tabs to spaces.
+  pub const SOFT_TAB: LinemodeMode = LinemodeMode(1 << 3);
+  /// The client echoes non-printable characters literally.
+  pub const LIT_ECHO: LinemodeMode = LinemodeMode(1 << 4);
+
+  /// Get the raw bits.
+  pub fn bits(self) -> u8 {
+    self.0
+  }
+  /// Whether every bit in `other` is set.
+  pub fn contains(self, other: LinemodeMode) -> bool {
+    self.0 & other.0 == other.0
+  }
+  pub fn insert(&mut self, other: LinemodeMode) {
+    self.0 |= other.0;
+  }
+  pub fn remove(&mut self, other: LinemodeMode) {
+    self.0 &= !other.0;
+  }
+  /// Get the mode without its `MODE_ACK` bit.
+  pub fn without_ack(mut self) -> Self {
+    self.remove(LinemodeMode::MODE_ACK);
+    self
+  }
+  /// Decode the payload of a LINEMODE `MODE` subnegotiation.
+  ///
+  /// # Returns
+  ///
+  /// `Option<LinemodeMode>` - The mode, or None if the payload is not a `MODE` subnegotiation.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    match *data {
+      [MODE, mask] => Some(LinemodeMode(mask)),
+      _ => None,
+    }
+  }
+  /// Encode the mode as the payload of a LINEMODE `MODE` subnegotiation.
+  pub fn encode(self) -> Bytes {
+    Bytes::copy_from_slice(&[MODE, self.0])
+  }
+}
+
+impl BitOr for LinemodeMode {
+  type Output = LinemodeMode;
+  fn bitor(self, other: LinemodeMode) -> LinemodeMode {
+    LinemodeMode(self.0 | other.0)
+  }
+}
+
+impl BitOrAssign for LinemodeMode {
+  fn bitor_assign(&mut self, other: LinemodeMode) {
+    self.insert(other);
+  }
+}
+
+#[cfg(test)]
+mod test_linemode {
+  use super::*;
+
+  #[test]
+  fn test_mode() {
+    let mode = LinemodeMode::EDIT | LinemodeMode::TRAPSIG;
+    assert_eq!(&mode.encode()[..], &[MODE, 3]);
+    assert_eq!(LinemodeMode::decode(&mode.encode()), Some(mode));
+    let ack = mode | LinemodeMode::MODE_ACK;
+    assert!(ack.contains(LinemodeMode::MODE_ACK | LinemodeMode::EDIT));
+    assert_eq!(ack.without_ack(), mode);
+    assert_eq!(LinemodeMode::decode(&[SLC, 3]), None);
+    assert_eq!(LinemodeMode::decode(&[MODE]), None);
+  }
+
+  #[test]
+  fn test_slc_codec() {
+    let entries = [
+      SlcEntry::new(slc::IP, slc::VALUE | slc::FLUSHIN, 3),
+      SlcEntry::new(slc::EC, slc::VALUE | slc::ACK, 127),
+    ];
+    let encoded = encode_slc(&entries);
+    assert_eq!(&encoded[..], &[SLC, 3, 66, 3, 10, 130, 127]);
+    assert_eq!(decode_slc(&encoded).unwrap(), entries);
+    assert!(decode_slc(&encoded).unwrap()[1].is_ack());
+    assert_eq!(decode_slc(&[SLC, 3, 2]).unwrap(), []);
+    assert_eq!(decode_slc(&[MODE, 3]), None);
+  }
+
+  #[test]
+  fn test_slc_merge() {
+    let mut table = SlcTable::default();
+    // A new value is adopted and acknowledged.
+    let replies = table.merge(&[SlcEntry::new(slc::EC, slc::VALUE, 8)]);
+    assert_eq!(replies, [SlcEntry::new(slc::EC, slc::VALUE | slc::ACK, 8)]);
+    assert_eq!(table.get(slc::EC).unwrap().value, 8);
+    // Acknowledgements and values we already have are not answered.
+    assert_eq!(table.merge(&replies), []);
+    assert_eq!(table.merge(&[SlcEntry::new(slc::EC, slc::VALUE, 8)]), []);
+    // DEFAULT goes back to our default.
+    let replies = table.merge(&[SlcEntry::new(slc::EC, slc::DEFAULT, 0)]);
+    assert_eq!(replies, [SlcEntry::new(slc::EC, slc::VALUE, 127)]);
+    // A function we can't change is refused with our own value.
+    table.set_default(SlcEntry::new(slc::EOF, slc::CANTCHANGE, 4));
+    let replies = table.merge(&[SlcEntry::new(slc::EOF, slc::VALUE, 1)]);
+    assert_eq!(replies, [SlcEntry::new(slc::EOF, slc::CANTCHANGE, 4)]);
+    // Unknown functions are not supported.
+    let replies = table.merge(&[SlcEntry::new(40, slc::VALUE, 1)]);
+    assert_eq!(replies, [SlcEntry::new(40, slc::NOSUPPORT, 0)]);
+    // Function 0 asks for the whole table.
+    let replies = table.merge(&[SlcEntry::new(0, slc::DEFAULT, 0)]);
+    assert_eq!(replies.len(), 13);
+    assert_eq!(
+      replies[0],
+      SlcEntry::new(slc::IP, slc::VALUE | slc::FLUSHIN | slc::FLUSHOUT, 3)
+    );
+  }
+}
@@ -0,0 +1,101 @@
+use crate::telnet::environ::{EnvironKind, EnvironVar};
+use crate::telnet::mtts::MttsFlags;
+use alloc::{string::String, vec::Vec};
+
+/// The variables defined by MNES, the MUD NEW-ENVIRON Standard. They are all sent as well-known `VAR`s.
+pub const CHARSET: &str = "CHARSET";
+pub const CLIENT_NAME: &str = "CLIENT_NAME";
+pub const CLIENT_VERSION: &str = "CLIENT_VERSION";
+pub const MTTS: &str = "MTTS";
+pub const TERMINAL_TYPE: &str = "TERMINAL_TYPE";
+pub const IPADDRESS: &str = "IPADDRESS";
+
+/// Every MNES variable, in the order they are requested.
+pub const VARIABLES: [&str; 6] = [
+  CHARSET,
+  CLIENT_NAME,
+  CLIENT_VERSION,
+  MTTS,
+  TERMINAL_TYPE,
+  IPADDRESS,
+];
+
+/// The MNES variables of one end of a connection. A variable that is not known is None.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Mnes {
+  /// The character set in use, such as `UTF-8`.
+  pub charset: Option<String>,
+  pub client_name: Option<String>,
+  pub client_version: Option<String>,
+  /// The same capabilities as reported in the MTTS TERMINAL-TYPE exchange.
+  pub mtts: Option<MttsFlags>,
+  pub terminal_type: Option<String>,
+  /// The address of the player, as seen by a proxy that connects on their behalf.
+  pub ipaddress: Option<String>,
+}
+
+impl Mnes {
+  /// Collect the MNES variables from NEW-ENVIRON variables, such as `Parser::remote_environ`.
+  pub fn from_vars(vars: &[EnvironVar]) -> Self {
+    let get = |name: &str| {
+      vars
+        .iter()
+        .find(|var| var.kind == EnvironKind::Var && var.name == name)
+        .and_then(|var| var.value.clone())
+    };
+    Self {
+      charset: get(CHARSET),
+      client_name: get(CLIENT_NAME),
+      client_version: get(CLIENT_VERSION),
+      mtts: get(MTTS).and_then(|bits| bits.trim().parse().ok().map(MttsFlags)),
+      terminal_type: get(TERMINAL_TYPE),
+      ipaddress: get(IPADDRESS),
+    }
+  }
+  /// Get the NEW-ENVIRON variables for every MNES variable that is known.
+  pub fn to_vars(&self) -> Vec<EnvironVar> {
+    let mtts = self.mtts.map(|flags| alloc::format!("{}", flags.bits()));
+    let values = [
+      (CHARSET, self.charset.as_deref()),
+      (CLIENT_NAME, self.client_name.as_deref()),
+      (CLIENT_VERSION, self.client_version.as_deref()),
+      (MTTS, mtts.as_deref()),
+      (TERMINAL_TYPE, self.terminal_type.as_deref()),
+      (IPADDRESS, self.ipaddress.as_deref()),
+    ];
+    values
+      .iter()
+      .filter_map(|(name, value)| {
+        value.map(|value| EnvironVar::new(EnvironKind::Var, name, Some(value)))
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod test_mnes {
+  use super::*;
+
+  #[test]
+  fn test_vars() {
+    let mnes = Mnes {
+      charset: Some(String::from("UTF-8")),
+      client_name: Some(String::from("MUDLET")),
+      mtts: Some(MttsFlags(137)),
+      ..Default::default()
+    };
+    let vars = mnes.to_vars();
+    assert_eq!(vars.len(), 3);
+    assert_eq!(
+      vars[2],
+      EnvironVar::new(EnvironKind::Var, MTTS, Some("137"))
+    );
+    assert_eq!(Mnes::from_vars(&vars), mnes);
+    let user = [EnvironVar::new(
+      EnvironKind::UserVar,
+      CHARSET,
+      Some("UTF-8"),
+    )];
+    assert_eq!(Mnes::from_vars(&user), Mnes::default());
+  }
+}
@@ -0,0 +1,424 @@
+#[cfg(feature = "json")]
+use crate::telnet::gmcp::GmcpMessage;
+#[cfg(feature = "json")]
+use alloc::string::ToString;
+use alloc::{string::String, vec::Vec};
+use bytes::{BufMut, Bytes, BytesMut};
+#[cfg(feature = "json")]
+use serde_json::Value;
+
+/// MSDP subnegotiation control codes.
+pub const VAR: u8 = 1;
+pub const VAL: u8 = 2;
+pub const TABLE_OPEN: u8 = 3;
+pub const TABLE_CLOSE: u8 = 4;
+pub const ARRAY_OPEN: u8 = 5;
+pub const ARRAY_CLOSE: u8 = 6;
+
+/// The commands a client sends as MSDP variables.
+pub const LIST: &str = "LIST";
+pub const REPORT: &str = "REPORT";
+pub const UNREPORT: &str = "UNREPORT";
+pub const RESET: &str = "RESET";
+pub const SEND: &str = "SEND";
+
+/// The lists a client can ask for with `LIST`.
+pub const COMMANDS: &str = "COMMANDS";
+pub const LISTS: &str = "LISTS";
+pub const CONFIGURABLE_VARIABLES: &str = "CONFIGURABLE_VARIABLES";
+pub const REPORTABLE_VARIABLES: &str = "REPORTABLE_VARIABLES";
+pub const REPORTED_VARIABLES: &str = "REPORTED_VARIABLES";
+pub const SENDABLE_VARIABLES: &str = "SENDABLE_VARIABLES";
+
+/// Nesting deeper than this is treated as malformed.
+const MAX_DEPTH: usize = 64;
+
+/// A value carried by an MSDP (MUD Server Data Protocol) subnegotiation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MsdpValue {
+  String(String),
+  Array(Vec<MsdpValue>),
+  /// The entries of a table, in the order they were sent.
+  Table(Vec<(String, MsdpValue)>),
+}
+
+impl MsdpValue {
+  /// Get the text of a string value.
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      MsdpValue::String(text) => Some(text),
+      _ => None,
+    }
+  }
+  /// Look up a key in a table.
+  pub fn get(&self, key: &str) -> Option<&MsdpValue> {
+    match self {
+      MsdpValue::Table(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+      _ => None,
+    }
+  }
+  /// Convert the value to JSON, for sending over GMCP. Strings stay strings, even if they hold a number, so the
+  /// conversion can be reversed exactly.
+  #[cfg(feature = "json")]
+  pub fn to_json(&self) -> Value {
+    match self {
+      MsdpValue::String(text) => Value::String(text.clone()),
+      MsdpValue::Array(values) => Value::Array(values.iter().map(Self::to_json).collect()),
+      MsdpValue::Table(entries) => Value::Object(
+        entries
+          .iter()
+          .map(|(key, value)| (key.clone(), value.to_json()))
+          .collect(),
+      ),
+    }
+  }
+  /// Convert a JSON value, such as a GMCP payload, to MSDP.
+  ///
+  /// # Notes
+  ///
+  /// MSDP only has strings, so numbers keep their JSON text, booleans become `1` or `0`, and null becomes an empty
+  /// string.
+  #[cfg(feature = "json")]
+  pub fn from_json(value: &Value) -> Self {
+    match value {
+      Value::Null => MsdpValue::String(String::new()),
+      Value::Bool(true) => MsdpValue::from("1"),
+      Value::Bool(false) => MsdpValue::from("0"),
+      Value::Number(number) => MsdpValue::String(number.to_string()),
+      Value::String(text) => MsdpValue::String(text.clone()),
+      Value::Array(values) => MsdpValue::Array(values.iter().map(Self::from_json).collect()),
+      Value::Object(entries) => MsdpValue::Table(
+        entries
+          .iter()
+          .map(|(key, value)| (key.clone(), Self::from_json(value)))
+          .collect(),
+      ),
+    }
+  }
+  fn write(&self, buf: &mut BytesMut) {
+    match self {
+      MsdpValue::String(text) => buf.put(text.as_bytes()),
+      MsdpValue::Array(values) => {
+        buf.put_u8(ARRAY_OPEN);
+        for value in values {
+          buf.put_u8(VAL);
+          value.write(buf);
+        }
+        buf.put_u8(ARRAY_CLOSE);
+      }
+      MsdpValue::Table(entries) => {
+        buf.put_u8(TABLE_OPEN);
+        write_vars(entries, buf);
+        buf.put_u8(TABLE_CLOSE);
+      }
+    }
+  }
+}
+
+impl From<&str> for MsdpValue {
+  fn from(text: &str) -> Self {
+    MsdpValue::String(String::from(text))
+  }
+}
+
+impl From<String> for MsdpValue {
+  fn from(text: String) -> Self {
+    MsdpValue::String(text)
+  }
+}
+
+fn write_vars(vars: &[(String, MsdpValue)], buf: &mut BytesMut) {
+  for (name, value) in vars {
+    buf.put_u8(VAR);
+    buf.put(name.as_bytes());
+    buf.put_u8(VAL);
+    value.write(buf);
+  }
+}
+
+/// Encode variables as the payload of an MSDP subnegotiation. IAC bytes are not escaped, as
+/// `Parser::subnegotiation` does that when sending.
+pub fn encode(vars: &[(String, MsdpValue)]) -> Bytes {
+  let mut buf = BytesMut::with_capacity(64);
+  write_vars(vars, &mut buf);
+  buf.freeze()
+}
+
+/// Decode the payload of an MSDP subnegotiation.
+///
+/// # Arguments
+///
+/// `data` - The payload, with IAC bytes already unescaped. Text is UTF-8, so invalid bytes are replaced.
+///
+/// # Returns
+///
+/// `Option<Vec<(String, MsdpValue)>>` - The variables, in the order they were sent, or None if the payload is
+/// malformed.
+///
+/// # Notes
+///
+/// A variable followed by several `VAL`s, as some clients send for `REPORT`, is decoded as an array.
+pub fn decode(data: &[u8]) -> Option<Vec<(String, MsdpValue)>> {
+  let mut reader = Reader { data, pos: 0 };
+  let vars = reader.vars(0)?;
+  if reader.pos == data.len() {
+    Some(vars)
+  } else {
+    None
+  }
+}
+
+/// Convert variables to the GMCP form of MSDP, a `MSDP` message with a JSON object payload, such as
+/// `MSDP {"HEALTH":"100"}`. This lets a server keep one set of MSDP variables and send them over whichever protocol
+/// the client enabled.
+#[cfg(feature = "json")]
+pub fn to_gmcp(vars: &[(String, MsdpValue)]) -> GmcpMessage {
+  GmcpMessage::with_json("MSDP", &MsdpValue::Table(vars.to_vec()).to_json())
+}
+
+/// Convert a GMCP `MSDP` message back to variables.
+///
+/// # Returns
+///
+/// `Option<Vec<(String, MsdpValue)>>` - The variables, or None if the message is not an `MSDP` message with a JSON
+/// object payload.
+#[cfg(feature = "json")]
+pub fn from_gmcp(msg: &GmcpMessage) -> Option<Vec<(String, MsdpValue)>> {
+  if !msg.package.eq_ignore_ascii_case("MSDP") || !msg.message.is_empty() {
+    return None;
+  }
+  match MsdpValue::from_json(&msg.json()?) {
+    MsdpValue::Table(entries) => Some(entries),
+    _ => None,
+  }
+}
+
+struct Reader<'a> {
+  data: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn peek(&self) -> Option<u8> {
+    self.data.get(self.pos).copied()
+  }
+  fn text(&mut self) -> String {
+    let start = self.pos;
+    while let Some(b) = self.peek() {
+      if (VAR..=ARRAY_CLOSE).contains(&b) {
+        break;
+      }
+      self.pos += 1;
+    }
+    String::from_utf8_lossy(&self.data[start..self.pos]).into_owned()
+  }
+  /// Read `VAR name VAL value` pairs until the end of the data or a table close.
+  fn vars(&mut self, depth: usize) -> Option<Vec<(String, MsdpValue)>> {
+    let mut vars = Vec::new();
+    while self.peek() == Some(VAR) {
+      self.pos += 1;
+      let name = self.text();
+      let mut values = Vec::new();
+      while self.peek() == Some(VAL) {
+        self.pos += 1;
+        values.push(self.value(depth)?);
+      }
+      let value = match values.len() {
+        0 => MsdpValue::String(String::new()),
+        1 => values.remove(0),
+        _ => MsdpValue::Array(values),
+      };
+      vars.push((name, value));
+    }
+    Some(vars)
+  }
+  fn value(&mut self, depth: usize) -> Option<MsdpValue> {
+    if depth > MAX_DEPTH {
+      return None;
+    }
+    match self.peek() {
+      Some(TABLE_OPEN) => {
+        self.pos += 1;
+        let entries = self.vars(depth + 1)?;
+        if self.peek() != Some(TABLE_CLOSE) {
+          return None;
+        }
+        self.pos += 1;
+        Some(MsdpValue::Table(entries))
+      }
+      Some(ARRAY_OPEN) => {
+        self.pos += 1;
+        let mut values = Vec::new();
+        while self.peek() == Some(VAL) {
+          self.pos += 1;
+          values.push(self.value(depth + 1)?);
+        }
+        if self.peek() != Some(ARRAY_CLOSE) {
+          return None;
+        }
+        self.pos += 1;
+        Some(MsdpValue::Array(values))
+      }
+      _ => Some(MsdpValue::String(self.text())),
+    }
+  }
+}
+
+/// A command sent by a client as an MSDP variable.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MsdpCommand {
+  /// Ask for one of the lists, such as `REPORTABLE_VARIABLES`.
+  List(String),
+  /// Ask for variables to be sent whenever they change.
+  Report(Vec<String>),
+  /// Stop reporting variables.
+  Unreport(Vec<String>),
+  /// Reset a list, such as `REPORTED_VARIABLES`.
+  Reset(String),
+  /// Ask for variables to be sent once.
+  Send(Vec<String>),
+}
+
+impl MsdpCommand {
+  /// Interpret a decoded variable as a command.
+  ///
+  /// # Returns
+  ///
+  /// `Option<MsdpCommand>` - The command, or None if the variable is not a command or its arguments are not strings.
+  pub fn from_var(name: &str, value: &MsdpValue) -> Option<Self> {
+    let args = || -> Option<Vec<String>> {
+      match value {
+        MsdpValue::String(text) => Some(alloc::vec![text.clone()]),
+        MsdpValue::Array(values) => values
+          .iter()
+          .map(|value| value.as_str().map(String::from))
+          .collect(),
+        MsdpValue::Table(_) => None,
+      }
+    };
+    match name {
+      LIST => Some(MsdpCommand::List(String::from(value.as_str()?))),
+      REPORT => Some(MsdpCommand::Report(args()?)),
+      UNREPORT => Some(MsdpCommand::Unreport(args()?)),
+      RESET => Some(MsdpCommand::Reset(String::from(value.as_str()?))),
+      SEND => Some(MsdpCommand::Send(args()?)),
+      _ => None,
+    }
+  }
+  /// Get the command as a variable to be encoded.
+  pub fn to_var(&self) -> (String, MsdpValue) {
+    let list = |names: &[String]| -> MsdpValue {
+      if names.len() == 1 {
+        MsdpValue::String(names[0].clone())
+      } else {
+        MsdpValue::Array(names.iter().cloned().map(MsdpValue::String).collect())
+      }
+    };
+    match self {
+      MsdpCommand::List(name) => (String::from(LIST), MsdpValue::from(name.as_str())),
+      MsdpCommand::Report(names) => (String::from(REPORT), list(names)),
+      MsdpCommand::Unreport(names) => (String::from(UNREPORT), list(names)),
+      MsdpCommand::Reset(name) => (String::from(RESET), MsdpValue::from(name.as_str())),
+      MsdpCommand::Send(names) => (String::from(SEND), list(names)),
+    }
+  }
+  /// Encode the command as the payload of an MSDP subnegotiation.
+  pub fn encode(&self) -> Bytes {
+    encode(&[self.to_var()])
+  }
+}
+
+#[cfg(test)]
+mod test_msdp {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let vars = alloc::vec![
+      (String::from("HEALTH"), MsdpValue::from("100")),
+      (
+        String::from("ROOM"),
+        MsdpValue::Table(alloc::vec![
+          (String::from("VNUM"), MsdpValue::from("6008")),
+          (
+            String::from("EXITS"),
+            MsdpValue::Array(alloc::vec![MsdpValue::from("n"), MsdpValue::from("e")])
+          ),
+        ])
+      ),
+    ];
+    let encoded = encode(&vars);
+    assert_eq!(
+      &encoded[..],
+      &b"\x01HEALTH\x02100\x01ROOM\x02\x03\x01VNUM\x026008\x01EXITS\x02\x05\x02n\x02e\x06\x04"[..]
+    );
+    let decoded = decode(&encoded).unwrap();
+    assert_eq!(decoded, vars);
+    assert_eq!(
+      decoded[1].1.get("VNUM").and_then(MsdpValue::as_str),
+      Some("6008")
+    );
+    assert_eq!(decode(b"\x01ROOM\x02\x03\x01VNUM\x026008"), None);
+    assert_eq!(decode(b"junk"), None);
+  }
+
+  #[test]
+  fn test_commands() {
+    let report = MsdpCommand::Report(alloc::vec![String::from("HEALTH"), String::from("MANA")]);
+    assert_eq!(
+      &report.encode()[..],
+      &b"\x01REPORT\x02\x05\x02HEALTH\x02MANA\x06"[..]
+    );
+    let vars = decode(b"\x01REPORT\x02HEALTH\x02MANA").unwrap();
+    assert_eq!(MsdpCommand::from_var(&vars[0].0, &vars[0].1), Some(report));
+    let list = MsdpCommand::List(String::from(REPORTABLE_VARIABLES));
+    let vars = decode(&list.encode()).unwrap();
+    assert_eq!(MsdpCommand::from_var(&vars[0].0, &vars[0].1), Some(list));
+    assert_eq!(MsdpCommand::from_var("HEALTH", &MsdpValue::from("1")), None);
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn test_gmcp() {
+    let vars = alloc::vec![
+      (String::from("HEALTH"), MsdpValue::from("100")),
+      (
+        String::from("AFFECTS"),
+        MsdpValue::Array(alloc::vec![MsdpValue::from("bless")])
+      ),
+    ];
+    let msg = to_gmcp(&vars);
+    assert_eq!(msg.name(), "MSDP");
+    assert_eq!(msg.data, r#"{"HEALTH":"100","AFFECTS":["bless"]}"#);
+    assert_eq!(from_gmcp(&msg), Some(vars));
+    let sent = GmcpMessage::new(
+      "MSDP",
+      r#"{"HEALTH": 100, "POISONED": false, "TARGET": null}"#,
+    );
+    assert_eq!(
+      from_gmcp(&sent).unwrap(),
+      [
+        (String::from("HEALTH"), MsdpValue::from("100")),
+        (String::from("POISONED"), MsdpValue::from("0")),
+        (String::from("TARGET"), MsdpValue::from("")),
+      ]
+    );
+    assert_eq!(from_gmcp(&GmcpMessage::new("MSDP", "[]")), None);
+    assert_eq!(from_gmcp(&GmcpMessage::new("Char.Vitals", "{}")), None);
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn test_json() {
+    let value: Value = serde_json::from_str(r#"{"ROOM":{"EXITS":["n","e"],"VNUM":6008}}"#).unwrap();
+    let msdp = MsdpValue::from_json(&value);
+    assert_eq!(
+      msdp.get("ROOM").and_then(|room| room.get("VNUM")),
+      Some(&MsdpValue::from("6008"))
+    );
+    assert_eq!(
+      msdp.to_json().to_string(),
+      r#"{"ROOM":{"EXITS":["n","e"],"VNUM":"6008"}}"#
+    );
+  }
+}
@@ -0,0 +1,284 @@
+use alloc::{string::String, vec::Vec};
+use bytes::Bytes;
+
+/// The start of each kind of MSP trigger.
+pub const SOUND: &[u8] = b"!!SOUND(";
+pub const MUSIC: &[u8] = b"!!MUSIC(";
+/// The longest trigger looked for. Anything longer is left as text.
+pub const MAX_TRIGGER_LEN: usize = 512;
+
+/// The kinds of MSP (MUD Sound Protocol) trigger.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MspKind {
+  /// A sound effect, from `!!SOUND(...)`.
+  Sound,
+  /// Background music, from `!!MUSIC(...)`.
+  Music,
+}
+
+/// A parsed MSP trigger, such as `!!SOUND(weather/thunder.wav V=80 L=2 T=weather)`.
+///
+/// A parameter that was not given, or could not be parsed, is None.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MspTrigger {
+  pub kind: MspKind,
+  /// The file to play, which may include a path or `*` wildcards. `Off` stops the current sound or music.
+  pub file: String,
+  /// `V`: the volume, from 0 to 100.
+  pub volume: Option<u8>,
+  /// `L`: how many times to play the file, or -1 to repeat until stopped.
+  pub repeats: Option<i32>,
+  /// `P`: the priority of a sound, from 0 to 100.
+  pub priority: Option<u8>,
+  /// `C`: whether music that is already playing carries on rather than restarting.
+  pub continue_music: Option<bool>,
+  /// `T`: the type of sound, such as `combat`.
+  pub sound_type: Option<String>,
+  /// `U`: the URL to download the file from.
+  pub url: Option<String>,
+}
+
+impl MspTrigger {
+  pub fn new(kind: MspKind, file: &str) -> Self {
+    Self {
+      kind,
+      file: String::from(file),
+      volume: None,
+      repeats: None,
+      priority: None,
+      continue_music: None,
+      sound_type: None,
+      url: None,
+    }
+  }
+  /// Whether the trigger stops the current sound or music.
+  pub fn is_off(&self) -> bool {
+    self.file.eq_ignore_ascii_case("Off")
+  }
+  /// Parse the parameters between the parentheses of a trigger.
+  ///
+  /// # Returns
+  ///
+  /// `Option<MspTrigger>` - The trigger, or None if there is no file name.
+  pub fn parse(kind: MspKind, params: &str) -> Option<Self> {
+    let mut words = params.split_whitespace();
+    let mut trigger = Self::new(kind, words.next()?);
+    for word in words {
+      let (key, value) = match word.find('=') {
+        Some(index) => (&word[..index], &word[index + 1..]),
+        None => continue,
+      };
+      match key.to_ascii_uppercase().as_str() {
+        "V" => trigger.volume = value.parse().ok(),
+        "L" => trigger.repeats = value.parse().ok(),
+        "P" => trigger.priority = value.parse().ok(),
+        "C" => trigger.continue_music = value.parse::<u8>().ok().map(|c| c != 0),
+        "T" => trigger.sound_type = Some(String::from(value)),
+        "U" => trigger.url = Some(String::from(value)),
+        _ => (),
+      }
+    }
+    Some(trigger)
+  }
+  /// Format the trigger as it is sent by a server.
+  pub fn to_trigger(&self) -> String {
+    let mut out = String::from(match self.kind {
+      MspKind::Sound => "!!SOUND(",
+      MspKind::Music => "!!MUSIC(",
+    });
+    out.push_str(&self.file);
+    let mut param = |key: &str, value: Option<String>| {
+      if let Some(value) = value {
+        out.push(' ');
+        out.push_str(key);
+        out.push('=');
+        out.push_str(&value);
+      }
+    };
+    param("V", self.volume.map(|v| alloc::format!("{}", v)));
+    param("L", self.repeats.map(|l| alloc::format!("{}", l)));
+    param("P", self.priority.map(|p| alloc::format!("{}", p)));
+    param(
+      "C",
+      self
+        .continue_music
+        .map(|c| String::from(if c { "1" } else { "0" })),
+    );
+    param("T", self.sound_type.clone());
+    param("U", self.url.clone());
+    out.push(')');
+    out
+  }
+}
+
+/// A piece of received data, split up by `MspScanner`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MspSegment {
+  /// Text between triggers.
+  Data(Bytes),
+  Trigger(MspTrigger),
+}
+
+/// Finds MSP triggers in received data, removing them from the text.
+#[derive(Clone, Debug, Default)]
+pub struct MspScanner {
+  /// The start of a trigger that was split across calls to `scan`.
+  partial: Vec<u8>,
+}
+
+impl MspScanner {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  /// Scan received data for triggers.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<MspSegment>` - The text and triggers, in the order they were received.
+  ///
+  /// # Notes
+  ///
+  /// Data ending in what could be the start of a trigger, from `!!` on, is held back until the next call, or
+  /// `take_partial`.
+  pub fn scan(&mut self, data: &[u8]) -> Vec<MspSegment> {
+    let mut input = core::mem::take(&mut self.partial);
+    input.extend_from_slice(data);
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut index = 0;
+    while index < input.len() {
+      if input[index] != b'!' {
+        index += 1;
+        continue;
+      }
+      match trigger(&input[index..]) {
+        Found::Trigger(len, trigger) => {
+          push_data(&mut out, &input[start..index]);
+          out.push(MspSegment::Trigger(trigger));
+          index += len;
+          start = index;
+        }
+        Found::Partial => {
+          push_data(&mut out, &input[start..index]);
+          self.partial.extend_from_slice(&input[index..]);
+          return out;
+        }
+        Found::None => index += 1,
+      }
+    }
+    push_data(&mut out, &input[start..]);
+    out
+  }
+  /// Take the data held back as the possible start of a trigger.
+  pub fn take_partial(&mut self) -> Option<Bytes> {
+    if self.partial.is_empty() {
+      None
+    } else {
+      Some(Bytes::from(core::mem::take(&mut self.partial)))
+    }
+  }
+}
+
+fn push_data(out: &mut Vec<MspSegment>, data: &[u8]) {
+  if !data.is_empty() {
+    out.push(MspSegment::Data(Bytes::copy_from_slice(data)));
+  }
+}
+
+enum Found {
+  /// A trigger, with its length.
+  Trigger(usize, MspTrigger),
+  /// The start of what could be a trigger.
+  Partial,
+  None,
+}
+
+/// Match a trigger at the start of `data`, which begins with `!`.
+fn trigger(data: &[u8]) -> Found {
+  let kind = if data.starts_with(SOUND) {
+    MspKind::Sound
+  } else if data.starts_with(MUSIC) {
+    MspKind::Music
+  } else if data.len() > 1 && (SOUND.starts_with(data) || MUSIC.starts_with(data)) {
+    // A lone `!` at the end of the data is far more likely to end a sentence than to start a trigger.
+    return Found::Partial;
+  } else {
+    return Found::None;
+  };
+  let search = &data[..data.len().min(MAX_TRIGGER_LEN)];
+  match search.iter().position(|&b| b == b')') {
+    Some(end) => {
+      let params = String::from_utf8_lossy(&data[SOUND.len()..end]);
+      match MspTrigger::parse(kind, &params) {
+        Some(trigger) => Found::Trigger(end + 1, trigger),
+        None => Found::None,
+      }
+    }
+    None if data.len() < MAX_TRIGGER_LEN => Found::Partial,
+    None => Found::None,
+  }
+}
+
+#[cfg(test)]
+mod test_msp {
+  use super::*;
+
+  #[test]
+  fn test_parse() {
+    let trigger =
+      MspTrigger::parse(MspKind::Sound, "weather/thunder.wav V=80 l=2 T=weather").unwrap();
+    assert_eq!(trigger.file, "weather/thunder.wav");
+    assert_eq!(trigger.volume, Some(80));
+    assert_eq!(trigger.repeats, Some(2));
+    assert_eq!(trigger.priority, None);
+    assert_eq!(trigger.sound_type.as_deref(), Some("weather"));
+    assert_eq!(
+      trigger.to_trigger(),
+      "!!SOUND(weather/thunder.wav V=80 L=2 T=weather)"
+    );
+    let off = MspTrigger::parse(MspKind::Music, "Off").unwrap();
+    assert!(off.is_off());
+    assert_eq!(MspTrigger::parse(MspKind::Music, " "), None);
+  }
+
+  #[test]
+  fn test_scan() {
+    let mut scanner = MspScanner::new();
+    let out = scanner.scan(b"Boom! !!SOUND(boom.wav V=50)It hits.\r\n!!MUS");
+    assert_eq!(
+      out,
+      [
+        MspSegment::Data(Bytes::from_static(b"Boom! ")),
+        MspSegment::Trigger(MspTrigger {
+          volume: Some(50),
+          ..MspTrigger::new(MspKind::Sound, "boom.wav")
+        }),
+        MspSegment::Data(Bytes::from_static(b"It hits.\r\n")),
+      ]
+    );
+    let out = scanner.scan(b"IC(song.mid L=-1 C=1)");
+    assert_eq!(
+      out,
+      [MspSegment::Trigger(MspTrigger {
+        repeats: Some(-1),
+        continue_music: Some(true),
+        ..MspTrigger::new(MspKind::Music, "song.mid")
+      })]
+    );
+    assert_eq!(
+      scanner.scan(b"Hi!"),
+      [MspSegment::Data(Bytes::from_static(b"Hi!"))]
+    );
+    assert_eq!(scanner.scan(b"!!SOUND(x"), []);
+    assert_eq!(
+      scanner.take_partial(),
+      Some(Bytes::from_static(b"!!SOUND(x"))
+    );
+    assert_eq!(
+      scanner.scan(b"!!SOUND()"),
+      [MspSegment::Data(Bytes::from_static(b"!!SOUND()"))]
+    );
+  }
+}
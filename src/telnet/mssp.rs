@@ -0,0 +1,143 @@
+use alloc::{string::String, vec::Vec};
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// MSSP subnegotiation type codes.
+pub const VAR: u8 = 1;
+pub const VAL: u8 = 2;
+
+/// Well-known MSSP variable names.
+pub const NAME: &str = "NAME";
+pub const PLAYERS: &str = "PLAYERS";
+pub const UPTIME: &str = "UPTIME";
+pub const CODEBASE: &str = "CODEBASE";
+pub const CONTACT: &str = "CONTACT";
+pub const CRAWL_DELAY: &str = "CRAWL DELAY";
+pub const CREATED: &str = "CREATED";
+pub const HOSTNAME: &str = "HOSTNAME";
+pub const ICON: &str = "ICON";
+pub const IP: &str = "IP";
+pub const IPV6: &str = "IPV6";
+pub const LANGUAGE: &str = "LANGUAGE";
+pub const LOCATION: &str = "LOCATION";
+pub const MINIMUM_AGE: &str = "MINIMUM AGE";
+pub const PORT: &str = "PORT";
+pub const REFERRAL: &str = "REFERRAL";
+pub const SSL: &str = "SSL";
+pub const WEBSITE: &str = "WEBSITE";
+pub const FAMILY: &str = "FAMILY";
+pub const GENRE: &str = "GENRE";
+pub const GAMEPLAY: &str = "GAMEPLAY";
+pub const STATUS: &str = "STATUS";
+pub const GAMESYSTEM: &str = "GAMESYSTEM";
+pub const INTERMUD: &str = "INTERMUD";
+pub const SUBGENRE: &str = "SUBGENRE";
+
+/// The variables of an MSSP (MUD Server Status Protocol) subnegotiation, in the order they were sent.
+///
+/// A variable can have several values, such as one `PORT` for each port the server listens on.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MsspData(pub Vec<(String, Vec<String>)>);
+
+impl MsspData {
+  /// Get the values of a variable, or None if it was not sent.
+  pub fn get(&self, name: &str) -> Option<&[String]> {
+    self
+      .0
+      .iter()
+      .find(|(var, _)| var == name)
+      .map(|(_, values)| &values[..])
+  }
+  /// Get the first value of a variable, or None if it was not sent or has no values.
+  pub fn first(&self, name: &str) -> Option<&str> {
+    self.get(name)?.first().map(String::as_str)
+  }
+  /// Add a value for a variable, after any values it already has.
+  pub fn push(&mut self, name: &str, value: &str) {
+    match self.0.iter_mut().find(|(var, _)| var == name) {
+      Some((_, values)) => values.push(String::from(value)),
+      None => self
+        .0
+        .push((String::from(name), alloc::vec![String::from(value)])),
+    }
+  }
+  /// Decode the payload of an MSSP subnegotiation.
+  ///
+  /// The payload is expected to have already had its IAC bytes unescaped. A variable sent more than once has its
+  /// values merged. Returns None if the payload does not start with `VAR`.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    if data.first() != Some(&VAR) {
+      return None;
+    }
+    let mut mssp = MsspData::default();
+    for var in data[1..].split(|&b| b == VAR) {
+      let mut parts = var.split(|&b| b == VAL);
+      let name = String::from_utf8_lossy(parts.next().unwrap_or_default());
+      let values: Vec<String> = parts
+        .map(|value| String::from_utf8_lossy(value).into_owned())
+        .collect();
+      match mssp.0.iter_mut().find(|(var, _)| *var == name) {
+        Some((_, existing)) => existing.extend(values),
+        None => mssp.0.push((name.into_owned(), values)),
+      }
+    }
+    Some(mssp)
+  }
+  /// Encode the variables as the payload of an MSSP subnegotiation.
+  pub fn encode(&self) -> Bytes {
+    let mut buf = BytesMut::with_capacity(64);
+    for (name, values) in self.0.iter() {
+      buf.put_u8(VAR);
+      buf.put(name.as_bytes());
+      for value in values {
+        buf.put_u8(VAL);
+        buf.put(value.as_bytes());
+      }
+    }
+    buf.freeze()
+  }
+}
+
+impl<N: AsRef<str>, V: AsRef<str>> core::iter::FromIterator<(N, V)> for MsspData {
+  /// Build MSSP data from `(name, value)` pairs. Repeating a name adds another value to it.
+  fn from_iter<I: IntoIterator<Item = (N, V)>>(iter: I) -> Self {
+    let mut mssp = MsspData::default();
+    for (name, value) in iter {
+      mssp.push(name.as_ref(), value.as_ref());
+    }
+    mssp
+  }
+}
+
+#[cfg(test)]
+mod test_mssp {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let mssp: MsspData = alloc::vec![
+      (NAME, "Test MUD"),
+      (PLAYERS, "3"),
+      (PORT, "4000"),
+      (PORT, "4001")
+    ]
+    .into_iter()
+    .collect();
+    let encoded = mssp.encode();
+    assert_eq!(
+      &encoded[..],
+      &b"\x01NAME\x02Test MUD\x01PLAYERS\x023\x01PORT\x024000\x024001"[..]
+    );
+    assert_eq!(MsspData::decode(&encoded), Some(mssp.clone()));
+    assert_eq!(mssp.get(PORT).unwrap(), ["4000", "4001"]);
+    assert_eq!(mssp.first(NAME), Some("Test MUD"));
+    assert_eq!(mssp.first(UPTIME), None);
+  }
+
+  #[test]
+  fn test_repeated_var() {
+    let mssp = MsspData::decode(b"\x01PORT\x024000\x01PORT\x024001\x01EMPTY").unwrap();
+    assert_eq!(mssp.get(PORT).unwrap(), ["4000", "4001"]);
+    assert_eq!(mssp.get("EMPTY").unwrap().len(), 0);
+    assert_eq!(MsspData::decode(b"\x02oops"), None);
+  }
+}
@@ -0,0 +1,130 @@
+use alloc::{format, string::String, vec::Vec};
+use core::ops::{BitOr, BitOrAssign};
+
+/// MTTS capability bits, reported by a client in the third round of TERMINAL-TYPE as `MTTS <bits>`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MttsFlags(pub u32);
+
+impl MttsFlags {
+  pub const ANSI: MttsFlags = MttsFlags(1);
+  pub const VT100: MttsFlags = MttsFlags(1 << 1);
+  pub const UTF8: MttsFlags = MttsFlags(1 << 2);
+  pub const COLORS_256: MttsFlags = MttsFlags(1 << 3);
+  pub const MOUSE_TRACKING: MttsFlags = MttsFlags(1 << 4);
+  pub const OSC_COLOR_PALETTE: MttsFlags = MttsFlags(1 << 5);
+  pub const SCREEN_READER: MttsFlags = MttsFlags(1 << 6);
+  pub const PROXY: MttsFlags = MttsFlags(1 << 7);
+  pub const TRUECOLOR: MttsFlags = MttsFlags(1 << 8);
+  pub const MNES: MttsFlags = MttsFlags(1 << 9);
+  pub const MSLP: MttsFlags = MttsFlags(1 << 10);
+  pub const SSL: MttsFlags = MttsFlags(1 << 11);
+
+  /// Get the raw bits.
+  pub fn bits(self) -> u32 {
+    self.0
+  }
+  /// Whether every bit in `other` is set.
+  pub fn contains(self, other: MttsFlags) -> bool {
+    self.0 & other.0 == other.0
+  }
+  pub fn insert(&mut self, other: MttsFlags) {
+    self.0 |= other.0;
+  }
+  pub fn remove(&mut self, other: MttsFlags) {
+    self.0 &= !other.0;
+  }
+  /// Parse a third round terminal type, such as `MTTS 137`.
+  ///
+  /// # Returns
+  ///
+  /// `Option<MttsFlags>` - The flags, or None if the terminal type is not an MTTS report.
+  pub fn from_terminal_type(name: &str) -> Option<Self> {
+    let bits = name.strip_prefix("MTTS ")?;
+    bits.trim().parse().ok().map(MttsFlags)
+  }
+  /// Format the flags as a terminal type, such as `MTTS 137`.
+  pub fn to_terminal_type(self) -> String {
+    format!("MTTS {}", self.0)
+  }
+}
+
+impl BitOr for MttsFlags {
+  type Output = MttsFlags;
+  fn bitor(self, other: MttsFlags) -> MttsFlags {
+    MttsFlags(self.0 | other.0)
+  }
+}
+
+impl BitOrAssign for MttsFlags {
+  fn bitor_assign(&mut self, other: MttsFlags) {
+    self.insert(other);
+  }
+}
+
+/// A client's answers to the three rounds of an MTTS TERMINAL-TYPE exchange.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Mtts {
+  /// The client name, and often version, such as `MUDLET`.
+  pub client: String,
+  /// The terminal type, such as `XTERM-256COLOR`.
+  pub terminal: String,
+  pub flags: MttsFlags,
+}
+
+impl Mtts {
+  pub fn new(client: &str, terminal: &str, flags: MttsFlags) -> Self {
+    Self {
+      client: String::from(client),
+      terminal: String::from(terminal),
+      flags,
+    }
+  }
+  /// Interpret the terminal types reported by a client, such as from `Parser::terminal_types`.
+  ///
+  /// # Returns
+  ///
+  /// `Option<Mtts>` - The MTTS report, or None if the third terminal type is not an `MTTS` report.
+  pub fn from_terminal_types<T: AsRef<str>>(types: &[T]) -> Option<Self> {
+    match types {
+      [client, terminal, flags, ..] => Some(Self {
+        client: String::from(client.as_ref()),
+        terminal: String::from(terminal.as_ref()),
+        flags: MttsFlags::from_terminal_type(flags.as_ref())?,
+      }),
+      _ => None,
+    }
+  }
+  /// Get the terminal types a client reports, in order, for `Parser::set_terminal_types`.
+  pub fn to_terminal_types(&self) -> Vec<String> {
+    alloc::vec![
+      self.client.clone(),
+      self.terminal.clone(),
+      self.flags.to_terminal_type(),
+    ]
+  }
+}
+
+#[cfg(test)]
+mod test_mtts {
+  use super::*;
+
+  #[test]
+  fn test_flags() {
+    let flags = MttsFlags::ANSI | MttsFlags::UTF8 | MttsFlags::TRUECOLOR;
+    assert_eq!(flags.bits(), 261);
+    assert!(flags.contains(MttsFlags::ANSI | MttsFlags::UTF8));
+    assert!(!flags.contains(MttsFlags::SCREEN_READER));
+    assert_eq!(flags.to_terminal_type(), "MTTS 261");
+    assert_eq!(MttsFlags::from_terminal_type("MTTS 261"), Some(flags));
+    assert_eq!(MttsFlags::from_terminal_type("XTERM"), None);
+  }
+
+  #[test]
+  fn test_terminal_types() {
+    let mtts = Mtts::new("MUDLET", "XTERM-256COLOR", MttsFlags(2317));
+    let types = mtts.to_terminal_types();
+    assert_eq!(types, ["MUDLET", "XTERM-256COLOR", "MTTS 2317"]);
+    assert_eq!(Mtts::from_terminal_types(&types), Some(mtts));
+    assert_eq!(Mtts::from_terminal_types(&["MUDLET", "XTERM"]), None);
+  }
+}
@@ -0,0 +1,233 @@
+use alloc::vec::Vec;
+use bytes::Bytes;
+
+const ESC: u8 = 0x1b;
+/// The longest line tag, `ESC [ 99 z`, is 5 bytes.
+const MAX_TAG_LEN: usize = 5;
+
+/// The mode of a line of MXP (MUD eXtension Protocol) text, set by an `ESC [ n z` line tag.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MxpMode {
+  /// Only the safe, open tags are allowed.
+  #[default]
+  Open,
+  /// Every tag is allowed.
+  Secure,
+  /// No tags are parsed, and `<` is plain text.
+  Locked,
+  /// Only the next tag is secure, after which the line is open.
+  TempSecure,
+  /// A line whose contents are given meaning by the server, from 10 to 99, such as 10 for a room name.
+  User(u8),
+}
+
+impl MxpMode {
+  /// Whether tags that are not open are allowed.
+  pub fn is_secure(self) -> bool {
+    matches!(
+      self,
+      MxpMode::Secure | MxpMode::TempSecure | MxpMode::User(_)
+    )
+  }
+}
+
+/// A line tag, sent as `ESC [ n z`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MxpLineTag {
+  /// Change the mode of the current line, as with modes 0 to 2, 4 and 10 to 99.
+  Line(MxpMode),
+  /// Close all open tags and go back to open mode, as with mode 3.
+  Reset,
+  /// Change the mode of the current and every following line, as with modes 5 to 7.
+  Lock(MxpMode),
+}
+
+impl MxpLineTag {
+  /// Interpret the number of a line tag.
+  pub fn from_code(code: u8) -> Option<Self> {
+    match code {
+      0 => Some(MxpLineTag::Line(MxpMode::Open)),
+      1 => Some(MxpLineTag::Line(MxpMode::Secure)),
+      2 => Some(MxpLineTag::Line(MxpMode::Locked)),
+      3 => Some(MxpLineTag::Reset),
+      4 => Some(MxpLineTag::Line(MxpMode::TempSecure)),
+      5 => Some(MxpLineTag::Lock(MxpMode::Open)),
+      6 => Some(MxpLineTag::Lock(MxpMode::Secure)),
+      7 => Some(MxpLineTag::Lock(MxpMode::Locked)),
+      10..=99 => Some(MxpLineTag::Line(MxpMode::User(code))),
+      _ => None,
+    }
+  }
+}
+
+/// A piece of text found by `MxpScanner`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MxpText {
+  /// The text, with line tags removed. A piece ends at a line tag or just after a newline.
+  pub data: Bytes,
+  pub mode: MxpMode,
+}
+
+/// Removes MXP line tags from received data, tracking the mode of each line.
+///
+/// Tags within the text, such as `<SEND>`, are left in place for a full MXP parser, which can use the mode of the
+/// text to decide which are allowed. Other escape sequences, such as colors, are left untouched.
+#[derive(Clone, Debug, Default)]
+pub struct MxpScanner {
+  /// The mode each line starts in, as set by a lock.
+  default: MxpMode,
+  /// The mode of the current line.
+  mode: MxpMode,
+  /// The start of a line tag that was split across calls to `scan`.
+  partial: Vec<u8>,
+}
+
+impl MxpScanner {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  /// Get the mode of the current line.
+  pub fn mode(&self) -> MxpMode {
+    self.mode
+  }
+  /// Scan received data, such as the contents of a `DataReceive` event.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<MxpText>` - The text, split wherever the mode may have changed.
+  ///
+  /// # Notes
+  ///
+  /// Data ending in what could be the start of a line tag is held back until the next call.
+  pub fn scan(&mut self, data: &[u8]) -> Vec<MxpText> {
+    let mut input = core::mem::take(&mut self.partial);
+    input.extend_from_slice(data);
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut index = 0;
+    while index < input.len() {
+      match input[index] {
+        b'\n' => {
+          index += 1;
+          self.push(&mut out, &input[start..index]);
+          start = index;
+          self.mode = self.default;
+        }
+        ESC => match line_tag(&input[index..]) {
+          Tag::Complete(len, code) => {
+            self.push(&mut out, &input[start..index]);
+            index += len;
+            start = index;
+            match MxpLineTag::from_code(code) {
+              Some(MxpLineTag::Line(mode)) => self.mode = mode,
+              Some(MxpLineTag::Reset) => {
+                self.default = MxpMode::Open;
+                self.mode = MxpMode::Open;
+              }
+              Some(MxpLineTag::Lock(mode)) => {
+                self.default = mode;
+                self.mode = mode;
+              }
+              None => (),
+            }
+          }
+          Tag::Partial => {
+            self.partial.extend_from_slice(&input[index..]);
+            self.push(&mut out, &input[start..index]);
+            return out;
+          }
+          Tag::None => index += 1,
+        },
+        _ => index += 1,
+      }
+    }
+    self.push(&mut out, &input[start..]);
+    out
+  }
+  fn push(&self, out: &mut Vec<MxpText>, data: &[u8]) {
+    if !data.is_empty() {
+      out.push(MxpText {
+        data: Bytes::copy_from_slice(data),
+        mode: self.mode,
+      });
+    }
+  }
+}
+
+enum Tag {
+  /// A line tag, with its length and number.
+  Complete(usize, u8),
+  /// The start of what could be a line tag.
+  Partial,
+  None,
+}
+
+/// Match a line tag at the start of `data`, which begins with ESC.
+fn line_tag(data: &[u8]) -> Tag {
+  match data.get(1) {
+    None => return Tag::Partial,
+    Some(b'[') => (),
+    Some(_) => return Tag::None,
+  }
+  let mut code: u8 = 0;
+  for (index, &b) in data.iter().enumerate().take(MAX_TAG_LEN).skip(2) {
+    match b {
+      b'0'..=b'9' if index < 4 => code = code * 10 + (b - b'0'),
+      b'z' if index > 2 => return Tag::Complete(index + 1, code),
+      _ => return Tag::None,
+    }
+  }
+  if data.len() < MAX_TAG_LEN {
+    Tag::Partial
+  } else {
+    Tag::None
+  }
+}
+
+#[cfg(test)]
+mod test_mxp {
+  use super::*;
+
+  fn text(data: &'static [u8], mode: MxpMode) -> MxpText {
+    MxpText {
+      data: Bytes::from_static(data),
+      mode,
+    }
+  }
+
+  #[test]
+  fn test_line_modes() {
+    let mut scanner = MxpScanner::new();
+    let out = scanner.scan(b"\x1b[1z<B>hi</B>\r\nplain \x1b[31mred\x1b[10zRoom\n");
+    assert_eq!(
+      out,
+      [
+        text(b"<B>hi</B>\r\n", MxpMode::Secure),
+        text(b"plain \x1b[31mred", MxpMode::Open),
+        text(b"Room\n", MxpMode::User(10)),
+      ]
+    );
+    assert_eq!(scanner.mode(), MxpMode::Open);
+    let out = scanner.scan(b"\x1b[7zone\ntwo\x1b[3zthree");
+    assert_eq!(
+      out,
+      [
+        text(b"one\n", MxpMode::Locked),
+        text(b"two", MxpMode::Locked),
+        text(b"three", MxpMode::Open),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_split_tag() {
+    let mut scanner = MxpScanner::new();
+    assert_eq!(scanner.scan(b"a\x1b["), [text(b"a", MxpMode::Open)]);
+    assert_eq!(scanner.scan(b"1"), []);
+    assert_eq!(scanner.scan(b"zb"), [text(b"b", MxpMode::Secure)]);
+    assert_eq!(
+      scanner.scan(b"\x1b[123z"),
+      [text(b"\x1b[123z", MxpMode::Secure)]
+    );
+  }
+}
@@ -0,0 +1,80 @@
+use bytes::Bytes;
+
+/// A window size, as reported by a NAWS (RFC 1073) subnegotiation.
+///
+/// A width or height of 0 means that dimension is unknown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Naws {
+  pub width: u16,
+  pub height: u16,
+}
+
+impl Naws {
+  pub fn new(width: u16, height: u16) -> Self {
+    Self { width, height }
+  }
+  /// Decode the payload of a NAWS subnegotiation.
+  ///
+  /// # Arguments
+  ///
+  /// `data` - The payload, either with IAC bytes already collapsed (as in a received `TelnetSubnegotiation`) or
+  /// still escaped as on the wire, where a 255 in the size is sent twice.
+  ///
+  /// # Returns
+  ///
+  /// `Option<Naws>` - The window size, or None if the payload is not two 16-bit values.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    let mut bytes = [0u8; 4];
+    let mut len = 0;
+    let mut index = 0;
+    while index < data.len() {
+      if len == bytes.len() {
+        return None;
+      }
+      bytes[len] = data[index];
+      len += 1;
+      // Only skip the second byte of an escaped IAC when the payload is too long to be unescaped.
+      if data[index] == 255 && data.len() > 4 && data.get(index + 1) == Some(&255) {
+        index += 1;
+      }
+      index += 1;
+    }
+    if len != bytes.len() {
+      return None;
+    }
+    Some(Self {
+      width: u16::from_be_bytes([bytes[0], bytes[1]]),
+      height: u16::from_be_bytes([bytes[2], bytes[3]]),
+    })
+  }
+  /// Encode the window size as the payload of a NAWS subnegotiation. IAC bytes are not escaped, as
+  /// `Parser::subnegotiation` does that when sending.
+  pub fn encode(&self) -> Bytes {
+    let [w1, w2] = self.width.to_be_bytes();
+    let [h1, h2] = self.height.to_be_bytes();
+    Bytes::copy_from_slice(&[w1, w2, h1, h2])
+  }
+}
+
+#[cfg(test)]
+mod test_naws {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let naws = Naws::new(255, 0x1234);
+    assert_eq!(&naws.encode()[..], &[0, 255, 0x12, 0x34]);
+    assert_eq!(Naws::decode(&naws.encode()), Some(naws));
+    assert_eq!(Naws::decode(&[0, 255, 255, 0x12, 0x34]), Some(naws));
+    assert_eq!(
+      Naws::decode(&[255, 255, 255, 255]),
+      Some(Naws::new(0xffff, 0xffff))
+    );
+    assert_eq!(
+      Naws::decode(&[255, 255, 255, 255, 255, 255, 255, 255]),
+      Some(Naws::new(0xffff, 0xffff))
+    );
+    assert_eq!(Naws::decode(&[0, 80, 0]), None);
+    assert_eq!(Naws::decode(&[0, 80, 0, 24, 0]), None);
+  }
+}
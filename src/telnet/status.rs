@@ -0,0 +1,184 @@
+use crate::compatibility::CompatibilityTable;
+use crate::telnet::op_command::{DO, SB, SE, WILL};
+use alloc::vec::Vec;
+use bytes::Bytes;
+
+/// STATUS (RFC 859) subnegotiation command codes.
+pub const IS: u8 = 0;
+pub const SEND: u8 = 1;
+
+/// A decoded STATUS `IS` report: the option states as the sender of the report sees them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct StatusReport {
+  /// Options the sender has enabled on its side (reported as `WILL`).
+  pub will: Vec<u8>,
+  /// Options the sender has asked the receiver of the report to enable (reported as `DO`).
+  pub do_: Vec<u8>,
+  /// The last subnegotiation parameters the sender has seen for options, as `(option, data)`.
+  pub subnegotiations: Vec<(u8, Bytes)>,
+}
+
+impl StatusReport {
+  /// Build the report describing our own view of the option states, as sent in answer to `SEND`.
+  ///
+  /// # Notes
+  ///
+  /// Subnegotiation parameters are not included, as the compatibility table doesn't record them.
+  pub fn from_table(table: &CompatibilityTable) -> Self {
+    let mut report = StatusReport::default();
+    for option in 0..=255 {
+      let entry = table.get_option(option);
+      if entry.local && entry.local_state {
+        report.will.push(option);
+      }
+      if entry.remote && entry.remote_state {
+        report.do_.push(option);
+      }
+    }
+    report
+  }
+  /// Decode the payload of a STATUS subnegotiation, with its leading `IS` byte.
+  ///
+  /// # Returns
+  ///
+  /// `Option<StatusReport>` - The report, or None if the payload is not an `IS` report.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    if data.first() != Some(&IS) {
+      return None;
+    }
+    let mut report = StatusReport::default();
+    let mut index = 1;
+    while index + 1 < data.len() {
+      let (command, option) = (data[index], data[index + 1]);
+      index += 2;
+      match command {
+        WILL => report.will.push(option),
+        DO => report.do_.push(option),
+        SB => {
+          // Parameters run to a lone SE. An SE within them is doubled.
+          let mut params = Vec::new();
+          while index < data.len() {
+            if data[index] == SE {
+              if data.get(index + 1) == Some(&SE) {
+                params.push(SE);
+                index += 2;
+                continue;
+              }
+              index += 1;
+              break;
+            }
+            params.push(data[index]);
+            index += 1;
+          }
+          report.subnegotiations.push((option, Bytes::from(params)));
+        }
+        // WONT and DONT are the default state, and anything else is not understood.
+        _ => (),
+      }
+    }
+    Some(report)
+  }
+  /// Encode the report as the payload of a STATUS subnegotiation, with its leading `IS` byte.
+  pub fn encode(&self) -> Bytes {
+    let mut out = Vec::with_capacity(1 + 2 * (self.will.len() + self.do_.len()));
+    out.push(IS);
+    for &option in self.will.iter() {
+      out.push(WILL);
+      out.push(option);
+    }
+    for &option in self.do_.iter() {
+      out.push(DO);
+      out.push(option);
+    }
+    for (option, params) in self.subnegotiations.iter() {
+      out.push(SB);
+      out.push(*option);
+      for &byte in params.iter() {
+        out.push(byte);
+        if byte == SE {
+          out.push(SE);
+        }
+      }
+      out.push(SE);
+    }
+    Bytes::from(out)
+  }
+  /// Compare the report from the remote end against our own view of the option states.
+  ///
+  /// # Returns
+  ///
+  /// `Vec<StateMismatch>` - Every option side where the two ends disagree, in option order.
+  pub fn compare(&self, table: &CompatibilityTable) -> Vec<StateMismatch> {
+    let mut mismatches = Vec::new();
+    for option in 0..=255 {
+      let entry = table.get_option(option);
+      // Their DO is about our side of the option, and their WILL about theirs.
+      let ours = entry.local && entry.local_state;
+      if self.do_.contains(&option) != ours {
+        mismatches.push(StateMismatch {
+          option,
+          local: true,
+          ours,
+        });
+      }
+      let ours = entry.remote && entry.remote_state;
+      if self.will.contains(&option) != ours {
+        mismatches.push(StateMismatch {
+          option,
+          local: false,
+          ours,
+        });
+      }
+    }
+    mismatches
+  }
+}
+
+/// A disagreement between the two ends about the state of one side of an option.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateMismatch {
+  pub option: u8,
+  /// Whether this is about our side of the option (WILL/WONT) rather than theirs (DO/DONT).
+  pub local: bool,
+  /// Whether we consider that side enabled. The remote end believes the opposite.
+  pub ours: bool,
+}
+
+#[cfg(test)]
+mod test_status {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let report = StatusReport {
+      will: alloc::vec![1, 3],
+      do_: alloc::vec![31],
+      subnegotiations: alloc::vec![(24, Bytes::from_static(&[0, SE, b'x']))],
+    };
+    let encoded = report.encode();
+    assert_eq!(
+      &encoded[..],
+      &[IS, WILL, 1, WILL, 3, DO, 31, SB, 24, 0, SE, SE, b'x', SE]
+    );
+    assert_eq!(StatusReport::decode(&encoded), Some(report));
+    assert_eq!(StatusReport::decode(&[SEND]), None);
+  }
+
+  #[test]
+  fn test_from_table() {
+    let mut table = CompatibilityTable::new();
+    table.support_local(1);
+    table.support_remote(31);
+    table.support(3);
+    let mut entry = table.get_option(1);
+    entry.local_state = true;
+    table.set_option(1, entry);
+    let mut entry = table.get_option(31);
+    entry.remote_state = true;
+    table.set_option(31, entry);
+    let report = StatusReport::from_table(&table);
+    assert_eq!(report.will, [1]);
+    assert_eq!(report.do_, [31]);
+  }
+}
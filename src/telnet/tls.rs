@@ -0,0 +1,19 @@
+/// The only START_TLS subnegotiation command: TLS negotiation follows immediately after it.
+pub const FOLLOWS: u8 = 1;
+
+/// Whether the payload of a START_TLS subnegotiation (without the leading option byte) is `FOLLOWS`.
+pub fn is_follows(data: &[u8]) -> bool {
+  data == [FOLLOWS]
+}
+
+#[cfg(test)]
+mod test_tls {
+  use super::*;
+
+  #[test]
+  fn test_follows() {
+    assert!(is_follows(&[FOLLOWS]));
+    assert!(!is_follows(&[]));
+    assert!(!is_follows(&[FOLLOWS, 0]));
+  }
+}
@@ -0,0 +1,368 @@
+use crate::events::TelnetEvents;
+use crate::telnet::{op_command, op_option, TelnetCommand};
+use crate::Parser;
+use alloc::{string::String, vec::Vec};
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// TN3270E (RFC 2355) subnegotiation codes.
+pub const ASSOCIATE: u8 = 0;
+pub const CONNECT: u8 = 1;
+pub const DEVICE_TYPE: u8 = 2;
+pub const FUNCTIONS: u8 = 3;
+pub const IS: u8 = 4;
+pub const REASON: u8 = 5;
+pub const REJECT: u8 = 6;
+pub const REQUEST: u8 = 7;
+pub const SEND: u8 = 8;
+
+/// The reasons a server can give for rejecting a `DEVICE-TYPE REQUEST`.
+pub mod reason {
+  pub const CONN_PARTNER: u8 = 0;
+  pub const DEVICE_IN_USE: u8 = 1;
+  pub const INV_ASSOCIATE: u8 = 2;
+  pub const INV_NAME: u8 = 3;
+  pub const INV_DEVICE_TYPE: u8 = 4;
+  pub const TYPE_NAME_ERROR: u8 = 5;
+  pub const UNKNOWN_ERROR: u8 = 6;
+  pub const UNSUPPORTED_REQ: u8 = 7;
+}
+
+/// The functions negotiated with `FUNCTIONS REQUEST` and `FUNCTIONS IS`.
+pub mod function {
+  pub const BIND_IMAGE: u8 = 0;
+  pub const DATA_STREAM_CTL: u8 = 1;
+  pub const RESPONSES: u8 = 2;
+  pub const SCS_CTL_CODES: u8 = 3;
+  pub const SYSREQ: u8 = 4;
+}
+
+/// The `data_type` of a record header.
+pub mod data_type {
+  pub const DATA_3270: u8 = 0;
+  pub const SCS_DATA: u8 = 1;
+  pub const RESPONSE: u8 = 2;
+  pub const BIND_IMAGE: u8 = 3;
+  pub const UNBIND: u8 = 4;
+  pub const NVT_DATA: u8 = 5;
+  pub const REQUEST: u8 = 6;
+  pub const SSCP_LU_DATA: u8 = 7;
+  pub const PRINT_EOJ: u8 = 8;
+}
+
+/// The LU a client asks to be connected to, or the printer it asks to be associated with.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceResource {
+  /// `CONNECT`: a specific LU or LU pool, by name.
+  Connect(String),
+  /// `ASSOCIATE`: the printer associated with a terminal's device name.
+  Associate(String),
+}
+
+/// A decoded TN3270E subnegotiation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Tn3270eMessage {
+  /// `SEND DEVICE-TYPE`: the server asks the client for its device type.
+  SendDeviceType,
+  /// `DEVICE-TYPE REQUEST`: the client's device type, such as `IBM-3278-2-E`, and optionally the LU it wants.
+  DeviceTypeRequest {
+    device_type: String,
+    resource: Option<DeviceResource>,
+  },
+  /// `DEVICE-TYPE IS`: the server accepts a device type, and names the LU the client is connected to.
+  DeviceTypeIs {
+    device_type: String,
+    device_name: String,
+  },
+  /// `DEVICE-TYPE REJECT`: the server refuses a device type, with one of the `reason` codes.
+  DeviceTypeReject(u8),
+  /// `FUNCTIONS REQUEST`: the functions one side would like to use.
+  FunctionsRequest(Vec<u8>),
+  /// `FUNCTIONS IS`: the functions both sides have agreed on.
+  FunctionsIs(Vec<u8>),
+}
+
+impl Tn3270eMessage {
+  /// Decode the payload of a TN3270E subnegotiation (without the leading option byte).
+  ///
+  /// # Returns
+  ///
+  /// `Option<Tn3270eMessage>` - The message, or None if it is malformed or not one of the above.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    match data {
+      [SEND, DEVICE_TYPE] => Some(Tn3270eMessage::SendDeviceType),
+      [DEVICE_TYPE, REQUEST, rest @ ..] => {
+        let (device_type, resource) = split_name(rest);
+        let resource = match resource {
+          Some((CONNECT, name)) => Some(DeviceResource::Connect(name)),
+          Some((ASSOCIATE, name)) => Some(DeviceResource::Associate(name)),
+          Some(_) => return None,
+          None => None,
+        };
+        Some(Tn3270eMessage::DeviceTypeRequest {
+          device_type,
+          resource,
+        })
+      }
+      [DEVICE_TYPE, IS, rest @ ..] => match split_name(rest) {
+        (device_type, Some((CONNECT, device_name))) => Some(Tn3270eMessage::DeviceTypeIs {
+          device_type,
+          device_name,
+        }),
+        _ => None,
+      },
+      [DEVICE_TYPE, REJECT, REASON, code] => Some(Tn3270eMessage::DeviceTypeReject(*code)),
+      [FUNCTIONS, REQUEST, functions @ ..] => {
+        Some(Tn3270eMessage::FunctionsRequest(functions.to_vec()))
+      }
+      [FUNCTIONS, IS, functions @ ..] => Some(Tn3270eMessage::FunctionsIs(functions.to_vec())),
+      _ => None,
+    }
+  }
+  /// Encode this message as the payload of a TN3270E subnegotiation.
+  pub fn encode(&self) -> Bytes {
+    let mut buf = BytesMut::new();
+    match self {
+      Tn3270eMessage::SendDeviceType => buf.put(&[SEND, DEVICE_TYPE][..]),
+      Tn3270eMessage::DeviceTypeRequest {
+        device_type,
+        resource,
+      } => {
+        buf.put(&[DEVICE_TYPE, REQUEST][..]);
+        buf.put(device_type.as_bytes());
+        match resource {
+          Some(DeviceResource::Connect(name)) => {
+            buf.put_u8(CONNECT);
+            buf.put(name.as_bytes());
+          }
+          Some(DeviceResource::Associate(name)) => {
+            buf.put_u8(ASSOCIATE);
+            buf.put(name.as_bytes());
+          }
+          None => (),
+        }
+      }
+      Tn3270eMessage::DeviceTypeIs {
+        device_type,
+        device_name,
+      } => {
+        buf.put(&[DEVICE_TYPE, IS][..]);
+        buf.put(device_type.as_bytes());
+        buf.put_u8(CONNECT);
+        buf.put(device_name.as_bytes());
+      }
+      Tn3270eMessage::DeviceTypeReject(code) => buf.put(&[DEVICE_TYPE, REJECT, REASON, *code][..]),
+      Tn3270eMessage::FunctionsRequest(functions) => {
+        buf.put(&[FUNCTIONS, REQUEST][..]);
+        buf.put(&functions[..]);
+      }
+      Tn3270eMessage::FunctionsIs(functions) => {
+        buf.put(&[FUNCTIONS, IS][..]);
+        buf.put(&functions[..]);
+      }
+    }
+    buf.freeze()
+  }
+}
+
+/// Split a device type from the `CONNECT` or `ASSOCIATE` code and name that may follow it.
+///
+/// Device types and names are printable ASCII, so the first byte below 32 is the code.
+fn split_name(data: &[u8]) -> (String, Option<(u8, String)>) {
+  match data.iter().position(|&b| b < 32) {
+    Some(index) => (
+      String::from_utf8_lossy(&data[..index]).into_owned(),
+      Some((
+        data[index],
+        String::from_utf8_lossy(&data[index + 1..]).into_owned(),
+      )),
+    ),
+    None => (String::from_utf8_lossy(data).into_owned(), None),
+  }
+}
+
+/// The 5 byte header that starts each TN3270E record.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Tn3270eHeader {
+  /// One of the `data_type` codes.
+  pub data_type: u8,
+  pub request_flag: u8,
+  pub response_flag: u8,
+  /// The sequence number, used to match a `RESPONSE` to the record it answers.
+  pub seq_number: u16,
+}
+
+impl Tn3270eHeader {
+  pub const LEN: usize = 5;
+
+  pub fn new(data_type: u8, seq_number: u16) -> Self {
+    Self {
+      data_type,
+      seq_number,
+      ..Self::default()
+    }
+  }
+  /// Decode a header from the start of a record.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    match *data.get(..Self::LEN)? {
+      [data_type, request_flag, response_flag, high, low] => Some(Self {
+        data_type,
+        request_flag,
+        response_flag,
+        seq_number: u16::from_be_bytes([high, low]),
+      }),
+      _ => None,
+    }
+  }
+  pub fn encode(&self) -> [u8; 5] {
+    let [high, low] = self.seq_number.to_be_bytes();
+    [
+      self.data_type,
+      self.request_flag,
+      self.response_flag,
+      high,
+      low,
+    ]
+  }
+}
+
+/// A complete TN3270E record: a header and the 3270 data stream, SCS data or response that follows it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tn3270eRecord {
+  pub header: Tn3270eHeader,
+  /// The data, with IAC bytes unescaped.
+  pub data: Bytes,
+}
+
+impl Tn3270eRecord {
+  pub fn new(header: Tn3270eHeader, data: Bytes) -> Self {
+    Self { header, data }
+  }
+  /// Decode a record, as received between two `IAC EOR` sequences.
+  ///
+  /// # Returns
+  ///
+  /// `Option<Tn3270eRecord>` - The record, or None if it is shorter than its header.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    Some(Self {
+      header: Tn3270eHeader::decode(data)?,
+      data: Bytes::copy_from_slice(&data[Tn3270eHeader::LEN..]),
+    })
+  }
+  /// Encode the record for sending: the header and data, with IAC bytes escaped, ended by `IAC EOR`.
+  pub fn encode(&self) -> Bytes {
+    let mut buf = BytesMut::with_capacity(Tn3270eHeader::LEN + self.data.len() + 2);
+    buf.put(&self.header.encode()[..]);
+    buf.put(&self.data[..]);
+    let mut buf = BytesMut::from(&Parser::escape_iac(buf.freeze())[..]);
+    buf.put(&[op_command::IAC, op_command::EOR][..]);
+    buf.freeze()
+  }
+}
+
+/// Collects received data into TN3270E records, each ended by `IAC EOR`.
+///
+/// BINARY and END-OF-RECORD must be enabled both ways, as RFC 2355 requires, so the data reaches this untranslated.
+/// Prompt detection should be left off, as it would turn each record into a `Prompt` event.
+#[derive(Clone, Debug, Default)]
+pub struct Tn3270eSession {
+  /// The data received since the last `IAC EOR`.
+  buffer: BytesMut,
+}
+
+impl Tn3270eSession {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  /// Ask the client for its device type. Sent by the server once TN3270E is enabled.
+  pub fn request_device_type(&self, parser: &mut Parser) -> Option<TelnetEvents> {
+    parser.send_tn3270e(&Tn3270eMessage::SendDeviceType)
+  }
+  /// Send a record.
+  ///
+  /// # Returns
+  ///
+  /// `Option<TelnetEvents::DataSend>` - The record to be sent, or None if TN3270E is not enabled.
+  pub fn send_record(&self, parser: &Parser, record: &Tn3270eRecord) -> Option<TelnetEvents> {
+    let opt = parser.options.get_option(op_option::TN3270E);
+    if (opt.local && opt.local_state) || (opt.remote && opt.remote_state) {
+      Some(TelnetEvents::build_send(record.encode()))
+    } else {
+      None
+    }
+  }
+  /// Feed an event from the parser, returning a record once it is complete.
+  ///
+  /// # Notes
+  ///
+  /// A record too short to hold a header is dropped.
+  pub fn process(&mut self, event: &TelnetEvents) -> Option<Tn3270eRecord> {
+    match event {
+      TelnetEvents::DataReceive(data) => {
+        self.buffer.extend_from_slice(data);
+        None
+      }
+      TelnetEvents::IAC(iac) if iac.command == TelnetCommand::EndOfRecord => {
+        let record = self.buffer.split().freeze();
+        Tn3270eRecord::decode(&record)
+      }
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test_tn3270e {
+  use super::*;
+
+  #[test]
+  fn test_messages() {
+    let request = Tn3270eMessage::DeviceTypeRequest {
+      device_type: String::from("IBM-3278-2-E"),
+      resource: Some(DeviceResource::Connect(String::from("LU1"))),
+    };
+    assert_eq!(&request.encode()[..], b"\x02\x07IBM-3278-2-E\x01LU1");
+    assert_eq!(Tn3270eMessage::decode(&request.encode()), Some(request));
+    let request = Tn3270eMessage::DeviceTypeRequest {
+      device_type: String::from("IBM-3287-1"),
+      resource: None,
+    };
+    assert_eq!(Tn3270eMessage::decode(&request.encode()), Some(request));
+    let is = Tn3270eMessage::DeviceTypeIs {
+      device_type: String::from("IBM-3278-2-E"),
+      device_name: String::from("TCP00001"),
+    };
+    assert_eq!(Tn3270eMessage::decode(&is.encode()), Some(is));
+    assert_eq!(
+      Tn3270eMessage::decode(&[DEVICE_TYPE, REJECT, REASON, reason::INV_NAME]),
+      Some(Tn3270eMessage::DeviceTypeReject(reason::INV_NAME))
+    );
+    let functions = Tn3270eMessage::FunctionsIs(alloc::vec![function::RESPONSES, function::SYSREQ]);
+    assert_eq!(&functions.encode()[..], &[FUNCTIONS, IS, 2, 4]);
+    assert_eq!(Tn3270eMessage::decode(&functions.encode()), Some(functions));
+    assert_eq!(
+      Tn3270eMessage::decode(&[SEND, DEVICE_TYPE]),
+      Some(Tn3270eMessage::SendDeviceType)
+    );
+    assert_eq!(Tn3270eMessage::decode(&[DEVICE_TYPE, IS, b'A']), None);
+    assert_eq!(Tn3270eMessage::decode(&[SEND]), None);
+  }
+
+  #[test]
+  fn test_record() {
+    let header = Tn3270eHeader::new(data_type::DATA_3270, 0x0102);
+    assert_eq!(header.encode(), [0, 0, 0, 1, 2]);
+    assert_eq!(Tn3270eHeader::decode(&header.encode()), Some(header));
+    assert_eq!(Tn3270eHeader::decode(&[0, 0, 0, 1]), None);
+    let record = Tn3270eRecord::new(header, Bytes::from_static(&[0xf5, 0xff, 0xc3]));
+    assert_eq!(
+      &record.encode()[..],
+      &[0, 0, 0, 1, 2, 0xf5, 0xff, 0xff, 0xc3, 0xff, 0xef]
+    );
+    assert_eq!(
+      Tn3270eRecord::decode(&[0, 0, 0, 1, 2, 0xf5, 0xff, 0xc3]),
+      Some(record)
+    );
+  }
+}
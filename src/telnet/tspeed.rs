@@ -0,0 +1,70 @@
+use alloc::format;
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// TERMINAL-SPEED (RFC 1079) subnegotiation command codes.
+pub const IS: u8 = 0;
+pub const SEND: u8 = 1;
+
+/// A decoded TERMINAL-SPEED subnegotiation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TspeedMessage {
+  /// The line speeds reported by the client, in bits per second.
+  Is { transmit: u32, receive: u32 },
+  /// A request from the server for the client's speeds.
+  Send,
+}
+
+impl TspeedMessage {
+  /// Decode the payload of a TERMINAL-SPEED subnegotiation (without the leading option byte).
+  ///
+  /// # Returns
+  ///
+  /// `Option<TspeedMessage>` - The message, or None if the command is unknown or an `IS` is not two decimal speeds
+  /// separated by a comma.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    match *data.first()? {
+      IS => {
+        let text = core::str::from_utf8(&data[1..]).ok()?;
+        let mut speeds = text.splitn(2, ',');
+        let transmit = speeds.next()?.trim().parse().ok()?;
+        let receive = speeds.next()?.trim().parse().ok()?;
+        Some(TspeedMessage::Is { transmit, receive })
+      }
+      SEND => Some(TspeedMessage::Send),
+      _ => None,
+    }
+  }
+  /// Encode this message as the payload of a TERMINAL-SPEED subnegotiation.
+  pub fn encode(&self) -> Bytes {
+    match self {
+      TspeedMessage::Is { transmit, receive } => {
+        let speeds = format!("{},{}", transmit, receive);
+        let mut buf = BytesMut::with_capacity(1 + speeds.len());
+        buf.put_u8(IS);
+        buf.put(speeds.as_bytes());
+        buf.freeze()
+      }
+      TspeedMessage::Send => Bytes::from_static(&[SEND]),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test_tspeed {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let is = TspeedMessage::Is {
+      transmit: 38400,
+      receive: 9600,
+    };
+    assert_eq!(&is.encode()[..], b"\x0038400,9600");
+    assert_eq!(TspeedMessage::decode(&is.encode()), Some(is));
+    assert_eq!(TspeedMessage::decode(&[SEND]), Some(TspeedMessage::Send));
+    assert_eq!(TspeedMessage::decode(b"\x0038400"), None);
+    assert_eq!(TspeedMessage::decode(b"\x00fast,slow"), None);
+    assert_eq!(TspeedMessage::decode(&[2]), None);
+  }
+}
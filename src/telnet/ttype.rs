@@ -0,0 +1,57 @@
+use alloc::string::String;
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// TERMINAL-TYPE (RFC 1091) subnegotiation command codes.
+pub const IS: u8 = 0;
+pub const SEND: u8 = 1;
+
+/// A decoded TERMINAL-TYPE subnegotiation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TtypeMessage {
+  /// A terminal type reported by the client.
+  Is(String),
+  /// A request from the server for the next terminal type.
+  Send,
+}
+
+impl TtypeMessage {
+  /// Decode the payload of a TERMINAL-TYPE subnegotiation (without the leading option byte).
+  ///
+  /// The payload is expected to have already had its IAC bytes unescaped. Terminal types are ASCII, so any other
+  /// bytes are replaced.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    match *data.first()? {
+      IS => Some(TtypeMessage::Is(
+        String::from_utf8_lossy(&data[1..]).into_owned(),
+      )),
+      SEND => Some(TtypeMessage::Send),
+      _ => None,
+    }
+  }
+  /// Encode this message as the payload of a TERMINAL-TYPE subnegotiation.
+  pub fn encode(&self) -> Bytes {
+    match self {
+      TtypeMessage::Is(name) => {
+        let mut buf = BytesMut::with_capacity(1 + name.len());
+        buf.put_u8(IS);
+        buf.put(name.as_bytes());
+        buf.freeze()
+      }
+      TtypeMessage::Send => Bytes::from_static(&[SEND]),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test_ttype {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let is = TtypeMessage::Is(String::from("XTERM-256COLOR"));
+    assert_eq!(&is.encode()[..], b"\x00XTERM-256COLOR");
+    assert_eq!(TtypeMessage::decode(&is.encode()), Some(is));
+    assert_eq!(TtypeMessage::decode(&[SEND]), Some(TtypeMessage::Send));
+    assert_eq!(TtypeMessage::decode(&[2]), None);
+  }
+}
@@ -0,0 +1,60 @@
+use alloc::string::String;
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// X-DISPLAY-LOCATION (RFC 1096) subnegotiation command codes.
+pub const IS: u8 = 0;
+pub const SEND: u8 = 1;
+
+/// A decoded X-DISPLAY-LOCATION subnegotiation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum XdisplocMessage {
+  /// The X display reported by the client, as `host:display[.screen]`.
+  Is(String),
+  /// A request from the server for the client's display.
+  Send,
+}
+
+impl XdisplocMessage {
+  /// Decode the payload of an X-DISPLAY-LOCATION subnegotiation (without the leading option byte).
+  ///
+  /// The display is ASCII, so any other bytes are replaced.
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    match *data.first()? {
+      IS => Some(XdisplocMessage::Is(
+        String::from_utf8_lossy(&data[1..]).into_owned(),
+      )),
+      SEND => Some(XdisplocMessage::Send),
+      _ => None,
+    }
+  }
+  /// Encode this message as the payload of an X-DISPLAY-LOCATION subnegotiation.
+  pub fn encode(&self) -> Bytes {
+    match self {
+      XdisplocMessage::Is(display) => {
+        let mut buf = BytesMut::with_capacity(1 + display.len());
+        buf.put_u8(IS);
+        buf.put(display.as_bytes());
+        buf.freeze()
+      }
+      XdisplocMessage::Send => Bytes::from_static(&[SEND]),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test_xdisploc {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let is = XdisplocMessage::Is(String::from("localhost:0.0"));
+    assert_eq!(&is.encode()[..], b"\x00localhost:0.0");
+    assert_eq!(XdisplocMessage::decode(&is.encode()), Some(is));
+    assert_eq!(
+      XdisplocMessage::decode(&[SEND]),
+      Some(XdisplocMessage::Send)
+    );
+    assert_eq!(XdisplocMessage::decode(&[]), None);
+  }
+}
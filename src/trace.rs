@@ -0,0 +1,248 @@
+//! Human-readable formatting of telnet traffic, for protocol debugging consoles and logs.
+//!
+//! `format_event` writes a parser event as a single line, such as `<- IAC WILL GMCP` or `-> SB NAWS 120 40`, and
+//! `format_bytes` does the same for a raw run of bytes read from or written to the socket. Commands and options are
+//! written by name where they have one, and data is quoted with anything unprintable escaped.
+
+pub use crate::compression::Direction;
+use crate::events::TelnetEvents;
+use crate::telnet::{op_command::*, op_option, TelnetCommand, TelnetOption};
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+/// Format an event from the parser as a single line.
+///
+/// # Returns
+///
+/// `String` - The line, without a line ending.
+///
+/// # Notes
+///
+/// `DataSend` and `Outbound` events are written as sent, and every other event as received. Events with no telnet
+/// meaning of their own, such as `Gmcp`, are written with their `Debug` format.
+pub fn format_event(event: &TelnetEvents) -> String {
+  let direction = match event {
+    TelnetEvents::DataSend(_) | TelnetEvents::Outbound(_) => Direction::Outbound,
+    _ => Direction::Inbound,
+  };
+  let mut out = String::from(direction.arrow());
+  write_event(&mut out, event);
+  out
+}
+
+/// Format a raw run of bytes, as read from or written to the socket, as a single line.
+///
+/// # Notes
+///
+/// Each IAC sequence is written separately from the data around it, as in `<- "Name: " IAC GA`. A sequence cut off
+/// at the end of the run is written as far as it goes.
+pub fn format_bytes(direction: Direction, data: &[u8]) -> String {
+  let mut out = String::from(direction.arrow());
+  write_bytes(&mut out, data);
+  out
+}
+
+fn write_event(out: &mut String, event: &TelnetEvents) {
+  match event {
+    TelnetEvents::IAC(iac) => {
+      out.push_str(" IAC");
+      write_command(out, iac.raw_command());
+    }
+    TelnetEvents::DataMark => out.push_str(" IAC DM"),
+    TelnetEvents::Negotiation(negotiation) => {
+      out.push_str(" IAC");
+      write_command(out, negotiation.raw_command());
+      write_option(out, negotiation.option);
+    }
+    TelnetEvents::Subnegotiation(sub) => {
+      out.push_str(" SB");
+      write_option(out, sub.option);
+      write_payload(out, sub.option, &sub.buffer);
+    }
+    TelnetEvents::DataReceive(data) | TelnetEvents::Line(data) | TelnetEvents::Prompt(data) => {
+      write_data(out, data)
+    }
+    TelnetEvents::Text(text) => write_data(out, text.as_bytes()),
+    TelnetEvents::DataSend(data) => write_bytes(out, data),
+    TelnetEvents::Outbound(event) => write_event(out, event),
+    other => {
+      let _ = write!(out, " {:?}", other);
+    }
+  }
+}
+
+fn write_bytes(out: &mut String, data: &[u8]) {
+  let mut index = 0;
+  while index < data.len() {
+    if data[index] != IAC {
+      let end = data[index..]
+        .iter()
+        .position(|&b| b == IAC)
+        .map_or(data.len(), |len| index + len);
+      write_data(out, &data[index..end]);
+      index = end;
+      continue;
+    }
+    match data.get(index + 1) {
+      None => {
+        out.push_str(" IAC");
+        index += 1;
+      }
+      Some(&IAC) => {
+        write_data(out, &[IAC]);
+        index += 2;
+      }
+      Some(&SB) => {
+        out.push_str(" IAC SB");
+        let option = match data.get(index + 2) {
+          Some(&option) => option,
+          None => return,
+        };
+        write_option(out, option);
+        let (payload, len, ended) = unescape_subnegotiation(&data[index + 3..]);
+        write_payload(out, option, &payload);
+        if ended {
+          out.push_str(" IAC SE");
+        }
+        index += 3 + len;
+      }
+      Some(&command @ (WILL | WONT | DO | DONT)) => {
+        out.push_str(" IAC");
+        write_command(out, command);
+        if let Some(&option) = data.get(index + 2) {
+          write_option(out, option);
+        }
+        index += 3;
+      }
+      Some(&command) => {
+        out.push_str(" IAC");
+        write_command(out, command);
+        index += 2;
+      }
+    }
+  }
+}
+
+/// Collapse the escaped IAC bytes of a subnegotiation payload.
+///
+/// # Returns
+///
+/// `(Vec<u8>, usize, bool)` - The payload, the number of bytes used including any `IAC SE`, and whether the
+/// `IAC SE` was found.
+fn unescape_subnegotiation(data: &[u8]) -> (Vec<u8>, usize, bool) {
+  let mut payload = Vec::new();
+  let mut index = 0;
+  while index < data.len() {
+    if data[index] == IAC {
+      match data.get(index + 1) {
+        Some(&IAC) => {
+          payload.push(IAC);
+          index += 2;
+          continue;
+        }
+        Some(&SE) => return (payload, index + 2, true),
+        _ => (),
+      }
+    }
+    payload.push(data[index]);
+    index += 1;
+  }
+  (payload, data.len(), false)
+}
+
+fn write_command(out: &mut String, command: u8) {
+  let _ = write!(out, " {}", TelnetCommand::from(command));
+}
+
+fn write_option(out: &mut String, option: u8) {
+  let _ = write!(out, " {}", TelnetOption::from(option));
+}
+
+/// Write a subnegotiation payload. Window sizes are written as `width height`, and anything else as data.
+fn write_payload(out: &mut String, option: u8, payload: &[u8]) {
+  match (option, payload) {
+    (op_option::NAWS, &[w1, w2, h1, h2]) => {
+      let _ = write!(
+        out,
+        " {} {}",
+        u16::from_be_bytes([w1, w2]),
+        u16::from_be_bytes([h1, h2])
+      );
+    }
+    _ => write_data(out, payload),
+  }
+}
+
+/// Write data as quoted runs of text, with any other bytes written as numbers.
+///
+/// A lone printable byte between other bytes is written as a number too, so binary payloads such as
+/// `0 120 0 40` read as numbers rather than scattered characters.
+fn write_data(out: &mut String, data: &[u8]) {
+  let text = |b: u8| (b' '..=b'~').contains(&b) || matches!(b, b'\r' | b'\n' | b'\t');
+  let mut index = 0;
+  while index < data.len() {
+    let run = data[index..].iter().take_while(|&&b| text(b)).count();
+    if run > 1 || (run == 1 && data.len() == 1) {
+      out.push_str(" \"");
+      for &b in &data[index..index + run] {
+        match b {
+          b'\r' => out.push_str("\\r"),
+          b'\n' => out.push_str("\\n"),
+          b'\t' => out.push_str("\\t"),
+          b'"' => out.push_str("\\\""),
+          b'\\' => out.push_str("\\\\"),
+          b => out.push(b as char),
+        }
+      }
+      out.push('"');
+      index += run;
+    } else {
+      let _ = write!(out, " {}", data[index]);
+      index += 1;
+    }
+  }
+}
+
+#[cfg(test)]
+mod test_trace {
+  use super::*;
+  use crate::events::TelnetNegotiation;
+  use bytes::Bytes;
+
+  #[test]
+  fn test_format_event() {
+    let will = TelnetEvents::Negotiation(TelnetNegotiation::new(WILL, op_option::GMCP));
+    assert_eq!(format_event(&will), "<- IAC WILL GMCP");
+    let naws =
+      TelnetEvents::build_subnegotiation(op_option::NAWS, Bytes::from_static(&[0, 120, 0, 40]));
+    assert_eq!(format_event(&naws), "<- SB NAWS 120 40");
+    let data = TelnetEvents::DataReceive(Bytes::from_static(b"Say \"hi\"\r\n\x1b[0m"));
+    assert_eq!(
+      format_event(&data),
+      "<- \"Say \\\"hi\\\"\\r\\n\" 27 \"[0m\""
+    );
+    let send = TelnetEvents::DataSend(Bytes::from_static(&[IAC, DO, op_option::TTYPE]));
+    assert_eq!(format_event(&send), "-> IAC DO TERMINAL-TYPE");
+    assert_eq!(format_event(&TelnetEvents::build_iac(AYT)), "<- IAC AYT");
+  }
+
+  #[test]
+  fn test_format_bytes() {
+    let data = b"Name: \xff\xf9\xff\xfa\x18\x00xterm\xff\xf0\xff\xff\xff\xfd";
+    assert_eq!(
+      format_bytes(Direction::Inbound, data),
+      "<- \"Name: \" IAC GA IAC SB TERMINAL-TYPE 0 \"xterm\" IAC SE 255 IAC DO"
+    );
+    assert_eq!(
+      format_bytes(
+        Direction::Outbound,
+        &[IAC, SB, op_option::NAWS, 0, 80, 0, IAC, IAC, IAC, SE]
+      ),
+      "-> IAC SB NAWS 80 255 IAC SE"
+    );
+    assert_eq!(
+      format_bytes(Direction::Outbound, &[IAC, SB, 200, 1]),
+      "-> IAC SB 200 1"
+    );
+  }
+}
@@ -0,0 +1,180 @@
+use crate::events::TelnetEvents;
+#[cfg(feature = "json")]
+use crate::events::{TelnetIAC, TelnetNegotiation, TelnetSubnegotiation};
+use crate::Parser;
+use alloc::string::{String, ToString};
+#[cfg(feature = "json")]
+use alloc::vec::Vec;
+use bytes::Bytes;
+#[cfg(feature = "json")]
+use core::convert::TryFrom;
+#[cfg(feature = "json")]
+use serde_json::{Map, Value};
+
+/// A WebSocket message carrying telnet traffic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WsFrame {
+  /// A text frame.
+  Text(String),
+  /// A binary frame.
+  Binary(Bytes),
+}
+
+/// The rules used to map telnet events onto WebSocket frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BridgeMode {
+  /// Plain data travels in text frames, while IAC sequences (and data that is not valid UTF-8)
+  /// travel in binary frames as raw, already-escaped telnet bytes.
+  Split,
+  /// Every event is wrapped in a JSON envelope and sent in a text frame.
+  ///
+  /// Envelopes take the form `{"type":"data","text":"..."}`, `{"type":"iac","command":249}`,
+  /// `{"type":"negotiation","command":251,"option":201}` and
+  /// `{"type":"subnegotiation","option":201,"text":"..."}`. Payloads that are not valid UTF-8 use a
+  /// `"bytes"` array of numbers in place of `"text"`.
+  #[cfg(feature = "json")]
+  Json,
+}
+
+/// Convert an event parsed from the telnet side of a gateway into a WebSocket frame for the web client.
+///
+/// # Returns
+///
+/// `Option<WsFrame>` - The frame to forward, or None for events that have no meaning to the web client (such as `DataSend`).
+pub fn event_to_frame(event: &TelnetEvents, mode: BridgeMode) -> Option<WsFrame> {
+  match mode {
+    BridgeMode::Split => match event {
+      TelnetEvents::DataReceive(data) => Some(match core::str::from_utf8(data) {
+        Ok(text) => WsFrame::Text(text.to_string()),
+        Err(_) => WsFrame::Binary(Parser::escape_iac(data.clone())),
+      }),
+      TelnetEvents::IAC(iac) => Some(WsFrame::Binary((*iac).into())),
+      TelnetEvents::Negotiation(neg) => Some(WsFrame::Binary((*neg).into())),
+      TelnetEvents::Subnegotiation(sub) => Some(WsFrame::Binary(sub.clone().into())),
+      _ => None,
+    },
+    #[cfg(feature = "json")]
+    BridgeMode::Json => {
+      let envelope = match event {
+        TelnetEvents::DataReceive(data) => envelope("data", &[], Some(data)),
+        TelnetEvents::IAC(iac) => envelope("iac", &[("command", iac.raw_command())], None),
+        TelnetEvents::Negotiation(neg) => envelope(
+          "negotiation",
+          &[("command", neg.raw_command()), ("option", neg.option)],
+          None,
+        ),
+        TelnetEvents::Subnegotiation(sub) => envelope(
+          "subnegotiation",
+          &[("option", sub.option)],
+          Some(&sub.buffer),
+        ),
+        _ => return None,
+      };
+      Some(WsFrame::Text(envelope.to_string()))
+    }
+  }
+}
+
+/// Convert a frame received from the web client into data to be sent to the telnet side of a gateway.
+///
+/// # Returns
+///
+/// `Option<TelnetEvents::DataSend>` - The encoded data to write to the telnet connection, or None if the frame could not be understood.
+///
+/// # Notes
+///
+/// Binary frames are always treated as raw telnet bytes and are passed through untouched.
+pub fn frame_to_event(frame: &WsFrame, mode: BridgeMode) -> Option<TelnetEvents> {
+  let text = match frame {
+    WsFrame::Binary(data) => return Some(TelnetEvents::build_send(data.clone())),
+    WsFrame::Text(text) => text,
+  };
+  match mode {
+    BridgeMode::Split => Some(TelnetEvents::build_send(Parser::escape_iac(
+      text.as_bytes().to_vec(),
+    ))),
+    #[cfg(feature = "json")]
+    BridgeMode::Json => json_to_event(&serde_json::from_str(text).ok()?),
+  }
+}
+
+#[cfg(feature = "json")]
+fn json_to_event(value: &Value) -> Option<TelnetEvents> {
+  let payload = || -> Option<Bytes> {
+    if let Some(text) = value.get("text") {
+      return Some(Bytes::copy_from_slice(text.as_str()?.as_bytes()));
+    }
+    value
+      .get("bytes")?
+      .as_array()?
+      .iter()
+      .map(as_u8)
+      .collect::<Option<Vec<u8>>>()
+      .map(Bytes::from)
+  };
+  let field = |name: &str| value.get(name).and_then(as_u8);
+  let data: Bytes = match value.get("type")?.as_str()? {
+    "data" => Parser::escape_iac(payload()?),
+    "iac" => TelnetIAC::new(field("command")?).into(),
+    "negotiation" => TelnetNegotiation::new(field("command")?, field("option")?).into(),
+    "subnegotiation" => TelnetSubnegotiation::new(field("option")?, payload()?).into(),
+    _ => return None,
+  };
+  Some(TelnetEvents::build_send(data))
+}
+
+#[cfg(feature = "json")]
+fn as_u8(value: &Value) -> Option<u8> {
+  value.as_u64().and_then(|number| u8::try_from(number).ok())
+}
+
+#[cfg(feature = "json")]
+fn envelope(kind: &str, fields: &[(&str, u8)], payload: Option<&Bytes>) -> Value {
+  let mut entries = Map::new();
+  entries.insert("type".to_string(), Value::from(kind));
+  for (name, value) in fields {
+    entries.insert(name.to_string(), Value::from(*value));
+  }
+  if let Some(payload) = payload {
+    match core::str::from_utf8(payload) {
+      Ok(text) => entries.insert("text".to_string(), Value::from(text)),
+      Err(_) => entries.insert("bytes".to_string(), Value::from(payload.to_vec())),
+    };
+  }
+  Value::Object(entries)
+}
+
+#[cfg(test)]
+mod test_websocket {
+  use super::*;
+  use crate::telnet::op_command::*;
+
+  #[test]
+  fn test_split_mode() {
+    let frame = event_to_frame(
+      &TelnetEvents::build_receive(Bytes::from_static(b"hello")),
+      BridgeMode::Split,
+    );
+    assert_eq!(frame, Some(WsFrame::Text("hello".to_string())));
+    let frame = event_to_frame(&TelnetEvents::build_iac(GA), BridgeMode::Split);
+    assert_eq!(frame, Some(WsFrame::Binary(Bytes::from_static(&[IAC, GA]))));
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn test_json_roundtrip() {
+    use crate::telnet::op_option::GMCP;
+    let event = TelnetEvents::build_subnegotiation(GMCP, Bytes::from_static(b"Core.Hello {}"));
+    let frame = event_to_frame(&event, BridgeMode::Json).unwrap();
+    assert_eq!(
+      frame,
+      WsFrame::Text(r#"{"type":"subnegotiation","option":201,"text":"Core.Hello {}"}"#.to_string())
+    );
+    let sent: Bytes = frame_to_event(&frame, BridgeMode::Json).unwrap().into();
+    assert_eq!(sent, Bytes::from(event));
+
+    let frame = WsFrame::Text(r#"{"type":"data","bytes":[255,1]}"#.to_string());
+    let sent: Bytes = frame_to_event(&frame, BridgeMode::Json).unwrap().into();
+    assert_eq!(&sent[..], &[IAC, IAC, 1]);
+  }
+}
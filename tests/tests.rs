@@ -4,17 +4,52 @@ use libtelnet_rs::vbytes;
 
 use libtelnet_rs::*;
 use libtelnet_rs::compatibility::{CompatibilityEntry, CompatibilityTable};
+use libtelnet_rs::telnet::status::StateMismatch;
 
-/// Test the parser and its general functionality.
+// Test the parser and its general functionality.
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Debug)]
 enum Event {
   IAC,
+  DATAMARK,
   NEGOTIATION,
+  COMPLETE,
   SUBNEGOTIATION,
   RECV,
   SEND,
+  LINE,
+  TEXT,
+  OUTBOUND,
   DECOM,
+  DECOMCLIENT,
+  ENVIRON,
+  ERROR,
+  PEERSTACK,
+  ENDCOMPRESSION,
+  DECOMEND,
+  STATS,
+  ANOMALY,
+  KEEPALIVE,
+  PONG,
+  LOGOUTREQUEST,
+  LOGOUT,
+  RESYNCED,
+  MISMATCH,
+  PROMPT,
+  SOUND,
+  AARD102,
+  TSPEED,
+  XDISPLOC,
+  LFLOW,
+  AUTHNAME,
+  AUTH,
+  ENCRYPT,
+  ENCRYPTED,
+  STARTTLS,
+  COMPORT,
+  TN3270E,
+  SHUTDOWN,
 }
 
 macro_rules! events {
@@ -63,10 +98,21 @@ fn handle_events(event_list: Vec<events::TelnetEvents>) -> CapturedEvents {
         println!("IAC: {}", ev.command);
         events.push(Event::IAC);
       }
+      events::TelnetEvents::DataMark => {
+        println!("Data Mark");
+        events.push(Event::DATAMARK);
+      }
       events::TelnetEvents::Negotiation(ev) => {
         println!("Negotiation: {} {}", ev.command, ev.option);
         events.push(Event::NEGOTIATION);
       }
+      events::TelnetEvents::NegotiationComplete(ev) => {
+        println!(
+          "Negotiation complete: {} accepted={} local={}",
+          ev.option, ev.accepted, ev.local
+        );
+        events.push(Event::COMPLETE);
+      }
       events::TelnetEvents::Subnegotiation(ev) => {
         println!("Subnegotiation: {} {:?}", ev.option, ev.buffer);
         events.push(Event::SUBNEGOTIATION);
@@ -82,10 +128,135 @@ fn handle_events(event_list: Vec<events::TelnetEvents>) -> CapturedEvents {
         println!("Send: {:?}", buffer);
         events.push(Event::SEND);
       }
+      events::TelnetEvents::Line(line) => {
+        println!("Line: {:?}", line);
+        events.push(Event::LINE);
+      }
+      events::TelnetEvents::Outbound(event) => {
+        println!("Outbound: {:?}", event);
+        events.push(Event::OUTBOUND);
+      }
+      events::TelnetEvents::EnvironChange(vars) => {
+        println!("Environ: {:?}", vars);
+        events.push(Event::ENVIRON);
+      }
+      events::TelnetEvents::Error(kind, buffer) => {
+        println!("Error: {:?} {:?}", kind, buffer);
+        events.push(Event::ERROR);
+      }
+      events::TelnetEvents::PeerStack(stack) => {
+        println!("Peer stack: {:?}", stack);
+        events.push(Event::PEERSTACK);
+      }
+      events::TelnetEvents::EndCompression(option) => {
+        println!("End compression: {}", option);
+        events.push(Event::ENDCOMPRESSION);
+      }
+      events::TelnetEvents::DecompressionEnded(option) => {
+        println!("Decompression ended: {}", option);
+        events.push(Event::DECOMEND);
+      }
+      events::TelnetEvents::CompressionStats(stats) => {
+        println!("Compression stats: {:?}", stats);
+        events.push(Event::STATS);
+      }
+      events::TelnetEvents::AnomalousSubnegotiation(option, len) => {
+        println!("Anomalous subnegotiation: {} {}", option, len);
+        events.push(Event::ANOMALY);
+      }
+      events::TelnetEvents::Text(text) => {
+        println!("Text: {:?}", text);
+        events.push(Event::TEXT);
+      }
+      events::TelnetEvents::Prompt(line) => {
+        println!("Prompt: {:?}", line);
+        events.push(Event::PROMPT);
+      }
+      events::TelnetEvents::Sound(trigger) => {
+        println!("Sound: {:?}", trigger);
+        events.push(Event::SOUND);
+      }
+      events::TelnetEvents::Aard102(update) => {
+        println!("Aard102: {:?}", update);
+        events.push(Event::AARD102);
+      }
+      events::TelnetEvents::TerminalSpeed(message) => {
+        println!("TerminalSpeed: {:?}", message);
+        events.push(Event::TSPEED);
+      }
+      events::TelnetEvents::DisplayLocation(message) => {
+        println!("DisplayLocation: {:?}", message);
+        events.push(Event::XDISPLOC);
+      }
+      events::TelnetEvents::FlowControl(state) => {
+        println!("FlowControl: {:?}", state);
+        events.push(Event::LFLOW);
+      }
+      events::TelnetEvents::AuthName(name) => {
+        println!("AuthName: {}", name);
+        events.push(Event::AUTHNAME);
+      }
+      events::TelnetEvents::AuthComplete { pair, accepted } => {
+        println!("AuthComplete: {:?} {}", pair, accepted);
+        events.push(Event::AUTH);
+      }
+      events::TelnetEvents::Encrypt(message) => {
+        println!("Encrypt: {:?}", message);
+        events.push(Event::ENCRYPT);
+      }
+      events::TelnetEvents::ComPort(message) => {
+        println!("ComPort: {:?}", message);
+        events.push(Event::COMPORT);
+      }
+      events::TelnetEvents::Tn3270e(message) => {
+        println!("Tn3270e: {:?}", message);
+        events.push(Event::TN3270E);
+      }
+      events::TelnetEvents::Resynced(option) => {
+        println!("Resynced: {}", option);
+        events.push(Event::RESYNCED);
+      }
+      events::TelnetEvents::StateMismatch(mismatches) => {
+        println!("State mismatch: {:?}", mismatches);
+        events.push(Event::MISMATCH);
+      }
+      events::TelnetEvents::KeepaliveAck => {
+        println!("Keepalive ack");
+        events.push(Event::KEEPALIVE);
+      }
+      events::TelnetEvents::Pong { rtt_hint } => {
+        println!("Pong: {}", rtt_hint);
+        events.push(Event::PONG);
+      }
+      events::TelnetEvents::LogoutRequested => {
+        println!("Logout requested");
+        events.push(Event::LOGOUTREQUEST);
+      }
+      events::TelnetEvents::LogoutAnnounced => {
+        println!("Logout announced");
+        events.push(Event::LOGOUT);
+      }
+      events::TelnetEvents::Shutdown => {
+        println!("Shutdown");
+        events.push(Event::SHUTDOWN);
+      }
       events::TelnetEvents::DecompressImmediate(buffer) => {
         println!("DECOMPRESS: {:?}", buffer);
         events.push(Event::DECOM);
       }
+      events::TelnetEvents::DecompressClientStream(buffer) => {
+        println!("DECOMPRESS CLIENT: {:?}", buffer);
+        events.push(Event::DECOMCLIENT);
+      }
+      events::TelnetEvents::Encrypted(buffer) => {
+        println!("ENCRYPTED: {:?}", buffer);
+        events.push(Event::ENCRYPTED);
+      }
+      events::TelnetEvents::StartTls(buffer) => {
+        println!("START TLS: {:?}", buffer);
+        events.push(Event::STARTTLS);
+      }
+      other => panic!("unhandled event: {:?}", other),
     };
   }
   events
@@ -106,7 +277,10 @@ fn test_parser() {
     handle_events(instance.receive(&[b"Hello, rust!", &[255, 249][..]].concat())),
     events![Event::RECV, Event::IAC]
   );
-  assert_eq!(handle_events(instance.receive(&[255, 253, 201])), events![]);
+  assert_eq!(
+    handle_events(instance.receive(&[255, 253, 201])),
+    events![Event::NEGOTIATION, Event::COMPLETE]
+  );
   assert_eq!(
     handle_events(instance.receive(&[&[255, 253, 200][..], b"Some random data"].concat())),
     events![Event::SEND, Event::RECV]
@@ -221,7 +395,7 @@ fn test_subneg_utf8_content() {
 
     // The event should be a Subnegotiation for the GMCP option, with the correct in-tact
     // buffer contents.
-    if let TelnetEvents::Subnegotiation(sub) = events.get(0).unwrap() {
+    if let TelnetEvents::Subnegotiation(sub) = events.first().unwrap() {
         assert_eq!(sub.option, 201, "option should be GMCP");
         assert_eq!(
             sub.buffer, wave_emoji,
@@ -282,3 +456,3769 @@ fn test_bad_subneg_dbuffer() {
     cmd::SE,
   ]);
 }
+
+#[test]
+fn test_buffer_pool() {
+  use libtelnet_rs::pool::BufferPool;
+  use std::sync::{Arc, Mutex};
+
+  #[derive(Default)]
+  struct CountingPool {
+    free: Mutex<Vec<bytes::BytesMut>>,
+  }
+
+  impl BufferPool for CountingPool {
+    fn acquire(&self, capacity: usize) -> bytes::BytesMut {
+      self
+        .free
+        .lock()
+        .unwrap()
+        .pop()
+        .unwrap_or_else(|| bytes::BytesMut::with_capacity(capacity))
+    }
+    fn release(&self, mut buffer: bytes::BytesMut) {
+      buffer.clear();
+      self.free.lock().unwrap().push(buffer);
+    }
+  }
+
+  let pool = Arc::new(CountingPool::default());
+  {
+    let mut parser = Parser::with_pool(pool.clone());
+    assert_eq!(
+      handle_events(parser.receive(b"Hello, pool!")),
+      events![Event::RECV]
+    );
+  }
+  assert_eq!(pool.free.lock().unwrap().len(), 1);
+  {
+    // The options can still be moved out, and the buffer is returned once the rest of the parser is dropped.
+    let parser = Parser::with_pool(pool.clone());
+    assert_eq!(pool.free.lock().unwrap().len(), 0);
+    let options: CompatibilityTable = parser.options;
+    assert!(!options.get_option(opt::GMCP).enabled());
+  }
+  assert_eq!(pool.free.lock().unwrap().len(), 1);
+  let _parser = Parser::with_pool(pool.clone());
+  assert_eq!(pool.free.lock().unwrap().len(), 0);
+}
+
+#[test]
+fn test_buffer_pool_release() {
+  use libtelnet_rs::pool::BufferPool;
+  use std::sync::{Arc, Mutex};
+
+  // Records the allocation handed out, and the one given back.
+  #[derive(Default)]
+  struct RecordingPool {
+    acquired: Mutex<Option<(usize, usize)>>,
+    released: Mutex<Option<(usize, usize)>>,
+  }
+
+  impl BufferPool for RecordingPool {
+    fn acquire(&self, _capacity: usize) -> bytes::BytesMut {
+      let buffer = bytes::BytesMut::with_capacity(4096);
+      *self.acquired.lock().unwrap() = Some((buffer.as_ptr() as usize, buffer.capacity()));
+      buffer
+    }
+    fn release(&self, buffer: bytes::BytesMut) {
+      *self.released.lock().unwrap() = Some((buffer.as_ptr() as usize, buffer.capacity()));
+    }
+  }
+
+  let pool = Arc::new(RecordingPool::default());
+  {
+    let mut parser = Parser::builder()
+      .pool(pool.clone())
+      .max_subnegotiation_len(4)
+      .build();
+    parser.options.support_local(opt::GMCP);
+    parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+    assert_eq!(
+      handle_events(parser.receive(b"Hello\xff\xf9, pool!\xff")),
+      events![Event::RECV, Event::IAC, Event::RECV]
+    );
+    parser.receive(&[cmd::IAC, cmd::SB, opt::GMCP]);
+    parser.receive(b"Core.Ping and more");
+    parser.receive(&[cmd::IAC, cmd::SE]);
+    parser.receive(b"rest\xff");
+  }
+  let acquired = pool.acquired.lock().unwrap().unwrap();
+  assert_eq!(*pool.released.lock().unwrap(), Some(acquired));
+}
+
+#[test]
+fn test_mccp3_server_side() {
+  let mut server = Parser::new();
+  server.options.support_local(opt::MCCP3);
+  assert!(server._will(opt::MCCP3).is_some());
+  let events = server.receive(
+    &[
+      &[cmd::IAC, cmd::SB, opt::MCCP3, cmd::IAC, cmd::SE][..],
+      &[0x78, 0x9c, 0x01][..],
+    ]
+    .concat(),
+  );
+  match &events[..] {
+    [events::TelnetEvents::Subnegotiation(sub), events::TelnetEvents::DecompressClientStream(data)] =>
+    {
+      assert_eq!(sub.option, opt::MCCP3);
+      assert_eq!(&data[..], &[0x78, 0x9c, 0x01]);
+    }
+    _ => panic!("unexpected events: {:?}", events),
+  }
+}
+
+#[test]
+fn test_mccp1_legacy_terminator() {
+  for start in [
+    &[cmd::IAC, cmd::SB, opt::MCCP1, cmd::WILL, cmd::SE][..],
+    &[cmd::IAC, cmd::SB, opt::MCCP1, cmd::IAC, cmd::WILL, cmd::SE][..],
+  ] {
+    for mode in [ParserMode::Lenient, ParserMode::Strict] {
+      let mut client = Parser::builder().mode(mode).build();
+      client.options.support_local(opt::MCCP1);
+      client._will(opt::MCCP1);
+      let events = client.receive(&[b"Welcome!", start, b"compressed"].concat());
+      assert_eq!(
+        handle_events(events),
+        events![Event::RECV, Event::SUBNEGOTIATION, Event::DECOM]
+      );
+    }
+  }
+}
+
+#[test]
+fn test_rejected_start_keeps_data() {
+  use libtelnet_rs::events::{TelnetErrorKind, TelnetEvents};
+  use libtelnet_rs::ParserMode;
+
+  // The data and errors received, whether the bytes arrive in one call or one at a time.
+  fn received(parser: &mut Parser, data: &[u8], whole: bool) -> (Vec<u8>, Vec<TelnetErrorKind>) {
+    let events = if whole {
+      parser.receive(data)
+    } else {
+      data.iter().flat_map(|b| parser.receive(&[*b])).collect()
+    };
+    let mut text = Vec::new();
+    let mut errors = Vec::new();
+    for event in events {
+      match event {
+        TelnetEvents::DataReceive(data) => text.extend_from_slice(&data),
+        TelnetEvents::Error(kind, _) => errors.push(kind),
+        event => panic!("unexpected event {:?}", event),
+      }
+    }
+    (text, errors)
+  }
+
+  type NewParser = fn() -> Parser;
+  let cases: [(NewParser, Vec<u8>, TelnetErrorKind); 3] = [
+    // MCCP2 is not enabled.
+    (
+      Parser::new,
+      vec![cmd::IAC, cmd::SB, opt::MCCP2, cmd::IAC, cmd::SE],
+      TelnetErrorKind::OptionNotEnabled,
+    ),
+    // The start is longer than the subnegotiation limit.
+    (
+      || Parser::builder().max_subnegotiation_len(1).build(),
+      vec![cmd::IAC, cmd::SB, opt::MCCP2, 1, 2, cmd::IAC, cmd::SE],
+      TelnetErrorKind::Overflow,
+    ),
+    // The start holds a command, which strict mode rejects.
+    (
+      || Parser::builder().mode(ParserMode::Strict).build(),
+      vec![
+        cmd::IAC,
+        cmd::SB,
+        opt::MCCP2,
+        cmd::IAC,
+        cmd::NOP,
+        cmd::IAC,
+        cmd::SE,
+      ],
+      TelnetErrorKind::Malformed,
+    ),
+  ];
+  for (parser, start, error) in cases {
+    let data = [&start[..], b"after"].concat();
+    for whole in [true, false] {
+      assert_eq!(
+        received(&mut parser(), &data, whole),
+        (b"after".to_vec(), vec![error]),
+        "whole: {}",
+        whole
+      );
+    }
+  }
+}
+
+#[test]
+fn test_prompt_repaint() {
+  let mut server = Parser::new();
+  let prompt: Bytes = server.send_prompt("HP: 10> ").into();
+  assert_eq!(
+    &prompt[..],
+    &[&b"HP: 10> "[..], &[cmd::IAC, cmd::GA]].concat()[..]
+  );
+  let sent: Bytes = server.send_async_text("A goblin arrives.").into();
+  assert_eq!(
+    &sent[..],
+    &[
+      &b"\r\nA goblin arrives.\r\nHP: 10> "[..],
+      &[cmd::IAC, cmd::GA]
+    ]
+    .concat()[..]
+  );
+  server.receive(b"look\r\n");
+  let sent: Bytes = server.send_async_text("A goblin leaves.").into();
+  assert_eq!(&sent[..], b"A goblin leaves.\r\n");
+}
+
+#[test]
+fn test_negotiation_complete() {
+  use events::TelnetEvents;
+
+  let mut parser = Parser::new();
+  parser.options.support(opt::GMCP);
+  parser.options.support(opt::NAWS);
+  parser._will(opt::GMCP);
+  parser._do(opt::NAWS);
+  assert!(parser.negotiation_pending(opt::GMCP, true));
+  assert!(parser.negotiation_pending(opt::NAWS, false));
+
+  // Answers to our own requests must not be acknowledged again.
+  let events = parser.receive(&[
+    cmd::IAC,
+    cmd::DONT,
+    opt::GMCP,
+    cmd::IAC,
+    cmd::WILL,
+    opt::NAWS,
+  ]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![
+      Event::NEGOTIATION,
+      Event::COMPLETE,
+      Event::NEGOTIATION,
+      Event::COMPLETE
+    ]
+  );
+  match (&events[1], &events[3]) {
+    (TelnetEvents::NegotiationComplete(gmcp), TelnetEvents::NegotiationComplete(naws)) => {
+      assert_eq!(
+        (gmcp.option, gmcp.accepted, gmcp.local),
+        (opt::GMCP, false, true)
+      );
+      assert_eq!(
+        (naws.option, naws.accepted, naws.local),
+        (opt::NAWS, true, false)
+      );
+    }
+    _ => panic!("unexpected events: {:?}", events),
+  }
+  assert!(!parser.options.get_option(opt::GMCP).local_state);
+  assert!(parser.options.get_option(opt::NAWS).remote_state);
+  assert!(!parser.negotiation_pending(opt::GMCP, true));
+  assert!(!parser.negotiation_pending(opt::NAWS, false));
+}
+
+#[test]
+fn test_environ_info() {
+  use libtelnet_rs::telnet::environ::{self, EnvironKind};
+
+  let mut client = Parser::new();
+  client.options.support_local(opt::NEWENVIRON);
+  let mut server = Parser::new();
+  server.options.support_remote(opt::NEWENVIRON);
+
+  assert!(client
+    .set_environ_var(EnvironKind::Var, "IPADDRESS", Some("10.0.0.1"))
+    .is_none());
+  let will: Bytes = client
+    .receive(&[cmd::IAC, cmd::DO, opt::NEWENVIRON])
+    .remove(0)
+    .into();
+  server.receive(&will);
+
+  let send: Bytes = server.request_environ(&[]).unwrap().into();
+  assert_eq!(
+    &send[..],
+    &[
+      cmd::IAC,
+      cmd::SB,
+      opt::NEWENVIRON,
+      environ::SEND,
+      cmd::IAC,
+      cmd::SE
+    ]
+  );
+  let mut events = client.receive(&send);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::SEND]
+  );
+  let is: Bytes = events.remove(1).into();
+  assert_eq!(
+    handle_events(server.receive(&is)),
+    events![Event::SUBNEGOTIATION, Event::ENVIRON]
+  );
+  assert_eq!(
+    server.remote_environ_var(EnvironKind::Var, "IPADDRESS"),
+    Some("10.0.0.1")
+  );
+
+  // Unchanged values don't generate an update, changed ones do.
+  assert!(client
+    .set_environ_var(EnvironKind::Var, "IPADDRESS", Some("10.0.0.1"))
+    .is_none());
+  let info: Bytes = client
+    .set_environ_var(EnvironKind::Var, "IPADDRESS", Some("10.0.0.2"))
+    .unwrap()
+    .into();
+  assert_eq!(
+    handle_events(server.receive(&info)),
+    events![Event::SUBNEGOTIATION, Event::ENVIRON]
+  );
+  assert_eq!(
+    server.remote_environ_var(EnvironKind::Var, "IPADDRESS"),
+    Some("10.0.0.2")
+  );
+}
+
+#[test]
+fn test_receive_into_sink() {
+  use libtelnet_rs::sink::EventSink;
+
+  #[derive(Default)]
+  struct DataCounter {
+    data: usize,
+    other: usize,
+  }
+
+  impl EventSink for DataCounter {
+    fn push(&mut self, event: events::TelnetEvents) {
+      match event {
+        events::TelnetEvents::DataReceive(data) => self.data += data.len(),
+        _ => self.other += 1,
+      }
+    }
+  }
+
+  let mut parser = Parser::new();
+  let mut counter = DataCounter::default();
+  parser.receive_into(b"Hello", &mut counter);
+  parser.receive_into(
+    &[b" world", &[cmd::IAC, cmd::GA][..]].concat(),
+    &mut counter,
+  );
+  assert_eq!((counter.data, counter.other), (11, 1));
+
+  let mut queue = std::collections::VecDeque::new();
+  parser.receive_into(&[cmd::IAC, cmd::WILL, opt::ECHO], &mut queue);
+  assert_eq!(handle_events(queue.into()), events![Event::SEND]);
+}
+
+#[test]
+fn test_negotiation_loop_guard() {
+  let mut parser = Parser::builder().negotiation_limit(2).build();
+  let will = [cmd::IAC, cmd::WILL, opt::GMCP];
+  assert_eq!(handle_events(parser.receive(&will)), events![Event::SEND]);
+  assert_eq!(handle_events(parser.receive(&will)), events![Event::SEND]);
+  assert_eq!(handle_events(parser.receive(&will)), events![Event::ERROR]);
+  assert_eq!(handle_events(parser.receive(&will)), events![]);
+
+  // Other options are unaffected.
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::WILL, opt::NAWS])),
+    events![Event::SEND]
+  );
+}
+
+#[test]
+fn test_receive_with_handler() {
+  use libtelnet_rs::handler::TelnetHandler;
+  use libtelnet_rs::telnet::TelnetCommand;
+
+  #[derive(Default)]
+  struct Recorder {
+    data: Vec<u8>,
+    commands: Vec<TelnetCommand>,
+    sent: Vec<Bytes>,
+  }
+
+  impl TelnetHandler for Recorder {
+    fn on_data(&mut self, data: Bytes) {
+      self.data.extend_from_slice(&data);
+    }
+    fn on_iac(&mut self, iac: events::TelnetIAC) {
+      self.commands.push(iac.command);
+    }
+    fn on_send(&mut self, data: Bytes) {
+      self.sent.push(data);
+    }
+  }
+
+  let mut parser = Parser::new();
+  let mut recorder = Recorder::default();
+  parser.receive_with(
+    &[
+      &b"Name? "[..],
+      &[cmd::IAC, cmd::GA, cmd::IAC, cmd::DO, opt::ECHO][..],
+    ]
+    .concat(),
+    &mut recorder,
+  );
+  assert_eq!(recorder.data, b"Name? ");
+  assert_eq!(recorder.commands, vec![TelnetCommand::GoAhead]);
+  assert_eq!(
+    recorder.sent,
+    vec![vbytes!(&[cmd::IAC, cmd::WONT, opt::ECHO])]
+  );
+}
+
+#[cfg(feature = "legacy-vec-api")]
+#[test]
+fn test_legacy_vec_api() {
+  use libtelnet_rs::legacy;
+
+  let mut parser = Parser::new();
+  parser.options.support_local(opt::GMCP);
+  parser._will(opt::GMCP);
+  let events = parser.receive_legacy(
+    &[
+      &b"hi"[..],
+      &events::TelnetSubnegotiation::new(opt::GMCP, vbytes!(b"Core.Ping")).into_bytes(),
+    ]
+    .concat(),
+  );
+  match &events[..] {
+    [legacy::TelnetEvents::DataReceive(data), legacy::TelnetEvents::Subnegotiation(sub)] => {
+      assert_eq!(data, &b"hi".to_vec());
+      assert_eq!(sub.buffer, b"Core.Ping".to_vec());
+    }
+    _ => panic!("unexpected events: {:?}", events),
+  }
+  let events = parser.receive_legacy(&[cmd::IAC, cmd::GA, cmd::IAC, cmd::DONT, opt::GMCP]);
+  match &events[..] {
+    [legacy::TelnetEvents::IAC(iac), legacy::TelnetEvents::Negotiation(neg), ..] => {
+      assert_eq!(iac.command, cmd::GA);
+      assert_eq!((neg.command, neg.option), (cmd::DONT, opt::GMCP));
+    }
+    _ => panic!("unexpected events: {:?}", events),
+  }
+  assert_eq!(
+    legacy::TelnetNegotiation::new(cmd::DO, opt::NAWS).into_bytes(),
+    vec![cmd::IAC, cmd::DO, opt::NAWS]
+  );
+  assert_eq!(Parser::escape_iac_vec(&[255, 1]), vec![255, 255, 1]);
+}
+
+#[test]
+fn test_feed_and_next_event() {
+  let mut parser = Parser::new();
+  parser.feed(b"Hello");
+  parser.feed(&[cmd::IAC, cmd::GA, cmd::IAC, cmd::WILL, opt::ECHO]);
+  assert!(matches!(
+    parser.next_event(),
+    Some(events::TelnetEvents::DataReceive(_))
+  ));
+  assert_eq!(
+    handle_events(parser.events().collect()),
+    events![Event::IAC, Event::SEND]
+  );
+  assert!(parser.next_event().is_none());
+
+  // Incomplete sequences wait for more data.
+  parser.feed(&[cmd::IAC, cmd::SB, opt::GMCP, b'x']);
+  assert!(parser.next_event().is_none());
+}
+
+#[test]
+fn test_subnegotiation_refusal() {
+  use libtelnet_rs::error::SubnegotiationRefusal;
+
+  let mut parser = Parser::new();
+  assert_eq!(
+    parser
+      .try_subnegotiation(opt::GMCP, vbytes!(b"Core.Ping"))
+      .err(),
+    Some(SubnegotiationRefusal::Unsupported)
+  );
+  parser.options.support_local(opt::GMCP);
+  assert_eq!(
+    parser
+      .try_subnegotiation(opt::GMCP, vbytes!(b"Core.Ping"))
+      .err(),
+    Some(SubnegotiationRefusal::Disabled)
+  );
+  parser._will(opt::GMCP);
+  assert_eq!(
+    parser
+      .try_subnegotiation(opt::GMCP, vbytes!(b"Core.Ping"))
+      .err(),
+    Some(SubnegotiationRefusal::Pending)
+  );
+  assert!(parser
+    .subnegotiation(opt::GMCP, vbytes!(b"Core.Ping"))
+    .is_none());
+  parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+  assert!(parser
+    .try_subnegotiation(opt::GMCP, vbytes!(b"Core.Ping"))
+    .is_ok());
+
+  let forced: Bytes = parser
+    .force_subnegotiation(opt::NAWS, vbytes!(&[0, 80, 0, 24]))
+    .into();
+  assert_eq!(
+    &forced[..],
+    &[
+      cmd::IAC,
+      cmd::SB,
+      opt::NAWS,
+      0,
+      80,
+      0,
+      24,
+      cmd::IAC,
+      cmd::SE
+    ]
+  );
+}
+
+#[test]
+fn test_queued_subnegotiations() {
+  use libtelnet_rs::error::SubnegotiationRefusal;
+
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  table.support_local(opt::MSSP);
+  let mut parser = Parser::builder()
+    .support(table)
+    .queue_subnegotiations(true)
+    .build();
+  parser._will(opt::GMCP);
+  parser._will(opt::MSSP);
+  assert_eq!(
+    parser
+      .try_subnegotiation(opt::GMCP, vbytes!(b"Core.Hello {}"))
+      .err(),
+    Some(SubnegotiationRefusal::Queued)
+  );
+  assert!(parser.subnegotiation(opt::MSSP, vbytes!(b"x")).is_none());
+
+  let events = parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::NEGOTIATION, Event::COMPLETE, Event::SEND]
+  );
+  let sent: Bytes = events[2].clone().into();
+  assert_eq!(
+    sent,
+    Bytes::from(events::TelnetSubnegotiation::new(
+      opt::GMCP,
+      vbytes!(b"Core.Hello {}")
+    ))
+  );
+
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::DONT, opt::MSSP])),
+    events![Event::NEGOTIATION, Event::COMPLETE, Event::ERROR]
+  );
+}
+
+#[test]
+fn test_receive_into_reused_vec() {
+  let mut parser = Parser::new();
+  let mut events = Vec::with_capacity(8);
+  for _ in 0..4 {
+    events.clear();
+    parser.receive_into(&[b'a', cmd::IAC, cmd::GA, b'b'], &mut events);
+    assert_eq!(
+      handle_events(events.clone()),
+      events![Event::RECV, Event::IAC, Event::RECV]
+    );
+  }
+  assert_eq!(events.capacity(), 8);
+}
+
+#[test]
+fn test_fixtures() {
+  use libtelnet_rs::fixtures::{fixture, fixtures};
+
+  let mut table = compatibility::CompatibilityTable::new();
+  for option in 0..=255 {
+    table.support(option);
+  }
+  for fixture in fixtures() {
+    let mut parser = Parser::with_support(table.clone());
+    parser.receive(fixture.input);
+    let mut parser = Parser::with_support(table.clone());
+    for byte in fixture.input {
+      parser.receive(&[*byte]);
+    }
+  }
+  assert!(fixture("truncated_subnegotiation").is_some());
+  assert!(fixture("no_such_fixture").is_none());
+}
+
+#[test]
+fn test_zero_copy_payloads() {
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let mut parser = Parser::with_support(table);
+  parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+  let events = parser.receive(
+    &[
+      &b"hello"[..],
+      &[cmd::IAC, cmd::SB, opt::GMCP],
+      b"Core.Ping",
+      &[cmd::IAC, cmd::SE],
+    ]
+    .concat(),
+  );
+  let (data, sub) = match (&events[0], &events[1]) {
+    (events::TelnetEvents::DataReceive(data), events::TelnetEvents::Subnegotiation(sub)) => {
+      (data, &sub.buffer)
+    }
+    _ => panic!("unexpected events: {:?}", events),
+  };
+  assert_eq!(&data[..], b"hello");
+  assert_eq!(&sub[..], b"Core.Ping");
+  // Both payloads are views into the same received buffer.
+  assert_eq!(data.as_ptr() as usize + 8, sub.as_ptr() as usize);
+}
+
+#[test]
+fn test_mark_urgent() {
+  let data_of = |events: Vec<events::TelnetEvents>| -> Vec<u8> {
+    events
+      .into_iter()
+      .filter_map(|event| match event {
+        events::TelnetEvents::DataReceive(data) => Some(data.to_vec()),
+        _ => None,
+      })
+      .flatten()
+      .collect()
+  };
+  let mut parser = Parser::new();
+  parser.mark_urgent(8);
+  // The first Data Mark ends before the urgent pointer, so only the second one ends the SYNCH.
+  let events = parser.receive(&[
+    b'a',
+    b'b',
+    cmd::IAC,
+    cmd::DM,
+    b'c',
+    b'd',
+    cmd::IAC,
+    cmd::DM,
+    b'e',
+  ]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::DATAMARK, Event::DATAMARK, Event::RECV]
+  );
+  assert_eq!(data_of(events), b"e");
+
+  // The urgent pointer passed in an earlier chunk, so the next Data Mark ends the SYNCH.
+  parser.mark_urgent(2);
+  assert!(data_of(parser.receive(b"xyz")).is_empty());
+  let events = parser.receive(&[cmd::IAC, cmd::NOP, b'q', cmd::IAC, cmd::DM, b'r']);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::IAC, Event::DATAMARK, Event::RECV]
+  );
+  assert_eq!(data_of(events), b"r");
+
+  // Urgent data reported without a position discards everything up to the next Data Mark.
+  parser.receive(&[b's', cmd::IAC]);
+  parser.synch();
+  let events = parser.receive(&[cmd::IP, b't', cmd::IAC, cmd::DM, b'u']);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::IAC, Event::DATAMARK, Event::RECV]
+  );
+  assert_eq!(data_of(events), b"u");
+}
+
+#[test]
+fn test_long_data_scan() {
+  for at in 0..40 {
+    let mut data = vec![b'x'; 40];
+    data.splice(at..at, [cmd::IAC, cmd::GA]);
+    let mut parser = Parser::new();
+    let events = parser.receive(&data);
+    let expected = match at {
+      0 => events![Event::IAC, Event::RECV],
+      _ => events![Event::RECV, Event::IAC, Event::RECV],
+    };
+    assert_eq!(handle_events(events.clone()), expected);
+    if let events::TelnetEvents::DataReceive(first) = &events[0] {
+      assert_eq!(first.len(), at);
+    }
+  }
+}
+
+#[test]
+fn test_plain_data_fast_path() {
+  let mut parser = Parser::new();
+  let events = parser.receive(b"no commands here\r\n");
+  assert_eq!(handle_events(events.clone()), events![Event::RECV]);
+  assert_eq!(
+    Bytes::from(events[0].clone()),
+    Bytes::from_static(b"no commands here\r\n")
+  );
+
+  // Anything left over from an earlier chunk still goes through the parser.
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let mut parser = Parser::with_support(table);
+  parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+  assert!(parser.receive(&[cmd::IAC, cmd::SB, opt::GMCP]).is_empty());
+  assert!(parser.receive(b"Core.Ping").is_empty());
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::SE])),
+    events![Event::SUBNEGOTIATION]
+  );
+}
+
+#[test]
+fn test_mirror_outbound() {
+  let mut parser = Parser::builder().mirror_outbound(true).build();
+  let events = parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SEND, Event::OUTBOUND]
+  );
+  match &events[1] {
+    events::TelnetEvents::Outbound(event) => match **event {
+      events::TelnetEvents::Negotiation(neg) => {
+        assert_eq!(
+          (neg.command, neg.option),
+          (telnet::TelnetCommand::Wont, opt::GMCP)
+        )
+      }
+      _ => panic!("expected a negotiation"),
+    },
+    _ => panic!("expected an outbound event"),
+  }
+  assert_eq!(Bytes::from(events[1].clone()), Bytes::new());
+
+  let mut parser = Parser::new();
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP])),
+    events![Event::SEND]
+  );
+}
+
+#[test]
+fn test_max_subnegotiation_len() {
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let mut parser = Parser::builder()
+    .support(table)
+    .max_subnegotiation_len(8)
+    .build();
+  parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+
+  // Complete, but too long.
+  let events = parser.receive(
+    &[
+      &[cmd::IAC, cmd::SB, opt::GMCP][..],
+      b"123456789",
+      &[cmd::IAC, cmd::SE],
+    ]
+    .concat(),
+  );
+  assert_eq!(handle_events(events), events![Event::ERROR]);
+
+  // Unterminated, and discarded up to its IAC SE across several calls.
+  let events = parser.receive(&[&[cmd::IAC, cmd::SB, opt::GMCP][..], b"0123456789"].concat());
+  assert_eq!(handle_events(events), events![Event::ERROR]);
+  assert!(parser.receive(b"more payload").is_empty());
+  assert!(parser
+    .receive(&[b'x', cmd::IAC, cmd::IAC, cmd::IAC])
+    .is_empty());
+  let events = parser.receive(&[cmd::SE, b'o', b'k']);
+  assert_eq!(handle_events(events.clone()), events![Event::RECV]);
+  assert_eq!(Bytes::from(events[0].clone()), Bytes::from_static(b"ok"));
+
+  // Within the limit.
+  let events = parser.receive(
+    &[
+      &[cmd::IAC, cmd::SB, opt::GMCP][..],
+      b"12345678",
+      &[cmd::IAC, cmd::SE],
+    ]
+    .concat(),
+  );
+  assert_eq!(handle_events(events), events![Event::SUBNEGOTIATION]);
+}
+
+#[test]
+fn test_fingerprint() {
+  use libtelnet_rs::fingerprint::{PeerStack, TimingBucket};
+
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_remote(opt::TTYPE);
+  let mut parser = Parser::builder().support(table).fingerprint(true).build();
+  parser._do(opt::TTYPE);
+  parser.receive(&[cmd::IAC, cmd::WILL, opt::TTYPE]);
+  let events = parser.receive(
+    &[
+      &[cmd::IAC, cmd::SB, opt::TTYPE, cmd::IS][..],
+      b"TINTIN++",
+      &[cmd::IAC, cmd::SE],
+    ]
+    .concat(),
+  );
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::PEERSTACK]
+  );
+  assert!(matches!(
+    events[1],
+    events::TelnetEvents::PeerStack(PeerStack::TinTin)
+  ));
+  assert_eq!(parser.peer_stack(), Some(PeerStack::TinTin));
+
+  // A slow reply ends the opening, so later negotiations are not part of it.
+  for (delay, guess) in [
+    (TimingBucket::Immediate, Some(PeerStack::WindowsTelnet)),
+    (TimingBucket::Slow, None),
+  ] {
+    let mut parser = Parser::builder().fingerprint(true).build();
+    parser.receive(&[cmd::IAC, cmd::DO, opt::SGA]);
+    parser.observe_delay(delay);
+    parser.receive(&[cmd::IAC, cmd::WILL, opt::AUTHENTICATION]);
+    assert_eq!(parser.peer_stack(), guess);
+  }
+
+  let mut parser = Parser::new();
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::WILL, opt::AUTHENTICATION])),
+    events![Event::SEND]
+  );
+  parser.observe_delay(TimingBucket::Slow);
+  assert_eq!(parser.peer_stack(), None);
+}
+
+#[test]
+fn test_flush() {
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let mut parser = Parser::with_support(table);
+  parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+  assert!(parser.flush().is_empty());
+
+  assert!(parser
+    .receive(&[&[cmd::IAC, cmd::SB, opt::GMCP][..], b"Core.Goodbye"].concat())
+    .is_empty());
+  let events = parser.flush();
+  assert_eq!(handle_events(events.clone()), events![Event::ERROR]);
+  assert!(matches!(
+    events[0],
+    events::TelnetEvents::Error(events::TelnetErrorKind::Truncated, _)
+  ));
+  assert!(parser.flush().is_empty());
+
+  parser.feed(b"unread");
+  assert_eq!(handle_events(parser.flush()), events![Event::RECV]);
+}
+
+#[test]
+fn test_reset() {
+  let mut table = CompatibilityTable::new();
+  table.support(opt::GMCP);
+  table.support(opt::NAWS);
+  let mut parser = Parser::with_support(table);
+  assert!(parser.is_idle());
+  parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP, cmd::IAC, cmd::WILL, opt::NAWS]);
+  parser.receive(&[b'h', b'i', cmd::IAC, cmd::SB, opt::GMCP, b'C', b'o']);
+  assert_eq!(parser.pending_len(), 5);
+  assert!(parser.has_pending_subnegotiation());
+  assert!(!parser.is_idle());
+
+  parser.reset(false);
+  assert!(parser.is_idle());
+  assert!(!parser.has_pending_subnegotiation());
+  assert!(parser.options.get_option(opt::GMCP).local_state);
+  assert_eq!(handle_events(parser.receive(b"re")), events![Event::RECV]);
+
+  parser.receive(&[cmd::IAC, cmd::SB, opt::NAWS, 0, 80]);
+  assert_eq!(parser.pending_len(), 5);
+  parser.reset(true);
+  assert_eq!(parser.pending_len(), 0);
+  let gmcp = parser.options.get_option(opt::GMCP);
+  assert!(gmcp.local && !gmcp.local_state);
+  assert!(!parser.options.get_option(opt::NAWS).remote_state);
+}
+
+#[test]
+fn test_text_encoder() {
+  use libtelnet_rs::charset::{Latin1, Unmappable};
+
+  let mut parser = Parser::new();
+  parser.set_text_encoder(Latin1, Unmappable::Replace(b'?'));
+  assert_eq!(parser.text_encoding(), Some("ISO-8859-1"));
+  assert_eq!(
+    Bytes::from(parser.send_text("ÿ €")),
+    Bytes::from_static(b"\xff\xff ?\r\n")
+  );
+  parser.clear_text_encoder();
+  assert_eq!(
+    Bytes::from(parser.send_text("é")),
+    Bytes::from_static("é\r\n".as_bytes())
+  );
+}
+
+#[test]
+fn test_simulate() {
+  use libtelnet_rs::simulate::{Action, Boundaries, Observed, Peer, Simulation};
+
+  let mut server = compatibility::CompatibilityTable::new();
+  server.support_local(opt::GMCP);
+  let mut client = compatibility::CompatibilityTable::new();
+  client.support_remote(opt::GMCP);
+  let simulation = Simulation::new(server, client.clone())
+    .action(Action::Will(Peer::A, opt::GMCP))
+    .action(Action::Split(Peer::A, vec![4, 9]))
+    .action(Action::Subnegotiate(
+      Peer::A,
+      opt::GMCP,
+      b"Core.Hello {}".to_vec(),
+    ))
+    .action(Action::Drop(Peer::A, 2))
+    .action(Action::SendText(Peer::A, "??Welcome!".into()));
+  let transcript = simulation.run(Boundaries::Whole).unwrap();
+  assert_eq!(transcript.b.len(), 4);
+  assert_eq!(transcript.b[3], Observed::Data(b"Welcome!\r\n".to_vec()));
+  assert_eq!(simulation.run(Boundaries::At(4)), Ok(transcript));
+
+  let simulation = Simulation::new(compatibility::CompatibilityTable::new(), client)
+    .action(Action::Raw(Peer::A, vec![cmd::IAC, cmd::NOP]));
+  assert!(simulation.run_all().is_ok());
+}
+
+#[test]
+fn test_split_commands() {
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support(opt::GMCP);
+  let sequences: [&[u8]; 4] = [
+    &[cmd::IAC, cmd::WILL, opt::GMCP],
+    &[cmd::IAC, cmd::DO, opt::GMCP],
+    &[b'a', cmd::IAC, cmd::GA, b'b'],
+    &[cmd::IAC, cmd::SB, opt::GMCP, b'x', cmd::IAC, cmd::SE],
+  ];
+  for sequence in sequences.iter() {
+    let expected = handle_events(Parser::with_support(table.clone()).receive(sequence));
+    for split in 1..sequence.len() {
+      let mut parser = Parser::with_support(table.clone());
+      let mut events = parser.receive(&sequence[..split]);
+      events.extend(parser.receive(&sequence[split..]));
+      let mut captured = handle_events(events);
+      // Data on either side of the split arrives as separate events.
+      captured
+        .events
+        .dedup_by(|a, b| *a == Event::RECV && *b == Event::RECV);
+      assert_eq!(captured, expected, "{:?} split at {}", sequence, split);
+    }
+  }
+}
+
+#[test]
+fn test_outbound_priority() {
+  use libtelnet_rs::outbound::{OutboundQueue, Priority};
+
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let mut parser = Parser::with_support(table);
+  let mut queue = OutboundQueue::new();
+  assert!(queue
+    .push_event(parser.send_text("A long room description"))
+    .is_none());
+  assert!(queue.push_event(parser.send_text("\u{ff} more")).is_none());
+  assert!(queue.push_event(parser.send_prompt("HP: 10>")).is_none());
+  assert!(queue.push_event(parser._will(opt::GMCP).unwrap()).is_none());
+  assert!(queue
+    .push_event(events::TelnetEvents::build_receive(Bytes::from_static(
+      b"x"
+    )))
+    .is_some());
+
+  let order: Vec<Priority> = queue.drain().map(|(priority, _)| priority).collect();
+  assert_eq!(
+    order,
+    vec![
+      Priority::Control,
+      Priority::Prompt,
+      Priority::Bulk,
+      Priority::Bulk
+    ]
+  );
+  assert!(queue.is_empty());
+}
+
+#[test]
+fn test_replay_buffer() {
+  use libtelnet_rs::replay::{ReplayBuffer, ReplayLimit};
+
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let mut parser = Parser::with_support(table);
+  let mut replay = ReplayBuffer::new(ReplayLimit::Lines(2));
+  assert!(replay.replay().is_none());
+  replay.record(&parser.send_text("one"));
+  replay.record(&parser._will(opt::GMCP).unwrap());
+  replay.record(&events::TelnetEvents::build_send(Parser::escape_iac(
+    &b"two \xff\r\n"[..],
+  )));
+  replay.record(&events::TelnetEvents::build_send(Bytes::from_static(&[
+    cmd::IAC,
+    cmd::SB,
+    opt::GMCP,
+    b'x',
+    cmd::IAC,
+    cmd::IAC,
+    cmd::IAC,
+    cmd::SE,
+  ])));
+  replay.record(&parser.send_text("three"));
+  replay.record(&parser.send_prompt("> "));
+  assert_eq!(
+    Bytes::from(replay.replay().unwrap()),
+    Bytes::from_static(b"two \xff\xff\r\nthree\r\n> ")
+  );
+
+  let mut replay = ReplayBuffer::new(ReplayLimit::Bytes(4));
+  replay.record(&parser.send_text("hello"));
+  assert_eq!(
+    Bytes::from(replay.replay().unwrap()),
+    Bytes::from_static(b"lo\r\n")
+  );
+}
+
+#[test]
+fn test_escaped_iac_data() {
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support(opt::BINARY);
+  let payloads: [&[u8]; 4] = [
+    &[0xff],
+    &[0xff, 0xff, 0xff],
+    &[b'a', 0xff, 0x00, 0xff, b'b'],
+    &[0xff, cmd::WILL, opt::ECHO, 0xff, cmd::SB],
+  ];
+  for payload in payloads.iter() {
+    let mut parser = Parser::with_support(table.clone());
+    parser.receive(&[cmd::IAC, cmd::WILL, opt::BINARY]);
+    let wire = Parser::escape_iac(payload.to_vec());
+    let events = parser.receive(&wire);
+    assert_eq!(handle_events(events.clone()), events![Event::RECV]);
+    assert_eq!(&Bytes::from(events[0].clone())[..], *payload);
+
+    // The same bytes, split at every point.
+    for split in 1..wire.len() {
+      let mut data = Vec::new();
+      for chunk in [&wire[..split], &wire[split..]].iter() {
+        for event in parser.receive(chunk) {
+          match event {
+            events::TelnetEvents::DataReceive(bytes) => data.extend_from_slice(&bytes),
+            event => panic!("unexpected event {:?}", event),
+          }
+        }
+      }
+      assert_eq!(&data[..], *payload, "split at {}", split);
+    }
+  }
+}
+
+#[test]
+fn test_shutdown_sequence() {
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::MCCP2);
+  table.support_remote(opt::NAWS);
+  table.support_local(opt::GMCP);
+  let mut parser = Parser::with_support(table);
+  parser.receive(&[
+    cmd::IAC,
+    cmd::DO,
+    opt::MCCP2,
+    cmd::IAC,
+    cmd::WILL,
+    opt::NAWS,
+  ]);
+  let events = parser.shutdown_sequence();
+  assert_eq!(
+    handle_events(events.clone()),
+    events![
+      Event::ENDCOMPRESSION,
+      Event::SEND,
+      Event::SEND,
+      Event::SHUTDOWN
+    ]
+  );
+  assert_eq!(
+    Bytes::from(events[1].clone()),
+    Bytes::from_static(&[cmd::IAC, cmd::DONT, opt::NAWS])
+  );
+  assert_eq!(
+    Bytes::from(events[2].clone()),
+    Bytes::from_static(&[cmd::IAC, cmd::WONT, opt::MCCP2])
+  );
+  assert_eq!(
+    handle_events(parser.shutdown_sequence()),
+    events![Event::SHUTDOWN]
+  );
+}
+
+#[test]
+fn test_compression_stats() {
+  use libtelnet_rs::compression::Direction;
+
+  let mut parser = Parser::builder().compression_stats_interval(1000).build();
+  assert!(parser.compression_stats().inbound.ratio().is_none());
+  assert!(parser
+    .record_compression(Direction::Outbound, 100, 600)
+    .is_none());
+  let event = parser.record_compression(Direction::Inbound, 50, 400);
+  assert_eq!(
+    handle_events(event.into_iter().collect()),
+    events![Event::STATS]
+  );
+  assert!(parser
+    .record_compression(Direction::Inbound, 1, 4)
+    .is_none());
+
+  let stats = parser.compression_stats();
+  assert_eq!(stats.outbound.ratio(), Some(6.0));
+  assert_eq!(stats.direction(Direction::Inbound).compressed, 51);
+  assert_eq!(stats.inbound.uncompressed, 404);
+}
+
+#[test]
+fn test_commands_inside_subnegotiation() {
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let mut parser = Parser::with_support(table);
+  parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+  let payload = |events: &[events::TelnetEvents], index: usize| match &events[index] {
+    events::TelnetEvents::Subnegotiation(sub) => sub.buffer.clone(),
+    event => panic!("expected a subnegotiation, got {:?}", event),
+  };
+
+  // A NOP keepalive and escaped IAC bytes, including an escaped IAC followed by an SE data byte.
+  let events = parser.receive(&[
+    cmd::IAC,
+    cmd::SB,
+    opt::GMCP,
+    b'a',
+    cmd::IAC,
+    cmd::NOP,
+    b'b',
+    cmd::IAC,
+    cmd::IAC,
+    cmd::SE,
+    cmd::IAC,
+    cmd::SE,
+  ]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION]
+  );
+  assert_eq!(&payload(&events, 0)[..], &[b'a', b'b', cmd::IAC, cmd::SE]);
+
+  // A new subnegotiation before the first was finished.
+  let events = parser.receive(&[
+    cmd::IAC,
+    cmd::SB,
+    opt::GMCP,
+    b'x',
+    cmd::IAC,
+    cmd::SB,
+    opt::GMCP,
+    b'y',
+    cmd::IAC,
+    cmd::SE,
+  ]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::ERROR, Event::SUBNEGOTIATION]
+  );
+  assert!(matches!(
+    events[0],
+    events::TelnetEvents::Error(events::TelnetErrorKind::InterruptedSubnegotiation, _)
+  ));
+  assert_eq!(&payload(&events, 1)[..], b"y");
+
+  // A negotiation before IAC SE is handled as a negotiation.
+  let events = parser.receive(&[
+    cmd::IAC,
+    cmd::SB,
+    opt::GMCP,
+    b'x',
+    cmd::IAC,
+    cmd::WILL,
+    opt::ECHO,
+  ]);
+  assert_eq!(handle_events(events), events![Event::ERROR, Event::SEND]);
+}
+
+#[test]
+fn test_command_filters() {
+  let mut parser = Parser::builder()
+    .deny_commands(&[cmd::BREAK, cmd::IP])
+    .build();
+  let events = parser.receive(&[cmd::IAC, cmd::IP, b'x', cmd::IAC, cmd::NOP]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::ERROR, Event::RECV, Event::IAC]
+  );
+  assert!(matches!(
+    events[0],
+    events::TelnetEvents::Error(events::TelnetErrorKind::DeniedCommand, _)
+  ));
+
+  let mut parser = Parser::builder().allow_commands(&[cmd::GA]).build();
+  assert_eq!(
+    handle_events(parser.receive(&[
+      cmd::IAC,
+      cmd::GA,
+      cmd::IAC,
+      cmd::BREAK,
+      cmd::IAC,
+      cmd::WILL,
+      opt::ECHO
+    ])),
+    events![Event::IAC, Event::ERROR, Event::SEND]
+  );
+}
+
+#[test]
+fn test_parser_modes() {
+  use libtelnet_rs::ParserMode;
+
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let input = [
+    &[cmd::IAC, cmd::DO, opt::GMCP, cmd::IAC, 7, cmd::IAC, cmd::SE][..],
+    &[
+      cmd::IAC,
+      cmd::SB,
+      opt::GMCP,
+      b'a',
+      cmd::IAC,
+      cmd::NOP,
+      cmd::IAC,
+      cmd::SE,
+    ],
+  ]
+  .concat();
+
+  let mut parser = Parser::builder()
+    .support(table.clone())
+    .mode(ParserMode::Strict)
+    .build();
+  let events = parser.receive(&input);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![
+      Event::SEND,
+      Event::NEGOTIATION,
+      Event::ERROR,
+      Event::ERROR,
+      Event::ERROR
+    ]
+  );
+  let kinds: Vec<events::TelnetErrorKind> = events
+    .into_iter()
+    .filter_map(|event| match event {
+      events::TelnetEvents::Error(kind, _) => Some(kind),
+      _ => None,
+    })
+    .collect();
+  assert_eq!(
+    kinds,
+    vec![
+      events::TelnetErrorKind::UnknownCommand,
+      events::TelnetErrorKind::Malformed,
+      events::TelnetErrorKind::Malformed
+    ]
+  );
+
+  let mut parser = Parser::builder().support(table).build();
+  assert_eq!(
+    handle_events(parser.receive(&input)),
+    events![
+      Event::SEND,
+      Event::NEGOTIATION,
+      Event::IAC,
+      Event::SUBNEGOTIATION
+    ]
+  );
+}
+
+#[test]
+fn test_sanitize() {
+  use libtelnet_rs::sanitize::{ControlChars, SanitizePolicy};
+
+  let mut parser = Parser::builder()
+    .sanitize(SanitizePolicy {
+      control_chars: ControlChars::Strip,
+      max_line_len: Some(8),
+    })
+    .build();
+  let received = |events: Vec<events::TelnetEvents>| -> Vec<Vec<u8>> {
+    events
+      .into_iter()
+      .map(|event| match event {
+        events::TelnetEvents::DataReceive(data) => data.to_vec(),
+        events::TelnetEvents::Error(events::TelnetErrorKind::LineTooLong, dropped) => {
+          [b"dropped:".as_ref(), &dropped].concat()
+        }
+        event => panic!("unexpected event {:?}", event),
+      })
+      .collect()
+  };
+  assert_eq!(
+    received(parser.receive(b"\x1b[1mhi\x1b[0m\r\n")),
+    vec![b"[1mhi[0m\r\n".to_vec()]
+  );
+  assert_eq!(
+    received(parser.receive(b"0123456789\r\nok")),
+    vec![
+      b"01234567\r\n".to_vec(),
+      b"dropped:89".to_vec(),
+      b"ok".to_vec()
+    ]
+  );
+
+  let mut parser = Parser::builder()
+    .sanitize(SanitizePolicy {
+      control_chars: ControlChars::Keep,
+      max_line_len: Some(3),
+    })
+    .build();
+  assert_eq!(
+    received(parser.receive(b"abcdef\r\nghijkl\r\nmnopqr\r\n")),
+    vec![
+      b"abc\r\n".to_vec(),
+      b"dropped:def".to_vec(),
+      b"ghi\r\n".to_vec(),
+      b"dropped:jkl".to_vec(),
+      b"mno\r\n".to_vec(),
+      b"dropped:pqr".to_vec()
+    ]
+  );
+}
+
+#[test]
+fn test_error_diagnostics() {
+  let mut parser = Parser::new();
+  let events = parser.receive(&[cmd::IAC, cmd::SB, opt::GMCP, b'x', cmd::IAC, cmd::SE]);
+  match &events[..] {
+    [events::TelnetEvents::Error(kind, data)] => {
+      assert_eq!(*kind, events::TelnetErrorKind::OptionNotEnabled);
+      assert_eq!(
+        &data[..],
+        &[cmd::IAC, cmd::SB, opt::GMCP, b'x', cmd::IAC, cmd::SE]
+      );
+      assert_eq!(
+        format!("{}", kind),
+        "subnegotiation for an option that is not enabled"
+      );
+    }
+    events => panic!("unexpected events {:?}", events),
+  }
+}
+
+#[test]
+fn test_switch_profile() {
+  let mut login = compatibility::CompatibilityTable::new();
+  login.support_local(opt::GMCP);
+  let mut parser = Parser::with_support(login);
+  parser._will(opt::GMCP);
+  parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+
+  let mut game = compatibility::CompatibilityTable::new();
+  game.support_remote(opt::NAWS);
+  let events = parser.switch_profile(&game);
+  match &events[..] {
+    [events::TelnetEvents::DataSend(data)] => assert_eq!(
+      &data[..],
+      &[cmd::IAC, cmd::WONT, opt::GMCP, cmd::IAC, cmd::DO, opt::NAWS]
+    ),
+    events => panic!("unexpected events {:?}", events),
+  }
+  assert!(!parser.options.get_option(opt::GMCP).local);
+  assert!(parser.options.get_option(opt::NAWS).remote);
+  assert!(parser.switch_profile(&game).is_empty());
+}
+
+#[test]
+fn test_try_receive() {
+  use libtelnet_rs::error::TelnetError;
+
+  let mut parser = Parser::builder().max_subnegotiation_len(4).build();
+  assert_eq!(parser.try_receive(b"hello").unwrap().len(), 1);
+  let err = parser
+    .try_receive(&[cmd::IAC, cmd::SB, opt::GMCP, 1, 2, 3, 4, 5])
+    .unwrap_err();
+  assert_eq!(err.kind(), events::TelnetErrorKind::Overflow);
+  assert!(matches!(err, TelnetError::BufferOverflow(..)));
+
+  let mut parser = Parser::builder().deny_commands(&[cmd::IP]).build();
+  let err = parser.try_receive(&[cmd::IAC, cmd::IP]).unwrap_err();
+  assert!(matches!(err, TelnetError::ProtocolViolation(..)));
+  assert_eq!(format!("{}", err), "protocol violation: command denied");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_timestamped_events() {
+  use libtelnet_rs::sink::Timestamped;
+  use std::time::Instant;
+
+  let before = Instant::now();
+  let mut parser = Parser::new();
+  let mut stamped: Vec<Timestamped> = Vec::new();
+  parser.receive_into(b"hello", &mut stamped);
+  parser.receive_into(&[cmd::IAC, cmd::NOP], &mut stamped);
+  assert_eq!(stamped.len(), 2);
+  assert!(stamped[0].at >= before);
+  assert!(stamped[1].at >= stamped[0].at);
+  assert!(matches!(stamped[1].event, events::TelnetEvents::IAC(_)));
+}
+
+#[test]
+fn test_line_ending() {
+  use libtelnet_rs::newline::LineEnding;
+
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::BINARY);
+  let mut parser = Parser::builder()
+    .support(table)
+    .line_ending(LineEnding::Lf)
+    .build();
+  let received: Vec<u8> = [
+    parser.receive(b"look\r"),
+    parser.receive(b"\nsay a\r\0b\r"),
+    parser.flush(),
+  ]
+  .concat()
+  .into_iter()
+  .flat_map(|event| match event {
+    events::TelnetEvents::DataReceive(data) => data.to_vec(),
+    event => panic!("unexpected event {:?}", event),
+  })
+  .collect();
+  assert_eq!(received, b"look\nsay a\rb\r".to_vec());
+
+  match parser.send_text("one\ntwo\rthree") {
+    events::TelnetEvents::DataSend(data) => assert_eq!(&data[..], b"one\r\ntwo\r\0three\r\n"),
+    event => panic!("unexpected event {:?}", event),
+  }
+  parser._will(opt::BINARY);
+  parser.receive(&[cmd::IAC, cmd::DO, opt::BINARY]);
+  match parser.send_text("one\ntwo") {
+    events::TelnetEvents::DataSend(data) => assert_eq!(&data[..], b"one\ntwo\r\n"),
+    event => panic!("unexpected event {:?}", event),
+  }
+}
+
+#[test]
+fn test_line_mode() {
+  let mut parser = Parser::builder().line_mode(8).build();
+  let events = [
+    parser.receive(b"north\r"),
+    parser.receive(&[b"\nsa".as_ref(), &[cmd::IAC, cmd::NOP], b"y hi\n"].concat()),
+    parser.receive(b"0123456789\nlook"),
+    parser.flush(),
+  ]
+  .concat();
+  assert_eq!(
+    handle_events(events.clone()),
+    events![
+      Event::LINE,
+      Event::IAC,
+      Event::LINE,
+      Event::ERROR,
+      Event::LINE
+    ]
+  );
+  let lines: Vec<Bytes> = events.into_iter().map(Bytes::from).collect();
+  assert_eq!(
+    lines,
+    vec![
+      Bytes::from_static(b"north"),
+      Bytes::from_static(&[cmd::IAC, cmd::NOP]),
+      Bytes::from_static(b"say hi"),
+      Bytes::from_static(b"01234567"),
+      Bytes::from_static(b"look"),
+    ]
+  );
+}
+
+#[test]
+fn test_subnegotiation_anomalies() {
+  use libtelnet_rs::anomaly::AnomalyPolicy;
+
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_remote(opt::NAWS);
+  let mut parser = Parser::builder()
+    .support(table)
+    .subnegotiation_anomalies(AnomalyPolicy {
+      option_max_len: vec![(opt::NAWS, 8)],
+      ..Default::default()
+    })
+    .build();
+  parser._do(opt::NAWS);
+  parser.receive(&[cmd::IAC, cmd::WILL, opt::NAWS]);
+  let naws = |payload: &[u8]| {
+    [
+      &[cmd::IAC, cmd::SB, opt::NAWS][..],
+      payload,
+      &[cmd::IAC, cmd::SE],
+    ]
+    .concat()
+  };
+  assert_eq!(
+    handle_events(parser.receive(&naws(&[0, 80, 0, 24]))),
+    events![Event::SUBNEGOTIATION]
+  );
+  assert_eq!(
+    handle_events(parser.receive(&naws(&[0; 64]))),
+    events![Event::ANOMALY, Event::SUBNEGOTIATION]
+  );
+  let sizes = parser.subnegotiation_sizes().get(opt::NAWS).unwrap();
+  assert_eq!(sizes.count, 2);
+  assert_eq!(sizes.sum, 68);
+}
+
+#[test]
+fn test_mccp2_toggling() {
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_remote(opt::MCCP2);
+  let mut parser = Parser::with_support(table);
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::WILL, opt::MCCP2])),
+    events![Event::SEND, Event::NEGOTIATION]
+  );
+  let start = [cmd::IAC, cmd::SB, opt::MCCP2, cmd::IAC, cmd::SE, 0x78, 0x9c];
+  assert_eq!(
+    handle_events(parser.receive(&start)),
+    events![Event::SUBNEGOTIATION, Event::DECOM]
+  );
+  assert_eq!(parser.decompressing(), Some(opt::MCCP2));
+
+  // The server ends its stream for a copyover, and zlib reports the end of it.
+  assert_eq!(
+    handle_events(parser.end_decompression().into_iter().collect()),
+    events![Event::DECOMEND]
+  );
+  assert!(parser.end_decompression().is_none());
+
+  // After the copyover, the server switches MCCP2 off, offers it twice, and starts a new stream.
+  let copyover = fixtures::fixture("mccp2_copyover").unwrap().input;
+  assert_eq!(
+    handle_events(parser.receive(copyover)),
+    events![
+      Event::SEND,
+      Event::NEGOTIATION,
+      Event::RECV,
+      Event::SEND,
+      Event::NEGOTIATION,
+      Event::SUBNEGOTIATION,
+      Event::DECOM
+    ]
+  );
+
+  // A restart without the end of the stream being reported still ends the old stream first.
+  assert_eq!(
+    handle_events(parser.receive(&start)),
+    events![Event::SUBNEGOTIATION, Event::DECOMEND, Event::DECOM]
+  );
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::WONT, opt::MCCP2])),
+    events![Event::DECOMEND, Event::SEND, Event::NEGOTIATION]
+  );
+  assert_eq!(parser.decompressing(), None);
+}
+
+#[test]
+fn test_receive_borrowed() {
+  use libtelnet_rs::events::TelnetEventRef;
+
+  let mut parser = Parser::new();
+  let input = b"plain text";
+  let mut seen = Vec::new();
+  parser.receive_borrowed(input, |event| match event {
+    TelnetEventRef::DataReceive(data) => {
+      // Borrowed straight from the input.
+      assert_eq!(data.as_ptr(), input.as_ptr());
+      seen.push(event.to_owned());
+    }
+    event => panic!("unexpected event {:?}", event),
+  });
+  parser.receive_borrowed(
+    &[
+      b"hi".as_ref(),
+      &[
+        cmd::IAC,
+        cmd::GA,
+        cmd::IAC,
+        cmd::SB,
+        opt::GMCP,
+        b'x',
+        cmd::IAC,
+        cmd::SE,
+      ],
+    ]
+    .concat(),
+    |event| seen.push(event.to_owned()),
+  );
+  assert_eq!(
+    handle_events(seen),
+    events![Event::RECV, Event::RECV, Event::IAC, Event::ERROR]
+  );
+}
+
+#[test]
+fn test_command_kinds() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::TelnetCommand;
+
+  let mut parser = Parser::builder()
+    .protocols(&[Protocol::Gmcp])
+    .auto_response(TelnetCommand::AreYouThere, "[Yes]\r\n")
+    .build();
+  let events = parser.receive(&[cmd::IAC, cmd::AYT, cmd::IAC, cmd::EL, cmd::IAC, 200]);
+  match &events[..] {
+    [events::TelnetEvents::IAC(ayt), events::TelnetEvents::DataSend(reply), events::TelnetEvents::IAC(el), events::TelnetEvents::IAC(other)] =>
+    {
+      assert_eq!(ayt.command, TelnetCommand::AreYouThere);
+      assert_eq!(&reply[..], b"[Yes]\r\n");
+      assert_eq!(el.command, TelnetCommand::EraseLine);
+      assert_eq!(other.command, TelnetCommand::Unknown(200));
+      assert_eq!(other.raw_command(), 200);
+    }
+    events => panic!("unexpected events {:?}", events),
+  }
+  let events = parser.receive(&[cmd::IAC, cmd::WILL, opt::GMCP]);
+  if let events::TelnetEvents::Negotiation(will) = &events[1] {
+    assert_eq!(will.command, TelnetCommand::Will);
+    assert_eq!(will.raw_command(), cmd::WILL);
+  } else {
+    panic!("Expected a Negotiation");
+  }
+  assert_eq!(
+    Bytes::from(parser.negotiate(TelnetCommand::Do, opt::NAWS)),
+    Bytes::from_static(&[cmd::IAC, cmd::DO, opt::NAWS])
+  );
+  for command in 0..=255u8 {
+    assert_eq!(u8::from(TelnetCommand::from(command)), command);
+  }
+  // Commands are equal by code, however they were built.
+  let brk = events::TelnetIAC {
+    command: TelnetCommand::Unknown(cmd::BREAK),
+  };
+  assert_eq!(brk, events::TelnetIAC::new(cmd::BREAK));
+  assert_eq!(TelnetCommand::Unknown(cmd::BREAK), TelnetCommand::Break);
+  let commands: std::collections::HashSet<_> =
+    vec![TelnetCommand::Break, TelnetCommand::Unknown(cmd::BREAK)]
+      .into_iter()
+      .collect();
+  assert_eq!(commands.len(), 1);
+}
+
+#[test]
+fn test_remote_subnegotiation() {
+  // A client that only lets the server enable GMCP still exchanges GMCP subnegotiations.
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::GMCP);
+  let mut parser = Parser::with_support(table);
+  parser.receive(&[cmd::IAC, cmd::WILL, opt::GMCP]);
+  let entry = parser.options.get_option(opt::GMCP);
+  assert!(entry.remote_state && !entry.local_state);
+  let events = parser.receive(
+    &events::TelnetSubnegotiation::new(opt::GMCP, Bytes::from_static(b"Core.Ping")).into_bytes(),
+  );
+  match &events[..] {
+    [events::TelnetEvents::Subnegotiation(sub)] => assert_eq!(&sub.buffer[..], b"Core.Ping"),
+    events => panic!("unexpected events {:?}", events),
+  }
+  assert_eq!(
+    handle_events(vec![parser
+      .subnegotiation(opt::GMCP, Bytes::from_static(b"Core.Ping"))
+      .unwrap()]),
+    events![Event::SEND]
+  );
+}
+
+#[test]
+fn test_option_kinds() {
+  use libtelnet_rs::telnet::TelnetOption;
+
+  let mut table = CompatibilityTable::new();
+  table.support(TelnetOption::Gmcp);
+  table.support_local(TelnetOption::Naws);
+  assert!(table.get_option(opt::GMCP).remote);
+  assert!(!table.get_option(TelnetOption::Naws).remote);
+  let mut parser = Parser::with_support(table);
+  let events = parser.receive(&[cmd::IAC, cmd::DO, opt::NAWS, cmd::IAC, cmd::WILL, opt::GMCP]);
+  match &events[..] {
+    [events::TelnetEvents::DataSend(_), events::TelnetEvents::Negotiation(naws), events::TelnetEvents::DataSend(_), events::TelnetEvents::Negotiation(gmcp)] =>
+    {
+      assert_eq!(naws.option_kind(), TelnetOption::Naws);
+      assert_eq!(gmcp.option_kind(), TelnetOption::Gmcp);
+    }
+    events => panic!("unexpected events {:?}", events),
+  }
+  assert!(parser.options.get_option(TelnetOption::Gmcp).remote_state);
+  let sub = parser.subnegotiation(TelnetOption::Gmcp, Bytes::from_static(b"Core.Ping"));
+  assert!(sub.is_some());
+  assert_eq!(
+    Bytes::from(parser._dont(TelnetOption::Gmcp).unwrap()),
+    Bytes::from_static(&[cmd::IAC, cmd::DONT, opt::GMCP])
+  );
+  assert_eq!(TelnetOption::from(86), TelnetOption::Mccp2);
+  assert_eq!(TelnetOption::from(200), TelnetOption::Unknown(200));
+  assert_eq!(TelnetOption::Unknown(86), TelnetOption::Mccp2);
+  for option in 0..=255u8 {
+    assert_eq!(u8::from(TelnetOption::from(option)), option);
+  }
+}
+
+#[test]
+fn test_option_names() {
+  use libtelnet_rs::error::ParseNameError;
+  use libtelnet_rs::telnet::{TelnetCommand, TelnetOption};
+
+  assert_eq!(TelnetOption::Gmcp.to_string(), "GMCP");
+  assert_eq!(TelnetOption::ComPortOption.to_string(), "COM-PORT-OPTION");
+  assert_eq!(TelnetOption::Unknown(200).to_string(), "200");
+  assert_eq!("naws".parse(), Ok(TelnetOption::Naws));
+  assert_eq!("start_tls".parse(), Ok(TelnetOption::StartTls));
+  assert_eq!(" 86 ".parse(), Ok(TelnetOption::Mccp2));
+  assert_eq!(TelnetOption::NewEnviron.to_string(), "NEW-ENVIRON");
+  assert_eq!(TelnetOption::Ttype.to_string(), "TERMINAL-TYPE");
+  assert_eq!("new-environ".parse(), Ok(TelnetOption::NewEnviron));
+  assert_eq!("NEWENVIRON".parse(), Ok(TelnetOption::NewEnviron));
+  assert_eq!("TTYPE".parse(), Ok(TelnetOption::Ttype));
+  assert_eq!("terminal_type".parse(), Ok(TelnetOption::Ttype));
+  assert_eq!(
+    "3270REGIME".parse(),
+    Ok(TelnetOption::from(opt::_3270REGIME))
+  );
+  assert_eq!("NAWSS".parse::<TelnetOption>(), Err(ParseNameError));
+  assert_eq!("256".parse::<TelnetOption>(), Err(ParseNameError));
+  assert_eq!(TelnetCommand::Will.to_string(), "WILL");
+  assert_eq!(TelnetCommand::Break.to_string(), "BRK");
+  assert_eq!(TelnetCommand::Unknown(100).to_string(), "100");
+  assert_eq!("ayt".parse(), Ok(TelnetCommand::AreYouThere));
+  assert_eq!("Dont".parse(), Ok(TelnetCommand::Dont));
+  assert_eq!("BREAK".parse(), Ok(TelnetCommand::Break));
+  assert_eq!("brk".parse(), Ok(TelnetCommand::Break));
+  assert_eq!("FOO".parse::<TelnetCommand>(), Err(ParseNameError));
+  for code in 0..=255u8 {
+    let option = TelnetOption::from(code);
+    assert_eq!(option.to_string().parse(), Ok(option));
+    let command = TelnetCommand::from(code);
+    assert_eq!(command.to_string().parse(), Ok(command));
+  }
+}
+
+#[test]
+fn test_auto_ayt_response() {
+  let mut parser = Parser::new();
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::AYT])),
+    events![Event::IAC]
+  );
+  let mut parser = Parser::builder().auto_ayt_response("alive\r\n").build();
+  let events = parser.receive(&[cmd::IAC, cmd::AYT]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::IAC, Event::SEND]
+  );
+  assert_eq!(
+    Bytes::from(events[1].clone()),
+    Bytes::from_static(b"alive\r\n")
+  );
+}
+
+#[test]
+fn test_send_conversions() {
+  use libtelnet_rs::text;
+
+  let sent = |event: events::TelnetEvents| match event {
+    events::TelnetEvents::DataSend(data) => data,
+    event => panic!("unexpected event {:?}", event),
+  };
+  assert_eq!(sent("hi".into()), Bytes::from_static(b"hi"));
+  assert_eq!(
+    sent(String::from("\u{ff}").into()),
+    Bytes::from_static(b"\xc3\xbf")
+  );
+  assert_eq!(
+    sent(vec![1, cmd::IAC, 2].into()),
+    Bytes::from_static(&[1, cmd::IAC, cmd::IAC, 2])
+  );
+  assert_eq!(
+    sent(text!("{} gold\r\n", 5)),
+    Bytes::from_static(b"5 gold\r\n")
+  );
+}
+
+#[test]
+fn test_keepalive() {
+  let mut parser = Parser::new();
+  assert_eq!(
+    Bytes::from(parser.keepalive()),
+    Bytes::from_static(&[cmd::IAC, cmd::NOP])
+  );
+  assert_eq!(
+    Bytes::from(parser.keepalive_probe()),
+    Bytes::from_static(&[cmd::IAC, cmd::DO, opt::TM])
+  );
+  parser.keepalive_probe();
+  assert_eq!(parser.keepalives_outstanding(), 2);
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::WILL, opt::TM, cmd::IAC, cmd::WONT, opt::TM])),
+    events![Event::KEEPALIVE, Event::KEEPALIVE]
+  );
+  assert_eq!(parser.keepalives_outstanding(), 0);
+  // With no probe outstanding, it is an ordinary negotiation again.
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::WILL, opt::TM])),
+    events![Event::SEND]
+  );
+}
+
+#[test]
+fn test_ping() {
+  let mut parser = Parser::new();
+  assert_eq!(
+    Bytes::from(parser.ping(1000)),
+    Bytes::from_static(&[cmd::IAC, cmd::DO, opt::TM])
+  );
+  parser.keepalive_probe();
+  parser.ping(1100);
+  assert_eq!(parser.keepalives_outstanding(), 3);
+  parser.set_time(1250);
+  let events = parser.receive(&[
+    cmd::IAC,
+    cmd::WILL,
+    opt::TM,
+    cmd::IAC,
+    cmd::WONT,
+    opt::TM,
+    cmd::IAC,
+    cmd::WILL,
+    opt::TM,
+  ]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::PONG, Event::KEEPALIVE, Event::PONG]
+  );
+  assert!(matches!(
+    events[0],
+    events::TelnetEvents::Pong { rtt_hint: 250 }
+  ));
+  assert!(matches!(
+    events[2],
+    events::TelnetEvents::Pong { rtt_hint: 150 }
+  ));
+  assert_eq!(parser.keepalives_outstanding(), 0);
+}
+
+#[test]
+fn test_logout() {
+  // Without support for LOGOUT, a request or an unsolicited logout is refused like any other option.
+  let mut server = Parser::new();
+  let events = server.receive(&[cmd::IAC, cmd::DO, opt::LOGOUT]);
+  assert_eq!(handle_events(events.clone()), events![Event::SEND]);
+  assert_eq!(
+    Bytes::from(events[0].clone()),
+    Bytes::from_static(&[cmd::IAC, cmd::WONT, opt::LOGOUT])
+  );
+  let events = server.receive(&[cmd::IAC, cmd::WILL, opt::LOGOUT]);
+  assert_eq!(handle_events(events.clone()), events![Event::SEND]);
+  assert_eq!(
+    Bytes::from(events[0].clone()),
+    Bytes::from_static(&[cmd::IAC, cmd::DONT, opt::LOGOUT])
+  );
+
+  let mut table = CompatibilityTable::new();
+  table.support_local(opt::LOGOUT);
+  let mut server = Parser::with_support(table);
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::LOGOUT);
+  let mut client = Parser::with_support(table);
+  let request = Bytes::from(client.request_logout());
+  assert_eq!(
+    request,
+    Bytes::from_static(&[cmd::IAC, cmd::DO, opt::LOGOUT])
+  );
+  assert_eq!(
+    handle_events(server.receive(&request)),
+    events![Event::LOGOUTREQUEST]
+  );
+  let ack = Bytes::from(server.acknowledge_logout());
+  assert_eq!(ack, Bytes::from_static(&[cmd::IAC, cmd::WILL, opt::LOGOUT]));
+  assert_eq!(handle_events(client.receive(&ack)), events![Event::LOGOUT]);
+  // A logout the client did not ask for is acknowledged.
+  let events = client.receive(&ack);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SEND, Event::LOGOUT]
+  );
+  assert_eq!(Bytes::from(events[0].clone()), request);
+}
+
+#[test]
+fn test_resync_option() {
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let mut parser = Parser::with_support(table);
+  assert!(parser.resync_option(opt::GMCP).is_empty());
+  parser._will(opt::GMCP);
+  parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+
+  let events = parser.resync_option(opt::GMCP);
+  assert_eq!(
+    events.into_iter().map(Bytes::from).collect::<Vec<_>>(),
+    vec![Bytes::from_static(&[cmd::IAC, cmd::WONT, opt::GMCP])]
+  );
+  assert!(parser.resync_option(opt::GMCP).is_empty());
+  let events = parser.receive(&[cmd::IAC, cmd::DONT, opt::GMCP]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::NEGOTIATION, Event::COMPLETE, Event::SEND]
+  );
+  assert_eq!(
+    Bytes::from(events[2].clone()),
+    Bytes::from_static(&[cmd::IAC, cmd::WILL, opt::GMCP])
+  );
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP])),
+    events![Event::NEGOTIATION, Event::COMPLETE, Event::RESYNCED]
+  );
+  assert!(parser.options.get_option(opt::GMCP).local_state);
+}
+
+#[test]
+fn test_prompt_detection() {
+  let mut parser = Parser::builder()
+    .detect_prompts(&[cmd::GA, cmd::EOR])
+    .build();
+  parser.receive(b"You are in a room.\r\n<100hp ");
+  let events = parser.receive(&[b"50mv>".as_ref(), &[cmd::IAC, cmd::GA]].concat());
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::RECV, Event::IAC, Event::PROMPT]
+  );
+  assert_eq!(
+    Bytes::from(events[2].clone()),
+    Bytes::from_static(b"<100hp 50mv>")
+  );
+  // NOP is not a marker, and the prompt line starts again after a marker.
+  let events =
+    parser.receive(&[b"> ".as_ref(), &[cmd::IAC, cmd::NOP, cmd::IAC, cmd::EOR]].concat());
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::RECV, Event::IAC, Event::IAC, Event::PROMPT]
+  );
+  assert_eq!(Bytes::from(events[3].clone()), Bytes::from_static(b"> "));
+
+  let mut parser = Parser::builder()
+    .line_mode(80)
+    .detect_prompts(&[cmd::GA])
+    .build();
+  let events = parser.receive(&[b"Name: ".as_ref(), &[cmd::IAC, cmd::GA]].concat());
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::IAC, Event::PROMPT]
+  );
+  assert_eq!(
+    Bytes::from(events[1].clone()),
+    Bytes::from_static(b"Name: ")
+  );
+  assert!(parser.flush().is_empty());
+}
+
+#[test]
+fn test_verify_states() {
+  let mut table = compatibility::CompatibilityTable::new();
+  table.support_remote(opt::STATUS);
+  table.support_local(opt::GMCP);
+  let mut parser = Parser::with_support(table);
+  assert!(parser.verify_states().is_none());
+  parser.receive(&[cmd::IAC, cmd::WILL, opt::STATUS]);
+  parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+
+  assert_eq!(
+    Bytes::from(parser.verify_states().unwrap()),
+    Bytes::from_static(&[cmd::IAC, cmd::SB, opt::STATUS, 1, cmd::IAC, cmd::SE])
+  );
+  let report = [
+    cmd::IAC,
+    cmd::SB,
+    opt::STATUS,
+    0,
+    cmd::WILL,
+    opt::STATUS,
+    cmd::DO,
+    opt::GMCP,
+    cmd::IAC,
+    cmd::SE,
+  ];
+  assert_eq!(
+    handle_events(parser.receive(&report)),
+    events![Event::SUBNEGOTIATION]
+  );
+
+  // The remote end has lost track of GMCP, and thinks it has ECHO enabled.
+  let report = [
+    cmd::IAC,
+    cmd::SB,
+    opt::STATUS,
+    0,
+    cmd::WILL,
+    opt::STATUS,
+    cmd::WILL,
+    opt::ECHO,
+    cmd::IAC,
+    cmd::SE,
+  ];
+  let events = parser.receive(&report);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::MISMATCH]
+  );
+  if let events::TelnetEvents::StateMismatch(mismatches) = &events[1] {
+    assert_eq!(
+      mismatches,
+      &vec![
+        StateMismatch {
+          option: opt::ECHO,
+          local: false,
+          ours: false
+        },
+        StateMismatch {
+          option: opt::GMCP,
+          local: true,
+          ours: true
+        },
+      ]
+    );
+  } else {
+    panic!("Expected a StateMismatch");
+  }
+}
+
+#[test]
+fn test_with_protocols() {
+  use libtelnet_rs::protocol::Protocol;
+  let mut parser = Parser::with_protocols(&[Protocol::Gmcp, Protocol::Naws, Protocol::Mccp2]);
+  for option in [opt::GMCP, opt::NAWS, opt::MCCP2].iter() {
+    let entry = parser.options.get_option(*option);
+    assert!(entry.local && entry.remote);
+  }
+  assert!(!parser.options.get_option(opt::ECHO).local);
+  assert_eq!(
+    handle_events(parser.receive(&[cmd::IAC, cmd::DO, opt::GMCP])),
+    events![Event::SEND, Event::NEGOTIATION]
+  );
+
+  let mut table = CompatibilityTable::new();
+  table.support_local(opt::ECHO);
+  let parser = Parser::builder()
+    .support(table)
+    .protocols(&[Protocol::EndOfRecord])
+    .build();
+  assert!(parser.options.get_option(opt::ECHO).local);
+  assert!(parser.options.get_option(opt::EOR).remote);
+}
+
+#[test]
+fn test_prompt_end_of_record() {
+  let mut table = CompatibilityTable::new();
+  table.support_local(opt::EOR);
+  let mut server = Parser::with_support(table);
+  let prompt: Bytes = server.send_prompt("> ").into();
+  assert_eq!(&prompt[..], &[b'>', b' ', cmd::IAC, cmd::GA]);
+  server._will(opt::EOR);
+  server.receive(&[cmd::IAC, cmd::DO, opt::EOR]);
+  let prompt: Bytes = server.send_prompt("> ").into();
+  assert_eq!(&prompt[..], &[b'>', b' ', cmd::IAC, cmd::EOR]);
+  let sent: Bytes = server.send_async_text("Tick.").into();
+  assert_eq!(
+    &sent[..],
+    &[&b"\r\nTick.\r\n> "[..], &[cmd::IAC, cmd::EOR]].concat()[..]
+  );
+}
+
+#[test]
+fn test_text_format() {
+  use libtelnet_rs::newline::{LineEnding, Terminator, TextFormat};
+  let mut table = CompatibilityTable::new();
+  table.support_local(opt::BINARY);
+  let mut parser = Parser::builder()
+    .support(table)
+    .text_format(TextFormat {
+      line_ending: Some(LineEnding::Lf),
+      terminator: Terminator::GoAhead,
+    })
+    .build();
+  assert_eq!(
+    Bytes::from(parser.send_text("hi")),
+    Bytes::from_static(&[b'h', b'i', b'\r', b'\n', cmd::IAC, cmd::GA])
+  );
+  let bare = TextFormat {
+    line_ending: None,
+    terminator: Terminator::None,
+  };
+  assert_eq!(
+    Bytes::from(parser.send_text_with("hi", bare)),
+    Bytes::from_static(b"hi")
+  );
+  // Once BINARY is enabled, the configured line ending is sent as is.
+  parser._will(opt::BINARY);
+  parser.receive(&[cmd::IAC, cmd::DO, opt::BINARY]);
+  assert_eq!(
+    Bytes::from(parser.send_text("hi")),
+    Bytes::from_static(&[b'h', b'i', b'\n', cmd::IAC, cmd::GA])
+  );
+  assert_eq!(
+    Bytes::from(Parser::new().send_text("hi")),
+    Bytes::from_static(b"hi\r\n")
+  );
+}
+
+#[test]
+fn test_send_data() {
+  let mut parser = Parser::builder()
+    .line_ending(newline::LineEnding::Lf)
+    .build();
+  assert_eq!(
+    Bytes::from(parser.send_data(&[0, b'\n', 255, b'\r'])),
+    Bytes::from_static(&[0, b'\n', 255, 255, b'\r'])
+  );
+  assert_eq!(
+    Bytes::from(parser.send_text("\n")),
+    Bytes::from_static(b"\r\n\r\n")
+  );
+}
+
+#[test]
+fn test_binary_enabled() {
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::BINARY);
+  let mut parser = Parser::builder()
+    .support(table)
+    .line_ending(newline::LineEnding::Lf)
+    .build();
+  let events = parser.receive(b"a\r\0b\r\n");
+  assert_eq!(
+    Bytes::from(events[0].clone()),
+    Bytes::from_static(b"a\rb\n")
+  );
+  parser.receive(&[cmd::IAC, cmd::WILL, opt::BINARY]);
+  assert!(parser.binary_enabled(false));
+  assert!(!parser.binary_enabled(true));
+  let events = parser.receive(b"a\r\0\xe9\r\n");
+  assert_eq!(
+    Bytes::from(events[0].clone()),
+    Bytes::from_static(b"a\r\0\xe9\r\n")
+  );
+  // Outbound text is still NVT, as BINARY is only enabled for the remote end.
+  assert_eq!(
+    Bytes::from(parser.send_text("x\n")),
+    Bytes::from_static(b"x\r\n\r\n")
+  );
+}
+
+#[test]
+fn test_server_echo() {
+  let mut server = Parser::new();
+  assert!(!server.character_mode());
+  assert_eq!(
+    Bytes::from(server.begin_server_echo().unwrap()),
+    Bytes::from_static(&[
+      cmd::IAC,
+      cmd::WILL,
+      opt::ECHO,
+      cmd::IAC,
+      cmd::WILL,
+      opt::SGA
+    ])
+  );
+  assert!(server.begin_server_echo().is_none());
+  server.receive(&[cmd::IAC, cmd::DO, opt::ECHO, cmd::IAC, cmd::DO, opt::SGA]);
+  assert!(server.character_mode());
+  assert_eq!(
+    Bytes::from(server.end_server_echo().unwrap()),
+    Bytes::from_static(&[cmd::IAC, cmd::WONT, opt::ECHO])
+  );
+  assert!(!server.character_mode());
+  assert!(server.end_server_echo().is_none());
+  // SGA is still enabled, so only ECHO is renegotiated.
+  assert_eq!(
+    Bytes::from(server.begin_server_echo().unwrap()),
+    Bytes::from_static(&[cmd::IAC, cmd::WILL, opt::ECHO])
+  );
+
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::ECHO);
+  table.support_remote(opt::SGA);
+  let mut client = Parser::with_support(table);
+  client.receive(&[
+    cmd::IAC,
+    cmd::WILL,
+    opt::ECHO,
+    cmd::IAC,
+    cmd::WILL,
+    opt::SGA,
+  ]);
+  assert!(client.character_mode());
+}
+
+#[test]
+fn test_naws() {
+  use libtelnet_rs::telnet::naws::Naws;
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::NAWS);
+  let mut server = Parser::with_support(table);
+  server.receive(&[cmd::IAC, cmd::WILL, opt::NAWS]);
+  assert_eq!(server.remote_window_size(), None);
+  let events = server.receive(&[
+    cmd::IAC,
+    cmd::SB,
+    opt::NAWS,
+    0,
+    255,
+    255,
+    0,
+    40,
+    cmd::IAC,
+    cmd::SE,
+  ]);
+  assert_eq!(server.remote_window_size(), Some((255, 40)));
+  if let events::TelnetEvents::Subnegotiation(sub) = &events[0] {
+    assert_eq!(sub.naws(), Some(Naws::new(255, 40)));
+  } else {
+    panic!("Expected a Subnegotiation");
+  }
+  let other = events::TelnetSubnegotiation::new(opt::GMCP, Bytes::from_static(&[0, 80, 0, 24]));
+  assert_eq!(other.naws(), None);
+  assert_eq!(
+    Bytes::from(events::TelnetSubnegotiation::new(
+      opt::NAWS,
+      Naws::new(255, 40).encode()
+    )),
+    Bytes::from_static(&[
+      cmd::IAC,
+      cmd::SB,
+      opt::NAWS,
+      0,
+      255,
+      255,
+      0,
+      40,
+      cmd::IAC,
+      cmd::SE
+    ])
+  );
+}
+
+#[test]
+fn test_window_size() {
+  let mut client = Parser::new();
+  assert!(client.resize(80, 24).is_none());
+  client.set_window_size(120, 40);
+  let events = client.receive(&[cmd::IAC, cmd::DO, opt::NAWS]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SEND, Event::NEGOTIATION, Event::SEND]
+  );
+  assert_eq!(
+    Bytes::from(events[0].clone()),
+    Bytes::from_static(&[cmd::IAC, cmd::WILL, opt::NAWS])
+  );
+  assert_eq!(
+    Bytes::from(events[2].clone()),
+    Bytes::from_static(&[
+      cmd::IAC,
+      cmd::SB,
+      opt::NAWS,
+      0,
+      120,
+      0,
+      40,
+      cmd::IAC,
+      cmd::SE
+    ])
+  );
+  assert!(client.resize(120, 40).is_none());
+  assert_eq!(
+    Bytes::from(client.resize(255, 50).unwrap()),
+    Bytes::from_static(&[
+      cmd::IAC,
+      cmd::SB,
+      opt::NAWS,
+      0,
+      255,
+      255,
+      0,
+      50,
+      cmd::IAC,
+      cmd::SE
+    ])
+  );
+  client.receive(&[cmd::IAC, cmd::DONT, opt::NAWS]);
+  assert!(client.resize(80, 24).is_none());
+}
+
+#[test]
+fn test_terminal_type_cycling() {
+  let ttype = |payload: &[u8]| {
+    [
+      &[cmd::IAC, cmd::SB, opt::TTYPE][..],
+      payload,
+      &[cmd::IAC, cmd::SE],
+    ]
+    .concat()
+  };
+  let mut client = Parser::new();
+  client.set_terminal_types(&["MUDLET", "XTERM"]);
+  client.receive(&[cmd::IAC, cmd::DO, opt::TTYPE]);
+  for expected in ["MUDLET", "XTERM", "XTERM", "MUDLET"].iter() {
+    let events = client.receive(&ttype(&[1]));
+    assert_eq!(
+      handle_events(events.clone()),
+      events![Event::SUBNEGOTIATION, Event::SEND]
+    );
+    assert_eq!(
+      Bytes::from(events[1].clone()),
+      Bytes::from(ttype(&[&[0], expected.as_bytes()].concat()))
+    );
+  }
+
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::TTYPE);
+  let mut server = Parser::with_support(table);
+  assert!(server.request_terminal_types().is_none());
+  server.receive(&[cmd::IAC, cmd::WILL, opt::TTYPE]);
+  assert_eq!(
+    Bytes::from(server.request_terminal_types().unwrap()),
+    Bytes::from(ttype(&[1]))
+  );
+  let events = server.receive(&ttype(b"\x00MUDLET"));
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::SEND]
+  );
+  assert_eq!(Bytes::from(events[1].clone()), Bytes::from(ttype(&[1])));
+  assert_eq!(
+    handle_events(server.receive(&ttype(b"\x00XTERM"))),
+    events![Event::SUBNEGOTIATION, Event::SEND]
+  );
+  assert_eq!(
+    handle_events(server.receive(&ttype(b"\x00XTERM"))),
+    events![Event::SUBNEGOTIATION]
+  );
+  assert_eq!(server.terminal_types(), &["MUDLET", "XTERM"]);
+  assert_eq!(server.mtts(), None);
+}
+
+#[test]
+fn test_mtts() {
+  use libtelnet_rs::telnet::mtts::{Mtts, MttsFlags};
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::TTYPE);
+  let mut server = Parser::with_support(table);
+  let mut client = Parser::new();
+  let mtts = Mtts::new("MUDLET", "ANSI", MttsFlags::ANSI | MttsFlags::UTF8);
+  let types = mtts.to_terminal_types();
+  client.set_terminal_types(&types.iter().map(String::as_str).collect::<Vec<_>>());
+  client.receive(&[cmd::IAC, cmd::DO, opt::TTYPE]);
+  server.receive(&[cmd::IAC, cmd::WILL, opt::TTYPE]);
+
+  // Pass every reply back and forth until both ends go quiet.
+  let mut to_client = Bytes::from(server.request_terminal_types().unwrap());
+  while !to_client.is_empty() {
+    let replies: Vec<u8> = client
+      .receive(&to_client)
+      .into_iter()
+      .filter(|event| matches!(event, events::TelnetEvents::DataSend(_)))
+      .flat_map(|event| Bytes::from(event).to_vec())
+      .collect();
+    to_client = server
+      .receive(&replies)
+      .into_iter()
+      .filter(|event| matches!(event, events::TelnetEvents::DataSend(_)))
+      .flat_map(|event| Bytes::from(event).to_vec())
+      .collect::<Vec<u8>>()
+      .into();
+  }
+  assert_eq!(server.terminal_types(), &types[..]);
+  assert_eq!(server.mtts(), Some(mtts));
+}
+
+#[test]
+fn test_mnes() {
+  use libtelnet_rs::telnet::mnes::Mnes;
+  use libtelnet_rs::telnet::mtts::MttsFlags;
+
+  let mut client = Parser::new();
+  let mnes = Mnes {
+    charset: Some(String::from("UTF-8")),
+    client_name: Some(String::from("MUDLET")),
+    client_version: Some(String::from("4.17")),
+    mtts: Some(MttsFlags::ANSI | MttsFlags::UTF8),
+    ..Default::default()
+  };
+  assert!(client.set_mnes(&mnes).is_none());
+  let mut server = Parser::new();
+  server.options.support_remote(opt::NEWENVIRON);
+  assert!(server.request_mnes().is_none());
+  let will: Bytes = client
+    .receive(&[cmd::IAC, cmd::DO, opt::NEWENVIRON])
+    .remove(0)
+    .into();
+  server.receive(&will);
+
+  let send: Bytes = server.request_mnes().unwrap().into();
+  let is: Bytes = client.receive(&send).remove(1).into();
+  server.receive(&is);
+  assert_eq!(server.remote_mnes(), mnes);
+
+  // Only the changed variable is sent as an update.
+  let update = Mnes {
+    client_version: Some(String::from("4.18")),
+    ..mnes.clone()
+  };
+  let info: Bytes = client.set_mnes(&update).unwrap().into();
+  assert_eq!(
+    handle_events(server.receive(&info)),
+    events![Event::SUBNEGOTIATION, Event::ENVIRON]
+  );
+  assert_eq!(server.remote_mnes(), update);
+}
+
+#[test]
+fn test_legacy_environ() {
+  use libtelnet_rs::telnet::environ::{EnvironKind, LegacyEnviron};
+
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::ENVIRON);
+  let mut server = Parser::builder()
+    .support(table)
+    .legacy_environ(LegacyEnviron::Swapped)
+    .build();
+  server.receive(&[cmd::IAC, cmd::WILL, opt::ENVIRON]);
+  assert_eq!(
+    Bytes::from(server.request_environ(&[]).unwrap()),
+    Bytes::from_static(&[cmd::IAC, cmd::SB, opt::ENVIRON, 1, cmd::IAC, cmd::SE])
+  );
+  // An old BSD client, with VAR as 1 and VALUE as 0.
+  let is = [
+    &[cmd::IAC, cmd::SB, opt::ENVIRON, 0, 1][..],
+    b"USER",
+    &[0],
+    b"guest",
+    &[cmd::IAC, cmd::SE],
+  ]
+  .concat();
+  assert_eq!(
+    handle_events(server.receive(&is)),
+    events![Event::SUBNEGOTIATION, Event::ENVIRON]
+  );
+  assert_eq!(
+    server.remote_environ_var(EnvironKind::Var, "USER"),
+    Some("guest")
+  );
+
+  // Without the compatibility mode, ENVIRON is left alone.
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::ENVIRON);
+  let mut server = Parser::with_support(table);
+  server.receive(&[cmd::IAC, cmd::WILL, opt::ENVIRON]);
+  assert!(server.request_environ(&[]).is_none());
+  assert_eq!(
+    handle_events(server.receive(&is)),
+    events![Event::SUBNEGOTIATION]
+  );
+}
+
+#[test]
+fn test_utf8_text() {
+  use libtelnet_rs::charset::InvalidUtf8;
+  let text = |event: &events::TelnetEvents| match event {
+    events::TelnetEvents::Text(text) => text.clone(),
+    _ => panic!("Expected Text"),
+  };
+  let mut parser = Parser::builder().utf8_text(InvalidUtf8::Replace).build();
+  let events = parser.receive(b"caf\xc3");
+  assert_eq!(handle_events(events.clone()), events![Event::TEXT]);
+  assert_eq!(text(&events[0]), "caf");
+  let events = parser.receive(&[&b"\xa9 \xe2\x82"[..], &[cmd::IAC, cmd::NOP], b"\xac"].concat());
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::TEXT, Event::IAC, Event::TEXT]
+  );
+  assert_eq!(text(&events[0]), "\u{e9} ");
+  assert_eq!(text(&events[2]), "\u{20ac}");
+  parser.receive(b"\xe2\x82");
+  let events = parser.flush();
+  assert_eq!(text(&events[0]), "\u{fffd}");
+
+  let mut parser = Parser::builder().utf8_text(InvalidUtf8::Raw).build();
+  assert_eq!(
+    handle_events(parser.receive(b"caf\xe9!")),
+    events![Event::RECV]
+  );
+}
+
+#[test]
+fn test_text_decoder() {
+  use libtelnet_rs::charset::Latin1;
+  let mut parser = Parser::new();
+  parser.set_text_decoder(Latin1);
+  let events = parser.receive(&[&b"caf\xe9"[..], &[cmd::IAC, cmd::GA]].concat());
+  assert_eq!(
+    Bytes::from(events[0].clone()),
+    Bytes::from_static("café".as_bytes())
+  );
+  parser.clear_text_decoder();
+  let events = parser.receive(b"caf\xe9");
+  assert_eq!(
+    Bytes::from(events[0].clone()),
+    Bytes::from_static(b"caf\xe9")
+  );
+}
+
+#[cfg(feature = "encodings")]
+#[test]
+fn test_set_encoding() {
+  use libtelnet_rs::charset::Unmappable;
+  let mut parser = Parser::new();
+  assert!(!parser.set_encoding("Shift_JIS", Unmappable::Skip));
+  assert!(parser.set_encoding("KOI8-R", Unmappable::Skip));
+  assert_eq!(parser.text_encoding(), Some("KOI8-R"));
+  assert_eq!(
+    Bytes::from(parser.receive(b"\xd0\xd2\xc9\xd7\xc5\xd4")[0].clone()),
+    Bytes::from_static("привет".as_bytes())
+  );
+  assert!(parser.set_encoding("CP437", Unmappable::Replace(b'?')));
+  assert_eq!(parser.text_encoding(), Some("IBM437"));
+  let events = parser.receive(b"\xc9\xcd\xbb");
+  assert_eq!(
+    Bytes::from(events[0].clone()),
+    Bytes::from_static("╔═╗".as_bytes())
+  );
+  assert_eq!(
+    Bytes::from(parser.send_text("╚═╝ €")),
+    Bytes::from_static(b"\xc8\xcd\xbc ?\r\n")
+  );
+  assert!(parser.set_encoding("utf-8", Unmappable::Skip));
+  assert_eq!(parser.text_encoding(), None);
+}
+
+#[test]
+fn test_mssp() {
+  use libtelnet_rs::telnet::mssp::{self, MsspData};
+  let mut table = CompatibilityTable::new();
+  table.support_local(opt::MSSP);
+  let mut server = Parser::with_support(table);
+  server.receive(&[cmd::IAC, cmd::DO, opt::MSSP]);
+  let data: MsspData = vec![
+    (mssp::NAME, "Test"),
+    (mssp::PORT, "23"),
+    (mssp::PORT, "4000"),
+  ]
+  .into_iter()
+  .collect();
+  let sent = server.subnegotiation(opt::MSSP, data.encode()).unwrap();
+  let bytes = Bytes::from(sent);
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::MSSP);
+  let mut client = Parser::with_support(table);
+  client.receive(&[cmd::IAC, cmd::WILL, opt::MSSP]);
+  let events = client.receive(&bytes);
+  if let events::TelnetEvents::Subnegotiation(sub) = &events[0] {
+    let received = sub.mssp().unwrap();
+    assert_eq!(received, data);
+    assert_eq!(received.get(mssp::PORT).unwrap(), ["23", "4000"]);
+  } else {
+    panic!("Expected a Subnegotiation");
+  }
+}
+
+#[test]
+fn test_gmcp() {
+  let mut table = CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let mut server = Parser::with_support(table);
+  assert!(server.send_gmcp("Core.Hello", "{}").is_none());
+  server.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+  let sent = server
+    .send_gmcp("Char.Vitals", "{\"hp\":100,\"name\":\"\u{ff}\"}")
+    .unwrap();
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::GMCP);
+  let mut client = Parser::with_support(table);
+  client.receive(&[cmd::IAC, cmd::WILL, opt::GMCP]);
+  let events = client.receive(&Bytes::from(sent));
+  if let events::TelnetEvents::Subnegotiation(sub) = &events[0] {
+    let msg = sub.gmcp().unwrap();
+    assert_eq!(msg.package, "Char");
+    assert_eq!(msg.message, "Vitals");
+    assert_eq!(msg.data, "{\"hp\":100,\"name\":\"\u{ff}\"}");
+    assert_eq!(sub.naws(), None);
+  } else {
+    panic!("Expected a Subnegotiation");
+  }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_gmcp_json() {
+  #[derive(Debug, PartialEq, serde::Deserialize)]
+  struct Hello {
+    client: String,
+    version: String,
+  }
+  let mut table = CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let mut server = Parser::with_support(table);
+  server.receive(&[cmd::IAC, cmd::DO, opt::GMCP]);
+  let value = serde_json::json!({ "client": "test", "version": "1.0" });
+  let sent = server.send_gmcp_json("Core.Hello", &value).unwrap();
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::GMCP);
+  let mut client = Parser::with_support(table);
+  client.receive(&[cmd::IAC, cmd::WILL, opt::GMCP]);
+  let events = client.receive(&Bytes::from(sent));
+  if let events::TelnetEvents::Subnegotiation(sub) = &events[0] {
+    let msg = sub.gmcp().unwrap();
+    assert_eq!(msg.name(), "Core.Hello");
+    let received = msg.json().unwrap();
+    assert_eq!(received, value);
+    assert_eq!(received["client"], "test");
+    assert_eq!(
+      msg.deserialize::<Hello>(),
+      Some(Hello {
+        client: String::from("test"),
+        version: String::from("1.0")
+      })
+    );
+  } else {
+    panic!("Expected a Subnegotiation");
+  }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_gmcp_core() {
+  use libtelnet_rs::telnet::gmcp::GmcpCore;
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::GMCP);
+  let mut client = Parser::with_support(table);
+  let mut client_core = GmcpCore::new()
+    .hello("test", "1.0")
+    .announce(&["Char 1", "Room 1"]);
+  let mut table = CompatibilityTable::new();
+  table.support_local(opt::GMCP);
+  let mut server = Parser::with_support(table);
+  let mut server_core = GmcpCore::new();
+  let will = server._will(opt::GMCP).unwrap();
+  let mut sent = Vec::new();
+  for event in client.receive(&Bytes::from(will)) {
+    if let Some(send) = client_core.process(&mut client, &event) {
+      sent.push(send);
+    }
+    if let events::TelnetEvents::DataSend(data) = event {
+      for event in server.receive(&data) {
+        assert!(server_core.process(&mut server, &event).is_none());
+      }
+    }
+  }
+  assert!(client_core.enabled());
+  assert!(server_core.enabled());
+  assert_eq!(sent.len(), 1);
+  assert_eq!(
+    Bytes::from(sent.remove(0)),
+    Bytes::from_static(
+      b"\xff\xfa\xc9Core.Hello {\"client\":\"test\",\"version\":\"1.0\"}\xff\xf0\
+        \xff\xfa\xc9Core.Supports.Set [\"Char 1\",\"Room 1\"]\xff\xf0"
+    )
+  );
+  let events = server.receive(&Bytes::from(
+    client
+      .send_gmcp("Core.Supports.Set", "[\"Char 1\",\"Room 1\"]")
+      .unwrap(),
+  ));
+  for event in events.iter() {
+    server_core.process(&mut server, event);
+  }
+  assert!(server_core.supports("Char 1"));
+  assert!(!server_core.supports("Char 2"));
+  let refused = client.receive(&[cmd::IAC, cmd::WONT, opt::GMCP]);
+  for event in refused.iter() {
+    client_core.process(&mut client, event);
+  }
+  assert!(!client_core.enabled());
+}
+
+#[test]
+fn test_msdp() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::msdp::{self, MsdpCommand, MsdpValue};
+  let mut server = Parser::with_protocols(&[Protocol::Msdp]);
+  let mut client = Parser::with_protocols(&[Protocol::Msdp]);
+  assert!(client
+    .send_msdp_command(&MsdpCommand::List(String::from(msdp::COMMANDS)))
+    .is_none());
+  for event in client.receive(&Bytes::from(server._will(opt::MSDP).unwrap())) {
+    if let events::TelnetEvents::DataSend(data) = event {
+      server.receive(&data);
+    }
+  }
+  let report = MsdpCommand::Report(vec![String::from("HEALTH"), String::from("ROOM")]);
+  let events = server.receive(&Bytes::from(client.send_msdp_command(&report).unwrap()));
+  if let events::TelnetEvents::Subnegotiation(sub) = &events[0] {
+    let vars = sub.msdp().unwrap();
+    assert_eq!(MsdpCommand::from_var(&vars[0].0, &vars[0].1), Some(report));
+  } else {
+    panic!("Expected a Subnegotiation");
+  }
+  let room = MsdpValue::Table(vec![
+    (String::from("VNUM"), MsdpValue::from("255")),
+    (String::from("NAME"), MsdpValue::from("\u{ff}")),
+  ]);
+  let events = client.receive(&Bytes::from(server.send_msdp("ROOM", &room).unwrap()));
+  if let events::TelnetEvents::Subnegotiation(sub) = &events[0] {
+    assert_eq!(sub.msdp().unwrap(), vec![(String::from("ROOM"), room)]);
+  } else {
+    panic!("Expected a Subnegotiation");
+  }
+}
+
+#[test]
+fn test_mxp() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::mxp::{MxpMode, MxpScanner};
+  let mut server = Parser::with_protocols(&[Protocol::Mxp]);
+  assert!(server.start_mxp().is_none());
+  server.receive(&[cmd::IAC, cmd::DO, opt::MXP]);
+  assert_eq!(
+    Bytes::from(server.start_mxp().unwrap()),
+    Bytes::from_static(&[cmd::IAC, cmd::SB, opt::MXP, cmd::IAC, cmd::SE])
+  );
+  let mut client = Parser::with_protocols(&[Protocol::Mxp]);
+  let mut scanner = MxpScanner::new();
+  let mut lines = Vec::new();
+  for event in client.receive(b"\x1b[1z<SEND>north</SEND>\r\n<B>open</B>\r\n") {
+    if let events::TelnetEvents::DataReceive(data) = event {
+      for text in scanner.scan(&data) {
+        lines.push((text.data, text.mode.is_secure()));
+      }
+    }
+  }
+  assert_eq!(
+    lines,
+    [
+      (Bytes::from_static(b"<SEND>north</SEND>\r\n"), true),
+      (Bytes::from_static(b"<B>open</B>\r\n"), false),
+    ]
+  );
+  assert_eq!(scanner.mode(), MxpMode::Open);
+}
+
+#[test]
+fn test_msp_triggers() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::msp::MspKind;
+  let mut client = Parser::builder()
+    .protocols(&[Protocol::Msp])
+    .msp_triggers()
+    .build();
+  assert_eq!(
+    handle_events(client.receive(b"!!SOUND(boom.wav)")),
+    events![Event::RECV]
+  );
+  client.receive(&[cmd::IAC, cmd::WILL, opt::MSP]);
+  let events = client.receive(b"Thunder! !!SOUND(thunder.wav V=80 T=weather)\r\n!!MUSIC(rain");
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::RECV, Event::SOUND, Event::RECV]
+  );
+  if let events::TelnetEvents::Sound(trigger) = &events[1] {
+    assert_eq!(trigger.kind, MspKind::Sound);
+    assert_eq!(trigger.file, "thunder.wav");
+    assert_eq!(trigger.volume, Some(80));
+    assert_eq!(trigger.sound_type.as_deref(), Some("weather"));
+  } else {
+    panic!("Expected a Sound");
+  }
+  let events = client.receive(b".mid L=-1)");
+  if let [events::TelnetEvents::Sound(trigger)] = &events[..] {
+    assert_eq!(trigger.kind, MspKind::Music);
+    assert_eq!(trigger.repeats, Some(-1));
+  } else {
+    panic!("Expected a Sound");
+  }
+  client.receive(b"!!SOU");
+  if let [events::TelnetEvents::DataReceive(data)] = &client.flush()[..] {
+    assert_eq!(&data[..], b"!!SOU");
+  } else {
+    panic!("Expected the held data");
+  }
+}
+
+#[test]
+fn test_aard102() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::aard102::{self, AardStatus};
+  let mut client = Parser::with_protocols(&[Protocol::Aard102]);
+  assert!(client.send_aard102(aard102::TELL, true).is_none());
+  client.receive(&[cmd::IAC, cmd::WILL, opt::AARD102]);
+  let events = client.receive(&[
+    cmd::IAC,
+    cmd::SB,
+    opt::AARD102,
+    aard102::STATUS,
+    4,
+    cmd::IAC,
+    cmd::SE,
+  ]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::AARD102]
+  );
+  if let events::TelnetEvents::Aard102(update) = &events[1] {
+    assert_eq!(update.status(), Some(AardStatus::Afk));
+  } else {
+    panic!("Expected an Aard102");
+  }
+  assert_eq!(
+    Bytes::from(client.send_aard102(aard102::TELL, false).unwrap()),
+    Bytes::from_static(&[
+      cmd::IAC,
+      cmd::SB,
+      opt::AARD102,
+      aard102::TELL,
+      aard102::OFF,
+      cmd::IAC,
+      cmd::SE
+    ])
+  );
+}
+
+#[test]
+fn test_status_responder() {
+  let mut table = CompatibilityTable::new();
+  table.support_local(opt::STATUS);
+  table.support_local(opt::ECHO);
+  table.support_remote(opt::NAWS);
+  let mut server = Parser::with_support(table);
+  server.receive(&[cmd::IAC, cmd::DO, opt::STATUS]);
+  server.receive(&[cmd::IAC, cmd::DO, opt::ECHO]);
+  server.receive(&[cmd::IAC, cmd::WILL, opt::NAWS]);
+  let events = server.receive(&[cmd::IAC, cmd::SB, opt::STATUS, 1, cmd::IAC, cmd::SE]);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::SEND]
+  );
+  let reply = Bytes::from(events[1].clone());
+  assert_eq!(
+    reply,
+    Bytes::from_static(&[
+      cmd::IAC,
+      cmd::SB,
+      opt::STATUS,
+      0,
+      cmd::WILL,
+      opt::ECHO,
+      cmd::WILL,
+      opt::STATUS,
+      cmd::DO,
+      opt::NAWS,
+      cmd::IAC,
+      cmd::SE
+    ])
+  );
+
+  // The client asked for the report, so both ends agree.
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::STATUS);
+  table.support_remote(opt::ECHO);
+  table.support_local(opt::NAWS);
+  let mut client = Parser::with_support(table);
+  client.receive(&[cmd::IAC, cmd::WILL, opt::STATUS]);
+  client.receive(&[cmd::IAC, cmd::WILL, opt::ECHO]);
+  client.receive(&[cmd::IAC, cmd::DO, opt::NAWS]);
+  assert_eq!(
+    handle_events(client.receive(&reply)),
+    events![Event::SUBNEGOTIATION]
+  );
+  let report = client.remote_status().unwrap();
+  assert_eq!(report.will, [opt::ECHO, opt::STATUS]);
+  assert_eq!(report.do_, [opt::NAWS]);
+}
+
+#[test]
+fn test_linemode_mode() {
+  use libtelnet_rs::telnet::linemode::LinemodeMode;
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::LINEMODE);
+  let mut server = Parser::with_support(table);
+  let mut table = CompatibilityTable::new();
+  table.support_local(opt::LINEMODE);
+  let mut client = Parser::with_support(table);
+  let mode = LinemodeMode::EDIT | LinemodeMode::TRAPSIG;
+  assert!(server.set_linemode(mode).is_none());
+  for event in client.receive(&Bytes::from(server._do(opt::LINEMODE).unwrap())) {
+    if let events::TelnetEvents::DataSend(data) = event {
+      server.receive(&data);
+    }
+  }
+  assert!(server.linemode_enabled());
+  assert_eq!(server.linemode(), None);
+
+  let request = Bytes::from(server.set_linemode(mode).unwrap());
+  let events = client.receive(&request);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::SEND]
+  );
+  assert_eq!(client.linemode(), Some(mode));
+  let ack = Bytes::from(events[1].clone());
+  assert_eq!(
+    ack,
+    Bytes::from_static(&[cmd::IAC, cmd::SB, opt::LINEMODE, 1, 7, cmd::IAC, cmd::SE])
+  );
+  assert_eq!(server.linemode(), None);
+  server.receive(&ack);
+  assert_eq!(server.linemode(), Some(mode));
+
+  // The same mode again is not acknowledged a second time.
+  assert_eq!(
+    handle_events(client.receive(&request)),
+    events![Event::SUBNEGOTIATION]
+  );
+}
+
+#[test]
+fn test_linemode_slc() {
+  use libtelnet_rs::telnet::linemode::{self, slc, SlcEntry};
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::LINEMODE);
+  let mut server = Parser::with_support(table);
+  let mut table = CompatibilityTable::new();
+  table.support_local(opt::LINEMODE);
+  let mut client = Parser::with_support(table);
+  let backspace = SlcEntry::new(slc::EC, slc::VALUE, 8);
+  assert!(server.set_slc(&[backspace]).is_none());
+  for event in client.receive(&Bytes::from(server._do(opt::LINEMODE).unwrap())) {
+    if let events::TelnetEvents::DataSend(data) = event {
+      server.receive(&data);
+    }
+  }
+
+  // The client adopts the new character and acknowledges it.
+  let request = Bytes::from(server.set_slc(&[backspace]).unwrap());
+  assert_eq!(server.slc().get(slc::EC), Some(backspace));
+  let events = client.receive(&request);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::SEND]
+  );
+  assert_eq!(client.slc().get(slc::EC), Some(backspace));
+  let ack = Bytes::from(events[1].clone());
+  assert_eq!(
+    ack,
+    Bytes::from_static(&[
+      cmd::IAC,
+      cmd::SB,
+      opt::LINEMODE,
+      linemode::SLC,
+      slc::EC,
+      130,
+      8,
+      cmd::IAC,
+      cmd::SE
+    ])
+  );
+  // The acknowledgement is not answered.
+  assert_eq!(
+    handle_events(server.receive(&ack)),
+    events![Event::SUBNEGOTIATION]
+  );
+
+  // A character of 255 is doubled on the wire, and decoded back.
+  let disabled = SlcEntry::new(slc::LNEXT, slc::VALUE, 255);
+  let request = Bytes::from(server.set_slc(&[disabled]).unwrap());
+  assert_eq!(
+    &request[..],
+    &[
+      cmd::IAC,
+      cmd::SB,
+      opt::LINEMODE,
+      linemode::SLC,
+      slc::LNEXT,
+      2,
+      255,
+      255,
+      cmd::IAC,
+      cmd::SE
+    ]
+  );
+  client.receive(&request);
+  assert_eq!(client.slc().get(slc::LNEXT), Some(disabled));
+}
+
+#[test]
+fn test_tspeed_xdisploc() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::tspeed::TspeedMessage;
+  use libtelnet_rs::telnet::xdisploc::XdisplocMessage;
+  let mut server = Parser::with_protocols(&[Protocol::TerminalSpeed, Protocol::XDisplayLocation]);
+  let mut client = Parser::with_protocols(&[Protocol::TerminalSpeed, Protocol::XDisplayLocation]);
+  assert!(server.request_terminal_speed().is_none());
+  assert!(client.send_display_location("localhost:0.0").is_none());
+  for option in [opt::TSPEED, opt::XDISPLOC] {
+    for event in client.receive(&Bytes::from(server._do(option).unwrap())) {
+      if let events::TelnetEvents::DataSend(data) = event {
+        server.receive(&data);
+      }
+    }
+  }
+
+  let events = client.receive(&Bytes::from(server.request_terminal_speed().unwrap()));
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::TSPEED]
+  );
+  assert!(matches!(
+    events[1],
+    events::TelnetEvents::TerminalSpeed(TspeedMessage::Send)
+  ));
+  let reply = Bytes::from(client.send_terminal_speed(38400, 9600).unwrap());
+  let events = server.receive(&reply);
+  if let events::TelnetEvents::TerminalSpeed(message) = &events[1] {
+    assert_eq!(
+      *message,
+      TspeedMessage::Is {
+        transmit: 38400,
+        receive: 9600
+      }
+    );
+  } else {
+    panic!("Expected a TerminalSpeed");
+  }
+
+  let events = client.receive(&Bytes::from(server.request_display_location().unwrap()));
+  assert!(matches!(
+    events[1],
+    events::TelnetEvents::DisplayLocation(XdisplocMessage::Send)
+  ));
+  let reply = Bytes::from(client.send_display_location("localhost:0.0").unwrap());
+  let events = server.receive(&reply);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::XDISPLOC]
+  );
+  if let events::TelnetEvents::DisplayLocation(XdisplocMessage::Is(display)) = &events[1] {
+    assert_eq!(display, "localhost:0.0");
+  } else {
+    panic!("Expected a DisplayLocation");
+  }
+}
+
+#[test]
+fn test_lflow() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::lflow::{LflowCommand, LflowState};
+  let mut server = Parser::with_protocols(&[Protocol::FlowControl]);
+  let mut client = Parser::with_protocols(&[Protocol::FlowControl]);
+  assert!(server.send_flow_control(LflowCommand::Off).is_none());
+  assert_eq!(client.flow_control(), None);
+  for event in client.receive(&Bytes::from(server._do(opt::LFLOW).unwrap())) {
+    if let events::TelnetEvents::DataSend(data) = event {
+      server.receive(&data);
+    }
+  }
+  assert_eq!(client.flow_control(), Some(LflowState::default()));
+
+  let off = Bytes::from(server.send_flow_control(LflowCommand::Off).unwrap());
+  assert_eq!(
+    off,
+    Bytes::from_static(&[cmd::IAC, cmd::SB, opt::LFLOW, 0, cmd::IAC, cmd::SE])
+  );
+  let events = client.receive(&off);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::LFLOW]
+  );
+  assert!(matches!(
+    events[1],
+    events::TelnetEvents::FlowControl(LflowState { enabled: false, .. })
+  ));
+  client.receive(&Bytes::from(
+    server.send_flow_control(LflowCommand::RestartXon).unwrap(),
+  ));
+  assert_eq!(
+    client.flow_control(),
+    Some(LflowState {
+      enabled: false,
+      restart_any: Some(false)
+    })
+  );
+  // Commands are only followed by the client.
+  assert_eq!(
+    handle_events(server.receive(&off)),
+    events![Event::SUBNEGOTIATION]
+  );
+}
+
+#[test]
+fn test_authentication() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::auth::{self, AuthMechanism, AuthPair, AuthStep};
+
+  /// A toy mechanism: the client sends a shared secret, and the server answers 1 to accept or 0 to reject.
+  struct Secret(&'static [u8]);
+
+  impl AuthMechanism for Secret {
+    fn pair(&self) -> AuthPair {
+      AuthPair::new(auth::SRP, auth::HOW_ONE_WAY)
+    }
+    fn start(&mut self) -> AuthStep {
+      AuthStep::Continue(Bytes::from_static(self.0))
+    }
+    fn step(&mut self, data: &[u8]) -> AuthStep {
+      match data {
+        [1] => AuthStep::Complete {
+          data: None,
+          accepted: true,
+        },
+        [0] => AuthStep::Complete {
+          data: None,
+          accepted: false,
+        },
+        _ => {
+          let accepted = data == self.0;
+          AuthStep::Complete {
+            data: Some(Bytes::copy_from_slice(&[accepted as u8])),
+            accepted,
+          }
+        }
+      }
+    }
+  }
+
+  let mut server = Parser::with_protocols(&[Protocol::Authentication]);
+  let mut client = Parser::with_protocols(&[Protocol::Authentication]);
+  server.add_auth_mechanism(Secret(b"sesame"));
+  client.add_auth_mechanism(Secret(b"sesame"));
+  assert!(server.request_authentication().is_none());
+  for event in client.receive(&Bytes::from(server._do(opt::AUTHENTICATION).unwrap())) {
+    if let events::TelnetEvents::DataSend(data) = event {
+      server.receive(&data);
+    }
+  }
+
+  let name = Bytes::from(client.send_auth_name("admin").unwrap());
+  let events = server.receive(&name);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::AUTHNAME]
+  );
+  assert!(matches!(&events[1], events::TelnetEvents::AuthName(name) if name == "admin"));
+
+  let offer = Bytes::from(server.request_authentication().unwrap());
+  assert_eq!(
+    offer,
+    Bytes::from_static(&[
+      cmd::IAC,
+      cmd::SB,
+      opt::AUTHENTICATION,
+      auth::SEND,
+      auth::SRP,
+      0,
+      cmd::IAC,
+      cmd::SE
+    ])
+  );
+  let events = client.receive(&offer);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::SEND]
+  );
+  let events = server.receive(&Bytes::from(events[1].clone()));
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::SEND, Event::AUTH]
+  );
+  assert!(matches!(
+    events[2],
+    events::TelnetEvents::AuthComplete { accepted: true, .. }
+  ));
+  let events = client.receive(&Bytes::from(events[1].clone()));
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::AUTH]
+  );
+  assert!(matches!(
+    events[1],
+    events::TelnetEvents::AuthComplete { accepted: true, .. }
+  ));
+
+  // A client without a matching mechanism answers IS NULL.
+  let mut other = Parser::with_protocols(&[Protocol::Authentication]);
+  other._will(opt::AUTHENTICATION);
+  other.receive(&[cmd::IAC, cmd::DO, opt::AUTHENTICATION]);
+  let events = other.receive(&offer);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::SEND, Event::AUTH]
+  );
+  let events = server.receive(&Bytes::from(events[1].clone()));
+  assert!(matches!(
+    events[1],
+    events::TelnetEvents::AuthComplete {
+      pair: AuthPair {
+        kind: auth::NULL,
+        ..
+      },
+      accepted: false
+    }
+  ));
+}
+
+#[test]
+fn test_encrypt() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::encrypt::{self, Cipher, EncryptMessage};
+
+  /// A toy cipher that flips bits with a fixed key.
+  struct Xor(u8);
+
+  impl Cipher for Xor {
+    fn kind(&self) -> u8 {
+      encrypt::NULL
+    }
+    fn encrypt(&mut self, data: &mut [u8]) {
+      data.iter_mut().for_each(|b| *b ^= self.0);
+    }
+    fn decrypt(&mut self, data: &mut [u8]) {
+      self.encrypt(data);
+    }
+  }
+
+  fn connect() -> (Parser, Parser) {
+    let mut server = Parser::with_protocols(&[Protocol::Encrypt]);
+    let mut client = Parser::with_protocols(&[Protocol::Encrypt]);
+    for event in server.receive(&Bytes::from(client._will(opt::ENCRYPT).unwrap())) {
+      if let events::TelnetEvents::DataSend(data) = event {
+        client.receive(&data);
+      }
+    }
+    client.set_cipher(Xor(0x5a));
+    (server, client)
+  }
+
+  let (mut server, mut client) = connect();
+  server.set_cipher(Xor(0x5a));
+  let support = server
+    .send_encrypt(&EncryptMessage::Support(vec![encrypt::DES3_CFB64]))
+    .unwrap();
+  let events = client.receive(&Bytes::from(support));
+  assert!(matches!(
+    &events[1],
+    events::TelnetEvents::Encrypt(EncryptMessage::Support(kinds)) if kinds[..] == [encrypt::DES3_CFB64]
+  ));
+
+  // The START and the encrypted data after it arrive together.
+  let mut stream = Vec::from(&Bytes::from(client.start_encryption(&[0]).unwrap())[..]);
+  assert!(client.encryption().output);
+  let text = Bytes::from(client.send_text("secret"));
+  let secret = client.encrypt(text);
+  assert_ne!(&secret[..], b"secret\r\n");
+  stream.extend_from_slice(&secret);
+  let events = server.receive(&stream);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::ENCRYPT, Event::RECV]
+  );
+  assert_eq!(
+    Bytes::from(events[2].clone()),
+    Bytes::from_static(b"secret\r\n")
+  );
+  assert!(server.encryption().input);
+
+  // Data after the END is not encrypted.
+  let mut stream = Vec::from(&Bytes::from(client.end_encryption().unwrap())[..]);
+  assert!(!client.encryption().output);
+  stream.extend_from_slice(b"plain");
+  let events = server.receive(&stream);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::ENCRYPT, Event::RECV]
+  );
+  assert_eq!(Bytes::from(events[2].clone()), Bytes::from_static(b"plain"));
+  assert!(!server.encryption().input);
+
+  // Without a cipher, the encrypted data is passed through for the application to decrypt.
+  let (mut server, mut client) = connect();
+  server.receive(&Bytes::from(client.start_encryption(&[0]).unwrap()));
+  let secret = client.encrypt(Bytes::from_static(b"hidden"));
+  let events = server.receive(&secret);
+  assert_eq!(handle_events(events.clone()), events![Event::ENCRYPTED]);
+  let mut data = Vec::from(&Bytes::from(events[0].clone())[..]);
+  data.iter_mut().for_each(|b| *b ^= 0x5a);
+  assert_eq!(
+    handle_events(server.receive_decrypted(&data)),
+    events![Event::RECV]
+  );
+}
+
+#[test]
+fn test_start_tls() {
+  use libtelnet_rs::protocol::Protocol;
+  let mut server = Parser::with_protocols(&[Protocol::StartTls]);
+  let mut client = Parser::with_protocols(&[Protocol::StartTls]);
+  assert!(server.request_tls().is_none());
+  for event in client.receive(&Bytes::from(server._do(opt::START_TLS).unwrap())) {
+    if let events::TelnetEvents::DataSend(data) = event {
+      server.receive(&data);
+    }
+  }
+
+  let follows = Bytes::from(server.request_tls().unwrap());
+  assert_eq!(
+    follows,
+    Bytes::from_static(&[cmd::IAC, cmd::SB, opt::START_TLS, 1, cmd::IAC, cmd::SE])
+  );
+  let events = client.receive(&follows);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::SEND, Event::STARTTLS]
+  );
+  assert_eq!(Bytes::from(events[1].clone()), follows);
+  assert!(client.tls_suspended());
+
+  // The client's FOLLOWS arrives with the start of its TLS handshake, which is handed back untouched.
+  let mut stream = Vec::from(&follows[..]);
+  stream.extend_from_slice(&[0x16, 0x03, 0x01, cmd::IAC]);
+  let events = server.receive(&stream);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::STARTTLS]
+  );
+  assert_eq!(
+    Bytes::from(events[1].clone()),
+    Bytes::from_static(&[0x16, 0x03, 0x01, cmd::IAC])
+  );
+  assert!(server.tls_suspended());
+  assert!(server.tls_established().is_empty());
+  assert!(!server.tls_suspended());
+
+  // Data given to a suspended parser is only parsed once the handshake is done, and flushing leaves it alone.
+  assert!(client.receive(b"Welcome\r\n").is_empty());
+  client.feed(b"> ");
+  assert!(client.next_event().is_none());
+  assert!(client.flush().is_empty());
+  assert_eq!(client.pending_len(), 11);
+  assert_eq!(
+    handle_events(client.tls_established()),
+    events![Event::RECV]
+  );
+  assert_eq!(handle_events(client.receive(b"more")), events![Event::RECV]);
+
+  // Resetting the parser for a new connection ends the suspension along with the buffered data.
+  client.receive(&follows);
+  assert!(client.tls_suspended());
+  client.receive(b"Welcome");
+  client.reset(false);
+  assert!(!client.tls_suspended());
+  assert!(client.is_idle());
+}
+
+#[test]
+fn test_com_port() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::comport::{self, ComPortCommand, ComPortMessage};
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::COM_PORT_OPTION);
+  let mut server = Parser::with_support(table);
+  let mut client = Parser::with_protocols(&[Protocol::ComPort]);
+  assert!(client
+    .send_com_port(ComPortCommand::SetBaudRate(9600))
+    .is_none());
+  for event in server.receive(&Bytes::from(client._will(opt::COM_PORT_OPTION).unwrap())) {
+    if let events::TelnetEvents::DataSend(data) = event {
+      client.receive(&data);
+    }
+  }
+
+  let request = Bytes::from(
+    client
+      .send_com_port(ComPortCommand::SetBaudRate(9600))
+      .unwrap(),
+  );
+  assert_eq!(
+    request,
+    Bytes::from_static(&[
+      cmd::IAC,
+      cmd::SB,
+      opt::COM_PORT_OPTION,
+      comport::SET_BAUDRATE,
+      0,
+      0,
+      0x25,
+      0x80,
+      cmd::IAC,
+      cmd::SE
+    ])
+  );
+  let events = server.receive(&request);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::COMPORT]
+  );
+  if let events::TelnetEvents::ComPort(message) = &events[1] {
+    assert_eq!(
+      *message,
+      ComPortMessage::new(false, ComPortCommand::SetBaudRate(9600))
+    );
+  } else {
+    panic!("Expected a ComPort");
+  }
+
+  // The server answers with its own codes, adding 100.
+  let reply = Bytes::from(
+    server
+      .send_com_port(ComPortCommand::SetBaudRate(9600))
+      .unwrap(),
+  );
+  assert_eq!(reply[3], comport::SET_BAUDRATE + comport::SERVER_OFFSET);
+  let events = client.receive(&reply);
+  assert!(matches!(
+    &events[1],
+    events::TelnetEvents::ComPort(ComPortMessage {
+      from_server: true,
+      ..
+    })
+  ));
+}
+
+#[test]
+fn test_serial_port_session() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::comport::{
+    modemstate, ComPortCommand, Parity, SerialEvent, SerialPortSession,
+  };
+  let mut table = CompatibilityTable::new();
+  table.support_remote(opt::COM_PORT_OPTION);
+  let mut server = Parser::with_support(table);
+  let mut client = Parser::with_protocols(&[Protocol::ComPort]);
+  for event in server.receive(&Bytes::from(client._will(opt::COM_PORT_OPTION).unwrap())) {
+    if let events::TelnetEvents::DataSend(data) = event {
+      client.receive(&data);
+    }
+  }
+
+  let mut session = SerialPortSession::new();
+  let mut stream = Vec::from(&Bytes::from(session.set_baud(&mut client, 115_200).unwrap())[..]);
+  stream.extend_from_slice(&Bytes::from(
+    session.set_parity(&mut client, Parity::Even).unwrap(),
+  ));
+  let mut confirmations = Vec::new();
+  for event in server.receive(&stream) {
+    if let events::TelnetEvents::ComPort(message) = event {
+      assert!(!message.from_server);
+      confirmations.push(Bytes::from(server.send_com_port(message.command).unwrap()));
+    }
+  }
+  assert_eq!(session.baud(), None);
+  let mut changes = Vec::new();
+  for data in confirmations {
+    for event in client.receive(&data) {
+      changes.extend(session.process(&event));
+    }
+  }
+  assert_eq!(
+    changes,
+    [
+      SerialEvent::Setting(ComPortCommand::SetBaudRate(115_200)),
+      SerialEvent::Setting(ComPortCommand::SetParity(3)),
+    ]
+  );
+  assert_eq!(session.baud(), Some(115_200));
+  assert_eq!(session.parity(), Some(Parity::Even));
+
+  let notify = server
+    .send_com_port(ComPortCommand::NotifyModemState(
+      modemstate::CD | modemstate::DELTA_CD,
+    ))
+    .unwrap();
+  let events = client.receive(&Bytes::from(notify));
+  assert_eq!(
+    session.process(&events[1]),
+    Some(SerialEvent::ModemState {
+      state: modemstate::CD | modemstate::DELTA_CD,
+      previous: 0
+    })
+  );
+  let suspend = server
+    .send_com_port(ComPortCommand::FlowControlSuspend)
+    .unwrap();
+  let events = client.receive(&Bytes::from(suspend));
+  assert_eq!(
+    session.process(&events[1]),
+    Some(SerialEvent::FlowSuspended)
+  );
+  assert!(session.suspended());
+}
+
+#[test]
+fn test_tn3270e() {
+  use libtelnet_rs::protocol::Protocol;
+  use libtelnet_rs::telnet::tn3270e::{
+    self, data_type, Tn3270eHeader, Tn3270eMessage, Tn3270eRecord, Tn3270eSession,
+  };
+  let protocols = [Protocol::Binary, Protocol::EndOfRecord, Protocol::Tn3270e];
+  let mut server = Parser::with_protocols(&protocols);
+  let mut client = Parser::with_protocols(&protocols);
+  let session = Tn3270eSession::new();
+  assert!(session
+    .send_record(
+      &client,
+      &Tn3270eRecord::new(Tn3270eHeader::default(), Bytes::new())
+    )
+    .is_none());
+  for option in [opt::BINARY, opt::EOR, opt::TN3270E] {
+    let mut pending = vec![
+      Bytes::from(server._do(option).unwrap()),
+      Bytes::from(server._will(option).unwrap()),
+    ];
+    let mut to_client = true;
+    while !pending.is_empty() {
+      let receiver = if to_client { &mut client } else { &mut server };
+      let mut replies = Vec::new();
+      for data in pending {
+        for event in receiver.receive(&data) {
+          if let events::TelnetEvents::DataSend(data) = event {
+            replies.push(data);
+          }
+        }
+      }
+      pending = replies;
+      to_client = !to_client;
+    }
+  }
+
+  let send = Bytes::from(session.request_device_type(&mut server).unwrap());
+  assert_eq!(
+    &send[..],
+    &[
+      cmd::IAC,
+      cmd::SB,
+      opt::TN3270E,
+      tn3270e::SEND,
+      tn3270e::DEVICE_TYPE,
+      cmd::IAC,
+      cmd::SE
+    ]
+  );
+  let events = client.receive(&send);
+  assert_eq!(
+    handle_events(events.clone()),
+    events![Event::SUBNEGOTIATION, Event::TN3270E]
+  );
+  assert!(matches!(
+    &events[1],
+    events::TelnetEvents::Tn3270e(Tn3270eMessage::SendDeviceType)
+  ));
+  let request = Tn3270eMessage::DeviceTypeRequest {
+    device_type: String::from("IBM-3278-2-E"),
+    resource: None,
+  };
+  let events = server.receive(&Bytes::from(client.send_tn3270e(&request).unwrap()));
+  if let events::TelnetEvents::Tn3270e(message) = &events[1] {
+    assert_eq!(*message, request);
+  } else {
+    panic!("Expected a Tn3270e");
+  }
+
+  // A record split across reads, with an escaped IAC in its data, is handed over once complete.
+  let record = Tn3270eRecord::new(
+    Tn3270eHeader::new(data_type::DATA_3270, 7),
+    Bytes::from_static(&[0xf5, 0xc3, 0xff, 0x11]),
+  );
+  let data = Bytes::from(session.send_record(&server, &record).unwrap());
+  let mut client_session = Tn3270eSession::new();
+  let mut records = Vec::new();
+  for chunk in [&data[..4], &data[4..]] {
+    for event in client.receive(chunk) {
+      records.extend(client_session.process(&event));
+    }
+  }
+  assert_eq!(records, [record]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_events() {
+  use libtelnet_rs::protocol::Protocol;
+
+  let mut parser = Parser::with_protocols(&[Protocol::Gmcp, Protocol::Naws]);
+  let mut events = parser.receive(&[
+    cmd::IAC,
+    cmd::WILL,
+    opt::GMCP,
+    b'h',
+    b'i',
+    cmd::IAC,
+    cmd::GA,
+  ]);
+  events.push(events::TelnetEvents::build_subnegotiation(
+    opt::NAWS,
+    Bytes::from_static(&[0, 80, 0, 24]),
+  ));
+  events.push(events::TelnetEvents::Error(
+    events::TelnetErrorKind::Overflow,
+    Bytes::from_static(&[255]),
+  ));
+  let log: Vec<String> = events
+    .iter()
+    .map(|event| serde_json::to_string(event).unwrap())
+    .collect();
+  assert_eq!(log[1], r#"{"Negotiation":{"command":251,"option":201}}"#);
+  assert_eq!(log[2], r#"{"DataReceive":[104,105]}"#);
+  let replayed: Vec<events::TelnetEvents> = log
+    .iter()
+    .map(|line| serde_json::from_str(line).unwrap())
+    .collect();
+  assert_eq!(replayed, events);
+}
+
+#[test]
+fn test_negotiation_snapshot() {
+  use libtelnet_rs::protocol::Protocol;
+
+  let mut parser = Parser::with_protocols(&[Protocol::Gmcp, Protocol::Naws]);
+  parser.receive(&[cmd::IAC, cmd::WILL, opt::GMCP]);
+  parser._do(opt::NAWS).unwrap();
+  let snapshot = parser.negotiation_snapshot();
+
+  let mut restored = Parser::new();
+  restored.restore_negotiation(snapshot);
+  assert!(restored.options.get_option(opt::GMCP).remote_state);
+  assert!(restored.negotiation_pending(opt::NAWS, false));
+  let events = restored.receive(&[cmd::IAC, cmd::WILL, opt::NAWS]);
+  assert!(events.iter().any(|event| matches!(
+    event,
+    events::TelnetEvents::NegotiationComplete(complete) if complete.option == opt::NAWS && complete.accepted
+  )));
+  assert!(!restored.negotiation_pending(opt::NAWS, false));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_negotiation_snapshot() {
+  use libtelnet_rs::compatibility::NegotiationSnapshot;
+  use libtelnet_rs::protocol::Protocol;
+
+  let mut parser = Parser::with_protocols(&[Protocol::Gmcp]);
+  parser.receive(&[cmd::IAC, cmd::WILL, opt::GMCP]);
+  let table = serde_json::to_string(&parser.options).unwrap();
+  assert_eq!(table, "[[201,11]]");
+  let table: CompatibilityTable = serde_json::from_str(&table).unwrap();
+  assert!(table.get_option(opt::GMCP).remote_state);
+
+  parser._will(opt::GMCP).unwrap();
+  let saved = serde_json::to_string(&parser.negotiation_snapshot()).unwrap();
+  let snapshot: NegotiationSnapshot = serde_json::from_str(&saved).unwrap();
+  let mut restored = Parser::new();
+  restored.restore_negotiation(snapshot);
+  assert!(restored.negotiation_pending(opt::GMCP, true));
+}
+
+#[test]
+fn test_event_equality() {
+  use std::collections::HashSet;
+
+  let mut parser = Parser::new();
+  let events = parser.receive(&[cmd::IAC, cmd::NOP, b'h', b'i', cmd::IAC, cmd::NOP]);
+  assert_eq!(
+    events,
+    [
+      events::TelnetEvents::build_iac(cmd::NOP),
+      events::TelnetEvents::build_receive(Bytes::from_static(b"hi")),
+      events::TelnetEvents::build_iac(cmd::NOP),
+    ]
+  );
+  let unique: HashSet<_> = events.iter().collect();
+  assert_eq!(unique.len(), 2);
+  assert_ne!(
+    events::TelnetNegotiation::new(cmd::WILL, opt::GMCP),
+    events::TelnetNegotiation::new(cmd::WONT, opt::GMCP)
+  );
+}
+
+#[test]
+fn test_event_debug() {
+  let gmcp = events::TelnetEvents::build_subnegotiation(
+    opt::GMCP,
+    Bytes::from_static(b"Core.Hello {\"client\": \"x\"}"),
+  );
+  assert_eq!(
+    format!("{:?}", gmcp),
+    "Subnegotiation(GMCP, 26 bytes: 43 6f 72 65 2e 48 65 6c 6c 6f 20 7b 22 63 6c 69 ...)"
+  );
+  assert_eq!(
+    format!("{:?}", events::TelnetEvents::build_iac(cmd::AYT)),
+    "IAC(AYT)"
+  );
+  assert_eq!(
+    format!("{:?}", events::TelnetNegotiation::new(cmd::WILL, opt::GMCP)),
+    "TelnetNegotiation(WILL, GMCP)"
+  );
+  assert_eq!(
+    format!(
+      "{:?}",
+      events::TelnetEvents::build_negotiation(cmd::DO, 200)
+    ),
+    "Negotiation(DO, 200)"
+  );
+  assert_eq!(
+    format!(
+      "{:?}",
+      events::TelnetEvents::DataSend(Bytes::from_static(&[cmd::IAC]))
+    ),
+    "DataSend(1 byte: ff)"
+  );
+  let large = events::TelnetEvents::build_receive(Bytes::from(vec![b'x'; 4096]));
+  assert!(format!("{:?}", large).len() < 80);
+}
+
+#[cfg(feature = "defmt")]
+#[test]
+fn test_defmt_format() {
+  fn assert_format<T: defmt::Format>() {}
+  assert_format::<events::TelnetEvents>();
+  assert_format::<events::TelnetSubnegotiation>();
+  assert_format::<events::TelnetErrorKind>();
+  assert_format::<telnet::TelnetOption>();
+  assert_format::<telnet::TelnetCommand>();
+  assert_format::<error::TelnetError>();
+  assert_format::<error::SubnegotiationRefusal>();
+  assert_format::<error::ParseNameError>();
+}